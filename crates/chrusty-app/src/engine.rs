@@ -0,0 +1,990 @@
+use std::{
+    fs, mem,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    time::Duration,
+};
+
+use crate::{
+    cssom::{ColorScheme, MediaContext, Origin, Stylesheet},
+    dom::{Document, ElementState, ElementStates, NodeId},
+    error::{ChrustyError, Stage},
+    layout::{layout_tree, Dimensions, LayoutBox, LayoutConstraints, LayoutContext, Rect, Size},
+    lifecycle::{Lifecycle, LifecycleEvent},
+    paint::{
+        build_display_list, diff_display_lists, pdf, png, raster::Canvas, svg, DisplayList,
+        DisplayListChange, Renderer,
+    },
+    parser::{CSSParser, HTMLParser, IParser},
+    resource::{Image, Resources},
+    style::{cascade_with_states, get_rule_usage, RuleUsage, StyleChange, StyleInvalidator},
+};
+
+/// Built-in styles every document is rendered with, below the page's own.
+const USER_AGENT_CSS: &str = "
+    html, div, p {
+        display: block;
+    }
+";
+
+const ERROR_PAGE_CSS: &str = "
+    div.error {
+        background: white;
+        color: rgb(200,0,0);
+    }
+";
+
+/// Largest width or height of a screenshot, in device pixels, keeping its
+/// canvas at most 256 MB.
+const MAX_SCREENSHOT_SIZE: f32 = 8192.0;
+
+/// Runs `work` for the given pipeline stage, turning a panic into an error.
+fn run_stage<R>(stage: Stage, work: impl FnOnce() -> R) -> Result<R, ChrustyError> {
+    panic::catch_unwind(AssertUnwindSafe(work))
+        .map_err(|payload| ChrustyError::from_panic(stage, payload))
+}
+
+fn parse(html: &str, css: &str) -> Result<(Document, Stylesheet), ChrustyError> {
+    let stylesheet = CSSParser::new(css)
+        .try_parse()
+        .map_err(ChrustyError::InvalidStylesheet)?;
+    Ok((HTMLParser::new(html).parse(), stylesheet))
+}
+
+fn error_page(error: &ChrustyError) -> (Document, Stylesheet) {
+    // One paragraph per line, so source snippets keep their shape.
+    let message: String = error
+        .to_string()
+        .replace('<', "&lt;")
+        .lines()
+        .map(|line| format!("<p>{}</p>", line))
+        .collect();
+    parse(
+        &format!("<div class=\"error\">{}</div>", message),
+        ERROR_PAGE_CSS,
+    )
+    .expect("The error page is valid")
+}
+
+/// Owns the current document and isolates failures in per-document work so
+/// a broken page can't take down the embedder.
+pub struct Engine {
+    document: Document,
+    stylesheet: Stylesheet,
+    user_agent_stylesheet: Stylesheet,
+    viewport: Rect,
+    /// The screen media queries and viewport lengths are evaluated against,
+    /// sized like `viewport`.
+    media: MediaContext,
+    layout_context: LayoutContext,
+    /// Display list of the last frame painted, handed out again while
+    /// nothing changed.
+    display_list: Option<DisplayList>,
+    /// Display list of the last frame handed out by `paint_diff`.
+    last_display_list: DisplayList,
+    lifecycle: Lifecycle,
+    resources: Resources,
+    /// Hover, focus and active state of the document's elements, updated
+    /// from the input events forwarded by the embedder.
+    element_states: ElementStates,
+    /// Elements whose style changed since the last paint.
+    invalidator: StyleInvalidator,
+    has_loaded: bool,
+    first_paint_pending: bool,
+    /// Set when something changed what the next frame looks like.
+    needs_paint: bool,
+    /// Whether each layout is dumped to `layout_dump`, for debugging.
+    dumps_layout: bool,
+    layout_dump: Option<String>,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Engine {
+    pub fn new() -> Engine {
+        let (document, stylesheet) = parse("", "").unwrap();
+        let mut engine = Engine {
+            document,
+            stylesheet,
+            user_agent_stylesheet: CSSParser::new(USER_AGENT_CSS)
+                .parse()
+                .with_origin(Origin::UserAgent),
+            viewport: Rect {
+                x: 0.0,
+                y: 0.0,
+                width: 800.0,
+                height: 600.0,
+            },
+            media: MediaContext::default(),
+            layout_context: LayoutContext::new(),
+            display_list: None,
+            last_display_list: vec![],
+            lifecycle: Lifecycle::new(),
+            resources: Resources::new(),
+            element_states: ElementStates::new(),
+            invalidator: StyleInvalidator::default(),
+            has_loaded: false,
+            first_paint_pending: false,
+            needs_paint: false,
+            dumps_layout: false,
+            layout_dump: None,
+        };
+        engine.update_invalidator();
+        engine
+    }
+
+    pub fn viewport(&self) -> Rect {
+        self.viewport
+    }
+
+    /// Resizes the viewport. Styles are recomputed on the next frame, so
+    /// `@media` rules and viewport lengths follow the new size.
+    pub fn set_viewport(&mut self, viewport: Rect) {
+        self.needs_paint |= viewport != self.viewport;
+        self.viewport = viewport;
+        self.media.width = viewport.width;
+        self.media.height = viewport.height;
+    }
+
+    pub fn media(&self) -> &MediaContext {
+        &self.media
+    }
+
+    /// Sets the device pixels per px of the screen the page is shown on,
+    /// e.g. as the window moves to another screen. Layouts are snapped to
+    /// device pixels, and embedders paint frames onto a canvas sized in
+    /// device pixels with its scale set to the ratio, while the viewport
+    /// stays sized in px. Ratios that aren't finite and positive are
+    /// rejected, as layouts are snapped by dividing by them.
+    pub fn set_device_pixel_ratio(&mut self, device_pixel_ratio: f32) -> Result<(), ChrustyError> {
+        if !(device_pixel_ratio > 0.0 && device_pixel_ratio.is_finite()) {
+            return Err(ChrustyError::InvalidDevicePixelRatio(device_pixel_ratio));
+        }
+        self.needs_paint |= device_pixel_ratio != self.media.device_pixel_ratio;
+        self.media.device_pixel_ratio = device_pixel_ratio;
+        Ok(())
+    }
+
+    pub fn set_color_scheme(&mut self, color_scheme: ColorScheme) {
+        self.needs_paint |= color_scheme != self.media.color_scheme;
+        self.media.color_scheme = color_scheme;
+    }
+
+    /// Whether the document changed since the last successful `paint`, so
+    /// embedders know when to schedule a frame.
+    pub fn needs_paint(&self) -> bool {
+        self.needs_paint
+    }
+
+    /// Lets the embedder size the element with the given `id` attribute, for
+    /// content whose intrinsic size only the host knows.
+    pub fn register_measure(
+        &mut self,
+        element_id: &str,
+        measure: impl Fn(LayoutConstraints) -> Size + Send + Sync + 'static,
+    ) {
+        self.layout_context.register_measure(element_id, measure);
+        self.needs_paint = true;
+    }
+
+    pub fn unregister_measure(&mut self, element_id: &str) {
+        self.layout_context.unregister_measure(element_id);
+        self.needs_paint = true;
+    }
+
+    pub fn document(&self) -> &Document {
+        &self.document
+    }
+
+    pub fn stylesheet(&self) -> &Stylesheet {
+        &self.stylesheet
+    }
+
+    pub fn user_agent_stylesheet(&self) -> &Stylesheet {
+        &self.user_agent_stylesheet
+    }
+
+    /// Edits the current stylesheet, e.g. with `insert_rule`. If the sheet
+    /// changed, resources it now references are loaded and the document is
+    /// restyled on the next paint.
+    pub fn edit_stylesheet<R>(&mut self, edit: impl FnOnce(&mut Stylesheet) -> R) -> R {
+        let version = self.stylesheet.version();
+        let result = edit(&mut self.stylesheet);
+        if self.stylesheet.version() != version {
+            self.load_images();
+            self.load_fonts();
+            self.update_invalidator();
+            self.needs_paint = true;
+        }
+        result
+    }
+
+    /// Which rules of the current stylesheet matched the current document.
+    pub fn rule_usage(&self) -> RuleUsage {
+        get_rule_usage(&self.document, &self.stylesheet)
+    }
+
+    /// Loads a new document. On failure the error page is loaded instead and
+    /// the error is returned for reporting.
+    pub fn load(&mut self, html: &str, css: &str) -> Result<(), ChrustyError> {
+        if self.has_loaded {
+            self.lifecycle.emit(LifecycleEvent::NavigationAway);
+        }
+        self.lifecycle.emit(LifecycleEvent::LoadStarted);
+        let result = match run_stage(Stage::Parse, || parse(html, css)).and_then(|parsed| parsed) {
+            Ok((document, stylesheet)) => {
+                self.document = document;
+                self.stylesheet = stylesheet;
+                self.element_states.clear();
+                self.update_invalidator();
+                Ok(())
+            }
+            Err(error) => Err(self.show_error(error)),
+        };
+        // A failed load still completes, with the error page as its document.
+        self.has_loaded = true;
+        self.first_paint_pending = true;
+        self.needs_paint = true;
+        self.lifecycle.emit(LifecycleEvent::DomParsed);
+        // Stylesheets are passed in with the document, so images and fonts
+        // are the only resources left to fetch.
+        self.load_images();
+        self.load_fonts();
+        self.lifecycle.emit(LifecycleEvent::ResourcesComplete);
+        result
+    }
+
+    /// Registers a callback invoked with each lifecycle event and the time
+    /// since the engine was created.
+    pub fn on_lifecycle(&mut self, callback: impl FnMut(LifecycleEvent, Duration) + 'static) {
+        self.lifecycle.add_listener(callback);
+    }
+
+    /// Sets the callback used to load the images referenced by documents,
+    /// e.g. `background-image: url(...)` or `<img src="...">`. Images are
+    /// requested during `load`.
+    pub fn on_image_request(&mut self, request: impl FnMut(&str) -> Option<Image> + 'static) {
+        self.resources.set_image_request(request);
+    }
+
+    /// Sets the callback used to fetch the sources of `@font-face` rules.
+    /// Fonts are requested during `load`.
+    pub fn on_font_request(&mut self, request: impl FnMut(&str) -> Option<Vec<u8>> + 'static) {
+        self.resources.set_font_request(request);
+    }
+
+    pub fn resources(&self) -> &Resources {
+        &self.resources
+    }
+
+    pub fn lifecycle(&self) -> &Lifecycle {
+        &self.lifecycle
+    }
+
+    pub fn element_states(&self) -> &ElementStates {
+        &self.element_states
+    }
+
+    /// Moves the pointer to `(x, y)` in viewport coordinates, hovering the
+    /// element under it along with its ancestors.
+    pub fn on_mouse_move(&mut self, x: f32, y: f32) -> Result<(), ChrustyError> {
+        let hovered = self.hit_test(x, y)?;
+        self.update_states(&hovered, |state| &mut state.hover);
+        Ok(())
+    }
+
+    /// Unhovers every element, e.g. when the pointer leaves the window.
+    pub fn on_mouse_leave(&mut self) {
+        self.update_states(&[], |state| &mut state.hover);
+    }
+
+    /// Presses the pointer at `(x, y)`, activating the element under it along
+    /// with its ancestors, and focusing it.
+    pub fn on_mouse_down(&mut self, x: f32, y: f32) -> Result<(), ChrustyError> {
+        let pressed = self.hit_test(x, y)?;
+        self.update_states(&pressed, |state| &mut state.active);
+        let focused = pressed.last().map(std::slice::from_ref).unwrap_or_default();
+        self.update_states(focused, |state| &mut state.focus);
+        Ok(())
+    }
+
+    pub fn on_mouse_up(&mut self) {
+        self.update_states(&[], |state| &mut state.active);
+    }
+
+    /// Moves the focus to `node`, or clears it, e.g. as the embedder handles
+    /// keyboard navigation.
+    pub fn set_focus(&mut self, node: Option<NodeId>) {
+        let focused = node.as_slice();
+        self.update_states(focused, |state| &mut state.focus);
+    }
+
+    /// Ids of the element painted topmost at `(x, y)` and of the elements
+    /// it's in, from the root down.
+    fn hit_test(&mut self, x: f32, y: f32) -> Result<Vec<NodeId>, ChrustyError> {
+        self.with_layout(|layout_root| match layout_root.hit_test(x, y) {
+            Some(hit) => hit
+                .path
+                .iter()
+                .filter_map(|layout_box| layout_box.node_id())
+                .collect(),
+            None => vec![],
+        })
+    }
+
+    /// Sets a state flag on exactly `nodes`. The document only needs a
+    /// restyle and repaint when a stylesheet uses the matching pseudo-class.
+    fn update_states(&mut self, nodes: &[NodeId], flag: impl Fn(&mut ElementState) -> &mut bool) {
+        for node in self.element_states.set_only(nodes, &flag) {
+            let new = self.element_states.get(node);
+            let mut old = new;
+            *flag(&mut old) ^= true;
+            self.invalidator
+                .invalidate(node, StyleChange::State { old, new });
+        }
+        self.needs_paint |= self.invalidator.needs_restyle();
+    }
+
+    /// Starts over from the features the current stylesheets use.
+    fn update_invalidator(&mut self) {
+        self.invalidator = StyleInvalidator::new(&[&self.user_agent_stylesheet, &self.stylesheet]);
+    }
+
+    /// Turns dumping each layout of the document on or off. See
+    /// `LayoutBox::dump` for the format.
+    pub fn set_dumps_layout(&mut self, dumps_layout: bool) {
+        self.dumps_layout = dumps_layout;
+        // The next frame is laid out again to be dumped.
+        self.needs_paint |= dumps_layout;
+        if !dumps_layout {
+            self.layout_dump = None;
+        }
+    }
+
+    /// Dump of the last layout, when dumping layouts is on.
+    pub fn layout_dump(&self) -> Option<&str> {
+        self.layout_dump.as_deref()
+    }
+
+    /// Styles and lays out the current document, snapped to device pixels, and
+    /// hands the layout tree to `f`. On failure the error page replaces the
+    /// document, so the next call renders it.
+    pub fn with_layout<R>(&mut self, f: impl FnOnce(&LayoutBox) -> R) -> Result<R, ChrustyError> {
+        let result = run_stage(Stage::Style, || {
+            cascade_with_states(
+                &self.document,
+                &[&self.user_agent_stylesheet, &self.stylesheet],
+                &self.element_states,
+                &self.media,
+            )
+        })
+        .and_then(|styled_tree| {
+            let containing_block = Dimensions {
+                content: self.viewport,
+                ..Default::default()
+            };
+            run_stage(Stage::Layout, || {
+                let mut layout_root =
+                    layout_tree(&styled_tree, containing_block, &self.layout_context);
+                layout_root.snap_to_pixels(self.media.device_pixel_ratio);
+                let dump = self.dumps_layout.then(|| layout_root.dump());
+                (f(&layout_root), dump)
+            })
+        });
+        match result {
+            Ok((result, dump)) => {
+                if dump.is_some() {
+                    self.layout_dump = dump;
+                }
+                Ok(result)
+            }
+            Err(error) => Err(self.show_error(error)),
+        }
+    }
+
+    /// Display list of the current document. Frames where nothing changed
+    /// since the last one reuse its display list, without styling, laying
+    /// out or painting the document again.
+    pub fn paint(&mut self) -> Result<DisplayList, ChrustyError> {
+        if let (false, Some(display_list)) = (self.needs_paint, &self.display_list) {
+            return Ok(display_list.clone());
+        }
+        // Moved out while painting since `with_layout` borrows the engine.
+        let resources = mem::take(&mut self.resources);
+        let result = self.with_layout(|layout_root| {
+            run_stage(Stage::Paint, || build_display_list(layout_root, &resources))
+        });
+        self.resources = resources;
+        let display_list = result?.map_err(|error| self.show_error(error))?;
+        self.display_list = Some(display_list.clone());
+        self.needs_paint = false;
+        self.invalidator.clear();
+        if self.first_paint_pending {
+            self.first_paint_pending = false;
+            self.lifecycle.emit(LifecycleEvent::FirstPaint);
+        }
+        Ok(display_list)
+    }
+
+    /// Paints the current document and returns what changed since the last
+    /// call, for embedders that composite retained display items themselves.
+    pub fn paint_diff(&mut self) -> Result<Vec<DisplayListChange>, ChrustyError> {
+        let display_list = self.paint()?;
+        let changes = diff_display_lists(&self.last_display_list, &display_list);
+        self.last_display_list = display_list;
+        Ok(changes)
+    }
+
+    /// Writes the current document to `path` as a PDF file, on pages the size
+    /// of the viewport.
+    pub fn export_pdf(&mut self, path: impl AsRef<Path>) -> Result<(), ChrustyError> {
+        let (width, height) = self.export_size()?;
+        let display_list = self.paint()?;
+        let pdf = pdf::export_pdf(&display_list, width, height);
+        let path = path.as_ref();
+        fs::write(path, pdf).map_err(|error| ChrustyError::Write {
+            path: path.display().to_string(),
+            message: error.to_string(),
+        })
+    }
+
+    /// Size of the viewport, for exporting pages that have one.
+    fn export_size(&self) -> Result<(f32, f32), ChrustyError> {
+        let Rect { width, height, .. } = self.viewport;
+        if !(width > 0.0 && width.is_finite() && height > 0.0 && height.is_finite()) {
+            return Err(ChrustyError::InvalidSize { width, height });
+        }
+        Ok((width, height))
+    }
+
+    /// Writes the current document to `path` as an SVG file the size of the
+    /// viewport.
+    pub fn export_svg(&mut self, path: impl AsRef<Path>) -> Result<(), ChrustyError> {
+        let (width, height) = self.export_size()?;
+        let display_list = self.paint()?;
+        let svg = svg::export_svg(&display_list, width, height);
+        let path = path.as_ref();
+        fs::write(path, svg).map_err(|error| ChrustyError::Write {
+            path: path.display().to_string(),
+            message: error.to_string(),
+        })
+    }
+
+    /// Paints one frame of the current document and writes it to `path` as a
+    /// PNG file, sized in device pixels. Screenshots are at most
+    /// `MAX_SCREENSHOT_SIZE` device pixels on a side.
+    pub fn screenshot(&mut self, path: impl AsRef<Path>) -> Result<(), ChrustyError> {
+        self.screenshot_with(&mut Canvas::new(1, 1), path)
+    }
+
+    /// Like `screenshot`, with the frame rendered by `renderer`, e.g. a
+    /// `gpu::GpuRenderer`.
+    pub fn screenshot_with(
+        &mut self,
+        renderer: &mut dyn Renderer,
+        path: impl AsRef<Path>,
+    ) -> Result<(), ChrustyError> {
+        let scale = self.media.device_pixel_ratio;
+        let width = (self.viewport.width * scale).ceil();
+        let height = (self.viewport.height * scale).ceil();
+        let valid = |size: f32| (1.0..=MAX_SCREENSHOT_SIZE).contains(&size);
+        if !(valid(width) && valid(height)) {
+            return Err(ChrustyError::InvalidSize { width, height });
+        }
+        let display_list = self.paint()?;
+        renderer.resize(width as u32, height as u32, scale);
+        renderer.render(None, &display_list);
+        let png = png::encode_png(&renderer.to_image());
+        let path = path.as_ref();
+        fs::write(path, png).map_err(|error| ChrustyError::Write {
+            path: path.display().to_string(),
+            message: error.to_string(),
+        })
+    }
+
+    /// Requests the images of the current document and stylesheet, and
+    /// sizes the `img` elements showing them from their pixels.
+    fn load_images(&mut self) {
+        self.resources.load_images(&self.stylesheet);
+        self.resources.load_content_images(&self.document);
+        for (src, image) in self.resources.images() {
+            let size = Size {
+                width: image.width as f32,
+                height: image.height as f32,
+            };
+            self.layout_context.set_intrinsic_size(src, size);
+        }
+    }
+
+    /// Loads the `@font-face` rules of the current stylesheet, for text to
+    /// be laid out with the faces that loaded.
+    fn load_fonts(&mut self) {
+        self.resources.load_fonts(&self.stylesheet);
+        self.layout_context
+            .set_fonts(self.resources.fonts().clone());
+    }
+
+    fn show_error(&mut self, error: ChrustyError) -> ChrustyError {
+        let (document, stylesheet) = error_page(&error);
+        self.document = document;
+        self.stylesheet = stylesheet;
+        self.update_invalidator();
+        self.needs_paint = true;
+        error
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        rc::Rc,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use crate::{
+        cssom::ColorScheme,
+        engine::Engine,
+        error::{ChrustyError, Stage},
+        layout::{BoxType, Rect, Size},
+        lifecycle::LifecycleEvent,
+        paint::{Color, DisplayCommand, DisplayList, DisplayListChange},
+        parser::{CSSParser, IParser},
+        resource::Image,
+    };
+
+    #[test]
+    fn converts_panics_into_error_page() {
+        let mut engine = Engine::new();
+        assert_eq!(
+            engine.load("<div>Hello</div>", "div { color: red; }"),
+            Ok(())
+        );
+
+        let Err(ChrustyError::Panic { stage, message }) =
+            engine.load("<span></span>", "div { color: red; }")
+        else {
+            panic!("Unsupported tag did not produce an error")
+        };
+        assert_eq!(stage, Stage::Parse);
+        assert!(message.contains("span"));
+        assert!(engine.document().to_string().contains("Engine panicked"));
+
+        let children = engine
+            .with_layout(|layout_tree| layout_tree.children.len())
+            .unwrap();
+        assert_eq!(children, 1);
+    }
+
+    #[test]
+    fn reports_stylesheet_syntax_errors() {
+        let mut engine = Engine::new();
+        let Err(ChrustyError::InvalidStylesheet(error)) =
+            engine.load("<div></div>", "div {\n    color red;\n}")
+        else {
+            panic!("Invalid stylesheet did not produce an error")
+        };
+        assert_eq!((error.line, error.column), (2, 11));
+        assert_eq!(error.snippet(), "    color red;\n          ^");
+        let page = engine.document().to_string();
+        assert!(page.contains("Invalid stylesheet: Expected ':' but found 'r'"));
+        assert!(page.contains("color red;"));
+    }
+
+    #[test]
+    fn catches_layout_panics() {
+        let mut engine = Engine::new();
+        engine.load("<div id=\"widget\"></div>", "").unwrap();
+        engine.register_measure("widget", |_| panic!("The widget can't be measured"));
+        let result = engine.with_layout(|layout_tree| layout_tree.children.len());
+        assert!(matches!(
+            result,
+            Err(ChrustyError::Panic {
+                stage: Stage::Layout,
+                ..
+            })
+        ));
+        engine.unregister_measure("widget");
+        let is_block = engine
+            .with_layout(|layout_tree| matches!(layout_tree.box_type, BoxType::Block(_)))
+            .unwrap();
+        assert!(is_block);
+    }
+
+    #[test]
+    fn dumps_layouts_when_asked() {
+        let mut engine = Engine::new();
+        engine.load("<div><p>Hello</p></div>", "").unwrap();
+        engine.paint().unwrap();
+        assert_eq!(engine.layout_dump(), None);
+
+        engine.set_dumps_layout(true);
+        engine.paint().unwrap();
+        let dump = engine.layout_dump().unwrap();
+        assert!(dump.starts_with("Block <html>"), "{dump}");
+        assert!(dump.contains("Inline \"Hello\""), "{dump}");
+
+        engine.set_dumps_layout(false);
+        assert_eq!(engine.layout_dump(), None);
+    }
+
+    #[test]
+    fn lays_out_deeply_nested_documents() {
+        let depth = 5000;
+        let html = "<div>".repeat(depth) + "a" + &"</div>".repeat(depth);
+        let mut engine = Engine::new();
+        engine.load(&html, "div { padding: 1px; }").unwrap();
+        let height = engine
+            .with_layout(|layout_root| layout_root.dimensions.content.height)
+            .unwrap();
+        // Each div adds its padding around the line of text, snapped to
+        // whole pixels.
+        assert_eq!(height, 2.0 * depth as f32 + 19.0);
+        assert!(!engine.paint().unwrap().is_empty());
+    }
+
+    #[test]
+    fn paints_img_sources() {
+        let mut engine = Engine::new();
+        let requested = Rc::new(RefCell::new(vec![]));
+        let log = Rc::clone(&requested);
+        engine.on_image_request(move |url| {
+            log.borrow_mut().push(url.to_string());
+            (url == "wide.png").then(|| Image {
+                width: 2,
+                height: 1,
+                pixels: vec![255, 0, 0, 255, 0, 0, 255, 255],
+            })
+        });
+        let html = "<div><img src=\"wide.png\"><img src=\"missing.png\"></div>";
+        engine.load(html, "img { width: 20px; }").unwrap();
+        assert_eq!(*requested.borrow(), ["wide.png", "missing.png"]);
+
+        // The image is sized from its pixels and stretched over the box.
+        let list = engine.paint().unwrap();
+        let images: Vec<Rect> = list
+            .iter()
+            .filter_map(|item| match &item.command {
+                DisplayCommand::ScaledImage(image, rect) => {
+                    assert_eq!(image.width, 2);
+                    Some(*rect)
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(images.len(), 1);
+        assert_eq!((images[0].width, images[0].height), (20.0, 10.0));
+    }
+
+    #[test]
+    fn emits_display_list_diffs() {
+        let mut engine = Engine::new();
+        let css = "div { height: 10px; background: red; }";
+        engine.load("<div></div>", css).unwrap();
+        let first = engine.paint_diff().unwrap();
+        assert!(matches!(
+            first[..],
+            [DisplayListChange::Added { index: 0, .. }]
+        ));
+        assert!(engine.paint_diff().unwrap().is_empty());
+
+        engine.load("<div></div><div></div>", css).unwrap();
+        let second = engine.paint_diff().unwrap();
+        assert!(matches!(
+            second[..],
+            [DisplayListChange::Added { index: 1, .. }]
+        ));
+    }
+
+    #[test]
+    fn restyles_after_stylesheet_edits() {
+        let mut engine = Engine::new();
+        engine
+            .load("<div></div>", "div { height: 10px; background: red; }")
+            .unwrap();
+        assert!(engine.needs_paint());
+        engine.paint_diff().unwrap();
+        assert!(!engine.needs_paint());
+
+        let rule = CSSParser::new("div { background: blue; }")
+            .try_parse_rule()
+            .unwrap();
+        let index = engine.edit_stylesheet(|stylesheet| stylesheet.insert_rule(rule, 1));
+        assert_eq!(index, Ok(1));
+        assert!(engine.needs_paint());
+        let changes = engine.paint_diff().unwrap();
+        assert!(matches!(
+            &changes[..],
+            [DisplayListChange::Updated { item }]
+                if item.command == DisplayCommand::SolidColor(Color { r: 0, g: 0, b: 255, a: 255 }, item.command.rect())
+        ));
+
+        // Edits that don't change the sheet don't invalidate it.
+        let error = engine.edit_stylesheet(|stylesheet| stylesheet.delete_rule(5));
+        assert!(error.is_err());
+        assert!(!engine.needs_paint());
+    }
+
+    #[test]
+    fn emits_lifecycle_events() {
+        let events = Rc::new(RefCell::new(vec![]));
+        let mut engine = Engine::new();
+        let recorded = Rc::clone(&events);
+        engine.on_lifecycle(move |event, _| recorded.borrow_mut().push(event));
+
+        engine.load("<div></div>", "").unwrap();
+        assert!(engine.lifecycle().reached(LifecycleEvent::DomParsed));
+        assert!(!engine.lifecycle().reached(LifecycleEvent::FirstPaint));
+        engine.paint().unwrap();
+        engine.paint().unwrap();
+        assert!(engine.lifecycle().reached(LifecycleEvent::FirstPaint));
+        engine.load("<p></p>", "").unwrap();
+        assert!(!engine.lifecycle().reached(LifecycleEvent::FirstPaint));
+
+        assert_eq!(
+            *events.borrow(),
+            [
+                LifecycleEvent::LoadStarted,
+                LifecycleEvent::DomParsed,
+                LifecycleEvent::ResourcesComplete,
+                LifecycleEvent::FirstPaint,
+                LifecycleEvent::NavigationAway,
+                LifecycleEvent::LoadStarted,
+                LifecycleEvent::DomParsed,
+                LifecycleEvent::ResourcesComplete,
+            ]
+        );
+        let history = engine.lifecycle().history();
+        assert!(history.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+    }
+
+    #[test]
+    fn restyles_hovered_elements() {
+        let mut engine = Engine::new();
+        let css = "
+            div { height: 10px; background: red; }
+            div:hover { background: blue; }
+        ";
+        engine.load("<div></div><div></div>", css).unwrap();
+        let is_blue = |list: &DisplayList, index: usize| {
+            matches!(
+                list[index].command,
+                DisplayCommand::SolidColor(
+                    Color {
+                        r: 0,
+                        g: 0,
+                        b: 255,
+                        a: 255
+                    },
+                    _
+                )
+            )
+        };
+        let first = engine.paint().unwrap();
+        assert!(!is_blue(&first, 0) && !is_blue(&first, 1));
+
+        // The second div is below the first.
+        engine.on_mouse_move(5.0, 15.0).unwrap();
+        assert!(engine.needs_paint());
+        let hovered = engine.paint().unwrap();
+        assert!(!is_blue(&hovered, 0) && is_blue(&hovered, 1));
+
+        engine.on_mouse_move(6.0, 16.0).unwrap();
+        assert!(!engine.needs_paint());
+        // No rule uses `:active` or `:focus`, so pressing doesn't need a restyle.
+        engine.on_mouse_down(6.0, 16.0).unwrap();
+        engine.on_mouse_up();
+        assert!(!engine.needs_paint());
+        engine.on_mouse_leave();
+        assert!(engine.needs_paint());
+        let left = engine.paint().unwrap();
+        assert!(!is_blue(&left, 0) && !is_blue(&left, 1));
+    }
+
+    #[test]
+    fn reuses_display_lists_while_nothing_changes() {
+        let mut engine = Engine::new();
+        engine
+            .load("<div id=\"widget\"></div>", "div { background: red; }")
+            .unwrap();
+        let measured = Arc::new(AtomicUsize::new(0));
+        let count = Arc::clone(&measured);
+        engine.register_measure("widget", move |_| {
+            count.fetch_add(1, Ordering::Relaxed);
+            Size {
+                width: 10.0,
+                height: 10.0,
+            }
+        });
+        let first = engine.paint().unwrap();
+        let layouts = measured.load(Ordering::Relaxed);
+        assert!(layouts > 0);
+
+        // Nothing changed, so the document isn't laid out again.
+        assert_eq!(engine.paint().unwrap(), first);
+        assert_eq!(measured.load(Ordering::Relaxed), layouts);
+
+        engine.set_viewport(Rect {
+            width: 400.0,
+            ..engine.viewport()
+        });
+        engine.paint().unwrap();
+        assert!(measured.load(Ordering::Relaxed) > layouts);
+    }
+
+    #[test]
+    fn exports_pdf_files() {
+        let mut engine = Engine::new();
+        engine
+            .load("<div><p>Hello</p></div>", "div { background: red; }")
+            .unwrap();
+        let path = std::env::temp_dir().join(format!("chrusty-{}.pdf", std::process::id()));
+        engine.export_pdf(&path).unwrap();
+        let pdf = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(pdf.starts_with(b"%PDF-1.4"));
+        assert!(String::from_utf8_lossy(&pdf).contains("/Count 1"));
+
+        let missing = std::env::temp_dir()
+            .join("chrusty-missing")
+            .join("page.pdf");
+        let error = engine.export_pdf(&missing).unwrap_err();
+        assert!(matches!(error, ChrustyError::Write { .. }), "{error}");
+
+        engine.set_viewport(Rect {
+            width: 100.0,
+            ..Default::default()
+        });
+        let error = engine.export_pdf(&path).unwrap_err();
+        assert_eq!(
+            error,
+            ChrustyError::InvalidSize {
+                width: 100.0,
+                height: 0.0
+            }
+        );
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn exports_svg_files() {
+        let mut engine = Engine::new();
+        engine
+            .load("<div><p>Hello</p></div>", "div { background: red; }")
+            .unwrap();
+        let path = std::env::temp_dir().join(format!("chrusty-{}.svg", std::process::id()));
+        engine.export_svg(&path).unwrap();
+        let svg = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.contains(">Hello</text>"));
+
+        for (width, height) in [(0.0, 100.0), (100.0, -1.0), (f32::NAN, 100.0)] {
+            engine.set_viewport(Rect {
+                width,
+                height,
+                ..Default::default()
+            });
+            let error = engine.export_svg(&path).unwrap_err();
+            assert!(matches!(error, ChrustyError::InvalidSize { .. }), "{error}");
+        }
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn saves_screenshots() {
+        let mut engine = Engine::new();
+        engine
+            .load("<div></div>", "div { height: 10px; background: red; }")
+            .unwrap();
+        engine.set_viewport(Rect {
+            width: 30.0,
+            height: 20.0,
+            ..Default::default()
+        });
+        engine.set_device_pixel_ratio(2.0).unwrap();
+        let path = std::env::temp_dir().join(format!("chrusty-{}.png", std::process::id()));
+        engine.screenshot(&path).unwrap();
+        let png = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        // 60 by 40 device pixels.
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\0\x3c\0\0\0\x28"));
+        // The first row is red.
+        let row: Vec<u8> = [255, 0, 0, 255].repeat(60);
+        assert!(png.windows(row.len()).any(|window| window == row));
+
+        // Empty or oversized screenshots are rejected before painting.
+        for (size, scale) in [
+            (0.0, 1.0),
+            (-1.0, 1.0),
+            (f32::NAN, 1.0),
+            (30.0, 1e9),
+            (1e5, 1.0),
+        ] {
+            engine.set_viewport(Rect {
+                width: size,
+                height: size,
+                ..Default::default()
+            });
+            engine.set_device_pixel_ratio(scale).unwrap();
+            let error = engine.screenshot(&path).unwrap_err();
+            assert!(matches!(error, ChrustyError::InvalidSize { .. }), "{error}");
+        }
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn restyles_on_resize() {
+        let mut engine = Engine::new();
+        let css = "
+            div { height: 10vh; }
+            @media (max-width: 500px) { div { height: 5px; } }
+        ";
+        engine.load("<div></div>", css).unwrap();
+        let div_height = |engine: &mut Engine| {
+            engine
+                .with_layout(|root| root.children[0].dimensions.content.height)
+                .unwrap()
+        };
+        assert_eq!(div_height(&mut engine), 60.0);
+
+        engine.paint().unwrap();
+        engine.set_viewport(Rect {
+            width: 800.0,
+            height: 300.0,
+            ..engine.viewport()
+        });
+        assert!(engine.needs_paint());
+        assert_eq!(engine.media().height, 300.0);
+        assert_eq!(div_height(&mut engine), 30.0);
+        engine.set_viewport(Rect {
+            width: 400.0,
+            ..engine.viewport()
+        });
+        assert_eq!(div_height(&mut engine), 5.0);
+
+        engine.paint().unwrap();
+        engine.set_color_scheme(ColorScheme::Light);
+        assert!(!engine.needs_paint());
+        engine.set_device_pixel_ratio(2.0).unwrap();
+        assert!(engine.needs_paint());
+
+        engine.paint().unwrap();
+        for ratio in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            let error = engine.set_device_pixel_ratio(ratio).unwrap_err();
+            assert!(
+                matches!(error, ChrustyError::InvalidDevicePixelRatio(_)),
+                "{error}"
+            );
+        }
+        assert_eq!(engine.media().device_pixel_ratio, 2.0);
+        assert!(!engine.needs_paint());
+    }
+}
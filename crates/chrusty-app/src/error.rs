@@ -0,0 +1,74 @@
+use std::{
+    any::Any,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::parser::ParseError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stage {
+    Parse,
+    Style,
+    Layout,
+    Paint,
+}
+
+impl Display for Stage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let output = match self {
+            Self::Parse => "parse",
+            Self::Style => "style",
+            Self::Layout => "layout",
+            Self::Paint => "paint",
+        };
+        write!(f, "{}", output)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChrustyError {
+    /// A panic caught at the engine boundary while processing a document.
+    Panic { stage: Stage, message: String },
+    /// The stylesheet of a document has invalid syntax.
+    InvalidStylesheet(ParseError),
+    /// A file the engine exported couldn't be written.
+    Write { path: String, message: String },
+    /// A page or image can't be exported at this size, in px or device
+    /// pixels.
+    InvalidSize { width: f32, height: f32 },
+    /// Device pixel ratios are finite and positive.
+    InvalidDevicePixelRatio(f32),
+}
+
+impl ChrustyError {
+    pub fn from_panic(stage: Stage, payload: Box<dyn Any + Send>) -> ChrustyError {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast::<&str>() {
+                Ok(message) => message.to_string(),
+                Err(_) => "unknown panic".to_string(),
+            },
+        };
+        ChrustyError::Panic { stage, message }
+    }
+}
+
+impl Display for ChrustyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Panic { stage, message } => {
+                write!(f, "Engine panicked during {}: {}", stage, message)
+            }
+            Self::InvalidStylesheet(error) => write!(f, "Invalid stylesheet: {}", error),
+            Self::Write { path, message } => write!(f, "Failed to write {}: {}", path, message),
+            Self::InvalidSize { width, height } => {
+                write!(f, "Invalid export size {}x{}", width, height)
+            }
+            Self::InvalidDevicePixelRatio(ratio) => {
+                write!(f, "Invalid device pixel ratio {}", ratio)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ChrustyError {}
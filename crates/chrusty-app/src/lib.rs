@@ -0,0 +1,9 @@
+pub use chrusty_core::{
+    atom, color, cssom, dom, font, json, layout, parser, resource, style, text, utils, values,
+};
+pub use chrusty_paint::{self as paint, fixtures};
+
+pub mod engine;
+pub mod error;
+pub mod lifecycle;
+pub mod server;
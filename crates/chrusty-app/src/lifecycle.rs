@@ -0,0 +1,65 @@
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LifecycleEvent {
+    LoadStarted,
+    DomParsed,
+    ResourcesComplete,
+    FirstPaint,
+    NavigationAway,
+}
+
+pub type LifecycleCallback = dyn FnMut(LifecycleEvent, Duration);
+
+/// Dispatches page lifecycle events to listeners and keeps a history of them.
+/// Timestamps are measured from the creation of the lifecycle, so they are
+/// monotonic.
+pub struct Lifecycle {
+    start: Instant,
+    listeners: Vec<Box<LifecycleCallback>>,
+    history: Vec<(LifecycleEvent, Duration)>,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lifecycle {
+    pub fn new() -> Lifecycle {
+        Lifecycle {
+            start: Instant::now(),
+            listeners: vec![],
+            history: vec![],
+        }
+    }
+
+    pub fn add_listener(&mut self, listener: impl FnMut(LifecycleEvent, Duration) + 'static) {
+        self.listeners.push(Box::new(listener));
+    }
+
+    pub fn history(&self) -> &[(LifecycleEvent, Duration)] {
+        &self.history
+    }
+
+    /// Whether `event` happened since the last `LoadStarted`.
+    pub fn reached(&self, event: LifecycleEvent) -> bool {
+        let load_started = self
+            .history
+            .iter()
+            .rposition(|(past, _)| *past == LifecycleEvent::LoadStarted)
+            .unwrap_or(0);
+        self.history[load_started..]
+            .iter()
+            .any(|(past, _)| *past == event)
+    }
+
+    pub fn emit(&mut self, event: LifecycleEvent) {
+        let timestamp = self.start.elapsed();
+        self.history.push((event, timestamp));
+        for listener in &mut self.listeners {
+            listener(event, timestamp);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use std::{env, fs, process};
+
+#[cfg(feature = "gpu")]
+use chrusty_app::paint::gpu::GpuRenderer;
+use chrusty_app::{
+    engine::Engine,
+    layout::Rect,
+    paint::Renderer,
+    parser::{CSSParser, IParser},
+    server::Server,
+};
+
+const DEFAULT_SERVE_ADDR: &str = "127.0.0.1:8000";
+
+const DEFAULT_SCREENSHOT_SIZE: &str = "800x600";
+
+/// Value following the flag `name` in `args`.
+fn option<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    let i = args.iter().position(|arg| arg == name)?;
+    let value = args.get(i + 1);
+    Some(value.unwrap_or_else(|| panic!("{name} requires a value")))
+}
+
+fn serve(args: &[String]) {
+    let root = args.first().map(String::as_str).unwrap_or(".");
+    let addr = option(args, "--addr").unwrap_or(DEFAULT_SERVE_ADDR);
+    let server = Server::bind(root, addr).expect("Failed to bind dev server");
+    println!(
+        "Serving {} on http://{}",
+        root,
+        server.local_addr().unwrap()
+    );
+    server.run().expect("Dev server stopped unexpectedly");
+}
+
+/// Renderer of screenshots taken with `--gpu`.
+#[cfg(feature = "gpu")]
+fn gpu_renderer() -> Box<dyn Renderer> {
+    Box::new(GpuRenderer::new().expect("No GPU adapter is available"))
+}
+
+#[cfg(not(feature = "gpu"))]
+fn gpu_renderer() -> Box<dyn Renderer> {
+    panic!("--gpu requires chrusty to be built with the gpu feature")
+}
+
+/// Renders one frame of an HTML file, with the stylesheet given by `--css`,
+/// and saves it as a PNG file, without opening a window. With `--gpu`, the
+/// frame is rendered on the GPU:
+///
+/// ```sh
+/// chrusty page.html --screenshot page.png [--css page.css] [--size 800x600] [--scale 2] [--gpu]
+/// ```
+fn screenshot(args: &[String]) {
+    let output = option(args, "--screenshot").unwrap();
+    let input = args.first().expect("screenshot requires an HTML file");
+    let html = fs::read_to_string(input).expect("Failed to read the HTML file");
+    let css = match option(args, "--css") {
+        Some(path) => fs::read_to_string(path).expect("Failed to read the stylesheet"),
+        None => String::new(),
+    };
+    let size = option(args, "--size").unwrap_or(DEFAULT_SCREENSHOT_SIZE);
+    let (width, height) = size
+        .split_once('x')
+        .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+        .expect("--size should be WIDTHxHEIGHT in px");
+    let scale = option(args, "--scale").map_or(1.0, |scale| {
+        scale.parse().expect("--scale should be a number")
+    });
+
+    let mut renderer = args.iter().any(|arg| arg == "--gpu").then(gpu_renderer);
+
+    let mut engine = Engine::new();
+    engine.set_viewport(Rect {
+        width,
+        height,
+        ..Default::default()
+    });
+    let result = engine
+        .set_device_pixel_ratio(scale)
+        .and_then(|()| engine.load(&html, &css))
+        .and_then(|()| match &mut renderer {
+            Some(renderer) => engine.screenshot_with(renderer.as_mut(), output),
+            None => engine.screenshot(output),
+        });
+    if let Err(error) = result {
+        eprintln!("{error}");
+        process::exit(1);
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("serve") {
+        return serve(&args[1..]);
+    }
+    if args.iter().any(|arg| arg == "--screenshot") {
+        return screenshot(&args);
+    }
+
+    let input = "
+            div#id.hello {
+                height: 100%;
+                background: purple;
+                color: #ffffff !important;
+            }
+
+            div.my-div,div.my-div-2 {
+                width: 100px;
+                height: 100%;
+                background: blue;
+                color: #ffffff;
+            }
+
+            html {
+                background: green;
+            }
+        ";
+    let parsed = CSSParser::new(input).parse();
+    print!("{}", parsed);
+}
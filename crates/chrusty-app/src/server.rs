@@ -0,0 +1,263 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::{Component, Path, PathBuf},
+    thread,
+    time::{Duration, UNIX_EPOCH},
+};
+
+const CHUNK_SIZE: usize = 1024;
+
+/// Tiny static file server used to serve test fixtures over HTTP.
+pub struct Server {
+    listener: TcpListener,
+    root: PathBuf,
+    chunk_delay: Option<Duration>,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    if_none_match: Option<String>,
+}
+
+impl Server {
+    pub fn bind(root: impl Into<PathBuf>, addr: &str) -> io::Result<Server> {
+        Ok(Server {
+            listener: TcpListener::bind(addr)?,
+            root: root.into(),
+            chunk_delay: None,
+        })
+    }
+
+    /// Sleep between body chunks so progressive loading paths can be exercised.
+    pub fn with_chunk_delay(mut self, delay: Duration) -> Server {
+        self.chunk_delay = Some(delay);
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn run(self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let root = self.root.clone();
+            let chunk_delay = self.chunk_delay;
+            thread::spawn(move || {
+                let _ = handle_connection(stream, &root, chunk_delay);
+            });
+        }
+        Ok(())
+    }
+
+    /// Runs the server on a background thread and returns the bound address.
+    pub fn spawn(self) -> io::Result<SocketAddr> {
+        let addr = self.local_addr()?;
+        thread::spawn(move || self.run());
+        Ok(addr)
+    }
+}
+
+fn read_request(stream: &TcpStream) -> io::Result<Option<Request>> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Ok(None);
+    };
+
+    let mut if_none_match = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("if-none-match") {
+                if_none_match = Some(value.trim().to_string());
+            }
+        }
+    }
+
+    let path = target.split(['?', '#']).next().unwrap_or("/");
+    Ok(Some(Request {
+        method: method.to_string(),
+        path: percent_decode(path),
+        if_none_match,
+    }))
+}
+
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut output = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(byte) = input
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                output.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        output.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+/// Maps a request path onto the served directory, refusing anything that
+/// would escape it.
+fn resolve_path(root: &Path, request_path: &str) -> Option<PathBuf> {
+    let mut path = root.to_path_buf();
+    for component in Path::new(request_path.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    if path.is_dir() {
+        path.push("index.html");
+    }
+    Some(path)
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("html") | Some("htm") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("svg") => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}
+
+fn etag(metadata: &fs::Metadata) -> String {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_nanos())
+        .unwrap_or(0);
+    format!("\"{:x}-{:x}\"", metadata.len(), modified)
+}
+
+fn write_status(stream: &mut TcpStream, status: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        status
+    )
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    root: &Path,
+    chunk_delay: Option<Duration>,
+) -> io::Result<()> {
+    let Some(request) = read_request(&stream)? else {
+        return write_status(&mut stream, "400 Bad Request");
+    };
+    if request.method != "GET" && request.method != "HEAD" {
+        return write_status(&mut stream, "405 Method Not Allowed");
+    }
+    let Some(path) = resolve_path(root, &request.path) else {
+        return write_status(&mut stream, "403 Forbidden");
+    };
+    let (Ok(metadata), Ok(body)) = (fs::metadata(&path), fs::read(&path)) else {
+        return write_status(&mut stream, "404 Not Found");
+    };
+
+    let etag = etag(&metadata);
+    if request.if_none_match.as_deref() == Some(etag.as_str()) {
+        return write!(
+            stream,
+            "HTTP/1.1 304 Not Modified\r\nETag: {}\r\nConnection: close\r\n\r\n",
+            etag
+        );
+    }
+
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nETag: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        content_type(&path),
+        body.len(),
+        etag
+    )?;
+    if request.method == "HEAD" {
+        return Ok(());
+    }
+    for chunk in body.chunks(CHUNK_SIZE) {
+        stream.write_all(chunk)?;
+        stream.flush()?;
+        if let Some(delay) = chunk_delay {
+            thread::sleep(delay);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        io::{Read, Write},
+        net::{SocketAddr, TcpStream},
+    };
+
+    use super::Server;
+
+    fn get(addr: SocketAddr, path: &str, headers: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "GET {} HTTP/1.1\r\nHost: localhost\r\n{}\r\n",
+            path, headers
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn serves_fixtures() {
+        let root = std::env::temp_dir().join(format!("chrusty-serve-{}", std::process::id()));
+        fs::create_dir_all(root.join("css")).unwrap();
+        fs::write(root.join("index.html"), "<div>Hello</div>").unwrap();
+        fs::write(root.join("css/main.css"), "div { color: red; }").unwrap();
+        let addr = Server::bind(&root, "127.0.0.1:0").unwrap().spawn().unwrap();
+
+        let index = get(addr, "/", "");
+        assert!(index.starts_with("HTTP/1.1 200 OK"));
+        assert!(index.contains("Content-Type: text/html"));
+        assert!(index.ends_with("<div>Hello</div>"));
+
+        let css = get(addr, "/css/main.css?v=1", "");
+        assert!(css.contains("Content-Type: text/css"));
+        let etag = css
+            .lines()
+            .find_map(|line| line.strip_prefix("ETag: "))
+            .unwrap();
+        let cached = get(
+            addr,
+            "/css/main.css",
+            &format!("If-None-Match: {}\r\n", etag),
+        );
+        assert!(cached.starts_with("HTTP/1.1 304 Not Modified"));
+
+        assert!(get(addr, "/missing.html", "").starts_with("HTTP/1.1 404"));
+        assert!(get(addr, "/../secret", "").starts_with("HTTP/1.1 403"));
+        fs::remove_dir_all(root).unwrap();
+    }
+}
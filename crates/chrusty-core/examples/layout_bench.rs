@@ -0,0 +1,87 @@
+//! Times layout of wide and deep documents on one thread and on as many as
+//! the machine runs in parallel, or as given:
+//!
+//! ```sh
+//! cargo run --release -p chrusty-core --example layout_bench [threads]
+//! ```
+
+use std::{
+    env, thread,
+    time::{Duration, Instant},
+};
+
+use chrusty_core::{
+    layout::{layout_tree, Dimensions, LayoutContext},
+    parser::{CSSParser, HTMLParser, IParser},
+    style::get_styled_node,
+};
+
+const CSS: &str = "
+    div.section {
+        padding: 4px;
+        margin-bottom: 8px;
+    }
+
+    p.box {
+        width: 50%;
+        height: 20px;
+    }
+";
+
+const RUNS: u32 = 5;
+
+/// Many sibling sections, each with a few paragraphs of text.
+fn wide_document(sections: usize) -> String {
+    let section = "<div class=\"section\"><p>lorem ipsum dolor sit amet consectetur</p>\
+                   <div><p class=\"box\"></p><p>adipiscing elit sed do eiusmod</p></div></div>";
+    format!("<div>{}</div>", section.repeat(sections))
+}
+
+/// Sections nested `depth` deep, each with a paragraph next to the next one.
+fn deep_document(depth: usize) -> String {
+    let open = "<div class=\"section\"><p>tempor incididunt ut labore</p>";
+    format!(
+        "<div>{}{}</div>",
+        open.repeat(depth),
+        "</div>".repeat(depth)
+    )
+}
+
+/// Average time to lay out `html` on `threads` threads.
+fn time_layout(html: &str, threads: usize) -> Duration {
+    let stylesheet = CSSParser::new(CSS).parse();
+    let dom = HTMLParser::new(html).parse();
+    let styled_dom = get_styled_node(&dom, &stylesheet);
+    let mut viewport = Dimensions::default();
+    viewport.content.width = 1024.0;
+    let mut context = LayoutContext::new();
+    context.set_threads(threads);
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        layout_tree(&styled_dom, viewport, &context);
+    }
+    start.elapsed() / RUNS
+}
+
+fn main() {
+    let threads = match env::args().nth(1) {
+        Some(threads) => threads.parse().expect("threads should be a number"),
+        None => thread::available_parallelism().map_or(1, |threads| threads.get()),
+    };
+    let documents = [
+        ("wide (2000 sections)", wide_document(2000)),
+        ("wide (20000 sections)", wide_document(20000)),
+        ("deep (500 levels)", deep_document(500)),
+    ];
+    println!(
+        "{:<24}{:>14}{:>14}",
+        "document",
+        "1 thread",
+        format!("{threads} threads")
+    );
+    for (name, html) in &documents {
+        let sequential = time_layout(html, 1);
+        let parallel = time_layout(html, threads);
+        println!("{name:<24}{sequential:>14.2?}{parallel:>14.2?}");
+    }
+}
@@ -0,0 +1,126 @@
+use std::{
+    borrow::Borrow,
+    collections::HashSet,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Deref,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+/// An interned string, used for identifiers such as tag names, classes, ids
+/// and attribute names that are compared over and over during selector
+/// matching.
+///
+/// Equal strings share one allocation, so cloning an atom is a reference
+/// count bump and comparing two atoms is a pointer comparison.
+#[derive(Clone)]
+pub struct Atom(Arc<str>);
+
+fn interned() -> &'static Mutex<HashSet<Arc<str>>> {
+    static ATOMS: OnceLock<Mutex<HashSet<Arc<str>>>> = OnceLock::new();
+    ATOMS.get_or_init(Default::default)
+}
+
+impl Atom {
+    pub fn new(string: &str) -> Atom {
+        let mut atoms = interned().lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(atom) = atoms.get(string) {
+            return Atom(atom.clone());
+        }
+        let atom: Arc<str> = Arc::from(string);
+        atoms.insert(atom.clone());
+        Atom(atom)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for Atom {
+    fn eq(&self, other: &Atom) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Atom {}
+
+impl PartialEq<str> for Atom {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Atom {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+// Hashed by content rather than by address, so that maps keyed by atoms can
+// be looked up with a plain `&str`.
+impl Hash for Atom {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl Borrow<str> for Atom {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for Atom {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Atom {
+    fn from(string: &str) -> Atom {
+        Atom::new(string)
+    }
+}
+
+impl From<String> for Atom {
+    fn from(string: String) -> Atom {
+        Atom::new(&string)
+    }
+}
+
+impl fmt::Display for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Debug for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::Atom;
+
+    #[test]
+    fn interns_equal_strings_once() {
+        let a = Atom::from("container");
+        let b = Atom::from(String::from("container"));
+        assert_eq!(a, b);
+        assert!(std::ptr::eq(a.as_str(), b.as_str()));
+        assert_ne!(a, Atom::from("Container"));
+        assert_eq!(a, "container");
+
+        let mut map = HashMap::new();
+        map.insert(a, 1);
+        assert_eq!(map.get("container"), Some(&1));
+        assert_eq!(map.get(&b), Some(&1));
+    }
+}
@@ -0,0 +1,148 @@
+//! Counting bloom filter of the tags, ids and classes of an element's
+//! ancestors, for rejecting selectors with descendant combinators without
+//! walking up the tree.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use crate::{cssom::SimpleSelector, dom::ElementData};
+
+/// Number of counters. Each key sets two of them.
+const FILTER_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, Copy, Hash)]
+enum Key<'a> {
+    Tag(&'a str),
+    Id(&'a str),
+    Class(&'a str),
+}
+
+/// Hashes of the counters set by `key`.
+fn slots(key: Key) -> [usize; 2] {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    let hash = hasher.finish();
+    [
+        (hash as usize) % FILTER_SIZE,
+        ((hash >> 32) as usize) % FILTER_SIZE,
+    ]
+}
+
+/// Ancestors of the element being styled, pushed and popped as a tree walk
+/// enters and leaves them. A `false` answer is exact, a `true` one may be a
+/// false positive.
+#[derive(Debug)]
+pub struct AncestorFilter {
+    counters: Box<[u8; FILTER_SIZE]>,
+    /// Slots set by each pushed element, to unset them when it's popped.
+    pushed: Vec<Vec<usize>>,
+}
+
+impl Default for AncestorFilter {
+    fn default() -> Self {
+        AncestorFilter {
+            counters: Box::new([0; FILTER_SIZE]),
+            pushed: vec![],
+        }
+    }
+}
+
+impl AncestorFilter {
+    pub fn new() -> AncestorFilter {
+        AncestorFilter::default()
+    }
+
+    /// Number of elements in the filter.
+    pub fn depth(&self) -> usize {
+        self.pushed.len()
+    }
+
+    pub fn push(&mut self, element: &ElementData) {
+        let tag = element.tag_type.to_string();
+        let keys = std::iter::once(Key::Tag(&tag))
+            .chain(element.id().map(|id| Key::Id(id)))
+            .chain(element.classes().iter().map(|class| Key::Class(class)));
+        let mut set = vec![];
+        for slot in keys.flat_map(slots) {
+            let counter = &mut self.counters[slot];
+            // Saturated counters stay set, as their count is lost.
+            if *counter < u8::MAX {
+                *counter += 1;
+                set.push(slot);
+            }
+        }
+        self.pushed.push(set);
+    }
+
+    /// Removes the element pushed last.
+    pub fn pop(&mut self) {
+        for slot in self.pushed.pop().unwrap_or_default() {
+            self.counters[slot] -= 1;
+        }
+    }
+
+    fn may_contain(&self, key: Key) -> bool {
+        slots(key).iter().all(|slot| self.counters[*slot] > 0)
+    }
+
+    /// Whether an ancestor may match `selector`, going by its tag, id and
+    /// classes. Other parts of the selector aren't checked.
+    pub fn may_match(&self, selector: &SimpleSelector) -> bool {
+        let tag = selector.tag.as_ref().map(|tag| tag.to_string());
+        tag.iter().all(|tag| self.may_contain(Key::Tag(tag)))
+            && selector.id.iter().all(|id| self.may_contain(Key::Id(id)))
+            && selector
+                .class
+                .iter()
+                .all(|class| self.may_contain(Key::Class(class)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        atom::Atom,
+        bloom::AncestorFilter,
+        cssom::{new_css_selector, CSSSelector},
+        dom::{ElementData, TagType},
+    };
+
+    #[test]
+    fn tracks_pushed_ancestors() {
+        let element = |tag, attributes: &[(&str, &str)]| {
+            let attributes = attributes
+                .iter()
+                .map(|(name, value)| (Atom::from(*name), value.to_string()))
+                .collect::<HashMap<_, _>>();
+            ElementData::new(tag, attributes)
+        };
+        let selector = |tag, class: &[&str], id: Option<&str>| {
+            let class = class.iter().map(|class| class.to_string()).collect();
+            let CSSSelector::SimpleSelector(selector) =
+                new_css_selector(tag, class, id.map(str::to_string))
+            else {
+                unreachable!()
+            };
+            selector
+        };
+
+        let mut filter = AncestorFilter::new();
+        filter.push(&element(TagType::Div, &[("class", "nav main")]));
+        filter.push(&element(TagType::P, &[("id", "intro")]));
+        assert_eq!(filter.depth(), 2);
+        assert!(filter.may_match(&selector(Some(TagType::Div), &["main"], None)));
+        assert!(filter.may_match(&selector(None, &["nav", "main"], Some("intro"))));
+        assert!(!filter.may_match(&selector(Some(TagType::Style), &[], None)));
+        assert!(!filter.may_match(&selector(None, &["footer"], None)));
+
+        filter.pop();
+        assert!(!filter.may_match(&selector(None, &[], Some("intro"))));
+        filter.pop();
+        assert!(!filter.may_match(&selector(None, &["nav"], None)));
+        assert_eq!(filter.depth(), 0);
+    }
+}
@@ -0,0 +1,125 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::cssom::CSSValue;
+
+/// RGBA color with straight alpha. The default is transparent.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const BLACK: Color = Color {
+        r: 0,
+        g: 0,
+        b: 0,
+        a: 255,
+    };
+
+    pub const TRANSPARENT: Color = Color {
+        r: 0,
+        g: 0,
+        b: 0,
+        a: 0,
+    };
+
+    pub fn from_css_value(value: &CSSValue) -> Option<Color> {
+        match value {
+            CSSValue::Color(color) => Some(*color),
+            CSSValue::Keyword(keyword) => Color::from_keyword(keyword),
+            _ => None,
+        }
+    }
+
+    /// Parses a hex color or a color name.
+    pub fn from_keyword(keyword: &str) -> Option<Color> {
+        let keyword = keyword.trim();
+        Color::from_hex(keyword).or_else(|| Color::from_name(&keyword.to_ascii_lowercase()))
+    }
+
+    /// Parses `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`.
+    pub fn from_hex(hex: &str) -> Option<Color> {
+        let digits = hex.strip_prefix('#')?;
+        let channel = |i: usize, len: usize| {
+            let value = u8::from_str_radix(digits.get(i * len..(i + 1) * len)?, 16).ok()?;
+            Some(if len == 1 { value * 17 } else { value })
+        };
+        let (len, has_alpha) = match digits.len() {
+            3 => (1, false),
+            4 => (1, true),
+            6 => (2, false),
+            8 => (2, true),
+            _ => return None,
+        };
+        Some(Color {
+            r: channel(0, len)?,
+            g: channel(1, len)?,
+            b: channel(2, len)?,
+            a: match has_alpha {
+                true => channel(3, len)?,
+                false => 255,
+            },
+        })
+    }
+
+    fn from_name(name: &str) -> Option<Color> {
+        let (r, g, b) = match name {
+            "black" => (0, 0, 0),
+            "white" => (255, 255, 255),
+            "red" => (255, 0, 0),
+            "green" => (0, 128, 0),
+            "blue" => (0, 0, 255),
+            "yellow" => (255, 255, 0),
+            "purple" => (128, 0, 128),
+            "gray" | "grey" => (128, 128, 128),
+            "orange" => (255, 165, 0),
+            "transparent" => return Some(Color::TRANSPARENT),
+            _ => return None,
+        };
+        Some(Color { r, g, b, a: 255 })
+    }
+}
+
+/// Serializes as `rgb()`, or `rgba()` when the color isn't opaque.
+impl Display for Color {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.a {
+            255 => write!(f, "rgb({}, {}, {})", self.r, self.g, self.b),
+            a => {
+                let alpha = (a as f32 / 255.0 * 1000.0).round() / 1000.0;
+                write!(f, "rgba({}, {}, {}, {})", self.r, self.g, self.b, alpha)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{color::Color, cssom::CSSValue};
+
+    #[test]
+    fn parses_colors() {
+        let keyword = |value: &str| Color::from_css_value(&CSSValue::Keyword(value.to_string()));
+        let red = Color {
+            r: 255,
+            g: 0,
+            b: 0,
+            a: 255,
+        };
+        assert_eq!(keyword("#f00"), Some(red));
+        assert_eq!(keyword("#ff0000"), Some(red));
+        assert_eq!(keyword("red"), Some(red));
+        assert_eq!(keyword("RED"), Some(red));
+        assert_eq!(keyword("#f008").map(|color| color.a), Some(0x88));
+        assert_eq!(keyword("#ff000"), None);
+        assert_eq!(keyword("not-a-color"), None);
+        assert_eq!(red.to_string(), "rgb(255, 0, 0)");
+        assert_eq!(
+            Color { a: 128, ..red }.to_string(),
+            "rgba(255, 0, 0, 0.502)"
+        );
+    }
+}
@@ -0,0 +1,1797 @@
+use std::{
+    fmt::{Display, Formatter, Result},
+    ops::Deref,
+};
+
+use crate::{
+    atom::Atom,
+    color::Color,
+    dom::TagType,
+    parser::{CSSParser, IParser, ParseError},
+};
+
+pub struct Stylesheet {
+    /// Top-level rules, in source order.
+    pub rules: Vec<CSSItem>,
+    pub font_faces: Vec<FontFace>,
+    origin: Origin,
+    version: u64,
+}
+
+/// Who a stylesheet comes from. Normal declarations of later origins win
+/// over earlier ones, and `!important` declarations reverse the order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Origin {
+    /// The browser's built-in stylesheet.
+    UserAgent,
+    User,
+    /// The page's own stylesheets.
+    #[default]
+    Author,
+}
+
+impl Origin {
+    /// Cascade level of a declaration from this origin: normal declarations
+    /// by origin, then `!important` ones in reverse origin order.
+    pub fn cascade_level(self, is_important: bool) -> u8 {
+        match (is_important, self) {
+            (false, Origin::UserAgent) => 0,
+            (false, Origin::User) => 1,
+            (false, Origin::Author) => 2,
+            (true, Origin::Author) => 3,
+            (true, Origin::User) => 4,
+            (true, Origin::UserAgent) => 5,
+        }
+    }
+}
+
+/// Whitespace style of the `serialize` methods. Both modes produce valid
+/// CSS that parses back to the same rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerializeMode {
+    /// No optional whitespace, for the smallest output.
+    Minified,
+    /// One selector and one declaration per line, with blocks indented by
+    /// tabs. This is what `Display` prints.
+    Pretty,
+}
+
+impl SerializeMode {
+    fn list_separator(&self) -> &'static str {
+        match self {
+            SerializeMode::Minified => ",",
+            SerializeMode::Pretty => ", ",
+        }
+    }
+}
+
+/// Drops the whitespace after commas that aren't quoted, e.g. in
+/// `rgb(0, 0, 0)` or a `font-family` list.
+fn minify_value(value: &str) -> String {
+    let mut minified = String::with_capacity(value.len());
+    let mut quote = None;
+    for c in value.chars() {
+        match c {
+            '"' | '\'' if quote.is_none() => quote = Some(c),
+            c if quote == Some(c) => quote = None,
+            c if c.is_whitespace() && quote.is_none() && minified.ends_with(',') => continue,
+            _ => {}
+        }
+        minified.push(c);
+    }
+    minified
+}
+
+/// Writes a block such as a style rule, its `lines` being declarations
+/// without their `;`. The parser requires a `;` after every declaration, so
+/// the last one keeps it even when minified.
+fn serialize_block(
+    css: &mut String,
+    prelude: &str,
+    lines: &[String],
+    mode: SerializeMode,
+    depth: usize,
+) {
+    match mode {
+        SerializeMode::Minified => {
+            *css += prelude;
+            *css += "{";
+            for line in lines {
+                *css += line;
+                *css += ";";
+            }
+            *css += "}";
+        }
+        SerializeMode::Pretty => {
+            let indent = "\t".repeat(depth);
+            *css += &format!("{}{} {{\n", indent, prelude);
+            for line in lines {
+                *css += &format!("{}\t{};\n", indent, line);
+            }
+            *css += &format!("{}}}\n", indent);
+        }
+    }
+}
+
+/// Writes `items` one after the other. Nested `@media` blocks are walked
+/// with a stack rather than recursion.
+fn serialize_items(css: &mut String, items: &[CSSItem], mode: SerializeMode) {
+    let mut stack = vec![items.iter()];
+    while let Some(items) = stack.last_mut() {
+        let next = items.next();
+        let depth = stack.len() - 1;
+        let indent = match mode {
+            SerializeMode::Minified => String::new(),
+            SerializeMode::Pretty => "\t".repeat(depth),
+        };
+        match next {
+            Some(CSSItem::StyleRule(rule)) => rule.serialize_into(css, mode, depth),
+            Some(CSSItem::MediaRule(media)) => {
+                *css += &indent;
+                *css += "@media";
+                if !media.media.is_empty() {
+                    *css += " ";
+                    *css += &media.media.join(mode.list_separator());
+                }
+                *css += match mode {
+                    SerializeMode::Minified => "{",
+                    SerializeMode::Pretty => " {\n",
+                };
+                stack.push(media.rules.iter());
+            }
+            Some(CSSItem::ImportRule(import)) => {
+                *css += &indent;
+                *css += &import.serialize(mode);
+                if mode == SerializeMode::Pretty {
+                    *css += "\n";
+                }
+            }
+            None => {
+                stack.pop();
+                if !stack.is_empty() {
+                    *css += match mode {
+                        SerializeMode::Minified => "}".to_string(),
+                        SerializeMode::Pretty => format!("{}}}\n", "\t".repeat(depth - 1)),
+                    }
+                    .as_str();
+                }
+            }
+        }
+    }
+}
+
+impl Display for Stylesheet {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.serialize(SerializeMode::Pretty))
+    }
+}
+
+impl Stylesheet {
+    pub fn new(rules: Vec<CSSItem>) -> Stylesheet {
+        let mut stylesheet = Stylesheet {
+            rules,
+            font_faces: vec![],
+            origin: Origin::Author,
+            version: 0,
+        };
+        stylesheet.renumber_rules();
+        stylesheet
+    }
+
+    pub fn serialize(&self, mode: SerializeMode) -> String {
+        let mut css = String::new();
+        for font_face in self.font_faces.iter() {
+            css += &font_face.serialize(mode);
+        }
+        serialize_items(&mut css, &self.rules, mode);
+        css
+    }
+
+    /// Moves the sheet and its rules to `origin`. Sheets are `Author` ones
+    /// by default.
+    pub fn with_origin(mut self, origin: Origin) -> Stylesheet {
+        self.origin = origin;
+        self.renumber_rules();
+        self
+    }
+
+    pub fn origin(&self) -> Origin {
+        self.origin
+    }
+
+    /// Incremented by every change made through the methods below, so
+    /// whoever styled a document with the sheet can tell it is stale.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Appends `rule`, numbering its style rules after those already in the
+    /// sheet.
+    pub fn add_rule(&mut self, rule: impl Into<CSSItem>) {
+        self.rules.push(rule.into());
+        self.renumber_rules();
+    }
+
+    /// Inserts `rule` before the rule at `index`, or at the end when `index`
+    /// is the number of rules. Returns `index`, like CSSOM's `insertRule`.
+    pub fn insert_rule(
+        &mut self,
+        rule: impl Into<CSSItem>,
+        index: usize,
+    ) -> std::result::Result<usize, RuleIndexError> {
+        if index > self.rules.len() {
+            return Err(RuleIndexError {
+                index,
+                len: self.rules.len(),
+            });
+        }
+        self.rules.insert(index, rule.into());
+        self.renumber_rules();
+        Ok(index)
+    }
+
+    pub fn delete_rule(&mut self, index: usize) -> std::result::Result<CSSItem, RuleIndexError> {
+        if index >= self.rules.len() {
+            return Err(RuleIndexError {
+                index,
+                len: self.rules.len(),
+            });
+        }
+        let rule = self.rules.remove(index);
+        self.renumber_rules();
+        Ok(rule)
+    }
+
+    /// Replaces all rules of the sheet, keeping its `@font-face` rules.
+    pub fn replace_rules(&mut self, rules: Vec<CSSItem>) {
+        self.rules = rules;
+        self.renumber_rules();
+    }
+
+    pub fn rule(&self, index: usize) -> Option<&CSSItem> {
+        self.rules.get(index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &CSSItem> {
+        self.rules.iter()
+    }
+
+    /// Index of the first top-level style rule whose selector list reads
+    /// `selector_text`, e.g. `div.a, p`.
+    pub fn find_rule(&self, selector_text: &str) -> Option<usize> {
+        self.rules.iter().position(|item| {
+            item.style_rule()
+                .is_some_and(|rule| rule.selector_text() == selector_text)
+        })
+    }
+
+    /// Declarations of the style rule with the given source order, for
+    /// editing. The sheet is marked changed, as the caller may edit them.
+    pub fn declarations_mut(&mut self, source_order: usize) -> Option<&mut DeclarationBlock> {
+        self.version += 1;
+        let mut stack = vec![self.rules.iter_mut()];
+        while let Some(items) = stack.last_mut() {
+            match items.next() {
+                Some(CSSItem::StyleRule(rule)) if rule.source_order == source_order => {
+                    return Some(&mut rule.declarations)
+                }
+                Some(CSSItem::MediaRule(media)) => stack.push(media.rules.iter_mut()),
+                Some(_) => {}
+                None => {
+                    stack.pop();
+                }
+            }
+        }
+        None
+    }
+
+    /// Every style rule of the sheet, including those nested in `@media`
+    /// rules, in source order.
+    pub fn style_rules(&self) -> Vec<&CSSRule> {
+        self.collect_style_rules(|_| true)
+    }
+
+    /// The style rules that apply to the document on the default screen,
+    /// see `active_style_rules_in`.
+    pub fn active_style_rules(&self) -> Vec<&CSSRule> {
+        self.active_style_rules_in(&MediaContext::default())
+    }
+
+    /// The style rules that apply to the document rendered in `media`,
+    /// skipping those in `@media` rules whose queries don't match.
+    pub fn active_style_rules_in(&self, media: &MediaContext) -> Vec<&CSSRule> {
+        self.collect_style_rules(|rule| rule.applies_in(media))
+    }
+
+    fn collect_style_rules(&self, enter: impl Fn(&CSSMediaRule) -> bool) -> Vec<&CSSRule> {
+        let mut rules = vec![];
+        let mut stack = vec![self.rules.iter()];
+        while let Some(items) = stack.last_mut() {
+            match items.next() {
+                Some(CSSItem::StyleRule(rule)) => rules.push(rule),
+                Some(CSSItem::MediaRule(media)) if enter(media) => stack.push(media.rules.iter()),
+                Some(_) => {}
+                None => {
+                    stack.pop();
+                }
+            }
+        }
+        rules
+    }
+
+    /// Restores source order after rules moved, and marks the sheet changed.
+    /// Style rules are numbered through nested `@media` rules.
+    fn renumber_rules(&mut self) {
+        let mut source_order = 0;
+        let mut stack = vec![self.rules.iter_mut()];
+        while let Some(items) = stack.last_mut() {
+            match items.next() {
+                Some(CSSItem::StyleRule(rule)) => {
+                    rule.source_order = source_order;
+                    rule.origin = self.origin;
+                    source_order += 1;
+                }
+                Some(CSSItem::MediaRule(media)) => stack.push(media.rules.iter_mut()),
+                Some(CSSItem::ImportRule(_)) => {}
+                None => {
+                    stack.pop();
+                }
+            }
+        }
+        self.version += 1;
+    }
+}
+
+/// A top-level rule of a stylesheet, or a rule nested in an `@media` rule.
+pub enum CSSItem {
+    StyleRule(CSSRule),
+    MediaRule(CSSMediaRule),
+    ImportRule(CSSImportRule),
+}
+
+impl CSSItem {
+    pub fn style_rule(&self) -> Option<&CSSRule> {
+        match self {
+            CSSItem::StyleRule(rule) => Some(rule),
+            _ => None,
+        }
+    }
+}
+
+impl From<CSSRule> for CSSItem {
+    fn from(rule: CSSRule) -> CSSItem {
+        CSSItem::StyleRule(rule)
+    }
+}
+
+impl From<CSSMediaRule> for CSSItem {
+    fn from(rule: CSSMediaRule) -> CSSItem {
+        CSSItem::MediaRule(rule)
+    }
+}
+
+impl From<CSSImportRule> for CSSItem {
+    fn from(rule: CSSImportRule) -> CSSItem {
+        CSSItem::ImportRule(rule)
+    }
+}
+
+impl CSSItem {
+    pub fn serialize(&self, mode: SerializeMode) -> String {
+        let mut css = String::new();
+        serialize_items(&mut css, std::slice::from_ref(self), mode);
+        css
+    }
+}
+
+impl Display for CSSItem {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.serialize(SerializeMode::Pretty))
+    }
+}
+
+/// `@media` rule: its rules only apply when one of its queries matches.
+pub struct CSSMediaRule {
+    /// Media queries as written, e.g. `screen` or `(min-width: 600px)`. An
+    /// empty list matches all media.
+    pub media: Vec<String>,
+    pub rules: Vec<CSSItem>,
+}
+
+impl CSSMediaRule {
+    /// Whether the rule applies on the default screen, see `applies_in`.
+    pub fn applies(&self) -> bool {
+        self.applies_in(&MediaContext::default())
+    }
+
+    /// Whether one of the rule's queries matches `media`.
+    pub fn applies_in(&self, media: &MediaContext) -> bool {
+        self.media.is_empty() || self.media.iter().any(|query| media.matches(query))
+    }
+}
+
+/// Preferred color scheme of the user, for `prefers-color-scheme`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColorScheme {
+    #[default]
+    Light,
+    Dark,
+}
+
+/// The screen a document is rendered on, which media queries are evaluated
+/// against and viewport units are relative to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MediaContext {
+    /// Viewport size in CSS px.
+    pub width: f32,
+    pub height: f32,
+    /// Device pixels per CSS px.
+    pub device_pixel_ratio: f32,
+    pub color_scheme: ColorScheme,
+}
+
+impl Default for MediaContext {
+    fn default() -> Self {
+        MediaContext {
+            width: 800.0,
+            height: 600.0,
+            device_pixel_ratio: 1.0,
+            color_scheme: ColorScheme::Light,
+        }
+    }
+}
+
+/// How a media feature compares to the value of a query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Range {
+    Exact,
+    Min,
+    Max,
+}
+
+impl MediaContext {
+    /// Whether a media query, e.g. `screen and (min-width: 600px)`, matches.
+    /// Only the `all` and `screen` media types match, as do queries without
+    /// one. Unknown features never match.
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.trim().to_ascii_lowercase();
+        let (negated, query) = match query.strip_prefix("not ") {
+            Some(query) => (true, query.trim_start()),
+            None => (false, query.as_str()),
+        };
+        let query = query.strip_prefix("only ").unwrap_or(query);
+        let matches = query.split(" and ").all(|part| {
+            let part = part.trim();
+            match part
+                .strip_prefix('(')
+                .and_then(|part| part.strip_suffix(')'))
+            {
+                Some(feature) => self.matches_feature(feature.trim()),
+                None => matches!(part, "all" | "screen"),
+            }
+        });
+        matches != negated
+    }
+
+    fn matches_feature(&self, feature: &str) -> bool {
+        let Some((name, value)) = feature.split_once(':') else {
+            // A feature without a value matches if it isn't zero or `none`.
+            return matches!(
+                feature,
+                "width" | "height" | "resolution" | "orientation" | "prefers-color-scheme"
+            );
+        };
+        let (name, value) = (name.trim(), value.trim());
+        let (range, name) = match (name.strip_prefix("min-"), name.strip_prefix("max-")) {
+            (Some(name), _) => (Range::Min, name),
+            (_, Some(name)) => (Range::Max, name),
+            _ => (Range::Exact, name),
+        };
+        let compare = |actual: f32, expected: Option<f32>| {
+            let Some(expected) = expected else {
+                return false;
+            };
+            match range {
+                Range::Exact => (actual - expected).abs() < 0.01,
+                Range::Min => actual >= expected,
+                Range::Max => actual <= expected,
+            }
+        };
+        match name {
+            "width" => compare(self.width, media_length(value)),
+            "height" => compare(self.height, media_length(value)),
+            "resolution" => compare(self.device_pixel_ratio, media_resolution(value)),
+            "-webkit-device-pixel-ratio" => compare(self.device_pixel_ratio, value.parse().ok()),
+            "orientation" if range == Range::Exact => {
+                let portrait = self.height >= self.width;
+                value == if portrait { "portrait" } else { "landscape" }
+            }
+            "prefers-color-scheme" if range == Range::Exact => {
+                value
+                    == match self.color_scheme {
+                        ColorScheme::Light => "light",
+                        ColorScheme::Dark => "dark",
+                    }
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Splits a media feature value such as `600px` into its number and unit.
+fn split_dimension(value: &str) -> Option<(f32, &str)> {
+    let unit_start = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+        .unwrap_or(value.len());
+    let number = value[..unit_start].parse().ok()?;
+    Some((number, value[unit_start..].trim()))
+}
+
+/// A length in px. Font-relative units use the default font size, as in
+/// media queries they don't depend on the document's styles.
+fn media_length(value: &str) -> Option<f32> {
+    let (number, unit) = split_dimension(value)?;
+    match unit {
+        "px" => Some(number),
+        "" if number == 0.0 => Some(0.0),
+        "em" | "rem" => Some(number * crate::style::DEFAULT_FONT_SIZE),
+        _ => None,
+    }
+}
+
+/// A resolution in device pixels per CSS px.
+fn media_resolution(value: &str) -> Option<f32> {
+    let (number, unit) = split_dimension(value)?;
+    match unit {
+        "dppx" | "x" => Some(number),
+        "dpi" => Some(number / 96.0),
+        "dpcm" => Some(number * 2.54 / 96.0),
+        _ => None,
+    }
+}
+
+// Dropped iteratively, as the default recursive drop would overflow the
+// stack on deeply nested `@media` rules.
+impl Drop for CSSMediaRule {
+    fn drop(&mut self) {
+        let mut stack = std::mem::take(&mut self.rules);
+        while let Some(item) = stack.pop() {
+            if let CSSItem::MediaRule(mut media) = item {
+                stack.append(&mut media.rules);
+            }
+        }
+    }
+}
+
+/// `@import` rule. Imported sheets aren't fetched; the rule is kept so the
+/// stylesheet serializes back as written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CSSImportRule {
+    pub url: String,
+    /// Media queries the import is conditional on, as for `@media`.
+    pub media: Vec<String>,
+}
+
+impl CSSImportRule {
+    pub fn serialize(&self, mode: SerializeMode) -> String {
+        let mut css = format!("@import {}", CSSValue::Url(self.url.clone()));
+        if !self.media.is_empty() {
+            css += " ";
+            css += &self.media.join(mode.list_separator());
+        }
+        css + ";"
+    }
+}
+
+impl Display for CSSImportRule {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.serialize(SerializeMode::Pretty))
+    }
+}
+
+/// Rule index past the end of a stylesheet's rule list.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RuleIndexError {
+    pub index: usize,
+    pub len: usize,
+}
+
+impl Display for RuleIndexError {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(
+            f,
+            "Rule index {} is out of range for a stylesheet with {} rules",
+            self.index, self.len
+        )
+    }
+}
+
+impl std::error::Error for RuleIndexError {}
+
+/// Font declared by an `@font-face` rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontFace {
+    pub family: String,
+    /// URLs of the `src` descriptor, in order of preference.
+    pub sources: Vec<String>,
+}
+
+impl FontFace {
+    pub fn serialize(&self, mode: SerializeMode) -> String {
+        let sources = self
+            .sources
+            .iter()
+            .map(|url| CSSValue::Url(url.clone()).to_string())
+            .collect::<Vec<String>>();
+        let separator = match mode {
+            SerializeMode::Minified => ":",
+            SerializeMode::Pretty => ": ",
+        };
+        let mut css = String::new();
+        serialize_block(
+            &mut css,
+            "@font-face",
+            &[
+                format!("font-family{}\"{}\"", separator, self.family),
+                format!("src{}{}", separator, sources.join(mode.list_separator())),
+            ],
+            mode,
+            0,
+        );
+        css
+    }
+}
+
+impl Display for FontFace {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.serialize(SerializeMode::Pretty))
+    }
+}
+
+/// Precedence of a declaration in the cascade. Declarations are compared by
+/// cascade level, then by whether they come from a `style` attribute, then by
+/// the specificity of the selector that matched, then by the order of their
+/// stylesheet and of their rule within it.
+///
+/// `CSSSelector::specificity` only fills in the selector counts; the cascade
+/// adds the rest with `with_origin` and `with_order`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Specificity {
+    /// See `Origin::cascade_level`.
+    pub cascade_level: u8,
+    /// Whether the declaration comes from an element's `style` attribute,
+    /// which beats any selector.
+    pub inline: bool,
+    /// Number of id selectors.
+    pub ids: usize,
+    /// Number of class and attribute selectors and pseudo-classes.
+    pub classes: usize,
+    /// Number of type selectors and pseudo-elements.
+    pub tags: usize,
+    /// Position of the stylesheet among those being cascaded.
+    pub sheet_order: usize,
+    /// Position of the rule in its stylesheet.
+    pub source_order: usize,
+}
+
+impl Specificity {
+    pub fn new(ids: usize, classes: usize, tags: usize) -> Specificity {
+        Specificity {
+            ids,
+            classes,
+            tags,
+            ..Default::default()
+        }
+    }
+
+    /// Specificity of the declarations of a `style` attribute.
+    pub fn inline() -> Specificity {
+        Specificity {
+            inline: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_origin(self, origin: Origin, is_important: bool) -> Specificity {
+        Specificity {
+            cascade_level: origin.cascade_level(is_important),
+            ..self
+        }
+    }
+
+    pub fn with_order(self, sheet_order: usize, source_order: usize) -> Specificity {
+        Specificity {
+            sheet_order,
+            source_order,
+            ..self
+        }
+    }
+}
+
+impl Ord for Specificity {
+    fn cmp(&self, other: &Specificity) -> std::cmp::Ordering {
+        self.cascade_level
+            .cmp(&other.cascade_level)
+            .then(self.inline.cmp(&other.inline))
+            .then(self.ids.cmp(&other.ids))
+            .then(self.classes.cmp(&other.classes))
+            .then(self.tags.cmp(&other.tags))
+            .then(self.sheet_order.cmp(&other.sheet_order))
+            .then(self.source_order.cmp(&other.source_order))
+    }
+}
+
+impl PartialOrd for Specificity {
+    fn partial_cmp(&self, other: &Specificity) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+pub struct CSSRule {
+    pub selectors: Vec<CSSSelector>,
+    pub declarations: DeclarationBlock,
+    /// Position of the rule in its stylesheet. Among rules of equal
+    /// specificity, the one that comes last wins.
+    pub source_order: usize,
+    /// Origin of the stylesheet the rule belongs to.
+    pub origin: Origin,
+}
+
+impl CSSRule {
+    /// The rule's selectors, separated by commas.
+    pub fn selector_text(&self) -> String {
+        self.selectors
+            .iter()
+            .map(|selector| selector.to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+impl CSSRule {
+    pub fn serialize(&self, mode: SerializeMode) -> String {
+        let mut css = String::new();
+        self.serialize_into(&mut css, mode, 0);
+        css
+    }
+
+    fn serialize_into(&self, css: &mut String, mode: SerializeMode, depth: usize) {
+        let separator = match mode {
+            SerializeMode::Minified => ",".to_string(),
+            SerializeMode::Pretty => ",\n".to_string() + &"\t".repeat(depth),
+        };
+        let selectors = self
+            .selectors
+            .iter()
+            .map(|selector| selector.to_string())
+            .collect::<Vec<String>>()
+            .join(&separator);
+        let declarations = self
+            .declarations
+            .iter()
+            .map(|declaration| declaration.serialize(mode))
+            .collect::<Vec<String>>();
+        serialize_block(css, &selectors, &declarations, mode, depth);
+    }
+}
+
+impl Display for CSSRule {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}", self.serialize(SerializeMode::Pretty))
+    }
+}
+
+pub enum CSSSelector {
+    SimpleSelector(SimpleSelector),
+    /// Compound selectors separated by whitespace, e.g. `nav .item`. The last
+    /// one is matched against the element, and each one before it against
+    /// one of its ancestors, in order from the root.
+    Descendant(Vec<SimpleSelector>),
+}
+
+impl Display for CSSSelector {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let compounds: Vec<String> = self
+            .compounds()
+            .iter()
+            .map(|compound| compound.to_string())
+            .collect();
+        write!(f, "{}", compounds.join(" "))
+    }
+}
+
+impl Display for SimpleSelector {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let SimpleSelector {
+            tag,
+            id,
+            class,
+            attributes,
+            pseudo_classes,
+            pseudo_element,
+        } = self;
+        let tag = match tag {
+            Some(tag) => tag.to_string(),
+            None => "".to_string(),
+        };
+        let id = match id {
+            Some(id) => "#".to_string() + id.as_str(),
+            None => "".to_string(),
+        };
+        let class = match class.len() {
+            0 => "".to_string(),
+            _ => ".".to_string() + &class.join("."),
+        };
+        let attributes = attributes
+            .iter()
+            .map(|attribute| attribute.to_string())
+            .collect::<String>();
+        let pseudo_classes = pseudo_classes
+            .iter()
+            .map(|pseudo_class| ":".to_string() + pseudo_class)
+            .collect::<String>();
+        let pseudo_element = match pseudo_element {
+            Some(pseudo_element) => "::".to_string() + pseudo_element,
+            None => "".to_string(),
+        };
+        write!(
+            f,
+            "{}",
+            [tag, id, class, attributes, pseudo_classes, pseudo_element]
+                .into_iter()
+                .filter(|x| !x.is_empty())
+                .collect::<Vec<String>>()
+                .join("")
+        )
+    }
+}
+
+impl CSSSelector {
+    /// The compound selectors, from the outermost ancestor to the element.
+    pub fn compounds(&self) -> &[SimpleSelector] {
+        match self {
+            CSSSelector::SimpleSelector(selector) => std::slice::from_ref(selector),
+            CSSSelector::Descendant(compounds) => compounds,
+        }
+    }
+
+    /// The compound selector matched against the element itself.
+    pub fn subject(&self) -> &SimpleSelector {
+        self.compounds().last().unwrap()
+    }
+
+    pub fn specificity(&self) -> Specificity {
+        let compounds = self.compounds();
+        let a = compounds
+            .iter()
+            .map(|selector| selector.id.iter().count())
+            .sum();
+        let b = compounds
+            .iter()
+            .map(|selector| {
+                selector.class.len() + selector.attributes.len() + selector.pseudo_classes.len()
+            })
+            .sum();
+        let c = compounds
+            .iter()
+            .map(|selector| selector.tag.iter().count() + selector.pseudo_element.iter().count())
+            .sum();
+        Specificity::new(a, b, c)
+    }
+}
+
+#[derive(Debug)]
+pub struct SimpleSelector {
+    pub tag: Option<TagType>,
+    pub id: Option<Atom>,
+    pub class: Vec<Atom>,
+    pub attributes: Vec<AttributeSelector>,
+    /// Names of pseudo-classes such as `hover`, with the arguments of
+    /// functional ones, e.g. `nth-child(2)`.
+    pub pseudo_classes: Vec<String>,
+    pub pseudo_element: Option<String>,
+}
+
+/// How an attribute selector compares the attribute's value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeMatcher {
+    /// `[name]`
+    Exists,
+    /// `[name=value]`
+    Equals(String),
+    /// `[name~=value]`, one of the whitespace separated words.
+    Includes(String),
+    /// `[name|=value]`, the value or the value followed by `-`.
+    DashMatch(String),
+    /// `[name^=value]`
+    Prefix(String),
+    /// `[name$=value]`
+    Suffix(String),
+    /// `[name*=value]`
+    Substring(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeSelector {
+    pub name: Atom,
+    pub matcher: AttributeMatcher,
+}
+
+impl AttributeSelector {
+    pub fn matches(&self, value: Option<&String>) -> bool {
+        let Some(value) = value else {
+            return false;
+        };
+        match &self.matcher {
+            AttributeMatcher::Exists => true,
+            AttributeMatcher::Equals(expected) => value == expected,
+            AttributeMatcher::Includes(word) => value.split_whitespace().any(|x| x == word),
+            AttributeMatcher::DashMatch(prefix) => {
+                value == prefix || value.starts_with(&format!("{}-", prefix))
+            }
+            AttributeMatcher::Prefix(prefix) => !prefix.is_empty() && value.starts_with(prefix),
+            AttributeMatcher::Suffix(suffix) => !suffix.is_empty() && value.ends_with(suffix),
+            AttributeMatcher::Substring(part) => !part.is_empty() && value.contains(part),
+        }
+    }
+}
+
+impl Display for AttributeSelector {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let (operator, value) = match &self.matcher {
+            AttributeMatcher::Exists => return write!(f, "[{}]", self.name),
+            AttributeMatcher::Equals(value) => ("=", value),
+            AttributeMatcher::Includes(value) => ("~=", value),
+            AttributeMatcher::DashMatch(value) => ("|=", value),
+            AttributeMatcher::Prefix(value) => ("^=", value),
+            AttributeMatcher::Suffix(value) => ("$=", value),
+            AttributeMatcher::Substring(value) => ("*=", value),
+        };
+        write!(f, "[{}{}\"{}\"]", self.name, operator, value)
+    }
+}
+
+/// The `an+b` argument of `:nth-child()`, which matches the elements at the
+/// positions `an+b` for any `n >= 0`, counting from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnPlusB {
+    pub a: i32,
+    pub b: i32,
+}
+
+impl AnPlusB {
+    /// Parses `odd`, `even`, `b`, or `an` followed by an optional signed `b`,
+    /// e.g. `-n + 3`.
+    pub fn parse(input: &str) -> Option<AnPlusB> {
+        let input = input.trim().to_ascii_lowercase();
+        match input.as_str() {
+            "odd" => return Some(AnPlusB { a: 2, b: 1 }),
+            "even" => return Some(AnPlusB { a: 2, b: 0 }),
+            _ => {}
+        }
+        let Some((a, b)) = input.split_once('n') else {
+            return Some(AnPlusB {
+                a: 0,
+                b: input.parse().ok()?,
+            });
+        };
+        let a = match a {
+            "" | "+" => 1,
+            "-" => -1,
+            a => a.parse().ok()?,
+        };
+        let b = b.trim_start();
+        let b = match b.chars().next() {
+            None => 0,
+            Some(sign @ ('+' | '-')) => {
+                let digits = b[1..].trim_start();
+                if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+                    return None;
+                }
+                let b: i32 = digits.parse().ok()?;
+                if sign == '-' {
+                    -b
+                } else {
+                    b
+                }
+            }
+            Some(_) => return None,
+        };
+        Some(AnPlusB { a, b })
+    }
+
+    /// Whether the element at `index`, counting from 1, matches.
+    pub fn matches(&self, index: usize) -> bool {
+        let (a, b, index) = (self.a as i64, self.b as i64, index as i64);
+        if a == 0 {
+            return index == b;
+        }
+        (index - b) % a == 0 && (index - b) / a >= 0
+    }
+}
+
+/// The declarations of a rule, read and edited by property name like
+/// CSSOM's `CSSStyleDeclaration`. The block only holds longhands: setting or
+/// removing a shorthand sets or removes its longhands, which are also how it
+/// is read.
+#[derive(Debug, Default)]
+pub struct DeclarationBlock {
+    declarations: Vec<CSSDeclaration>,
+}
+
+impl DeclarationBlock {
+    pub fn new(declarations: Vec<CSSDeclaration>) -> DeclarationBlock {
+        DeclarationBlock { declarations }
+    }
+
+    /// The declaration that applies for `property`, i.e. the last one.
+    pub fn declaration(&self, property: &str) -> Option<&CSSDeclaration> {
+        let property = property.to_ascii_lowercase();
+        self.declarations
+            .iter()
+            .rev()
+            .find(|declaration| declaration.property.to_string() == property)
+    }
+
+    /// The serialized value of `property`, or an empty string when the block
+    /// doesn't set it.
+    pub fn get_property_value(&self, property: &str) -> String {
+        self.declaration(property)
+            .map(|declaration| declaration.value.to_string())
+            .unwrap_or_default()
+    }
+
+    /// `"important"` when `property` is set with `!important`, or an empty
+    /// string.
+    pub fn get_property_priority(&self, property: &str) -> &'static str {
+        match self.declaration(property) {
+            Some(declaration) if declaration.is_important => "important",
+            _ => "",
+        }
+    }
+
+    /// Parses `value` for `property` and sets it, replacing the property's
+    /// declarations in place or appending it. An invalid value leaves the
+    /// block unchanged.
+    pub fn set_property(
+        &mut self,
+        property: &str,
+        value: &str,
+        important: bool,
+    ) -> std::result::Result<(), ParseError> {
+        let priority = match important {
+            true => " !important",
+            false => "",
+        };
+        let text = format!("{}: {}{};", property.to_ascii_lowercase(), value, priority);
+        let declarations = CSSParser::new(&text).try_parse_declarations()?;
+        // A known property is only kept as unknown when its value isn't
+        // supported.
+        let is_supported = declarations
+            .iter()
+            .all(|declaration| match &declaration.property {
+                CSSProperty::Unknown(name) => {
+                    Shorthand::from_name(name).is_none()
+                        && matches!(
+                            CSSParser::parse_property(name.clone()),
+                            CSSProperty::Unknown(_)
+                        )
+                }
+                _ => true,
+            });
+        if declarations.is_empty() || !is_supported {
+            return Err(ParseError::new(
+                format!("Invalid value for '{}'", property),
+                &text,
+                property.len() + 2,
+            ));
+        }
+        for declaration in declarations {
+            let position = self
+                .declarations
+                .iter()
+                .position(|existing| existing.property == declaration.property);
+            self.declarations
+                .retain(|existing| existing.property != declaration.property);
+            match position {
+                Some(position) => self.declarations.insert(position, declaration),
+                None => self.declarations.push(declaration),
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes every declaration of `property`, or of its longhands for a
+    /// shorthand, returning the value that applied, or an empty string.
+    pub fn remove_property(&mut self, property: &str) -> String {
+        let value = self.get_property_value(property);
+        let property = property.to_ascii_lowercase();
+        let longhands = Shorthand::from_name(&property)
+            .map(|shorthand| shorthand.longhands())
+            .unwrap_or_default();
+        self.declarations.retain(|declaration| {
+            declaration.property.to_string() != property
+                && !longhands.contains(&declaration.property)
+        });
+        value
+    }
+}
+
+impl Deref for DeclarationBlock {
+    type Target = [CSSDeclaration];
+
+    fn deref(&self) -> &[CSSDeclaration] {
+        &self.declarations
+    }
+}
+
+impl<'a> IntoIterator for &'a DeclarationBlock {
+    type Item = &'a CSSDeclaration;
+    type IntoIter = std::slice::Iter<'a, CSSDeclaration>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.declarations.iter()
+    }
+}
+
+#[derive(Debug)]
+pub struct CSSDeclaration {
+    pub property: CSSProperty,
+    pub value: CSSValue,
+    pub is_important: bool,
+}
+
+impl CSSDeclaration {
+    /// The declaration without its trailing `;`.
+    pub fn serialize(&self, mode: SerializeMode) -> String {
+        match (mode, self.is_important) {
+            (SerializeMode::Minified, important) => format!(
+                "{}:{}{}",
+                self.property,
+                minify_value(&self.value.to_string()),
+                if important { "!important" } else { "" }
+            ),
+            (SerializeMode::Pretty, true) => {
+                format!("{}: {} !important", self.property, self.value)
+            }
+            (SerializeMode::Pretty, false) => format!("{}: {}", self.property, self.value),
+        }
+    }
+}
+
+impl Display for CSSDeclaration {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{};", self.serialize(SerializeMode::Pretty))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub enum CSSProperty {
+    BackgroundColor,
+    BackgroundImage,
+    BackgroundRepeat,
+    BackgroundPosition,
+    Color,
+    Width,
+    Height,
+    MinWidth,
+    MinHeight,
+    MaxWidth,
+    MaxHeight,
+    BoxSizing,
+    Display,
+    Position,
+    Float,
+    Clear,
+    FlexDirection,
+    JustifyContent,
+    AlignItems,
+    FlexGrow,
+    FlexShrink,
+    FlexBasis,
+    GridTemplateColumns,
+    GridTemplateRows,
+    Top,
+    Right,
+    Bottom,
+    Left,
+    MarginTop,
+    MarginRight,
+    MarginBottom,
+    MarginLeft,
+    PaddingTop,
+    PaddingRight,
+    PaddingBottom,
+    PaddingLeft,
+    Overflow,
+    Visibility,
+    Direction,
+    VerticalAlign,
+    WhiteSpace,
+    OverflowWrap,
+    WordBreak,
+    ZIndex,
+    LineHeight,
+    Opacity,
+    BorderTopLeftRadius,
+    BorderTopRightRadius,
+    BorderBottomRightRadius,
+    BorderBottomLeftRadius,
+    BorderTopWidth,
+    BorderRightWidth,
+    BorderBottomWidth,
+    BorderLeftWidth,
+    BorderTopStyle,
+    BorderRightStyle,
+    BorderBottomStyle,
+    BorderLeftStyle,
+    BorderTopColor,
+    BorderRightColor,
+    BorderBottomColor,
+    BorderLeftColor,
+    FontFamily,
+    FontSize,
+    FontStyle,
+    FontWeight,
+    /// A custom property, `--name`, whose value is kept as written for
+    /// `var()` to substitute.
+    Custom(Atom),
+    /// A property the engine doesn't understand yet, kept so the declaration
+    /// can be serialized back without losing data.
+    Unknown(String),
+}
+
+impl CSSProperty {
+    /// Whether elements without a value of their own take their parent's.
+    pub fn is_inherited(&self) -> bool {
+        matches!(
+            self,
+            Self::Color
+                | Self::Custom(_)
+                | Self::Visibility
+                | Self::Direction
+                | Self::WhiteSpace
+                | Self::OverflowWrap
+                | Self::WordBreak
+                | Self::LineHeight
+                | Self::FontFamily
+                | Self::FontSize
+                | Self::FontStyle
+                | Self::FontWeight
+        )
+    }
+}
+
+impl Display for CSSProperty {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let output = match self {
+            Self::BackgroundColor => "background-color",
+            Self::BackgroundImage => "background-image",
+            Self::BackgroundRepeat => "background-repeat",
+            Self::BackgroundPosition => "background-position",
+            Self::Color => "color",
+            Self::Height => "height",
+            Self::Width => "width",
+            Self::MinWidth => "min-width",
+            Self::MinHeight => "min-height",
+            Self::MaxWidth => "max-width",
+            Self::MaxHeight => "max-height",
+            Self::BoxSizing => "box-sizing",
+            Self::Display => "display",
+            Self::Position => "position",
+            Self::Float => "float",
+            Self::Clear => "clear",
+            Self::FlexDirection => "flex-direction",
+            Self::JustifyContent => "justify-content",
+            Self::AlignItems => "align-items",
+            Self::FlexGrow => "flex-grow",
+            Self::FlexShrink => "flex-shrink",
+            Self::FlexBasis => "flex-basis",
+            Self::GridTemplateColumns => "grid-template-columns",
+            Self::GridTemplateRows => "grid-template-rows",
+            Self::Top => "top",
+            Self::Right => "right",
+            Self::Bottom => "bottom",
+            Self::Left => "left",
+            Self::MarginTop => "margin-top",
+            Self::MarginRight => "margin-right",
+            Self::MarginBottom => "margin-bottom",
+            Self::MarginLeft => "margin-left",
+            Self::PaddingTop => "padding-top",
+            Self::PaddingRight => "padding-right",
+            Self::PaddingBottom => "padding-bottom",
+            Self::PaddingLeft => "padding-left",
+            Self::Overflow => "overflow",
+            Self::Visibility => "visibility",
+            Self::Direction => "direction",
+            Self::VerticalAlign => "vertical-align",
+            Self::WhiteSpace => "white-space",
+            Self::OverflowWrap => "overflow-wrap",
+            Self::WordBreak => "word-break",
+            Self::ZIndex => "z-index",
+            Self::LineHeight => "line-height",
+            Self::Opacity => "opacity",
+            Self::BorderTopLeftRadius => "border-top-left-radius",
+            Self::BorderTopRightRadius => "border-top-right-radius",
+            Self::BorderBottomRightRadius => "border-bottom-right-radius",
+            Self::BorderBottomLeftRadius => "border-bottom-left-radius",
+            Self::BorderTopWidth => "border-top-width",
+            Self::BorderRightWidth => "border-right-width",
+            Self::BorderBottomWidth => "border-bottom-width",
+            Self::BorderLeftWidth => "border-left-width",
+            Self::BorderTopStyle => "border-top-style",
+            Self::BorderRightStyle => "border-right-style",
+            Self::BorderBottomStyle => "border-bottom-style",
+            Self::BorderLeftStyle => "border-left-style",
+            Self::BorderTopColor => "border-top-color",
+            Self::BorderRightColor => "border-right-color",
+            Self::BorderBottomColor => "border-bottom-color",
+            Self::BorderLeftColor => "border-left-color",
+            Self::FontFamily => "font-family",
+            Self::FontSize => "font-size",
+            Self::FontStyle => "font-style",
+            Self::FontWeight => "font-weight",
+            Self::Custom(name) => name,
+            Self::Unknown(name) => name,
+        };
+        write!(f, "{}", output)
+    }
+}
+
+/// A property that sets several longhands at once. Shorthands are expanded
+/// when declarations are parsed, so only longhands reach the cascade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shorthand {
+    Margin,
+    Padding,
+    Border,
+    BorderTop,
+    BorderRight,
+    BorderBottom,
+    BorderLeft,
+    BorderWidth,
+    BorderStyle,
+    BorderColor,
+    BorderRadius,
+    Background,
+    Font,
+    Flex,
+}
+
+impl Display for Shorthand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let output = match self {
+            Shorthand::Margin => "margin",
+            Shorthand::Padding => "padding",
+            Shorthand::Border => "border",
+            Shorthand::BorderTop => "border-top",
+            Shorthand::BorderRight => "border-right",
+            Shorthand::BorderBottom => "border-bottom",
+            Shorthand::BorderLeft => "border-left",
+            Shorthand::BorderWidth => "border-width",
+            Shorthand::BorderStyle => "border-style",
+            Shorthand::BorderColor => "border-color",
+            Shorthand::BorderRadius => "border-radius",
+            Shorthand::Background => "background",
+            Shorthand::Font => "font",
+            Shorthand::Flex => "flex",
+        };
+        write!(f, "{}", output)
+    }
+}
+
+impl Shorthand {
+    pub fn from_name(name: &str) -> Option<Shorthand> {
+        Some(match name {
+            "margin" => Shorthand::Margin,
+            "padding" => Shorthand::Padding,
+            "border" => Shorthand::Border,
+            "border-top" => Shorthand::BorderTop,
+            "border-right" => Shorthand::BorderRight,
+            "border-bottom" => Shorthand::BorderBottom,
+            "border-left" => Shorthand::BorderLeft,
+            "border-width" => Shorthand::BorderWidth,
+            "border-style" => Shorthand::BorderStyle,
+            "border-color" => Shorthand::BorderColor,
+            "border-radius" => Shorthand::BorderRadius,
+            "background" => Shorthand::Background,
+            "font" => Shorthand::Font,
+            "flex" => Shorthand::Flex,
+            _ => return None,
+        })
+    }
+
+    /// The longhands the shorthand sets, all of them being reset to their
+    /// initial value when not given.
+    pub fn longhands(&self) -> Vec<CSSProperty> {
+        use CSSProperty::*;
+        match self {
+            Shorthand::Margin => vec![MarginTop, MarginRight, MarginBottom, MarginLeft],
+            Shorthand::Padding => vec![PaddingTop, PaddingRight, PaddingBottom, PaddingLeft],
+            Shorthand::Border => [
+                Shorthand::BorderTop,
+                Shorthand::BorderRight,
+                Shorthand::BorderBottom,
+                Shorthand::BorderLeft,
+            ]
+            .iter()
+            .flat_map(Shorthand::longhands)
+            .collect(),
+            Shorthand::BorderTop => vec![BorderTopWidth, BorderTopStyle, BorderTopColor],
+            Shorthand::BorderRight => vec![BorderRightWidth, BorderRightStyle, BorderRightColor],
+            Shorthand::BorderBottom => {
+                vec![BorderBottomWidth, BorderBottomStyle, BorderBottomColor]
+            }
+            Shorthand::BorderLeft => vec![BorderLeftWidth, BorderLeftStyle, BorderLeftColor],
+            Shorthand::BorderWidth => vec![
+                BorderTopWidth,
+                BorderRightWidth,
+                BorderBottomWidth,
+                BorderLeftWidth,
+            ],
+            Shorthand::BorderStyle => vec![
+                BorderTopStyle,
+                BorderRightStyle,
+                BorderBottomStyle,
+                BorderLeftStyle,
+            ],
+            Shorthand::BorderColor => vec![
+                BorderTopColor,
+                BorderRightColor,
+                BorderBottomColor,
+                BorderLeftColor,
+            ],
+            Shorthand::BorderRadius => vec![
+                BorderTopLeftRadius,
+                BorderTopRightRadius,
+                BorderBottomRightRadius,
+                BorderBottomLeftRadius,
+            ],
+            Shorthand::Background => vec![
+                BackgroundColor,
+                BackgroundImage,
+                BackgroundRepeat,
+                BackgroundPosition,
+            ],
+            Shorthand::Font => vec![FontStyle, FontWeight, FontSize, LineHeight, FontFamily],
+            Shorthand::Flex => vec![FlexGrow, FlexShrink, FlexBasis],
+        }
+    }
+
+    /// Assigns the whitespace separated `values` of the shorthand to its
+    /// longhands. `None` means the values don't fit the shorthand's grammar;
+    /// each longhand value is validated afterwards like a declaration.
+    pub fn expand(&self, values: &[CSSValue]) -> Option<Vec<(CSSProperty, CSSValue)>> {
+        let keyword = |keyword: &str| CSSValue::Keyword(keyword.to_string());
+        let longhands = self.longhands();
+        match self {
+            Shorthand::Margin
+            | Shorthand::Padding
+            | Shorthand::BorderWidth
+            | Shorthand::BorderStyle
+            | Shorthand::BorderColor
+            | Shorthand::BorderRadius => {
+                // Values are given clockwise, with missing ones copied from
+                // the opposite side. This also rejects the elliptical
+                // `border-radius: a / b` syntax.
+                let [first, second, third, fourth] = match values {
+                    [all] => [all, all, all, all],
+                    [first, second] => [first, second, first, second],
+                    [first, second, third] => [first, second, third, second],
+                    [first, second, third, fourth] => [first, second, third, fourth],
+                    _ => return None,
+                };
+                Some(
+                    longhands
+                        .into_iter()
+                        .zip([first, second, third, fourth])
+                        .map(|(property, value)| (property, value.clone()))
+                        .collect(),
+                )
+            }
+            Shorthand::BorderTop
+            | Shorthand::BorderRight
+            | Shorthand::BorderBottom
+            | Shorthand::BorderLeft
+            | Shorthand::Border => {
+                // Width, style and color, in any order.
+                let mut side = [None, None, None];
+                for value in values {
+                    let slot = (0..3).find(|slot| {
+                        side[*slot].is_none() && longhands[*slot].parse_longhand(value).is_some()
+                    })?;
+                    side[slot] = Some(value.clone());
+                }
+                let [width, style, color] = side;
+                let side = [
+                    width.unwrap_or_else(|| keyword("medium")),
+                    style.unwrap_or_else(|| keyword("none")),
+                    color.unwrap_or_else(|| keyword("currentcolor")),
+                ];
+                Some(
+                    longhands
+                        .into_iter()
+                        .zip(side.iter().cycle())
+                        .map(|(property, value)| (property, value.clone()))
+                        .collect(),
+                )
+            }
+            Shorthand::Background => {
+                let mut color = None;
+                let mut image = None;
+                let mut repeat = None;
+                let mut position: Vec<String> = vec![];
+                for value in values {
+                    match value {
+                        CSSValue::Keyword(token)
+                            if matches!(
+                                token.as_str(),
+                                "repeat"
+                                    | "repeat-x"
+                                    | "repeat-y"
+                                    | "no-repeat"
+                                    | "space"
+                                    | "round"
+                            ) && repeat.is_none() =>
+                        {
+                            repeat = Some(value.clone())
+                        }
+                        CSSValue::Keyword(token)
+                            if matches!(
+                                token.as_str(),
+                                "left" | "right" | "top" | "bottom" | "center"
+                            ) =>
+                        {
+                            position.push(token.clone())
+                        }
+                        CSSValue::Dimension(..) => position.push(value.to_string()),
+                        CSSValue::Url(_) if image.is_none() => image = Some(value.clone()),
+                        CSSValue::Keyword(token)
+                            if token.contains("gradient(") && image.is_none() =>
+                        {
+                            image = Some(value.clone())
+                        }
+                        CSSValue::Keyword(token) if token == "none" && image.is_none() => {
+                            image = Some(value.clone())
+                        }
+                        _ if color.is_none() => color = Some(value.clone()),
+                        _ => return None,
+                    }
+                }
+                let position = match position.is_empty() {
+                    true => keyword("0% 0%"),
+                    false => keyword(&position.join(" ")),
+                };
+                Some(vec![
+                    (
+                        CSSProperty::BackgroundColor,
+                        color.unwrap_or_else(|| keyword("transparent")),
+                    ),
+                    (
+                        CSSProperty::BackgroundImage,
+                        image.unwrap_or_else(|| keyword("none")),
+                    ),
+                    (
+                        CSSProperty::BackgroundRepeat,
+                        repeat.unwrap_or_else(|| keyword("repeat")),
+                    ),
+                    (CSSProperty::BackgroundPosition, position),
+                ])
+            }
+            Shorthand::Font => {
+                // `[<style> || <weight>]? <size> [/ <line-height>]? <family>`
+                let mut style = None;
+                let mut weight = None;
+                let mut values = values.iter().peekable();
+                while let Some(&value) = values.peek() {
+                    // `normal` resets either of them, so it needs no slot.
+                    let is_normal = *value == keyword("normal");
+                    if !is_normal
+                        && style.is_none()
+                        && CSSProperty::FontStyle.parse_longhand(value).is_some()
+                    {
+                        style = Some(value.clone());
+                    } else if !is_normal
+                        && weight.is_none()
+                        && CSSProperty::FontWeight.parse_longhand(value).is_some()
+                    {
+                        weight = Some(value.clone());
+                    } else if !is_normal {
+                        break;
+                    }
+                    values.next();
+                }
+                let size = values.next()?.clone();
+                let line_height = match values.peek() {
+                    Some(CSSValue::Keyword(slash)) if slash == "/" => {
+                        values.next();
+                        values.next()?.clone()
+                    }
+                    _ => keyword("normal"),
+                };
+                let family: Vec<String> = values.map(CSSValue::to_string).collect();
+                if family.is_empty() {
+                    return None;
+                }
+                Some(vec![
+                    (
+                        CSSProperty::FontStyle,
+                        style.unwrap_or_else(|| keyword("normal")),
+                    ),
+                    (
+                        CSSProperty::FontWeight,
+                        weight.unwrap_or_else(|| keyword("normal")),
+                    ),
+                    (CSSProperty::FontSize, size),
+                    (CSSProperty::LineHeight, line_height),
+                    (CSSProperty::FontFamily, keyword(&family.join(" "))),
+                ])
+            }
+            Shorthand::Flex => {
+                // `none | [<grow> <shrink>?] || <basis>`, where an omitted
+                // basis is 0 rather than its initial `auto`.
+                let (grow, shrink, basis) = match values {
+                    [CSSValue::Keyword(none)] if none == "none" => (0.0, 0.0, CSSValue::Auto),
+                    [CSSValue::Auto] => (1.0, 1.0, CSSValue::Auto),
+                    _ => {
+                        let mut factors = vec![];
+                        let mut basis = None;
+                        for (i, value) in values.iter().enumerate() {
+                            // The shrink factor directly follows the grow one.
+                            let is_factor = match factors.len() {
+                                0 => true,
+                                1 => matches!(values[i - 1], CSSValue::Number(_)),
+                                _ => false,
+                            };
+                            match value {
+                                CSSValue::Number(factor) if is_factor => factors.push(*factor),
+                                // Only a zero basis may be written without a
+                                // unit.
+                                CSSValue::Number(zero) if *zero == 0.0 && basis.is_none() => {
+                                    basis = Some(CSSValue::Dimension(0.0, Unit::Px))
+                                }
+                                CSSValue::Number(_) => return None,
+                                _ if basis.is_none() => basis = Some(value.clone()),
+                                _ => return None,
+                            }
+                        }
+                        if values.is_empty() {
+                            return None;
+                        }
+                        (
+                            factors.first().copied().unwrap_or(1.0),
+                            factors.get(1).copied().unwrap_or(1.0),
+                            basis.unwrap_or(CSSValue::Dimension(0.0, Unit::Percent)),
+                        )
+                    }
+                };
+                Some(vec![
+                    (CSSProperty::FlexGrow, CSSValue::Number(grow)),
+                    (CSSProperty::FlexShrink, CSSValue::Number(shrink)),
+                    (CSSProperty::FlexBasis, basis),
+                ])
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CSSValue {
+    Dimension(f32, Unit),
+    Integer(i32),
+    /// Unitless number, e.g. a `line-height` multiplier.
+    Number(f32),
+    Keyword(String),
+    Auto,
+    Color(Color),
+    Url(String),
+    /// Unparsed value text of an unknown or custom property.
+    Raw(String),
+    /// A value with `var()` references, kept as written until they're
+    /// substituted at computed-value time. Longhands set by a shorthand keep
+    /// its name, as the text is the shorthand's value.
+    Unresolved {
+        text: String,
+        shorthand: Option<Shorthand>,
+    },
+}
+
+impl Display for CSSValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Self::Dimension(value, unit) => write!(f, "{}{}", value, unit),
+            Self::Integer(value) => write!(f, "{}", value),
+            Self::Number(value) => write!(f, "{}", value),
+            Self::Keyword(kw) => write!(f, "{}", kw),
+            Self::Auto => write!(f, "auto"),
+            Self::Url(url) => write!(f, "url(\"{}\")", url),
+            Self::Raw(raw) => write!(f, "{}", raw),
+            Self::Unresolved { text, .. } => write!(f, "{}", text),
+            Self::Color(color) => write!(f, "{}", color),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unit {
+    Px,
+    Percent,
+    /// Relative to the element's font size, or its parent's for `font-size`.
+    Em,
+    /// Relative to the root element's font size.
+    Rem,
+    /// Percentages of the viewport's width, height, and smaller or larger
+    /// dimension.
+    Vw,
+    Vh,
+    Vmin,
+    Vmax,
+}
+
+impl Unit {
+    /// Unit of a dimension's suffix. Unsupported units are read as px.
+    pub fn from_suffix(suffix: &str) -> Unit {
+        match suffix.to_ascii_lowercase().as_str() {
+            "%" => Unit::Percent,
+            "em" => Unit::Em,
+            "rem" => Unit::Rem,
+            "vw" => Unit::Vw,
+            "vh" => Unit::Vh,
+            "vmin" => Unit::Vmin,
+            "vmax" => Unit::Vmax,
+            _ => Unit::Px,
+        }
+    }
+}
+
+impl Display for Unit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let output = match self {
+            Self::Px => "px",
+            Self::Percent => "%",
+            Self::Em => "em",
+            Self::Rem => "rem",
+            Self::Vw => "vw",
+            Self::Vh => "vh",
+            Self::Vmin => "vmin",
+            Self::Vmax => "vmax",
+        };
+        write!(f, "{}", output)
+    }
+}
+
+pub fn new_css_rule(selectors: Vec<CSSSelector>, declarations: Vec<CSSDeclaration>) -> CSSRule {
+    CSSRule {
+        selectors,
+        declarations: DeclarationBlock::new(declarations),
+        source_order: 0,
+        origin: Origin::Author,
+    }
+}
+
+pub fn new_css_declaration(
+    property: CSSProperty,
+    value: CSSValue,
+    is_important: bool,
+) -> CSSDeclaration {
+    CSSDeclaration {
+        property,
+        value,
+        is_important,
+    }
+}
+
+pub fn new_css_selector(
+    tag: Option<TagType>,
+    class: Vec<String>,
+    id: Option<String>,
+) -> CSSSelector {
+    CSSSelector::SimpleSelector(SimpleSelector {
+        tag,
+        id: id.map(Atom::from),
+        class: class.into_iter().map(Atom::from).collect(),
+        attributes: vec![],
+        pseudo_classes: vec![],
+        pseudo_element: None,
+    })
+}
@@ -0,0 +1,321 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        OnceLock,
+    },
+};
+
+use crate::{
+    atom::Atom,
+    cssom::DeclarationBlock,
+    parser::{CSSParser, IParser},
+};
+
+/// Nodes are shared with the threads laying out independent subtrees, so
+/// they're `Sync`.
+pub trait IDomNode: Sync {
+    fn get_children(&self) -> &Vec<Node>;
+    fn get_node_type(&self) -> &NodeType;
+}
+
+#[derive(Debug)]
+pub struct Document {
+    pub children: Vec<Node>,
+    pub node_type: NodeType,
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for child in &self.children {
+            child.recursive_fmt(f, 0)?;
+        }
+        Ok(())
+    }
+}
+
+impl IDomNode for Document {
+    fn get_children(&self) -> &Vec<Node> {
+        &self.children
+    }
+
+    fn get_node_type(&self) -> &NodeType {
+        &self.node_type
+    }
+}
+
+#[derive(Debug)]
+pub struct Node {
+    children: Vec<Node>,
+    node_type: NodeType,
+}
+
+// Dropped iteratively, as the default recursive drop would overflow the
+// stack on deeply nested documents.
+impl Drop for Node {
+    fn drop(&mut self) {
+        let mut stack = std::mem::take(&mut self.children);
+        while let Some(mut node) = stack.pop() {
+            stack.append(&mut node.children);
+        }
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.recursive_fmt(f, 0)
+    }
+}
+
+impl Node {
+    fn recursive_fmt(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        let indent_root = "\t".repeat(depth);
+        match &self.node_type {
+            NodeType::Element(element) => {
+                write!(f, "{}<{}", indent_root, element.tag_type)?;
+                for i in element.attributes() {
+                    let (key, val) = i;
+                    write!(f, " {}='{}'", key, val)?;
+                }
+                writeln!(f, ">")?;
+                if element.tag_type.is_void() {
+                    return Ok(());
+                }
+                for child in &self.children {
+                    child.recursive_fmt(f, depth + 1)?;
+                }
+                writeln!(f, "{}</{}>", indent_root, element.tag_type)
+            }
+            NodeType::Text(content) => writeln!(f, "{}{}", indent_root, content),
+        }
+    }
+}
+
+impl IDomNode for Node {
+    fn get_children(&self) -> &Vec<Node> {
+        &self.children
+    }
+
+    fn get_node_type(&self) -> &NodeType {
+        &self.node_type
+    }
+}
+
+#[derive(Debug)]
+pub enum NodeType {
+    Text(String),
+    Element(ElementData),
+}
+
+/// Identifies an element for as long as it lives, e.g. to keep its
+/// interaction state across restyles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    fn next() -> NodeId {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        NodeId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Interaction state of an element, matched by the `:hover`, `:focus` and
+/// `:active` pseudo-classes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ElementState {
+    pub hover: bool,
+    pub focus: bool,
+    pub active: bool,
+}
+
+impl ElementState {
+    /// Whether the state matches the pseudo-class `name`. Other pseudo-classes
+    /// never match.
+    pub fn matches(&self, name: &str) -> bool {
+        match name.to_ascii_lowercase().as_str() {
+            "hover" => self.hover,
+            "focus" => self.focus,
+            "active" => self.active,
+            _ => false,
+        }
+    }
+}
+
+/// Interaction states of the elements of a document. Elements that aren't
+/// in the store have the default state.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ElementStates {
+    states: HashMap<NodeId, ElementState>,
+}
+
+impl ElementStates {
+    pub fn new() -> ElementStates {
+        ElementStates::default()
+    }
+
+    pub fn get(&self, node: NodeId) -> ElementState {
+        self.states.get(&node).copied().unwrap_or_default()
+    }
+
+    /// Sets the state of `node`, returning whether it changed.
+    pub fn set(&mut self, node: NodeId, state: ElementState) -> bool {
+        let changed = self.get(node) != state;
+        if state == ElementState::default() {
+            self.states.remove(&node);
+        } else {
+            self.states.insert(node, state);
+        }
+        changed
+    }
+
+    /// Sets a flag, picked by e.g. `|state| &mut state.hover`, on `nodes`
+    /// and unsets it on every other element. Returns the elements whose flag
+    /// changed.
+    pub fn set_only(
+        &mut self,
+        nodes: &[NodeId],
+        flag: impl Fn(&mut ElementState) -> &mut bool,
+    ) -> Vec<NodeId> {
+        let mut changed = vec![];
+        for (node, state) in self.states.iter_mut() {
+            let set = nodes.contains(node);
+            if std::mem::replace(flag(state), set) != set {
+                changed.push(*node);
+            }
+        }
+        for node in nodes {
+            let state = self.states.entry(*node).or_default();
+            if !std::mem::replace(flag(state), true) {
+                changed.push(*node);
+            }
+        }
+        self.states
+            .retain(|_, state| *state != ElementState::default());
+        changed
+    }
+
+    pub fn clear(&mut self) {
+        self.states.clear();
+    }
+}
+
+#[derive(Debug)]
+pub struct ElementData {
+    pub tag_type: TagType,
+    node_id: NodeId,
+    attributes: AttrsMap,
+    // Interned once when the element is created, as selector matching looks
+    // them up for every rule.
+    id: Option<Atom>,
+    classes: Vec<Atom>,
+    // Parsed from the `style` attribute the first time it's needed.
+    inline_style: OnceLock<DeclarationBlock>,
+}
+
+impl ElementData {
+    pub fn new(tag_type: TagType, attributes: AttrsMap) -> ElementData {
+        let id = attributes.get("id").map(|id| Atom::from(id.as_str()));
+        let mut classes: Vec<Atom> = vec![];
+        for class in attributes
+            .get("class")
+            .into_iter()
+            .flat_map(|c| c.split_whitespace())
+        {
+            let class = Atom::from(class);
+            if !classes.contains(&class) {
+                classes.push(class);
+            }
+        }
+        ElementData {
+            tag_type,
+            node_id: NodeId::next(),
+            attributes,
+            id,
+            classes,
+            inline_style: OnceLock::new(),
+        }
+    }
+
+    pub fn node_id(&self) -> NodeId {
+        self.node_id
+    }
+
+    pub fn id(&self) -> Option<&Atom> {
+        self.id.as_ref()
+    }
+
+    pub fn classes(&self) -> &[Atom] {
+        &self.classes
+    }
+
+    pub fn attributes(&self) -> &AttrsMap {
+        &self.attributes
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&String> {
+        self.attributes.get(name)
+    }
+
+    /// Declarations of the `style` attribute, where the last `;` is optional.
+    /// A syntax error drops the whole attribute.
+    pub fn inline_style(&self) -> &DeclarationBlock {
+        self.inline_style.get_or_init(|| {
+            let Some(style) = self.attribute("style") else {
+                return DeclarationBlock::default();
+            };
+            let mut style = style.trim().to_string();
+            if !style.is_empty() && !style.ends_with(';') {
+                style.push(';');
+            }
+            CSSParser::new(&style)
+                .try_parse_declarations()
+                .map(DeclarationBlock::new)
+                .unwrap_or_default()
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TagType {
+    Html,
+    Div,
+    P,
+    Img,
+    Style,
+}
+
+impl TagType {
+    /// Whether elements of the type are void, with no content or end tag.
+    pub fn is_void(&self) -> bool {
+        matches!(self, TagType::Img)
+    }
+}
+
+impl std::fmt::Display for TagType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TagType::Html => write!(f, "html"),
+            TagType::Div => write!(f, "div"),
+            TagType::P => write!(f, "p"),
+            TagType::Img => write!(f, "img"),
+            TagType::Style => write!(f, "style"),
+        }
+    }
+}
+
+pub type AttrsMap = HashMap<Atom, String>;
+
+pub fn new_text(content: &str, children: Vec<Node>) -> Node {
+    Node {
+        children,
+        node_type: NodeType::Text(String::from(content.trim())),
+    }
+}
+
+pub fn new_element(tag_type: TagType, attributes: AttrsMap, children: Vec<Node>) -> Node {
+    Node {
+        children,
+        node_type: NodeType::Element(ElementData::new(tag_type, attributes)),
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+mod bitmap;
+mod truetype;
+
+pub use bitmap::{BitmapFont, GLYPH_HEIGHT, GLYPH_WIDTH};
+pub use truetype::TrueTypeFont;
+
+/// Directories searched for system fonts, in order.
+pub const SYSTEM_FONT_DIRS: &[&str] = &[
+    "/usr/share/fonts",
+    "/usr/local/share/fonts",
+    "/Library/Fonts",
+    "/System/Library/Fonts",
+    "C:\\Windows\\Fonts",
+];
+
+/// Regular sans-serif faces picked over any other font found on the system.
+const PREFERRED_FONTS: &[&str] = &[
+    "DejaVuSans.ttf",
+    "LiberationSans-Regular.ttf",
+    "Arial.ttf",
+    "arial.ttf",
+    "Helvetica.ttf",
+];
+
+/// Metrics needed to lay out text. All values are in px for a font `size` in px.
+pub trait FontMetrics {
+    fn ascent(&self, size: f32) -> f32;
+    fn descent(&self, size: f32) -> f32;
+    fn advance(&self, ch: char, size: f32) -> f32;
+
+    fn line_gap(&self, _size: f32) -> f32 {
+        0.0
+    }
+
+    fn line_height(&self, size: f32) -> f32 {
+        self.ascent(size) + self.descent(size) + self.line_gap(size)
+    }
+
+    fn measure(&self, text: &str, size: f32) -> f32 {
+        text.chars().map(|ch| self.advance(ch, size)).sum()
+    }
+}
+
+/// Fonts registered by family name, e.g. the faces of `@font-face` rules.
+/// Faces are shared rather than copied, so the store is cheap to clone, and
+/// can be used by layout threads.
+#[derive(Default, Clone)]
+pub struct FontStore {
+    faces: HashMap<String, Arc<dyn FontMetrics + Send + Sync>>,
+}
+
+impl FontStore {
+    pub fn new() -> FontStore {
+        FontStore::default()
+    }
+
+    /// Registers `font` under `family`, replacing any previous face. Family
+    /// names match case-insensitively, as in CSS.
+    pub fn register(&mut self, family: &str, font: impl FontMetrics + Send + Sync + 'static) {
+        self.faces.insert(family.to_lowercase(), Arc::new(font));
+    }
+
+    pub fn get(&self, family: &str) -> Option<Arc<dyn FontMetrics + Send + Sync>> {
+        self.faces.get(&family.to_lowercase()).cloned()
+    }
+
+    /// First registered face of a `font-family` list. `None` means the
+    /// renderer should fall back to its default font.
+    pub fn find(&self, families: &[String]) -> Option<Arc<dyn FontMetrics + Send + Sync>> {
+        families.iter().find_map(|family| self.get(family))
+    }
+}
+
+fn collect_font_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+    {
+        if path.is_dir() {
+            collect_font_files(&path, files);
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("ttf"))
+        {
+            files.push(path);
+        }
+    }
+}
+
+/// Finds a usable TrueType font in `dirs`, preferring common sans-serif faces.
+pub fn load_system_font<P: AsRef<Path>>(dirs: &[P]) -> Option<TrueTypeFont> {
+    let mut files = vec![];
+    for dir in dirs {
+        collect_font_files(dir.as_ref(), &mut files);
+    }
+    files.sort_by_key(|path| {
+        let name = path.file_name().and_then(|name| name.to_str());
+        PREFERRED_FONTS
+            .iter()
+            .position(|preferred| Some(*preferred) == name)
+            .unwrap_or(PREFERRED_FONTS.len())
+    });
+    files.iter().find_map(|path| TrueTypeFont::load(path))
+}
+
+/// System font when one is available, otherwise the bundled bitmap font.
+pub fn default_font() -> Box<dyn FontMetrics + Send + Sync> {
+    match load_system_font(SYSTEM_FONT_DIRS) {
+        Some(font) => Box::new(font),
+        None => Box::new(BitmapFont),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::font::{load_system_font, BitmapFont, FontMetrics, FontStore, GLYPH_WIDTH};
+
+    #[test]
+    fn bundled_font_metrics() {
+        let font = BitmapFont;
+        assert_eq!(font.ascent(16.0), 14.0);
+        assert_eq!(font.descent(16.0), 2.0);
+        assert_eq!(font.line_height(16.0), 16.0);
+        assert_eq!(font.measure("Hello", 16.0), 60.0);
+        assert_eq!(font.glyph('\u{e9}'), font.glyph('?'));
+        assert!(font.glyph('A').iter().all(|row| *row >> GLYPH_WIDTH == 0));
+    }
+
+    #[test]
+    fn missing_system_fonts() {
+        assert!(load_system_font(&["/nonexistent/chrusty/fonts"]).is_none());
+    }
+
+    #[test]
+    fn finds_registered_families() {
+        let mut store = FontStore::new();
+        store.register("Pixel Sans", BitmapFont);
+        let families = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+        let font = store
+            .find(&families(&["Missing", "pixel sans", "serif"]))
+            .unwrap();
+        assert_eq!(font.line_height(16.0), 16.0);
+        assert!(store.find(&families(&["Missing", "serif"])).is_none());
+    }
+}
@@ -0,0 +1,140 @@
+use std::{fs, path::Path};
+
+use crate::font::FontMetrics;
+
+/// Horizontal metrics read from a TrueType/OpenType font file. Only the
+/// tables needed for measuring text are parsed.
+#[derive(Debug)]
+pub struct TrueTypeFont {
+    data: Vec<u8>,
+    units_per_em: f32,
+    ascender: f32,
+    descender: f32,
+    line_gap: f32,
+    hmtx: usize,
+    num_h_metrics: u16,
+    cmap: usize,
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_i16(data: &[u8], offset: usize) -> Option<i16> {
+    read_u16(data, offset).map(|value| value as i16)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn find_table(data: &[u8], tag: &[u8; 4]) -> Option<usize> {
+    let num_tables = read_u16(data, 4)? as usize;
+    (0..num_tables)
+        .map(|i| 12 + i * 16)
+        .find(|record| data.get(*record..record + 4) == Some(tag))
+        .and_then(|record| read_u32(data, record + 8))
+        .map(|offset| offset as usize)
+}
+
+/// Offset of a format 4 (BMP) character map subtable.
+fn find_unicode_cmap(data: &[u8], cmap: usize) -> Option<usize> {
+    let num_subtables = read_u16(data, cmap + 2)? as usize;
+    (0..num_subtables)
+        .map(|i| cmap + 4 + i * 8)
+        .filter_map(|record| {
+            let platform = read_u16(data, record)?;
+            let encoding = read_u16(data, record + 2)?;
+            let subtable = cmap + read_u32(data, record + 4)? as usize;
+            let is_unicode = platform == 0 || (platform == 3 && encoding == 1);
+            (is_unicode && read_u16(data, subtable)? == 4).then_some(subtable)
+        })
+        .next()
+}
+
+impl TrueTypeFont {
+    pub fn parse(data: Vec<u8>) -> Option<TrueTypeFont> {
+        let head = find_table(&data, b"head")?;
+        let hhea = find_table(&data, b"hhea")?;
+        let cmap = find_unicode_cmap(&data, find_table(&data, b"cmap")?)?;
+        Some(TrueTypeFont {
+            units_per_em: read_u16(&data, head + 18)? as f32,
+            ascender: read_i16(&data, hhea + 4)? as f32,
+            descender: -(read_i16(&data, hhea + 6)? as f32),
+            line_gap: read_i16(&data, hhea + 8)? as f32,
+            num_h_metrics: read_u16(&data, hhea + 34)?,
+            hmtx: find_table(&data, b"hmtx")?,
+            cmap,
+            data,
+        })
+    }
+
+    pub fn load(path: &Path) -> Option<TrueTypeFont> {
+        TrueTypeFont::parse(fs::read(path).ok()?)
+    }
+
+    fn glyph_index(&self, ch: char) -> Option<u16> {
+        let code = u16::try_from(ch as u32).ok()?;
+        let data = &self.data;
+        let seg_count = read_u16(data, self.cmap + 6)? as usize / 2;
+        let end_codes = self.cmap + 14;
+        let start_codes = end_codes + seg_count * 2 + 2;
+        let id_deltas = start_codes + seg_count * 2;
+        let id_range_offsets = id_deltas + seg_count * 2;
+        let segment = (0..seg_count)
+            .find(|i| read_u16(data, end_codes + i * 2).is_some_and(|end_code| end_code >= code))?;
+        let start_code = read_u16(data, start_codes + segment * 2)?;
+        if start_code > code {
+            return None;
+        }
+
+        let id_delta = read_u16(data, id_deltas + segment * 2)?;
+        let range_offset_at = id_range_offsets + segment * 2;
+        let id_range_offset = read_u16(data, range_offset_at)? as usize;
+        let glyph = match id_range_offset {
+            0 => code.wrapping_add(id_delta),
+            _ => {
+                let at = range_offset_at + id_range_offset + (code - start_code) as usize * 2;
+                match read_u16(data, at)? {
+                    0 => 0,
+                    glyph => glyph.wrapping_add(id_delta),
+                }
+            }
+        };
+        (glyph != 0).then_some(glyph)
+    }
+
+    fn advance_units(&self, glyph: u16) -> Option<u16> {
+        // Glyphs past the last long metric share its advance width.
+        let metric = glyph.min(self.num_h_metrics.checked_sub(1)?) as usize;
+        read_u16(&self.data, self.hmtx + metric * 4)
+    }
+
+    fn scale(&self, size: f32) -> f32 {
+        size / self.units_per_em
+    }
+}
+
+impl FontMetrics for TrueTypeFont {
+    fn ascent(&self, size: f32) -> f32 {
+        self.ascender * self.scale(size)
+    }
+
+    fn descent(&self, size: f32) -> f32 {
+        self.descender * self.scale(size)
+    }
+
+    fn line_gap(&self, size: f32) -> f32 {
+        self.line_gap * self.scale(size)
+    }
+
+    fn advance(&self, ch: char, size: f32) -> f32 {
+        // Missing characters use the .notdef glyph, like real renderers.
+        let glyph = self.glyph_index(ch).unwrap_or(0);
+        self.advance_units(glyph).unwrap_or(0) as f32 * self.scale(size)
+    }
+}
@@ -0,0 +1,306 @@
+//! JSON dump of the styled tree, for golden tests and external tooling. Each
+//! node is written on its own line, indented by its depth, so dumps diff
+//! well.
+
+use std::fmt::{Debug, Write};
+
+use crate::{
+    dom::NodeType,
+    style::{ComputedStyle, StyledNode},
+    values::{LengthPercentage, LengthPercentageAuto, LengthPercentageNone, TrackSize},
+};
+
+/// Serializes `root` and its descendants. Each node is an object with:
+/// - `node`: the tag name, or `#text`
+/// - `attributes` or `text`, depending on the kind of node
+/// - `matched_rules`: the selectors of the rules matching the node, see
+///   `StyledNode::matched_rules`
+/// - `specified`: the cascaded declarations, sorted by property
+/// - `computed`: the computed values, as CSS text
+/// - `children`
+pub fn styled_tree_to_json(root: &StyledNode) -> String {
+    let mut json = String::new();
+    // Written with an explicit stack of nodes along with the number of their
+    // children written so far, as deeply nested documents would overflow the
+    // call stack.
+    write_node_start(&mut json, root, 0);
+    let mut stack = vec![(root, 0)];
+    while let Some((node, written)) = stack.last_mut() {
+        let node: &StyledNode = node;
+        match node.children.get(*written) {
+            Some(child) => {
+                if *written > 0 {
+                    json.push(',');
+                }
+                *written += 1;
+                json.push('\n');
+                write_node_start(&mut json, child, stack.len());
+                stack.push((child, 0));
+            }
+            None => {
+                if !node.children.is_empty() {
+                    json.push('\n');
+                    json.push_str(&"  ".repeat(stack.len() - 1));
+                }
+                json.push_str("]}");
+                stack.pop();
+            }
+        }
+    }
+    json.push('\n');
+    json
+}
+
+/// Writes the fields of `node` and opens its `children` array.
+fn write_node_start(json: &mut String, node: &StyledNode, depth: usize) {
+    json.push_str(&"  ".repeat(depth));
+    match node.node.get_node_type() {
+        NodeType::Text(text) => {
+            json.push_str("{\"node\": \"#text\", \"text\": ");
+            write_string(json, text);
+        }
+        NodeType::Element(element) => {
+            json.push_str("{\"node\": ");
+            write_string(json, &element.tag_type.to_string());
+            let mut attributes: Vec<_> = element.attributes().iter().collect();
+            attributes.sort_by(|a, b| a.0.cmp(b.0));
+            json.push_str(", \"attributes\": ");
+            write_object(
+                json,
+                attributes
+                    .into_iter()
+                    .map(|(name, value)| (name.to_string(), value.clone())),
+            );
+        }
+    }
+
+    json.push_str(", \"matched_rules\": [");
+    for (i, rule) in node.matched_rules.iter().enumerate() {
+        if i > 0 {
+            json.push_str(", ");
+        }
+        let selectors: Vec<String> = rule.selectors.iter().map(|s| s.to_string()).collect();
+        write_string(json, &selectors.join(", "));
+    }
+    json.push(']');
+
+    let mut specified: Vec<(String, String)> = node
+        .specified_values
+        .iter()
+        .map(|(property, value)| (property.to_string(), value.to_string()))
+        .collect();
+    specified.sort();
+    json.push_str(", \"specified\": ");
+    write_object(json, specified.into_iter());
+
+    json.push_str(", \"computed\": ");
+    write_object(json, computed_values(&node.style).into_iter());
+    json.push_str(", \"children\": [");
+}
+
+/// The computed values of `style` as CSS text, in declaration order.
+fn computed_values(style: &ComputedStyle) -> Vec<(String, String)> {
+    let lpa = |value: LengthPercentageAuto| match value {
+        LengthPercentageAuto::Length(length) => format!("{length}px"),
+        LengthPercentageAuto::Percent(percentage) => format!("{percentage}%"),
+        LengthPercentageAuto::Auto => "auto".to_string(),
+    };
+    let lpn = |value: LengthPercentageNone| match value {
+        LengthPercentageNone::Length(length) => format!("{length}px"),
+        LengthPercentageNone::Percent(percentage) => format!("{percentage}%"),
+        LengthPercentageNone::None => "none".to_string(),
+    };
+    let lp = |value: LengthPercentage| match value {
+        LengthPercentage::Length(length) => format!("{length}px"),
+        LengthPercentage::Percent(percentage) => format!("{percentage}%"),
+    };
+    let px = |length: f32| format!("{length}px");
+    let tracks = |tracks: &[TrackSize]| match tracks {
+        [] => "none".to_string(),
+        tracks => tracks
+            .iter()
+            .map(|track| match track {
+                TrackSize::Length(length) => format!("{length}px"),
+                TrackSize::Percent(percentage) => format!("{percentage}%"),
+                TrackSize::Fraction(fraction) => format!("{fraction}fr"),
+                TrackSize::Auto => "auto".to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(" "),
+    };
+    let families: Vec<String> = style
+        .font_families
+        .iter()
+        .map(|family| format!("{family:?}"))
+        .collect();
+    let values = [
+        ("display", keyword(&style.display)),
+        ("position", keyword(&style.position)),
+        ("float", keyword(&style.float)),
+        ("clear", keyword(&style.clear)),
+        ("flex-direction", keyword(&style.flex_direction)),
+        ("justify-content", keyword(&style.justify_content)),
+        ("align-items", keyword(&style.align_items)),
+        ("flex-grow", style.flex_grow.to_string()),
+        ("flex-shrink", style.flex_shrink.to_string()),
+        ("flex-basis", lpa(style.flex_basis)),
+        (
+            "grid-template-columns",
+            tracks(&style.grid_template_columns),
+        ),
+        ("grid-template-rows", tracks(&style.grid_template_rows)),
+        ("top", lpa(style.top)),
+        ("right", lpa(style.right)),
+        ("bottom", lpa(style.bottom)),
+        ("left", lpa(style.left)),
+        ("width", lpa(style.width)),
+        ("height", lpa(style.height)),
+        ("min-width", lpa(style.min_width)),
+        ("min-height", lpa(style.min_height)),
+        ("max-width", lpn(style.max_width)),
+        ("max-height", lpn(style.max_height)),
+        ("box-sizing", keyword(&style.box_sizing)),
+        ("margin-top", lpa(style.margin_top)),
+        ("margin-right", lpa(style.margin_right)),
+        ("margin-bottom", lpa(style.margin_bottom)),
+        ("margin-left", lpa(style.margin_left)),
+        ("padding-top", lp(style.padding_top)),
+        ("padding-right", lp(style.padding_right)),
+        ("padding-bottom", lp(style.padding_bottom)),
+        ("padding-left", lp(style.padding_left)),
+        ("border-top-width", px(style.border_top_width)),
+        ("border-right-width", px(style.border_right_width)),
+        ("border-bottom-width", px(style.border_bottom_width)),
+        ("border-left-width", px(style.border_left_width)),
+        ("border-top-style", keyword(&style.border_top_style)),
+        ("border-right-style", keyword(&style.border_right_style)),
+        ("border-bottom-style", keyword(&style.border_bottom_style)),
+        ("border-left-style", keyword(&style.border_left_style)),
+        ("border-top-color", style.border_top_color.to_string()),
+        ("border-right-color", style.border_right_color.to_string()),
+        ("border-bottom-color", style.border_bottom_color.to_string()),
+        ("border-left-color", style.border_left_color.to_string()),
+        ("border-top-left-radius", lp(style.border_top_left_radius)),
+        ("border-top-right-radius", lp(style.border_top_right_radius)),
+        (
+            "border-bottom-right-radius",
+            lp(style.border_bottom_right_radius),
+        ),
+        (
+            "border-bottom-left-radius",
+            lp(style.border_bottom_left_radius),
+        ),
+        ("overflow", keyword(&style.overflow)),
+        ("visibility", keyword(&style.visibility)),
+        ("direction", keyword(&style.direction)),
+        ("vertical-align", keyword(&style.vertical_align)),
+        ("white-space", keyword(&style.white_space)),
+        ("overflow-wrap", keyword(&style.overflow_wrap)),
+        ("word-break", keyword(&style.word_break)),
+        (
+            "z-index",
+            style
+                .z_index
+                .map_or("auto".to_string(), |z_index| z_index.to_string()),
+        ),
+        ("opacity", style.opacity.to_string()),
+        ("line-height", px(style.line_height)),
+        ("color", style.color.to_string()),
+        ("background-color", style.background_color.to_string()),
+        ("background-image", style.background_image.to_string()),
+        ("font-family", families.join(", ")),
+        ("font-size", px(style.font_size)),
+        ("font-style", keyword(&style.font_style)),
+        ("font-weight", style.font_weight.to_string()),
+    ];
+    values
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect()
+}
+
+/// The CSS keyword of a keyword enum variant, e.g. `inline-block` for
+/// `Display::InlineBlock`.
+fn keyword(value: &impl Debug) -> String {
+    let mut keyword = String::new();
+    for (i, c) in format!("{value:?}").chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            keyword.push('-');
+        }
+        keyword.push(c.to_ascii_lowercase());
+    }
+    keyword
+}
+
+fn write_object(json: &mut String, entries: impl Iterator<Item = (String, String)>) {
+    json.push('{');
+    for (i, (key, value)) in entries.enumerate() {
+        if i > 0 {
+            json.push_str(", ");
+        }
+        write_string(json, &key);
+        json.push_str(": ");
+        write_string(json, &value);
+    }
+    json.push('}');
+}
+
+fn write_string(json: &mut String, string: &str) {
+    json.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        json::styled_tree_to_json,
+        parser::{CSSParser, HTMLParser, IParser},
+        style::get_styled_node,
+    };
+
+    #[test]
+    fn dumps_styled_tree() {
+        let html = "<div class=\"main\" id=\"a\"><p>Say \"hi\"</p></div>";
+        let css = "
+            div, .main { display: inline-block; width: 50%; }
+            #a { color: red; }
+            p.missing { color: blue; }
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let json = styled_tree_to_json(&get_styled_node(&dom, &stylesheet));
+        let lines: Vec<&str> = json.lines().collect();
+        assert_eq!(lines.len(), 7, "{json}");
+
+        let div = lines[1];
+        assert!(div.starts_with(
+            "  {\"node\": \"div\", \"attributes\": {\"class\": \"main\", \"id\": \"a\"}, \
+             \"matched_rules\": [\"div, .main\", \"#a\"], \
+             \"specified\": {\"color\": \"rgb(255, 0, 0)\", \"display\": \"inline-block\", \"width\": \"50%\"}, \
+             \"computed\": {\"display\": \"inline-block\", \"position\": \"static\", "
+        ));
+        assert!(div.contains("\"width\": \"50%\", \"height\": \"auto\""));
+        assert!(div.contains("\"color\": \"rgb(255, 0, 0)\""));
+        assert!(div.ends_with("\"children\": ["));
+
+        // Text is escaped, and the brackets of nodes with children are closed
+        // on their own line.
+        let text = lines[3];
+        assert!(text.starts_with("      {\"node\": \"#text\", \"text\": \"Say \\\"hi\\\"\", "));
+        assert!(text.ends_with("\"children\": []}"), "{text}");
+        assert_eq!(lines[4..], ["    ]}", "  ]}", "]}"]);
+    }
+}
@@ -0,0 +1,127 @@
+//! Text dump of the layout tree, for debugging and golden tests. Each box
+//! is written on its own line, indented by its depth, with its line boxes
+//! and fragments below it.
+
+use std::fmt::Write;
+
+use crate::{
+    dom::NodeType,
+    layout::{LayoutBox, Rect},
+};
+
+fn rect(rect: Rect) -> String {
+    format!("({}, {}, {}, {})", rect.x, rect.y, rect.width, rect.height)
+}
+
+impl LayoutBox<'_> {
+    /// Writes the box and its descendants. Each box gets a line with:
+    /// - its kind, e.g. `Block`
+    /// - the tag of its element along with the element's id, or its text
+    /// - its content, padding, border and margin boxes, and the area its
+    ///   content overflows to, as `(x, y, width, height)`
+    ///
+    /// Its line boxes and fragments follow, then its children.
+    pub fn dump(&self) -> String {
+        let mut dump = String::new();
+        // Written with an explicit stack, as deeply nested documents would
+        // overflow the call stack.
+        let mut stack = vec![(self, 0)];
+        while let Some((layout_box, depth)) = stack.pop() {
+            let indent = "  ".repeat(depth);
+            let _ = write!(dump, "{indent}{:?}", layout_box.box_type.kind());
+            if let Some(style_node) = layout_box.get_style_node() {
+                match style_node.node.get_node_type() {
+                    NodeType::Element(element) => {
+                        let _ = write!(dump, " <{}> {:?}", element.tag_type, element.node_id());
+                    }
+                    NodeType::Text(text) => {
+                        let _ = write!(dump, " {text:?}");
+                    }
+                }
+            }
+            let dimensions = &layout_box.dimensions;
+            let _ = writeln!(
+                dump,
+                " content={} padding={} border={} margin={} overflow={}",
+                rect(dimensions.content),
+                rect(dimensions.padding_box()),
+                rect(dimensions.border_box()),
+                rect(dimensions.margin_box()),
+                rect(dimensions.scrollable_overflow),
+            );
+            for (i, line) in layout_box.lines.iter().enumerate() {
+                let _ = writeln!(
+                    dump,
+                    "{indent}  line {i} {} baseline={}",
+                    rect(line.rect),
+                    line.baseline
+                );
+            }
+            for fragment in &layout_box.fragments {
+                let _ = write!(
+                    dump,
+                    "{indent}  fragment line={} {} baseline={}",
+                    fragment.line,
+                    rect(fragment.rect),
+                    fragment.baseline
+                );
+                if !fragment.run.glyphs.is_empty() {
+                    let _ = write!(dump, " {:?}", fragment.run.text());
+                }
+                dump.push('\n');
+            }
+            let children = layout_box.children.iter().rev();
+            stack.extend(children.map(|child| (child, depth + 1)));
+        }
+        dump
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        layout::{layout_tree, Dimensions, LayoutBox, LayoutContext},
+        parser::{CSSParser, HTMLParser, IParser},
+        style::get_styled_node,
+    };
+
+    /// Lays out `html` styled by `css` in a viewport `width` px wide. The
+    /// document and its styles are leaked, for the boxes to borrow them
+    /// past the call.
+    fn layout_of(html: &str, css: &str, width: f32) -> LayoutBox<'static> {
+        let stylesheet = Box::leak(Box::new(CSSParser::new(css).parse()));
+        let dom = Box::leak(Box::new(HTMLParser::new(html).parse()));
+        let styled_dom = Box::leak(Box::new(get_styled_node(dom, stylesheet)));
+        let mut viewport = Dimensions::default();
+        viewport.content.width = width;
+        layout_tree(styled_dom, viewport, &LayoutContext::new())
+    }
+
+    #[test]
+    fn dumps_layout_tree() {
+        let html = "<div><p>ab</p></div>";
+        let css = "p { padding: 2px; }";
+        let layout_root = layout_of(html, css, 800.0);
+        let dump = layout_root.dump();
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines.len(), 7, "{dump}");
+
+        assert!(lines[0].starts_with("Block <html> NodeId("), "{dump}");
+        assert!(lines[2].starts_with("    Block <p> NodeId("));
+        assert!(lines[2].ends_with(
+            "content=(2, 2, 796, 19.2) padding=(0, 0, 800, 23.2) \
+             border=(0, 0, 800, 23.2) margin=(0, 0, 800, 23.2) overflow=(0, 0, 800, 23.2)"
+        ));
+        assert_eq!(
+            lines[3..],
+            [
+                "      AnonymousBlock content=(2, 2, 796, 19.2) padding=(2, 2, 796, 19.2) \
+                 border=(2, 2, 796, 19.2) margin=(2, 2, 796, 19.2) overflow=(2, 2, 796, 19.2)",
+                "        line 0 (2, 2, 796, 19.2) baseline=15.6",
+                "        Inline \"ab\" content=(2, 2, 24, 19.2) padding=(2, 2, 24, 19.2) \
+                 border=(2, 2, 24, 19.2) margin=(2, 2, 24, 19.2) overflow=(2, 2, 24, 19.2)",
+                "          fragment line=0 (2, 2, 24, 19.2) baseline=15.6 \"ab\"",
+            ]
+        );
+    }
+}
@@ -0,0 +1,298 @@
+//! Grid layout: tracks sized from `grid-template-columns` and
+//! `grid-template-rows`, with items placed one per cell in document order.
+
+use crate::{
+    layout::{padding_and_border, ContainingBlock, LayoutBox, LayoutContext},
+    style::{AlignItems, Direction},
+    values::TrackSize,
+};
+
+/// Sizes `tracks` along an axis `available` px long, when it's definite.
+/// Lengths and percentages of `available` are taken as they are, and `auto`
+/// tracks are as large as their largest item, of size `contents`. The space
+/// left is shared by `fr` tracks in proportion to their factor, or evenly by
+/// `auto` tracks when there are none. When `available` isn't definite,
+/// percentage and `fr` tracks are sized like `auto` ones.
+fn size_tracks(tracks: &[TrackSize], contents: &[f32], available: Option<f32>) -> Vec<f32> {
+    let mut sizes: Vec<f32> = tracks
+        .iter()
+        .zip(contents)
+        .map(|(track, content)| match (track, available) {
+            (TrackSize::Length(length), _) => *length,
+            (TrackSize::Percent(percentage), Some(available)) => percentage / 100.0 * available,
+            (TrackSize::Fraction(_), Some(_)) => 0.0,
+            _ => *content,
+        })
+        .collect();
+    let Some(available) = available else {
+        return sizes;
+    };
+    let free = (available - sizes.iter().sum::<f32>()).max(0.0);
+    let fraction = |track: &TrackSize| match track {
+        TrackSize::Fraction(fraction) => *fraction,
+        _ => 0.0,
+    };
+    let fractions: f32 = tracks.iter().map(fraction).sum();
+    let autos = tracks
+        .iter()
+        .filter(|track| **track == TrackSize::Auto)
+        .count();
+    for (size, track) in sizes.iter_mut().zip(tracks) {
+        // Factors adding up to less than 1 leave part of the space free.
+        if fractions > 0.0 {
+            *size += free * fraction(track) / fractions.max(1.0);
+        } else if *track == TrackSize::Auto {
+            *size += free / autos as f32;
+        }
+    }
+    sizes
+}
+
+/// Offsets of tracks of `sizes` laid end to end.
+fn track_offsets(sizes: &[f32]) -> Vec<f32> {
+    sizes
+        .iter()
+        .scan(0.0, |offset, size| {
+            let start = *offset;
+            *offset += size;
+            Some(start)
+        })
+        .collect()
+}
+
+impl LayoutBox<'_> {
+    /// Lays the children out as grid items, each in the next free cell of
+    /// the grid going along the rows. Rows past `grid-template-rows` are
+    /// `auto`, as is the single column of a grid without
+    /// `grid-template-columns`. Items without a width or height fill their
+    /// cell. `height` is the container's content height when it's definite,
+    /// and `direction` that of the items' content.
+    pub(super) fn layout_grid_items(
+        &mut self,
+        height: Option<f32>,
+        direction: Direction,
+        context: &LayoutContext,
+    ) {
+        let style = self.style();
+        let content = self.dimensions.content;
+        let columns = match style.grid_template_columns.as_slice() {
+            [] => &[TrackSize::Auto][..],
+            columns => columns,
+        };
+        let column_count = columns.len();
+        // Out-of-flow children aren't grid items, and start from the
+        // container's content box.
+        for child in &mut self.children {
+            if child.is_out_of_flow() {
+                child.set_static_position(content.x, content.y);
+            }
+        }
+        let item_count = self
+            .children
+            .iter()
+            .filter(|child| !child.is_out_of_flow())
+            .count();
+        let row_count = item_count
+            .div_ceil(column_count)
+            .max(style.grid_template_rows.len());
+        let rows: Vec<TrackSize> = (0..row_count)
+            .map(|row| {
+                let track = style.grid_template_rows.get(row);
+                track.copied().unwrap_or(TrackSize::Auto)
+            })
+            .collect();
+        let cell = |x: f32, y: f32, width: f32| ContainingBlock {
+            x,
+            y,
+            width,
+            height: None,
+            filled_height: 0.0,
+            direction,
+        };
+
+        let mut column_contents = vec![0.0f32; column_count];
+        let items = self.children.iter().filter(|child| !child.is_out_of_flow());
+        for (i, child) in items.enumerate() {
+            let column = &mut column_contents[i % column_count];
+            *column = column.max(child.max_content_outer_width(context));
+        }
+        let column_widths = size_tracks(columns, &column_contents, Some(content.width));
+
+        // Items are laid out in their column first to find the height of
+        // the rows. Those that end up as tall as they were measured keep that
+        // layout, moved into their cell, rather than laying out their
+        // content again at each level of nested grids.
+        let mut row_contents = vec![0.0f32; row_count];
+        let items = self
+            .children
+            .iter_mut()
+            .filter(|child| !child.is_out_of_flow());
+        for (i, child) in items.enumerate() {
+            let container = cell(0.0, 0.0, column_widths[i % column_count]);
+            (child.dimensions.padding, child.dimensions.border) =
+                padding_and_border(child.style(), container.width);
+            let width = child.item_width(container, AlignItems::Stretch, context);
+            let item_height = child
+                .definite_height(None)
+                .map(|item_height| child.clamp_height(item_height, None));
+            child.layout_item(container, width, item_height, context);
+            let row = &mut row_contents[i / column_count];
+            *row = row.max(child.dimensions.margin_box_height());
+        }
+        let row_heights = size_tracks(&rows, &row_contents, height);
+
+        let column_offsets = track_offsets(&column_widths);
+        let row_offsets = track_offsets(&row_heights);
+        let items = self
+            .children
+            .iter_mut()
+            .filter(|child| !child.is_out_of_flow());
+        for (i, child) in items.enumerate() {
+            let (row, column) = (i / column_count, i % column_count);
+            let row_height = row_heights[row];
+            let container = cell(
+                content.x + column_offsets[column],
+                content.y + row_offsets[row],
+                column_widths[column],
+            );
+            let edges = child.dimensions.margin_box_height() - child.dimensions.content.height;
+            let item_height = child
+                .definite_height(Some(row_height))
+                .unwrap_or((row_height - edges).max(0.0));
+            let item_height = child.clamp_height(item_height, Some(row_height));
+            let width = child.item_width(container, AlignItems::Stretch, context);
+            let measured = child.dimensions.content;
+            if (width, item_height) == (measured.width, measured.height)
+                && !child.depends_on_definite_height(context)
+            {
+                child.translate(container.x, container.y);
+                continue;
+            }
+            child.layout_item(container, width, Some(item_height), context);
+        }
+        self.dimensions.content.height = row_heights.iter().sum();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        layout::{layout_tree, BoxType, Dimensions, LayoutBox, LayoutContext, Rect},
+        parser::{CSSParser, HTMLParser, IParser},
+        style::get_styled_node,
+    };
+
+    /// Lays out `html` styled by `css` in a viewport `width` px wide. The
+    /// document and its styles are leaked, for the boxes to borrow them
+    /// past the call.
+    fn layout_of(html: &str, css: &str, width: f32) -> LayoutBox<'static> {
+        let stylesheet = Box::leak(Box::new(CSSParser::new(css).parse()));
+        let dom = Box::leak(Box::new(HTMLParser::new(html).parse()));
+        let styled_dom = Box::leak(Box::new(get_styled_node(dom, stylesheet)));
+        let mut viewport = Dimensions::default();
+        viewport.content.width = width;
+        layout_tree(styled_dom, viewport, &LayoutContext::new())
+    }
+
+    #[test]
+    fn lays_out_grid_items() {
+        let html = "
+            <div class=\"fixed\">
+                <p></p>
+                <p class=\"short\"></p>
+                <p></p>
+                <p>ab</p>
+            </div>
+            <div class=\"auto\">
+                <p>abc</p>
+                <p>a</p>
+            </div>
+        ";
+        let css = "
+            div.fixed {
+                display: grid;
+                width: 400px;
+                grid-template-columns: 100px 1fr 2fr;
+                grid-template-rows: 30px;
+            }
+
+            p.short {
+                height: 10px;
+            }
+
+            div.auto {
+                display: grid;
+                width: 200px;
+                grid-template-columns: auto auto;
+            }
+        ";
+        let layout_root = layout_of(html, css, 800.0);
+        let contents = |index: usize| -> Vec<Rect> {
+            layout_root.children[index]
+                .children
+                .iter()
+                .map(|item| item.dimensions.content)
+                .collect()
+        };
+        let rect = |x, y, width, height| Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+
+        // `fr` columns share what the fixed one leaves, and items fill their
+        // cell unless they have a height. The implicit row fits its item.
+        let fixed = contents(0);
+        assert_eq!(
+            fixed[..3],
+            [
+                rect(0.0, 0.0, 100.0, 30.0),
+                rect(100.0, 0.0, 100.0, 10.0),
+                rect(200.0, 0.0, 200.0, 30.0),
+            ]
+        );
+        assert_eq!((fixed[3].x, fixed[3].y, fixed[3].width), (0.0, 30.0, 100.0));
+        let height = layout_root.children[0].dimensions.content.height;
+        assert!((height - 30.0 - fixed[3].height).abs() < 1e-3);
+
+        // `auto` columns fit their content, then share the rest evenly.
+        let widths: Vec<f32> = contents(1).iter().map(|item| item.width).collect();
+        assert_eq!(widths, [36.0 + 76.0, 12.0 + 76.0]);
+    }
+
+    #[test]
+    fn lays_out_the_content_of_grid_items() {
+        let css = "
+            div {
+                display: grid;
+            }
+
+            div.outer {
+                width: 300px;
+            }
+        ";
+        // Grids nested deeply enough that laying out the content of each
+        // item twice per level would take minutes.
+        let depth = 40;
+        let html = format!(
+            "<div class=\"outer\">{}<p>a</p>{}</div>",
+            "<div>".repeat(depth),
+            "</div>".repeat(depth)
+        );
+        let layout_root = layout_of(&html, css, 800.0);
+
+        let mut item = &layout_root.children[0];
+        for _ in 0..depth {
+            item = &item.children[0];
+            assert!((item.dimensions.content.height - 19.2).abs() < 1e-3);
+        }
+        let p = &item.children[0];
+        assert_eq!(p.dimensions.content.width, 300.0);
+        let text = &p.children[0];
+        assert!(matches!(text.box_type, BoxType::AnonymousBlock));
+        assert!((text.dimensions.content.height - 19.2).abs() < 1e-3);
+        assert_eq!(text.lines[0].rect.y, 0.0);
+        assert_eq!(text.children[0].fragments[0].rect.y, 0.0);
+    }
+}
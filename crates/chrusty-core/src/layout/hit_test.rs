@@ -0,0 +1,192 @@
+//! Hit testing: finding the box painted topmost at a point, e.g. to know
+//! which element the pointer is over.
+
+use crate::{dom::NodeId, layout::LayoutBox};
+
+/// The box painted topmost at a point.
+pub struct Hit<'b, 'a> {
+    pub layout_box: &'b LayoutBox<'a>,
+    /// Boxes from the one hit tested down to `layout_box`.
+    pub path: Vec<&'b LayoutBox<'a>>,
+    /// Id of the element hit: the one generating `layout_box`, or the
+    /// nearest one generating a box on the path for text and anonymous
+    /// boxes.
+    pub node: Option<NodeId>,
+}
+
+impl<'a> LayoutBox<'a> {
+    /// Whether the box is drawn at the point, where it's painted: on one of
+    /// its border boxes, and inside the boxes clipping it.
+    fn is_hit(&self, x: f32, y: f32) -> bool {
+        let (dx, dy) = self.dimensions.scroll_translation;
+        let clipped = self
+            .dimensions
+            .clip
+            .is_some_and(|clip| !clip.contains(x, y));
+        self.is_visible()
+            && !clipped
+            && self
+                .border_boxes()
+                .iter()
+                .any(|border_box| border_box.translated(dx, dy).contains(x, y))
+    }
+
+    /// The box painted topmost at the point among the box and its
+    /// descendants, found going through them in reverse paint order. Inline
+    /// boxes split across lines are hit on any of their fragments, boxes
+    /// are hit where they're scrolled to, and hidden boxes and the parts of
+    /// boxes clipped away aren't hit.
+    pub fn hit_test<'b>(&'b self, x: f32, y: f32) -> Option<Hit<'b, 'a>> {
+        enum Step<'b, 'a> {
+            /// Tests the stacking context established by the box, or the
+            /// root.
+            Context(&'b LayoutBox<'a>),
+            /// Tests the box on its own.
+            Test(&'b LayoutBox<'a>),
+        }
+
+        let mut stack = vec![Step::Context(self)];
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Test(layout_box) => {
+                    if layout_box.is_hit(x, y) {
+                        let path = self.path_to(layout_box);
+                        let node = path
+                            .iter()
+                            .rev()
+                            .find_map(|layout_box| layout_box.node_id());
+                        return Some(Hit {
+                            layout_box,
+                            path,
+                            node,
+                        });
+                    }
+                }
+                // The box is painted below its descendants, which are
+                // pushed after it in paint order, so the last one painted
+                // is tested first.
+                Step::Context(layout_box) => {
+                    stack.push(Step::Test(layout_box));
+                    for entry in layout_box.paint_order() {
+                        stack.push(match entry.establishes_stacking_context() {
+                            true => Step::Context(entry),
+                            false => Step::Test(entry),
+                        });
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Boxes from this one down to `target`, which is one of its
+    /// descendants or itself.
+    fn path_to<'b>(&'b self, target: &LayoutBox<'a>) -> Vec<&'b LayoutBox<'a>> {
+        let mut path = vec![];
+        // Pre-order walk along with the depth of each box, keeping the
+        // boxes above the current one on the path.
+        let mut stack = vec![(self, 0)];
+        while let Some((layout_box, depth)) = stack.pop() {
+            path.truncate(depth);
+            path.push(layout_box);
+            if std::ptr::eq(layout_box, target) {
+                break;
+            }
+            let children = layout_box.children.iter().rev();
+            stack.extend(children.map(|child| (child, depth + 1)));
+        }
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        layout::{layout_tree, Dimensions, LayoutBox, LayoutContext},
+        parser::{CSSParser, HTMLParser, IParser},
+        style::get_styled_node,
+    };
+
+    /// Lays out `html` styled by `css` in a viewport `width` px wide. The
+    /// document and its styles are leaked, for the boxes to borrow them
+    /// past the call.
+    fn layout_of(html: &str, css: &str, width: f32) -> LayoutBox<'static> {
+        let stylesheet = Box::leak(Box::new(CSSParser::new(css).parse()));
+        let dom = Box::leak(Box::new(HTMLParser::new(html).parse()));
+        let styled_dom = Box::leak(Box::new(get_styled_node(dom, stylesheet)));
+        let mut viewport = Dimensions::default();
+        viewport.content.width = width;
+        layout_tree(styled_dom, viewport, &LayoutContext::new())
+    }
+
+    #[test]
+    fn hits_topmost_boxes() {
+        let html = "
+            <div class=\"back\"></div>
+            <div class=\"front\"></div>
+            <div class=\"hidden\"></div>
+            <div class=\"clip\"><p class=\"tall\">ab</p></div>
+        ";
+        let css = "
+            html {
+                height: 400px;
+            }
+
+            div.back, div.front, div.hidden {
+                position: absolute;
+                left: 0px;
+                top: 0px;
+                width: 100px;
+                height: 100px;
+            }
+
+            div.back {
+                z-index: 2;
+            }
+
+            div.front {
+                z-index: 1;
+            }
+
+            div.hidden {
+                top: 50px;
+                visibility: hidden;
+            }
+
+            div.clip {
+                margin-top: 200px;
+                height: 50px;
+                overflow: hidden;
+            }
+
+            p.tall {
+                height: 100px;
+            }
+        ";
+        let layout_root = layout_of(html, css, 800.0);
+        let hit = |x, y| layout_root.hit_test(x, y).unwrap();
+
+        // The box with the higher `z-index` is painted on top, though it
+        // comes first, and hidden boxes aren't hit.
+        let back = &layout_root.children[0];
+        assert!(std::ptr::eq(hit(10.0, 10.0).layout_box, back));
+        assert!(std::ptr::eq(hit(10.0, 60.0).layout_box, back));
+        assert_eq!(hit(10.0, 10.0).node, back.node_id());
+
+        // Text is hit on its line, and stands for the element it's in.
+        let clip = &layout_root.children[3];
+        let tall = &clip.children[0];
+        let top = clip.dimensions.content.y;
+        let text = hit(10.0, top + 5.0);
+        assert!(std::ptr::eq(
+            *text.path.last().unwrap(),
+            &tall.children[0].children[0]
+        ));
+        assert_eq!(text.path.len(), 5);
+        assert_eq!(text.node, tall.node_id());
+        assert!(std::ptr::eq(hit(10.0, top + 30.0).layout_box, tall));
+        // The paragraph overflowing its parent is clipped.
+        assert!(std::ptr::eq(hit(10.0, top + 75.0).layout_box, &layout_root));
+        assert!(layout_root.hit_test(900.0, 10.0).is_none());
+    }
+}
@@ -0,0 +1,4802 @@
+use std::{cell::Cell, collections::HashMap, panic, sync::Arc, thread};
+
+use crate::{
+    dom::{NodeType, TagType},
+    font::{BitmapFont, FontMetrics, FontStore},
+    style::{
+        AlignItems, BoxSizing, Clear, ComputedStyle, Direction, Display, Float, JustifyContent,
+        Overflow, OverflowWrap, Position, StyledNode, VerticalAlign, Visibility, WhiteSpace,
+        WordBreak, INITIAL_STYLE,
+    },
+    text::{self, GlyphRun},
+    values::{LengthPercentage, LengthPercentageAuto, LengthPercentageNone, TrackSize},
+};
+
+mod dump;
+mod grid;
+mod hit_test;
+mod snap;
+mod traversal;
+
+pub use hit_test::Hit;
+pub use traversal::{BoxKind, PostOrder, PreOrder};
+
+/// An axis-aligned rectangle in CSS px. Coordinates are fractional; only the
+/// rasterizer rounds them to device pixels.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+
+    /// The rect grown by `edges` on each side.
+    pub fn expanded_by(&self, edges: EdgeSizes) -> Rect {
+        Rect {
+            x: self.x - edges.left,
+            y: self.y - edges.top,
+            width: self.width + edges.left + edges.right,
+            height: self.height + edges.top + edges.bottom,
+        }
+    }
+
+    /// Smallest rect containing both `self` and `other`.
+    pub fn union(&self, other: Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Rect {
+            x,
+            y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+        }
+    }
+
+    /// The rect moved by `dx` and `dy`.
+    pub fn translated(&self, dx: f32, dy: f32) -> Rect {
+        Rect {
+            x: self.x + dx,
+            y: self.y + dy,
+            ..*self
+        }
+    }
+
+    /// The rect with its edges `scale` times as far from the origin, e.g.
+    /// in device pixels rather than px.
+    pub fn scaled(&self, scale: f32) -> Rect {
+        Rect {
+            x: self.x * scale,
+            y: self.y * scale,
+            width: self.width * scale,
+            height: self.height * scale,
+        }
+    }
+
+    /// Area covered by both `self` and `other`, empty at the edge of one of
+    /// them when they don't overlap.
+    pub fn intersection(&self, other: Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        Rect {
+            x,
+            y,
+            width: ((self.x + self.width).min(other.x + other.width) - x).max(0.0),
+            height: ((self.y + self.height).min(other.y + other.height) - y).max(0.0),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct EdgeSizes {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+/// Horizontal and vertical radii of a rounded corner.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct CornerRadius {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct CornerRadii {
+    pub top_left: CornerRadius,
+    pub top_right: CornerRadius,
+    pub bottom_right: CornerRadius,
+    pub bottom_left: CornerRadius,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Dimensions {
+    pub content: Rect,
+    pub padding: EdgeSizes,
+    pub border: EdgeSizes,
+    pub margin: EdgeSizes,
+    /// Rounded corners of the border box, resolved against its size.
+    pub radii: CornerRadii,
+    /// Area reached by the box's content: its padding box along with the
+    /// border boxes of its descendants, except those clipped by a box in
+    /// between. Larger than the padding box when the content overflows,
+    /// e.g. past a fixed height.
+    pub scrollable_overflow: Rect,
+    /// Area the box and its content are painted within: the padding boxes
+    /// of the ancestors clipping their overflow that the box is inside of,
+    /// intersected, where they're painted. `None` when no ancestor clips it.
+    pub clip: Option<Rect>,
+    /// Rounded corners of `clip`, those of the padding box of the nearest
+    /// ancestor clipping the box.
+    pub clip_radii: CornerRadii,
+    /// How far the box is painted from where it's laid out, as `(dx, dy)`,
+    /// by the scroll offsets of the scroll containers it's inside of.
+    pub scroll_translation: (f32, f32),
+}
+
+impl Dimensions {
+    /// Area of the content and padding, which background images start from.
+    pub fn padding_box(&self) -> Rect {
+        self.content.expanded_by(self.padding)
+    }
+
+    /// Area inside the outer edge of the borders, which backgrounds are
+    /// painted in and hit testing uses.
+    pub fn border_box(&self) -> Rect {
+        self.padding_box().expanded_by(self.border)
+    }
+
+    /// Area of the box including its margins, which sibling boxes are
+    /// stacked by.
+    pub fn margin_box(&self) -> Rect {
+        self.border_box().expanded_by(self.margin)
+    }
+
+    /// Rounded corners of the padding box, following the inner edge of the
+    /// borders.
+    pub fn padding_radii(&self) -> CornerRadii {
+        let inset = |radius: CornerRadius, x: f32, y: f32| CornerRadius {
+            x: (radius.x - x).max(0.0),
+            y: (radius.y - y).max(0.0),
+        };
+        let (radii, border) = (self.radii, self.border);
+        CornerRadii {
+            top_left: inset(radii.top_left, border.left, border.top),
+            top_right: inset(radii.top_right, border.right, border.top),
+            bottom_right: inset(radii.bottom_right, border.right, border.bottom),
+            bottom_left: inset(radii.bottom_left, border.left, border.bottom),
+        }
+    }
+
+    /// Whether the content reaches outside the padding box.
+    pub fn has_overflow(&self) -> bool {
+        self.scrollable_overflow != self.padding_box()
+    }
+
+    /// Outer height of the box, including its vertical margins.
+    fn margin_box_height(&self) -> f32 {
+        self.margin_box().height
+    }
+}
+
+/// The rectangle a box is sized and placed against. Boxes in the flow are
+/// laid out in the content box of their parent, absolutely positioned boxes
+/// in the padding box of their nearest positioned ancestor, and fixed boxes
+/// and the root in the initial containing block, the viewport.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ContainingBlock {
+    pub x: f32,
+    pub y: f32,
+    /// Percentages of widths, margins and padding refer to it.
+    pub width: f32,
+    /// Percentages of heights refer to it when it's definite. Otherwise they
+    /// behave as `auto`.
+    pub height: Option<f32>,
+    /// Height of the content already laid out in the block, below which
+    /// the next box in the flow starts.
+    pub filled_height: f32,
+    /// Direction of the block's content. Lines are filled from its start
+    /// edge, and over-constrained widths give up space on its end side.
+    pub direction: Direction,
+}
+
+impl ContainingBlock {
+    /// The content box of `dimensions`, whose height is given when it's
+    /// definite.
+    pub fn content_box(
+        dimensions: &Dimensions,
+        height: Option<f32>,
+        direction: Direction,
+    ) -> ContainingBlock {
+        let content = dimensions.content;
+        ContainingBlock {
+            x: content.x,
+            y: content.y,
+            width: content.width,
+            height,
+            filled_height: content.height,
+            direction,
+        }
+    }
+
+    /// An empty block covering `rect`, whose height is definite.
+    pub fn from_rect(rect: Rect, direction: Direction) -> ContainingBlock {
+        ContainingBlock {
+            x: rect.x,
+            y: rect.y,
+            width: rect.width,
+            height: Some(rect.height),
+            filled_height: 0.0,
+            direction,
+        }
+    }
+
+    /// Area of the block, as tall as its content when its height isn't
+    /// definite.
+    pub fn rect(&self) -> Rect {
+        Rect {
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height.unwrap_or(self.filled_height),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Size {
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Space offered to embedder-measured content during layout.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayoutConstraints {
+    /// Width left in the containing block after fixed margins.
+    pub available_width: f32,
+    /// Specified width and height, when they are definite.
+    pub width: Option<f32>,
+    pub height: Option<f32>,
+}
+
+/// Fewest boxes in the subtrees of sibling blocks for them to be laid out
+/// on several threads, below which starting the threads takes longer than
+/// the layout.
+const MIN_PARALLEL_LAYOUT_BOXES: usize = 64;
+
+/// Stack size of the threads laying out subtrees, which lay them out
+/// recursively like the thread that started the layout.
+const LAYOUT_THREAD_STACK_SIZE: usize = 64 << 20;
+
+/// Stack taken by each level of nested boxes as layout recurses into them,
+/// with room to spare for inline-blocks, the deepest.
+const LAYOUT_STACK_PER_LEVEL: usize = 16 << 10;
+
+/// Deepest trees laid out on the stack of the thread asking for the layout.
+/// Deeper ones get a thread with a stack sized to their depth.
+#[cfg(not(target_arch = "wasm32"))]
+const MAX_LAYOUT_DEPTH_ON_CALLER_STACK: usize = 64;
+
+/// Stack size for laying out a subtree `depth` boxes deep.
+fn layout_stack_size(depth: usize) -> usize {
+    LAYOUT_THREAD_STACK_SIZE.max(depth * LAYOUT_STACK_PER_LEVEL)
+}
+
+thread_local! {
+    /// Set on the threads laying out subtrees, which lay out the subtrees
+    /// inside them on their own.
+    static IN_LAYOUT_THREAD: Cell<bool> = const { Cell::new(false) };
+}
+
+pub type MeasureFn = dyn Fn(LayoutConstraints) -> Size + Send + Sync;
+
+/// State shared by the whole layout pass, and by the threads laying out
+/// independent subtrees.
+pub struct LayoutContext {
+    measurers: HashMap<String, Box<MeasureFn>>,
+    /// Intrinsic sizes of images by URL.
+    intrinsic_sizes: HashMap<String, Size>,
+    /// Font text is measured with, the bundled bitmap font unless the
+    /// embedder sets another.
+    font: Arc<dyn FontMetrics + Send + Sync>,
+    /// Loaded faces, used instead of `font` for text whose `font-family`
+    /// asks for one of them.
+    fonts: FontStore,
+    /// Most threads sibling blocks that don't depend on each other are laid
+    /// out on.
+    threads: usize,
+}
+
+impl Default for LayoutContext {
+    fn default() -> Self {
+        LayoutContext {
+            measurers: HashMap::new(),
+            intrinsic_sizes: HashMap::new(),
+            font: Arc::new(BitmapFont),
+            fonts: FontStore::new(),
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+        }
+    }
+}
+
+impl LayoutContext {
+    pub fn new() -> LayoutContext {
+        LayoutContext::default()
+    }
+
+    pub fn set_font(&mut self, font: impl FontMetrics + Send + Sync + 'static) {
+        self.font = Arc::new(font);
+    }
+
+    /// Sets the faces text is measured with when its `font-family` names
+    /// them, e.g. those of `@font-face` rules.
+    pub fn set_fonts(&mut self, fonts: FontStore) {
+        self.fonts = fonts;
+    }
+
+    /// Font to measure text styled with `style` with: the first loaded face
+    /// of its `font-family`, or the default font.
+    fn font_for(&self, style: &ComputedStyle) -> Arc<dyn FontMetrics + Send + Sync> {
+        self.fonts
+            .find(&style.font_families)
+            .unwrap_or_else(|| Arc::clone(&self.font))
+    }
+
+    /// Sets the most threads independent sibling blocks are laid out on, by
+    /// default as many as the machine runs in parallel. With 1, as where
+    /// threads aren't available, everything is laid out on the calling
+    /// thread.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Registers a callback that provides the intrinsic size of the element
+    /// with the given `id` attribute. The measured size is used wherever the
+    /// element's width or height is `auto`.
+    pub fn register_measure(
+        &mut self,
+        element_id: &str,
+        measure: impl Fn(LayoutConstraints) -> Size + Send + Sync + 'static,
+    ) {
+        self.measurers
+            .insert(element_id.to_string(), Box::new(measure));
+    }
+
+    pub fn unregister_measure(&mut self, element_id: &str) {
+        self.measurers.remove(element_id);
+    }
+
+    /// Sets the intrinsic size of the image at `src`, which `img` elements
+    /// showing it are sized from. It can be known from the image's header,
+    /// before its pixels are decoded.
+    pub fn set_intrinsic_size(&mut self, src: &str, size: Size) {
+        self.intrinsic_sizes.insert(src.to_string(), size);
+    }
+
+    fn get_measure(&self, style: Option<&StyledNode>) -> Option<&MeasureFn> {
+        let NodeType::Element(element) = style?.node.get_node_type() else {
+            return None;
+        };
+        self.measurers
+            .get(element.id()?.as_str())
+            .map(|measure| &**measure)
+    }
+}
+
+/// Length of a computed value in px, with percentages taken of `reference`
+/// and `auto` counting as zero.
+fn to_px(value: LengthPercentageAuto, reference: f32) -> f32 {
+    match value {
+        LengthPercentageAuto::Length(length) => length,
+        LengthPercentageAuto::Percent(percentage) => percentage / 100.0 * reference,
+        LengthPercentageAuto::Auto => 0.0,
+    }
+}
+
+/// Bounds in px set by `min-*` and `max-*` sizes. Percentages are taken of
+/// `reference`, or ignored when it isn't definite.
+fn size_bounds(
+    min: LengthPercentageAuto,
+    max: LengthPercentageNone,
+    reference: Option<f32>,
+) -> (f32, f32) {
+    let min = match (min, reference) {
+        (LengthPercentageAuto::Percent(_), None) => 0.0,
+        (min, reference) => to_px(min, reference.unwrap_or(0.0)),
+    };
+    let max = match (max, reference) {
+        (LengthPercentageNone::Length(length), _) => length,
+        (LengthPercentageNone::Percent(percentage), Some(reference)) => {
+            percentage / 100.0 * reference
+        }
+        (LengthPercentageNone::Percent(_), None) | (LengthPercentageNone::None, _) => f32::INFINITY,
+    };
+    (min, max)
+}
+
+/// Whether a height, min or max height of `style` is a percentage.
+fn has_percentage_height(style: &ComputedStyle) -> bool {
+    matches!(style.height, LengthPercentageAuto::Percent(_))
+        || matches!(style.min_height, LengthPercentageAuto::Percent(_))
+        || matches!(style.max_height, LengthPercentageNone::Percent(_))
+}
+
+/// Padding and border widths of `style` in px. Percentage paddings refer to
+/// the containing block's width, on every side.
+fn padding_and_border(style: &ComputedStyle, containing_width: f32) -> (EdgeSizes, EdgeSizes) {
+    let padding = |value: LengthPercentage| match value {
+        LengthPercentage::Length(length) => length,
+        LengthPercentage::Percent(percentage) => percentage / 100.0 * containing_width,
+    };
+    (
+        EdgeSizes {
+            left: padding(style.padding_left),
+            right: padding(style.padding_right),
+            top: padding(style.padding_top),
+            bottom: padding(style.padding_bottom),
+        },
+        EdgeSizes {
+            left: style.border_left_width,
+            right: style.border_right_width,
+            top: style.border_top_width,
+            bottom: style.border_bottom_width,
+        },
+    )
+}
+
+/// Floats placed so far in a block formatting context, which the content
+/// after them flows around.
+#[derive(Debug, Default, Clone)]
+struct FloatContext {
+    /// Margin boxes of the floats, in the order they were placed.
+    floats: Vec<(Float, Rect)>,
+}
+
+impl FloatContext {
+    /// Floats overlapping the band from `y` to `y + height`. Empty bands
+    /// overlap the floats around `y`.
+    fn in_band(&self, y: f32, height: f32) -> impl Iterator<Item = &(Float, Rect)> {
+        self.floats.iter().filter(move |(_, rect)| {
+            rect.y + rect.height > y && (rect.y < y + height || rect.y <= y)
+        })
+    }
+
+    /// Left and right edges of the space between `left` and `right` that
+    /// floats leave free over the band from `y` to `y + height`.
+    fn available(&self, y: f32, height: f32, left: f32, right: f32) -> (f32, f32) {
+        self.in_band(y, height)
+            .fold((left, right), |(left, right), (side, rect)| match side {
+                Float::Left => (left.max(rect.x + rect.width), right),
+                _ => (left, right.min(rect.x)),
+            })
+    }
+
+    /// Bottom of the float in the band that ends first, where the space
+    /// next to floats widens again.
+    fn next_bottom(&self, y: f32, height: f32) -> Option<f32> {
+        self.in_band(y, height)
+            .map(|(_, rect)| rect.y + rect.height)
+            .reduce(f32::min)
+    }
+
+    /// Bottom of the lowest float on a side that `clear` clears.
+    fn clearance(&self, clear: Clear) -> Option<f32> {
+        self.floats
+            .iter()
+            .filter(|(side, _)| clear.clears(*side))
+            .map(|(_, rect)| rect.y + rect.height)
+            .reduce(f32::max)
+    }
+
+    /// Bottom of the lowest float.
+    fn bottom(&self) -> Option<f32> {
+        self.clearance(Clear::Both)
+    }
+
+    /// Places a float with a margin box of `size` on its `side` of the space
+    /// between `left` and `right`, as high as it fits at or below `y`. Floats
+    /// are never placed above earlier ones, and only overlap them when
+    /// they're wider than the space between the edges.
+    fn place(&mut self, side: Float, size: Size, y: f32, left: f32, right: f32) -> Rect {
+        let mut y = self.floats.iter().map(|(_, rect)| rect.y).fold(y, f32::max);
+        let (left, right) = loop {
+            let (left, right) = self.available(y, size.height, left, right);
+            match self.next_bottom(y, size.height) {
+                Some(bottom) if right - left < size.width => y = bottom,
+                _ => break (left, right),
+            }
+        };
+        let rect = Rect {
+            x: match side {
+                Float::Left => left,
+                _ => right - size.width,
+            },
+            y,
+            width: size.width,
+            height: size.height,
+        };
+        self.floats.push((side, rect));
+        rect
+    }
+}
+
+/// A line of an inline formatting context, spanning the space floats leave
+/// next to it. The boxes on it share a baseline unless `vertical-align`
+/// moves them, and it's as tall as the ones reaching highest above it and
+/// lowest below it, or as the tallest one aligned with its top or bottom.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LineBox {
+    pub rect: Rect,
+    /// Distance from the top of the line to its baseline.
+    pub baseline: f32,
+}
+
+/// The part of an inline-level box on one line. Inline boxes split across
+/// lines have a fragment on each of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fragment {
+    /// Index of the line in the `lines` of the block container of the
+    /// inline formatting context.
+    pub line: usize,
+    /// Content area of the box on the line, which the glyphs of text are
+    /// positioned from.
+    pub rect: Rect,
+    /// Distance from the top of `rect` to the baseline of the box.
+    pub baseline: f32,
+    /// Where the baseline of the box is put on the line.
+    pub anchor: LineAnchor,
+    /// Glyphs of text boxes, empty for other boxes.
+    pub run: GlyphRun,
+}
+
+/// Where the baseline of a box is on its line, from its `vertical-align` and
+/// those of the inline boxes it's in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineAnchor {
+    /// This far below the baseline of the line.
+    Baseline(f32),
+    /// This far below the top of the line.
+    Top(f32),
+    /// This far above the bottom of the line.
+    Bottom(f32),
+}
+
+impl LineAnchor {
+    /// The anchor of a box whose baseline is `offset` below that of a box
+    /// anchored at `self`.
+    fn shifted(self, offset: f32) -> LineAnchor {
+        match self {
+            LineAnchor::Baseline(shift) => LineAnchor::Baseline(shift + offset),
+            LineAnchor::Top(shift) => LineAnchor::Top(shift + offset),
+            LineAnchor::Bottom(shift) => LineAnchor::Bottom(shift - offset),
+        }
+    }
+}
+
+/// Where words too long for the space left on their line break.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WordBreaking {
+    Never,
+    /// When they don't fit on a line of their own either.
+    Overflowing,
+    /// Wherever the line is full.
+    Anywhere,
+}
+
+impl WordBreaking {
+    fn of(style: &ComputedStyle) -> WordBreaking {
+        if style.white_space != WhiteSpace::Normal {
+            return WordBreaking::Never;
+        }
+        match (style.word_break, style.overflow_wrap) {
+            (WordBreak::BreakAll, _) => WordBreaking::Anywhere,
+            (WordBreak::BreakWord, _) | (_, OverflowWrap::BreakWord | OverflowWrap::Anywhere) => {
+                WordBreaking::Overflowing
+            }
+            _ => WordBreaking::Never,
+        }
+    }
+}
+
+/// Lines of an inline formatting context, filled from their start edge as
+/// inline boxes are laid out, the left one or the right one depending on the
+/// direction. Lines are sized to fit the boxes on them lined up on a shared
+/// baseline, and shortened to the space floats leave next to them. Boxes
+/// are placed at the top of their line until `align_in_lines` moves them
+/// to their anchor.
+struct LineBuilder {
+    /// Left and right edges of the containing block.
+    bounds: (f32, f32),
+    direction: Direction,
+    floats: FloatContext,
+    /// Height assumed for lines when finding the floats next to them.
+    strut: f32,
+    /// Left edge and width of the current line.
+    left: f32,
+    width: f32,
+    /// Top of the first line, and of the one being filled.
+    top: f32,
+    line_top: f32,
+    /// Width taken up on the current line so far, from its start edge.
+    x: f32,
+    /// Height of the current line, from the top of the box reaching highest
+    /// above its baseline to the bottom of the one reaching lowest below.
+    line_height: f32,
+    /// Distances from the baseline of the current line to its top and
+    /// bottom.
+    ascent: f32,
+    descent: f32,
+    /// Heights of the boxes aligned with the top and with the bottom of the
+    /// current line.
+    top_height: f32,
+    bottom_height: f32,
+    /// Anchor and font size of the inline box whose content is being
+    /// placed, which that content is aligned with.
+    anchor: LineAnchor,
+    font_size: f32,
+    /// Index of the current line, counting those of the block container
+    /// laid out before.
+    line: usize,
+    /// Lines filled so far.
+    lines: Vec<LineBox>,
+    /// Whether collapsed white space separates the next word from the
+    /// previous one.
+    pending_space: bool,
+    /// Whether the box being placed can wrap to the next line.
+    wraps: bool,
+}
+
+impl LineBuilder {
+    /// Lines starting below the content already laid out in
+    /// `containing_block`, next to `floats`, for content in `style`. They're
+    /// assumed to be as tall as its line height. The first one is the
+    /// `first_line`th of the block container.
+    fn new(
+        containing_block: ContainingBlock,
+        floats: &FloatContext,
+        style: &ComputedStyle,
+        first_line: usize,
+    ) -> LineBuilder {
+        let ContainingBlock {
+            x,
+            y,
+            width,
+            filled_height,
+            direction,
+            ..
+        } = containing_block;
+        let top = y + filled_height;
+        let mut lines = LineBuilder {
+            bounds: (x, x + width),
+            direction,
+            floats: floats.clone(),
+            strut: style.line_height,
+            left: x,
+            width,
+            top,
+            line_top: top,
+            x: 0.0,
+            line_height: 0.0,
+            ascent: 0.0,
+            descent: 0.0,
+            top_height: 0.0,
+            bottom_height: 0.0,
+            anchor: LineAnchor::Baseline(0.0),
+            font_size: style.font_size,
+            line: first_line,
+            lines: vec![],
+            pending_space: false,
+            wraps: true,
+        };
+        lines.fit_line();
+        lines
+    }
+
+    /// Narrows the current line to the space left by floats.
+    fn fit_line(&mut self) {
+        let (left, right) = self.bounds;
+        let (left, right) = self
+            .floats
+            .available(self.line_top, self.strut, left, right);
+        self.left = left;
+        self.width = (right - left).max(0.0);
+    }
+
+    /// Horizontal position of the end of the content on the current line.
+    fn cursor(&self) -> f32 {
+        match self.direction {
+            Direction::Ltr => self.left + self.x,
+            Direction::Rtl => self.left + self.width - self.x,
+        }
+    }
+
+    /// Total height of the lines laid out.
+    fn height(&self) -> f32 {
+        self.line_top + self.line_height - self.top
+    }
+
+    fn break_line(&mut self) {
+        self.push_line();
+        self.line += 1;
+        self.line_top += self.line_height;
+        self.x = 0.0;
+        self.line_height = 0.0;
+        self.ascent = 0.0;
+        self.descent = 0.0;
+        self.top_height = 0.0;
+        self.bottom_height = 0.0;
+        self.pending_space = false;
+        self.fit_line();
+    }
+
+    fn push_line(&mut self) {
+        self.lines.push(LineBox {
+            rect: Rect {
+                x: self.left,
+                y: self.line_top,
+                width: self.width,
+                height: self.line_height,
+            },
+            baseline: self.ascent,
+        });
+    }
+
+    /// The lines laid out, the current one being the last.
+    fn finish(mut self) -> Vec<LineBox> {
+        self.push_line();
+        self.lines
+    }
+
+    /// Splits `word` into the part to place on the current line and the
+    /// rest, which goes on the next one, when `breaking` lets it break and
+    /// it doesn't fit. Breaking a word too long for the space left moves to
+    /// the next line first when it only breaks when overflowing. Lines get
+    /// at least a character.
+    fn fit_word<'w>(
+        &mut self,
+        word: &'w str,
+        measure: impl Fn(&str) -> f32,
+        breaking: WordBreaking,
+    ) -> (&'w str, &'w str) {
+        if !self.wraps || self.x + measure(word) <= self.width {
+            return (word, "");
+        }
+        match breaking {
+            WordBreaking::Never => return (word, ""),
+            WordBreaking::Overflowing if self.x > 0.0 => {
+                self.break_line();
+                if measure(word) <= self.width {
+                    return (word, "");
+                }
+            }
+            _ => {}
+        }
+        let mut x = self.x;
+        let mut end = 0;
+        for (i, ch) in word.char_indices() {
+            let end_of_ch = i + ch.len_utf8();
+            x += measure(&word[i..end_of_ch]);
+            if x > self.width {
+                break;
+            }
+            end = end_of_ch;
+        }
+        if end == 0 {
+            if self.x > 0.0 {
+                self.break_line();
+                return self.fit_word(word, measure, breaking);
+            }
+            end = word.chars().next().map_or(0, char::len_utf8);
+        }
+        word.split_at(end)
+    }
+
+    /// Places a box of the given size, whose baseline is `baseline` below
+    /// its top, after the content of the current line, wrapping to the next
+    /// one first when it doesn't fit. Returns its rect and where it's
+    /// anchored on the line, given its `vertical_align`.
+    fn place(
+        &mut self,
+        width: f32,
+        height: f32,
+        baseline: f32,
+        vertical_align: VerticalAlign,
+    ) -> (Rect, LineAnchor) {
+        if self.wraps && self.x > 0.0 && self.x + width > self.width {
+            self.break_line();
+        }
+        // Empty lines too short for the box move down past the floats
+        // narrowing them.
+        while self.x == 0.0 && width > self.width {
+            let Some(bottom) = self.floats.next_bottom(self.line_top, self.strut) else {
+                break;
+            };
+            self.line_top = self.line_top.max(bottom);
+            self.fit_line();
+        }
+        let x = match self.direction {
+            Direction::Ltr => self.left + self.x,
+            Direction::Rtl => self.left + self.width - self.x - width,
+        };
+        let rect = Rect {
+            x,
+            y: self.line_top,
+            width,
+            height,
+        };
+        self.x += width;
+        let anchor = match vertical_align {
+            VerticalAlign::Baseline => self.anchor,
+            // The middle of the box goes half the x-height of the parent
+            // above its baseline, taking the x-height as half the font size
+            // as fonts don't give it.
+            VerticalAlign::Middle => self
+                .anchor
+                .shifted(baseline - height / 2.0 - self.font_size / 4.0),
+            VerticalAlign::Top => LineAnchor::Top(baseline),
+            VerticalAlign::Bottom => LineAnchor::Bottom(height - baseline),
+        };
+        match anchor {
+            LineAnchor::Baseline(shift) => {
+                self.ascent = self.ascent.max(baseline - shift);
+                self.descent = self.descent.max(height - baseline + shift);
+            }
+            LineAnchor::Top(shift) => {
+                self.top_height = self.top_height.max(shift - baseline + height);
+            }
+            LineAnchor::Bottom(shift) => {
+                self.bottom_height = self.bottom_height.max(shift + baseline);
+            }
+        }
+        self.line_height = (self.ascent + self.descent)
+            .max(self.top_height)
+            .max(self.bottom_height);
+        (rect, anchor)
+    }
+}
+
+/// Sizes of a flex item along the main axis of its container, in px. Sizes
+/// are of the content box, and `edges` adds up the item's margins, border
+/// and padding.
+#[derive(Debug, Clone, Copy)]
+struct FlexItem {
+    base: f32,
+    /// The hypothetical size, then the size after flexing.
+    size: f32,
+    min: f32,
+    max: f32,
+    edges: f32,
+    grow: f32,
+    shrink: f32,
+    /// Whether the item was laid out without a definite height to find its
+    /// basis.
+    laid_out: bool,
+}
+
+/// Grows or shrinks the sizes of `items` so they fill `line_size`. Items
+/// that would flex past their min or max size are frozen at it, and the
+/// others share the rest of the free space again.
+fn resolve_flexible_lengths(items: &mut [FlexItem], line_size: f32) {
+    let hypothetical: f32 = items.iter().map(|item| item.size + item.edges).sum();
+    let growing = hypothetical < line_size;
+    let mut frozen: Vec<bool> = items
+        .iter()
+        .map(|item| match growing {
+            true => item.grow == 0.0 || item.base > item.size,
+            false => item.shrink == 0.0 || item.base < item.size,
+        })
+        .collect();
+    for (item, frozen) in items.iter_mut().zip(&frozen) {
+        if !frozen {
+            item.size = item.base;
+        }
+    }
+    while frozen.contains(&false) {
+        let used: f32 = items.iter().map(|item| item.size + item.edges).sum();
+        let free = line_size - used;
+        let weight = |item: &FlexItem| match growing {
+            true => item.grow,
+            false => item.shrink * item.base,
+        };
+        let total: f32 = items
+            .iter()
+            .zip(&frozen)
+            .filter(|(_, frozen)| !**frozen)
+            .map(|(item, _)| weight(item))
+            .sum();
+        if total == 0.0 {
+            break;
+        }
+        let targets: Vec<(f32, f32)> = items
+            .iter()
+            .map(|item| {
+                let target = item.size + free * weight(item) / total;
+                (target, target.min(item.max).max(item.min))
+            })
+            .collect();
+        let violation: f32 = targets
+            .iter()
+            .zip(&frozen)
+            .filter(|(_, frozen)| !**frozen)
+            .map(|((target, clamped), _)| clamped - target)
+            .sum();
+        for ((item, frozen), (target, clamped)) in items.iter_mut().zip(&mut frozen).zip(targets) {
+            if *frozen {
+                continue;
+            }
+            // Once the violations cancel out, every item takes its clamped
+            // size. Otherwise only those clamped the same way as the total
+            // are frozen.
+            let freeze = if violation == 0.0 {
+                true
+            } else if violation > 0.0 {
+                clamped > target
+            } else {
+                clamped < target
+            };
+            if freeze {
+                item.size = clamped;
+                *frozen = true;
+            }
+        }
+    }
+}
+
+pub enum BoxType<'a> {
+    Block(&'a StyledNode<'a>),
+    Inline(&'a StyledNode<'a>),
+    InlineBlock(&'a StyledNode<'a>),
+    Flex(&'a StyledNode<'a>),
+    Grid(&'a StyledNode<'a>),
+    AnonymousBlock,
+}
+
+/// Scroll position of a scroll container whose content overflows it.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ScrollState {
+    /// How far the content is scrolled right and down, in px.
+    pub offset_x: f32,
+    pub offset_y: f32,
+    /// Largest offsets, which line the right and bottom edges of the
+    /// scrollable overflow up with those of the padding box.
+    pub max_x: f32,
+    pub max_y: f32,
+}
+
+impl ScrollState {
+    /// Scrolls to `x` and `y`, kept between 0 and the largest offsets.
+    pub fn scroll_to(&mut self, x: f32, y: f32) {
+        self.offset_x = x.clamp(0.0, self.max_x);
+        self.offset_y = y.clamp(0.0, self.max_y);
+    }
+}
+
+pub struct LayoutBox<'a> {
+    pub dimensions: Dimensions,
+    pub box_type: BoxType<'a>,
+    pub children: Vec<LayoutBox<'a>>,
+    /// Parts of an inline-level box, one per line it spans.
+    pub fragments: Vec<Fragment>,
+    /// Lines of the inline content of a block container.
+    pub lines: Vec<LineBox>,
+    /// Set on scroll containers whose content overflows them.
+    pub scroll: Option<ScrollState>,
+}
+
+// Dropped iteratively, as the default recursive drop would overflow the
+// stack on deeply nested trees.
+impl Drop for LayoutBox<'_> {
+    fn drop(&mut self) {
+        let mut stack = std::mem::take(&mut self.children);
+        while let Some(mut layout_box) = stack.pop() {
+            stack.append(&mut layout_box.children);
+        }
+    }
+}
+
+impl<'a> LayoutBox<'a> {
+    fn new(box_type: BoxType<'a>) -> LayoutBox<'a> {
+        LayoutBox {
+            dimensions: Dimensions::default(),
+            box_type,
+            children: vec![],
+            fragments: vec![],
+            lines: vec![],
+            scroll: None,
+        }
+    }
+
+    pub fn get_style_node(&self) -> Option<&'a StyledNode<'a>> {
+        match self.box_type {
+            BoxType::Block(node)
+            | BoxType::Inline(node)
+            | BoxType::InlineBlock(node)
+            | BoxType::Flex(node)
+            | BoxType::Grid(node) => Some(node),
+            BoxType::AnonymousBlock => None,
+        }
+    }
+
+    /// Computed style of the box. Anonymous boxes have the initial values.
+    pub fn style(&self) -> &'a ComputedStyle {
+        self.get_style_node()
+            .map_or(&INITIAL_STYLE, |style_node| &style_node.style)
+    }
+
+    /// Anonymous boxes never clip or scroll.
+    pub fn overflow(&self) -> Overflow {
+        self.style().overflow
+    }
+
+    /// Whether descendants that overflow the box are clipped to it.
+    pub fn clips_overflow(&self) -> bool {
+        self.overflow() != Overflow::Visible
+    }
+
+    pub fn is_scroll_container(&self) -> bool {
+        matches!(self.overflow(), Overflow::Scroll | Overflow::Auto)
+    }
+
+    /// Whether the painter draws the box itself. Its descendants may still
+    /// be visible.
+    pub fn is_visible(&self) -> bool {
+        self.style().visibility == Visibility::Visible
+    }
+
+    /// Stack level used by the painter, `None` meaning `z-index: auto`.
+    /// Only positioned boxes use their `z-index`.
+    pub fn z_index(&self) -> Option<i32> {
+        self.style()
+            .z_index
+            .filter(|_| self.establishes_stacking_context())
+    }
+
+    /// Group opacity applied when compositing the box and its descendants.
+    pub fn opacity(&self) -> f32 {
+        self.style().opacity
+    }
+
+    /// Whether the box is positioned with an integer `z-index`, or is
+    /// composited with an opacity below 1.
+    pub fn establishes_stacking_context(&self) -> bool {
+        let style = self.style();
+        (style.position != Position::Static && style.z_index.is_some()) || style.opacity < 1.0
+    }
+
+    /// Descendants of the stacking context the box establishes, or of the
+    /// root, in the order they're painted:
+    /// 1. stacking contexts with a negative `z-index`, lowest first
+    /// 2. non-positioned boxes, in tree order
+    /// 3. positioned boxes with `z-index: auto` or 0, and stacking contexts
+    ///    without a `z-index`, in tree order
+    /// 4. stacking contexts with a positive `z-index`, lowest first
+    ///
+    /// Nested stacking contexts are listed without their descendants, which
+    /// are in their own paint order. Floats and inline content aren't
+    /// painted in layers of their own.
+    pub fn paint_order(&self) -> Vec<&LayoutBox<'a>> {
+        self.paint_order_keyed((), |_, _| ())
+            .into_iter()
+            .map(|(layout_box, _)| layout_box)
+            .collect()
+    }
+
+    /// `paint_order`, with each box paired with a key derived along its
+    /// path from this box: `child_key(key, i)` is the key of the `i`th child
+    /// of the box keyed `key`, starting from `key` for this box.
+    pub fn paint_order_keyed<K>(
+        &self,
+        key: K,
+        child_key: impl Fn(&K, usize) -> K,
+    ) -> Vec<(&LayoutBox<'a>, K)> {
+        let mut entries = vec![];
+        // Pre-order walk with an explicit stack, so deep trees don't recurse.
+        let mut stack: Vec<(&LayoutBox<'a>, K)> = self
+            .children
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(i, child)| (child, child_key(&key, i)))
+            .collect();
+        while let Some((layout_box, key)) = stack.pop() {
+            if !layout_box.establishes_stacking_context() {
+                for (i, child) in layout_box.children.iter().enumerate().rev() {
+                    stack.push((child, child_key(&key, i)));
+                }
+            }
+            entries.push((layout_box, key));
+        }
+        // The sort is stable, so boxes of a layer stay in tree order.
+        entries.sort_by_key(|(layout_box, _)| layout_box.paint_layer());
+        entries
+    }
+
+    /// Sort key of the box in the paint order of its stacking context.
+    fn paint_layer(&self) -> (i32, bool) {
+        match self.z_index() {
+            Some(z_index) if z_index != 0 => (z_index, false),
+            _ => (
+                0,
+                self.is_positioned() || self.establishes_stacking_context(),
+            ),
+        }
+    }
+
+    /// Border boxes of the fragments of an inline-level box, or the box's
+    /// border box when it isn't laid out on lines. The start edges of a box
+    /// split across lines are on its first fragment, and its end edges on
+    /// the last one.
+    pub fn border_boxes(&self) -> Vec<Rect> {
+        self.border_edges()
+            .into_iter()
+            .map(|(border_box, _)| border_box)
+            .collect()
+    }
+
+    /// Border boxes as `border_boxes` has them, each with the widths of the
+    /// borders drawn along its edges.
+    pub fn border_edges(&self) -> Vec<(Rect, EdgeSizes)> {
+        if self.fragments.is_empty() {
+            return vec![(self.dimensions.border_box(), self.dimensions.border)];
+        }
+        let Dimensions {
+            padding, border, ..
+        } = self.dimensions;
+        let last = self.fragments.len() - 1;
+        let direction = self.style().direction;
+        self.fragments
+            .iter()
+            .enumerate()
+            .map(|(i, fragment)| {
+                let (starts, ends) = (i == 0, i == last);
+                let (has_left, has_right) = match direction {
+                    Direction::Ltr => (starts, ends),
+                    Direction::Rtl => (ends, starts),
+                };
+                let left = if has_left {
+                    padding.left + border.left
+                } else {
+                    0.0
+                };
+                let right = if has_right {
+                    padding.right + border.right
+                } else {
+                    0.0
+                };
+                let top = padding.top + border.top;
+                let bottom = padding.bottom + border.bottom;
+                let rect = fragment.rect;
+                let border_box = Rect {
+                    x: rect.x - left,
+                    y: rect.y - top,
+                    width: rect.width + left + right,
+                    height: rect.height + top + bottom,
+                };
+                let edges = EdgeSizes {
+                    left: if has_left { border.left } else { 0.0 },
+                    right: if has_right { border.right } else { 0.0 },
+                    ..border
+                };
+                (border_box, edges)
+            })
+            .collect()
+    }
+
+    /// Lays out the tree as the root of the layout. Layout recurses into
+    /// the children of each box, so deep trees are laid out on a thread with
+    /// a stack large enough for them, where threads can be started.
+    pub fn layout(&mut self, containing_block: ContainingBlock, context: &LayoutContext) {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let depth = self.depth();
+            if depth > MAX_LAYOUT_DEPTH_ON_CALLER_STACK
+                && self.layout_on_thread(depth, containing_block, context)
+            {
+                return;
+            }
+        }
+        self.layout_root(containing_block, context)
+    }
+
+    /// Lays out the tree on a thread with a stack for `depth` levels of
+    /// boxes, unless the thread can't be started.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn layout_on_thread(
+        &mut self,
+        depth: usize,
+        containing_block: ContainingBlock,
+        context: &LayoutContext,
+    ) -> bool {
+        thread::scope(|scope| {
+            let layout = thread::Builder::new()
+                .stack_size(layout_stack_size(depth))
+                .spawn_scoped(scope, || self.layout_root(containing_block, context));
+            match layout {
+                Ok(layout) => {
+                    let result = layout.join();
+                    result.unwrap_or_else(|payload| panic::resume_unwind(payload));
+                    true
+                }
+                Err(_) => false,
+            }
+        })
+    }
+
+    /// Number of levels of boxes in the subtree, the box included.
+    fn depth(&self) -> usize {
+        let mut depth = 0;
+        let mut stack = vec![(self, 1)];
+        while let Some((layout_box, level)) = stack.pop() {
+            depth = depth.max(level);
+            stack.extend(layout_box.children.iter().map(|child| (child, level + 1)));
+        }
+        depth
+    }
+
+    /// Lays out the box as the root of a block formatting context, which
+    /// grows to contain its floats. The root's containing block is also that
+    /// of fixed boxes, and of absolutely positioned boxes without a
+    /// positioned ancestor.
+    fn layout_root(&mut self, containing_block: ContainingBlock, context: &LayoutContext) {
+        let mut floats = FloatContext::default();
+        self.layout_in(containing_block, &mut floats, context);
+        if self.style().height == LengthPercentageAuto::Auto {
+            self.contain_floats(&floats);
+            self.layout_scrollable_overflow();
+        }
+        if self.style().position == Position::Relative {
+            let viewport = containing_block.rect();
+            let (dx, dy) = self.relative_offset(viewport.width, Some(viewport.height));
+            self.translate(dx, dy);
+        }
+        self.layout_relative_offsets();
+        self.layout_out_of_flow_descendants(containing_block, context);
+        self.layout_scroll_states();
+        self.apply_scroll_offsets();
+    }
+
+    /// Sets up scrolling on the scroll containers whose content overflows
+    /// them, from the top left, once the scrollable overflow is final.
+    fn layout_scroll_states(&mut self) {
+        self.for_each_mut(|layout_box| {
+            let padding_box = layout_box.dimensions.padding_box();
+            let overflow = layout_box.dimensions.scrollable_overflow;
+            let max_x = (overflow.x + overflow.width - padding_box.x - padding_box.width).max(0.0);
+            let max_y =
+                (overflow.y + overflow.height - padding_box.y - padding_box.height).max(0.0);
+            let scrolls = layout_box.is_scroll_container() && (max_x > 0.0 || max_y > 0.0);
+            layout_box.scroll = scrolls.then_some(ScrollState {
+                max_x,
+                max_y,
+                ..Default::default()
+            });
+        });
+    }
+
+    /// Sets the scroll translation and clip of the box's descendants from
+    /// the scroll offsets of the scroll containers, once the boxes are in
+    /// their final place. To be called again after scrolling. The box itself
+    /// isn't moved or clipped. Boxes are scrolled and clipped by the
+    /// ancestors they're in the flow of: absolutely positioned boxes only by
+    /// those that are, or contain, their containing block, and fixed boxes
+    /// by none.
+    pub fn apply_scroll_offsets(&mut self) {
+        // Boxes along with their clip and translation, and those of the
+        // absolutely positioned boxes whose containing block they'd be.
+        let unclipped = (None, CornerRadii::default());
+        let mut stack = vec![(&mut *self, (unclipped, (0.0, 0.0)), (unclipped, (0.0, 0.0)))];
+        while let Some((layout_box, in_flow, absolute)) = stack.pop() {
+            let ((clip, clip_radii), (dx, dy)) = match layout_box.style().position {
+                Position::Absolute => absolute,
+                Position::Fixed => (unclipped, (0.0, 0.0)),
+                _ => in_flow,
+            };
+            layout_box.dimensions.clip = clip;
+            layout_box.dimensions.clip_radii = clip_radii;
+            layout_box.dimensions.scroll_translation = (dx, dy);
+            let padding_box = layout_box.dimensions.padding_box().translated(dx, dy);
+            let children_clip = match layout_box.clips_overflow() {
+                true => (
+                    Some(clip.map_or(padding_box, |clip| clip.intersection(padding_box))),
+                    layout_box.dimensions.padding_radii(),
+                ),
+                false => (clip, clip_radii),
+            };
+            let children_translation = match layout_box.scroll {
+                Some(scroll) => (dx - scroll.offset_x, dy - scroll.offset_y),
+                None => (dx, dy),
+            };
+            let children = (children_clip, children_translation);
+            let is_containing_block =
+                layout_box.is_positioned() && !matches!(layout_box.box_type, BoxType::Inline(_));
+            let absolute = match is_containing_block {
+                true => children,
+                false => absolute,
+            };
+            let boxes = layout_box.children.iter_mut();
+            stack.extend(boxes.map(|child| (child, children, absolute)));
+        }
+    }
+
+    /// Lays out the box in `containing_block`. `floats` are those of the
+    /// block formatting context the box is in.
+    fn layout_in(
+        &mut self,
+        containing_block: ContainingBlock,
+        floats: &mut FloatContext,
+        context: &LayoutContext,
+    ) {
+        match self.box_type {
+            BoxType::Block(_)
+            | BoxType::InlineBlock(_)
+            | BoxType::Flex(_)
+            | BoxType::Grid(_)
+            | BoxType::AnonymousBlock => self.layout_block(containing_block, floats, context),
+            BoxType::Inline(_) => {
+                let mut lines = LineBuilder::new(containing_block, floats, self.style(), 0);
+                self.layout_inline(&mut lines, context);
+                self.align_in_lines(&lines.finish());
+            }
+        }
+    }
+
+    /// Floats that are absolutely positioned aren't floated.
+    pub fn is_floated(&self) -> bool {
+        self.style().float != Float::None && !self.is_out_of_flow()
+    }
+
+    /// Whether the box is only as wide as its content when its width is
+    /// `auto`, rather than stretched to its containing block.
+    fn shrinks_to_fit(&self) -> bool {
+        self.is_floated() || matches!(self.box_type, BoxType::InlineBlock(_))
+    }
+
+    /// Whether the box is absolutely positioned or fixed, taking it out of
+    /// the flow.
+    pub fn is_out_of_flow(&self) -> bool {
+        matches!(self.style().position, Position::Absolute | Position::Fixed)
+    }
+
+    /// Whether the box is positioned, which makes it the containing block
+    /// of its absolutely positioned descendants, unless it's inline.
+    fn is_positioned(&self) -> bool {
+        self.style().position != Position::Static
+    }
+
+    /// Leaves an out-of-flow box with an empty margin box at `x` and `y`, its
+    /// static position, where it would have started in the flow. Its
+    /// containing block lays it out later.
+    fn set_static_position(&mut self, x: f32, y: f32) {
+        self.dimensions = Dimensions::default();
+        self.dimensions.content.x = x;
+        self.dimensions.content.y = y;
+    }
+
+    /// Lays out the out-of-flow boxes of the tree, once the boxes in the
+    /// flow are. Each positioned box that isn't inline lays out the
+    /// absolutely positioned boxes inside it, up to the next positioned box,
+    /// against its padding box, and the root those without a positioned
+    /// ancestor against `viewport`. Fixed boxes are laid out against
+    /// `viewport`. Containing blocks are laid out before the boxes in them.
+    fn layout_out_of_flow_descendants(
+        &mut self,
+        viewport: ContainingBlock,
+        context: &LayoutContext,
+    ) {
+        let mut stack = vec![(&mut *self, true)];
+        while let Some((layout_box, is_root)) = stack.pop() {
+            if layout_box.style().position == Position::Fixed && !is_root {
+                layout_box.layout_out_of_flow(viewport, context);
+            }
+            let is_containing_block =
+                layout_box.is_positioned() && !matches!(layout_box.box_type, BoxType::Inline(_));
+            if is_containing_block || is_root {
+                let containing_block = match is_containing_block {
+                    true => ContainingBlock::from_rect(
+                        layout_box.dimensions.padding_box(),
+                        layout_box.style().direction,
+                    ),
+                    false => viewport,
+                };
+                layout_box.layout_absolute_descendants(containing_block, context);
+                layout_box.layout_scrollable_overflow();
+            }
+            stack.extend(layout_box.children.iter_mut().map(|child| (child, false)));
+        }
+    }
+
+    /// Lays out the absolutely positioned descendants of the box that have
+    /// no positioned box between them and it, in `containing_block`.
+    fn layout_absolute_descendants(
+        &mut self,
+        containing_block: ContainingBlock,
+        context: &LayoutContext,
+    ) {
+        let mut stack: Vec<&mut LayoutBox> = self.children.iter_mut().collect();
+        while let Some(layout_box) = stack.pop() {
+            let is_inline = matches!(layout_box.box_type, BoxType::Inline(_));
+            match layout_box.style().position {
+                Position::Absolute => layout_box.layout_out_of_flow(containing_block, context),
+                // Other positioned boxes contain their own, but inline ones
+                // leave theirs to their containing block.
+                Position::Relative | Position::Fixed if !is_inline => {}
+                _ => stack.extend(layout_box.children.iter_mut()),
+            }
+        }
+    }
+
+    /// Offset of a relatively positioned box: its `left`, or minus its
+    /// `right` when that's `auto`, and likewise with `top` and `bottom`.
+    /// Percentages refer to the containing block, and vertical ones count as
+    /// `auto` when its height isn't definite.
+    fn relative_offset(&self, containing_width: f32, containing_height: Option<f32>) -> (f32, f32) {
+        let style = self.style();
+        let offset = |start, end, reference: Option<f32>| {
+            let to_px = |value| match (value, reference) {
+                (LengthPercentageAuto::Length(length), _) => Some(length),
+                (LengthPercentageAuto::Percent(percentage), Some(reference)) => {
+                    Some(percentage / 100.0 * reference)
+                }
+                _ => None,
+            };
+            to_px(start)
+                .or_else(|| to_px(end).map(|end| -end))
+                .unwrap_or(0.0)
+        };
+        (
+            offset(style.left, style.right, Some(containing_width)),
+            offset(style.top, style.bottom, containing_height),
+        )
+    }
+
+    /// Moves the relatively positioned descendants of the box by their
+    /// offset, once the boxes around them are laid out. Out-of-flow boxes
+    /// are skipped, as they're laid out later and move theirs then.
+    fn layout_relative_offsets(&mut self) {
+        let mut stack = vec![&mut *self];
+        while let Some(layout_box) = stack.pop() {
+            let content = layout_box.dimensions.content;
+            let height = match layout_box.style().height {
+                LengthPercentageAuto::Auto => None,
+                _ => Some(content.height),
+            };
+            for child in &mut layout_box.children {
+                if child.is_out_of_flow() {
+                    continue;
+                }
+                if child.style().position == Position::Relative {
+                    let (dx, dy) = child.relative_offset(content.width, height);
+                    child.translate(dx, dy);
+                }
+                stack.push(child);
+            }
+        }
+    }
+
+    /// Lays out an out-of-flow box against the padding box
+    /// `containing_block`. Its left, right and width add up to the
+    /// containing block's width, with an `auto` width filling it when both
+    /// offsets are given and shrinking to fit the content otherwise, and
+    /// likewise vertically with its height fitting the content. The box
+    /// stays at its static position along axes where both offsets are
+    /// `auto`. Auto margins are zero.
+    fn layout_out_of_flow(&mut self, containing_block: ContainingBlock, context: &LayoutContext) {
+        let style = self.style();
+        let (static_x, static_y) = (self.dimensions.content.x, self.dimensions.content.y);
+        let Rect {
+            width: containing_width,
+            height: containing_height,
+            ..
+        } = containing_block.rect();
+        let offset = |value: LengthPercentageAuto, reference: f32| match value {
+            LengthPercentageAuto::Auto => None,
+            value => Some(to_px(value, reference)),
+        };
+        let (left, right) = (
+            offset(style.left, containing_width),
+            offset(style.right, containing_width),
+        );
+        let (top, bottom) = (
+            offset(style.top, containing_height),
+            offset(style.bottom, containing_height),
+        );
+        (self.dimensions.padding, self.dimensions.border) =
+            padding_and_border(style, containing_width);
+        let Dimensions {
+            padding, border, ..
+        } = self.dimensions;
+        let margin = |value| to_px(value, containing_width);
+        let horizontal_edges = margin(style.margin_left)
+            + margin(style.margin_right)
+            + padding.left
+            + padding.right
+            + border.left
+            + border.right;
+        let vertical_edges = margin(style.margin_top)
+            + margin(style.margin_bottom)
+            + padding.top
+            + padding.bottom
+            + border.top
+            + border.bottom;
+
+        let available = containing_width - left.unwrap_or(0.0) - right.unwrap_or(0.0);
+        let width = match (style.width, left, right) {
+            (LengthPercentageAuto::Auto, Some(_), Some(_)) => available - horizontal_edges,
+            (LengthPercentageAuto::Auto, _, _) => self
+                .max_content_width(context)
+                .min(available - horizontal_edges),
+            (width, _, _) => self.content_width(to_px(width, containing_width)),
+        };
+        let (min, max) = size_bounds(style.min_width, style.max_width, Some(containing_width));
+        let width = width
+            .min(self.content_width(max))
+            .max(self.content_width(min))
+            .max(0.0);
+        let height = match (style.height, top, bottom) {
+            (LengthPercentageAuto::Auto, Some(top), Some(bottom)) => {
+                Some((containing_height - top - bottom - vertical_edges).max(0.0))
+            }
+            (LengthPercentageAuto::Auto, _, _) => None,
+            (height, _, _) => Some(self.content_height(to_px(height, containing_height))),
+        }
+        .map(|height| self.clamp_height(height, Some(containing_height)));
+
+        let x = match (left, right) {
+            (Some(left), _) => containing_block.x + left,
+            (None, Some(right)) => {
+                containing_block.x + containing_width - right - width - horizontal_edges
+            }
+            (None, None) => static_x,
+        };
+        let y = match top {
+            Some(top) => containing_block.y + top,
+            None => static_y,
+        };
+        let origin = ContainingBlock {
+            x,
+            y,
+            filled_height: 0.0,
+            ..containing_block
+        };
+        self.layout_item(origin, width, height, context);
+        if height.is_none() {
+            self.dimensions.content.height =
+                self.clamp_height(self.dimensions.content.height, Some(containing_height));
+        }
+        if let (None, Some(bottom)) = (top, bottom) {
+            let margin_box = self.dimensions.margin_box();
+            let y = containing_block.y + containing_height - bottom - margin_box.height;
+            self.translate(0.0, y - margin_box.y);
+        }
+        self.layout_relative_offsets();
+    }
+
+    /// Whether the box lays out its children in a block formatting context
+    /// of its own, which floats outside it don't reach into and floats inside
+    /// it don't escape.
+    fn establishes_formatting_context(&self) -> bool {
+        matches!(
+            self.box_type,
+            BoxType::InlineBlock(_) | BoxType::Flex(_) | BoxType::Grid(_)
+        ) || self.is_floated()
+            || self.clips_overflow()
+    }
+
+    /// Grows the content height to reach the bottom of `floats`.
+    fn contain_floats(&mut self, floats: &FloatContext) {
+        if let Some(bottom) = floats.bottom() {
+            let content = &mut self.dimensions.content;
+            content.height = content.height.max(bottom - content.y);
+        }
+    }
+
+    /// Moves the box and its descendants by `dx` and `dy`.
+    fn translate(&mut self, dx: f32, dy: f32) {
+        self.for_each_mut(|layout_box| {
+            let translate = |rect: &mut Rect| {
+                rect.x += dx;
+                rect.y += dy;
+            };
+            translate(&mut layout_box.dimensions.content);
+            translate(&mut layout_box.dimensions.scrollable_overflow);
+            for fragment in &mut layout_box.fragments {
+                translate(&mut fragment.rect);
+            }
+            for line in &mut layout_box.lines {
+                translate(&mut line.rect);
+            }
+        });
+    }
+
+    /// Width of the content when none of it wraps. Explicit widths are
+    /// taken as they are, and percentages, which refer to the width being
+    /// found, count as zero.
+    fn max_content_width(&self, context: &LayoutContext) -> f32 {
+        let style = self.style();
+        let (padding, border) = padding_and_border(style, 0.0);
+        let edges = padding.left + padding.right + border.left + border.right;
+        if let LengthPercentageAuto::Length(width) = style.width {
+            return match style.box_sizing {
+                BoxSizing::ContentBox => width,
+                BoxSizing::BorderBox => (width - edges).max(0.0),
+            };
+        }
+        let mut width: f32 = 0.0;
+        if let Some(StyledNode { node, style, .. }) = self.get_style_node() {
+            if let NodeType::Text(text) = node.get_node_type() {
+                let font = context.font_for(style);
+                width = match style.white_space {
+                    WhiteSpace::Pre => text
+                        .split('\n')
+                        .map(|line| font.measure(line, style.font_size))
+                        .fold(0.0, f32::max),
+                    _ => {
+                        let words: Vec<&str> = text.split_whitespace().collect();
+                        font.measure(&words.join(" "), style.font_size)
+                    }
+                };
+            }
+        }
+        // Inline children share a line, block children are stacked.
+        let mut line: f32 = 0.0;
+        for child in &self.children {
+            let outer = child.max_content_outer_width(context);
+            match child.box_type {
+                BoxType::Inline(_) | BoxType::InlineBlock(_) => line += outer,
+                _ => {
+                    width = width.max(line).max(outer);
+                    line = 0.0;
+                }
+            }
+        }
+        width.max(line)
+    }
+
+    /// Like `max_content_width`, adding the horizontal margins, border and
+    /// padding.
+    fn max_content_outer_width(&self, context: &LayoutContext) -> f32 {
+        let style = self.style();
+        let (padding, border) = padding_and_border(style, 0.0);
+        self.max_content_width(context)
+            + to_px(style.margin_left, 0.0)
+            + to_px(style.margin_right, 0.0)
+            + padding.left
+            + padding.right
+            + border.left
+            + border.right
+    }
+
+    /// Lays the box out on the lines of its inline formatting context. Text
+    /// wraps between words, and each box is as tall as its line height, with
+    /// its baseline where its font puts it. The box is aligned on the line by
+    /// its `vertical-align`, and its content with it. The box gets a fragment
+    /// on each line its content is on, and its content rect is the union of
+    /// them. Its horizontal margins, with percentages of the line width, push
+    /// the content around it.
+    fn layout_inline(&mut self, lines: &mut LineBuilder, context: &LayoutContext) {
+        if let BoxType::InlineBlock(_) = self.box_type {
+            return self.layout_inline_block(lines, context);
+        }
+        let style = self.style();
+        let line_height = style.line_height;
+        let baseline = text::baseline(&*context.font_for(style), style.font_size, line_height);
+        let dimensions = &mut self.dimensions;
+        (dimensions.padding, dimensions.border) = padding_and_border(style, lines.width);
+        dimensions.margin.left = to_px(style.margin_left, lines.width);
+        dimensions.margin.right = to_px(style.margin_right, lines.width);
+        let (left_edges, right_edges) = (
+            dimensions.margin.left + dimensions.border.left + dimensions.padding.left,
+            dimensions.padding.right + dimensions.border.right + dimensions.margin.right,
+        );
+        let (start_edges, end_edges) = match lines.direction {
+            Direction::Ltr => (left_edges, right_edges),
+            Direction::Rtl => (right_edges, left_edges),
+        };
+        lines.x += start_edges;
+        // Even empty boxes make their line at least as tall as their own
+        // line height.
+        let (strut, anchor) = lines.place(0.0, line_height, baseline, style.vertical_align);
+        let strut_line = lines.line;
+        let parent = (lines.anchor, lines.font_size);
+        (lines.anchor, lines.font_size) = (anchor, style.font_size);
+        let mut fragments: Vec<Fragment> = vec![];
+        if let Some(StyledNode {
+            node,
+            style: text_style,
+            ..
+        }) = self.get_style_node()
+        {
+            if let NodeType::Text(text) = node.get_node_type() {
+                let face = context.font_for(text_style);
+                let font = &*face;
+                let measure = |text: &str| font.measure(text, text_style.font_size);
+                let white_space = text_style.white_space;
+                let breaking = WordBreaking::of(text_style);
+                let preserved = white_space == WhiteSpace::Pre;
+                // Preformatted text is kept as it is, each of its lines
+                // starting a new one. Elsewhere white space collapses to a
+                // single space between words, which is dropped at the start
+                // of a line.
+                let words: Vec<&str> = if preserved {
+                    lines.pending_space = false;
+                    text.split('\n').collect()
+                } else {
+                    lines.pending_space |= text.starts_with(char::is_whitespace);
+                    text.split_whitespace().collect()
+                };
+                for (i, word) in words.into_iter().enumerate() {
+                    if preserved && i > 0 {
+                        lines.break_line();
+                    }
+                    let spaced = lines.pending_space && lines.x > 0.0;
+                    if spaced {
+                        lines.x += measure(" ");
+                    }
+                    // Text that doesn't wrap still goes on the next line
+                    // when a space separates it from the content before.
+                    lines.wraps = white_space == WhiteSpace::Normal || (i == 0 && spaced);
+                    let mut space = if spaced { " " } else { "" };
+                    let mut rest = word;
+                    // Words too long for their line may be split across
+                    // lines.
+                    loop {
+                        let (word, remainder) = lines.fit_word(rest, measure, breaking);
+                        let (rect, _) = lines.place(
+                            measure(word),
+                            line_height,
+                            baseline,
+                            VerticalAlign::Baseline,
+                        );
+                        match fragments.last_mut() {
+                            Some(fragment) if fragment.line == lines.line => {
+                                match lines.direction {
+                                    Direction::Ltr => {
+                                        fragment.run.push_str(font, space);
+                                        fragment.run.push_str(font, word);
+                                    }
+                                    // Right-to-left words go before the ones
+                                    // already on the line, as runs are drawn
+                                    // left to right.
+                                    Direction::Rtl => {
+                                        let text = fragment.run.text();
+                                        fragment.run.glyphs.clear();
+                                        fragment.run.width = 0.0;
+                                        fragment.run.push_str(font, word);
+                                        fragment.run.push_str(font, space);
+                                        fragment.run.push_str(font, &text);
+                                    }
+                                }
+                                fragment.rect = fragment.rect.union(rect);
+                            }
+                            _ => {
+                                let mut run = GlyphRun::new(
+                                    font,
+                                    text_style.font_size,
+                                    text_style.line_height,
+                                );
+                                run.push_str(font, word);
+                                fragments.push(Fragment {
+                                    line: lines.line,
+                                    rect,
+                                    baseline,
+                                    anchor,
+                                    run,
+                                });
+                            }
+                        }
+                        if remainder.is_empty() {
+                            break;
+                        }
+                        lines.break_line();
+                        (rest, space) = (remainder, "");
+                    }
+                    lines.wraps = true;
+                    lines.pending_space = !preserved;
+                }
+                if !text.is_empty() && !preserved {
+                    lines.pending_space = text.ends_with(char::is_whitespace);
+                }
+            }
+        }
+        for child in &mut self.children {
+            if child.is_out_of_flow() {
+                child.set_static_position(lines.cursor(), lines.line_top);
+                continue;
+            }
+            child.layout_inline(lines, context);
+            // The fragments span the content of the box on each line, and
+            // are as tall as its own line height.
+            for child_fragment in &child.fragments {
+                let rect = child_fragment.rect;
+                match fragments.last_mut() {
+                    Some(fragment) if fragment.line == child_fragment.line => {
+                        let left = fragment.rect.x.min(rect.x);
+                        let right =
+                            (fragment.rect.x + fragment.rect.width).max(rect.x + rect.width);
+                        fragment.rect.x = left;
+                        fragment.rect.width = right - left;
+                    }
+                    _ => fragments.push(Fragment {
+                        line: child_fragment.line,
+                        rect: Rect {
+                            height: line_height,
+                            ..rect
+                        },
+                        baseline,
+                        anchor,
+                        run: GlyphRun::default(),
+                    }),
+                }
+            }
+        }
+        (lines.anchor, lines.font_size) = parent;
+        lines.x += end_edges;
+        if fragments.is_empty() {
+            fragments.push(Fragment {
+                line: strut_line,
+                rect: strut,
+                baseline,
+                anchor,
+                run: GlyphRun::default(),
+            });
+        }
+        self.dimensions.content = fragments
+            .iter()
+            .map(|fragment| fragment.rect)
+            .reduce(|bounds, rect| bounds.union(rect))
+            .unwrap_or(strut);
+        self.fragments = fragments;
+        self.layout_scrollable_overflow();
+    }
+
+    /// Lays the box out as a block formatting context of its own, as wide as
+    /// its content when its width is `auto`, then places its margin box on
+    /// the lines like a word. Its baseline is that of its last line box, or
+    /// its bottom margin edge when it has none or clips its overflow.
+    fn layout_inline_block(&mut self, lines: &mut LineBuilder, context: &LayoutContext) {
+        let containing_block = ContainingBlock {
+            width: lines.bounds.1 - lines.bounds.0,
+            direction: lines.direction,
+            ..Default::default()
+        };
+        self.layout_block(containing_block, &mut FloatContext::default(), context);
+        if lines.pending_space && lines.x > 0.0 {
+            let style = self.style();
+            lines.x += context.font_for(style).measure(" ", style.font_size);
+        }
+        lines.pending_space = false;
+        let margin_box = self.dimensions.margin_box();
+        let baseline = match self.last_baseline() {
+            Some(baseline) if !self.clips_overflow() => baseline,
+            _ => margin_box.y + margin_box.height,
+        };
+        let content_baseline = baseline - self.dimensions.content.y;
+        let (rect, anchor) = lines.place(
+            margin_box.width,
+            margin_box.height,
+            baseline - margin_box.y,
+            self.style().vertical_align,
+        );
+        self.translate(rect.x - margin_box.x, rect.y - margin_box.y);
+        self.fragments = vec![Fragment {
+            line: lines.line,
+            rect: self.dimensions.content,
+            baseline: content_baseline,
+            anchor,
+            run: GlyphRun::default(),
+        }];
+    }
+
+    /// Position of the baseline of the last line box in the flow inside the
+    /// box, when there's one.
+    fn last_baseline(&self) -> Option<f32> {
+        // Children are searched last to first, each before the boxes
+        // inside it.
+        let mut stack = vec![self];
+        while let Some(layout_box) = stack.pop() {
+            if let Some(line) = layout_box.lines.last() {
+                return Some(line.rect.y + line.baseline);
+            }
+            let children = layout_box.children.iter();
+            stack.extend(children.filter(|child| !child.is_out_of_flow() && !child.is_floated()));
+        }
+        None
+    }
+
+    /// Moves the fragments of an inline-level box, and those of the boxes
+    /// inside it, down from the top of their line so their baseline is at
+    /// their anchor, once the `lines` of the inline formatting context are
+    /// laid out.
+    fn align_in_lines(&mut self, lines: &[LineBox]) {
+        let top = |fragment: &Fragment| {
+            let line = lines[fragment.line];
+            let baseline = match fragment.anchor {
+                LineAnchor::Baseline(shift) => line.baseline + shift,
+                LineAnchor::Top(shift) => shift,
+                LineAnchor::Bottom(shift) => line.rect.height - shift,
+            };
+            line.rect.y + (baseline - fragment.baseline)
+        };
+        if let BoxType::InlineBlock(_) = self.box_type {
+            if let Some(fragment) = self.fragments.first() {
+                let dy = top(fragment) - fragment.rect.y;
+                self.translate(0.0, dy);
+            }
+            return;
+        }
+        for child in &mut self.children {
+            if !child.is_out_of_flow() {
+                child.align_in_lines(lines);
+            }
+        }
+        for fragment in &mut self.fragments {
+            fragment.rect.y = top(fragment);
+        }
+        if let Some(content) = self
+            .fragments
+            .iter()
+            .map(|fragment| fragment.rect)
+            .reduce(|bounds, rect| bounds.union(rect))
+        {
+            self.dimensions.content = content;
+        }
+        self.layout_scrollable_overflow();
+    }
+
+    fn layout_block(
+        &mut self,
+        containing_block: ContainingBlock,
+        floats: &mut FloatContext,
+        context: &LayoutContext,
+    ) {
+        (self.dimensions.padding, self.dimensions.border) =
+            padding_and_border(self.style(), containing_block.width);
+        // Flex and grid items are measured before they're laid out, so their
+        // descendants may be laid out more than once, and start over from an
+        // empty content box.
+        self.dimensions.content.height = 0.0;
+        let containing_height = containing_block.height;
+        let height = self
+            .definite_height(containing_height)
+            .map(|height| self.clamp_height(height, containing_height));
+        let measured = self
+            .measure(containing_block, height, context)
+            .or_else(|| self.replaced_size(containing_block, height, context));
+        let width = match (self.style().width, measured) {
+            (LengthPercentageAuto::Auto, Some(measured)) => {
+                LengthPercentageAuto::Length(measured.width)
+            }
+            (LengthPercentageAuto::Auto, None) if self.shrinks_to_fit() => {
+                LengthPercentageAuto::Length(self.shrink_to_fit_width(containing_block, context))
+            }
+            (LengthPercentageAuto::Auto, None) => LengthPercentageAuto::Auto,
+            (width, _) => LengthPercentageAuto::Length(
+                self.content_width(to_px(width, containing_block.width)),
+            ),
+        };
+        self.layout_block_width(containing_block, width);
+        self.clamp_block_width(containing_block);
+        self.layout_block_position(containing_block);
+        let direction = self.direction(containing_block);
+        self.layout_contents(height, direction, floats, context);
+        self.layout_block_height(height, measured);
+        self.dimensions.content.height =
+            self.clamp_height(self.dimensions.content.height, containing_height);
+        self.layout_border_radii();
+        self.layout_scrollable_overflow();
+    }
+
+    /// Lays out the children of a box whose content width and position are
+    /// known, growing its content height to fit them. `height` is the
+    /// content height when it's definite, and `direction` that of the
+    /// content.
+    fn layout_contents(
+        &mut self,
+        height: Option<f32>,
+        direction: Direction,
+        floats: &mut FloatContext,
+        context: &LayoutContext,
+    ) {
+        match self.box_type {
+            BoxType::Flex(_) => self.layout_flex_items(height, direction, context),
+            BoxType::Grid(_) => self.layout_grid_items(height, direction, context),
+            _ if self.establishes_formatting_context() => {
+                let mut floats = FloatContext::default();
+                self.layout_block_children(height, direction, &mut floats, context);
+                self.contain_floats(&floats);
+            }
+            _ => self.layout_block_children(height, direction, floats, context),
+        }
+    }
+
+    /// Direction of the box's content. Anonymous boxes have that of their
+    /// containing block.
+    fn direction(&self, containing_block: ContainingBlock) -> Direction {
+        self.get_style_node()
+            .map_or(containing_block.direction, |style_node| {
+                style_node.style.direction
+            })
+    }
+
+    /// Lays out a flex or grid item in `container` with the content `width`
+    /// and, when given, the content `height` that its container picked for
+    /// it, rather than with the sizes of its own style.
+    fn layout_item(
+        &mut self,
+        container: ContainingBlock,
+        width: f32,
+        height: Option<f32>,
+        context: &LayoutContext,
+    ) {
+        let style = self.style();
+        let containing_width = container.width;
+        (self.dimensions.padding, self.dimensions.border) =
+            padding_and_border(style, containing_width);
+        self.dimensions.margin.left = to_px(style.margin_left, containing_width);
+        self.dimensions.margin.right = to_px(style.margin_right, containing_width);
+        self.dimensions.content.width = width;
+        // Items are laid out again once their size is known, so they start
+        // over from an empty content box.
+        self.dimensions.content.height = 0.0;
+        self.layout_block_position(container);
+        let measured = self.measure(container, height, context);
+        // Flex items are formatting context roots.
+        let mut floats = FloatContext::default();
+        let direction = self.direction(container);
+        self.layout_contents(height, direction, &mut floats, context);
+        self.contain_floats(&floats);
+        self.layout_block_height(height, measured);
+        self.layout_border_radii();
+        self.layout_scrollable_overflow();
+    }
+
+    /// Lays the children out as flex items on a single line along the main
+    /// axis of `flex-direction`. Items start from their flex basis, then
+    /// share the free space of the line by their `flex-grow` factors, or
+    /// give up the space they overflow it by in proportion to their
+    /// `flex-shrink` factors times their basis, within their `min-*` and
+    /// `max-*` sizes. `justify-content` spreads what's left between them,
+    /// and `align-items` places them across the line. `height` is the
+    /// container's content height when it's definite, and
+    /// `inline_direction` the direction of the items' content.
+    fn layout_flex_items(
+        &mut self,
+        height: Option<f32>,
+        inline_direction: Direction,
+        context: &LayoutContext,
+    ) {
+        let style = self.style();
+        let direction = style.flex_direction;
+        let is_row = direction.is_row();
+        let align = style.align_items;
+        let content = self.dimensions.content;
+        let container = ContainingBlock::content_box(&self.dimensions, height, inline_direction);
+        let containing_width = content.width;
+        let main_size = match is_row {
+            true => Some(content.width),
+            false => height,
+        };
+        let specified = |value: LengthPercentageAuto, reference: Option<f32>| match value {
+            LengthPercentageAuto::Length(length) => Some(length),
+            LengthPercentageAuto::Percent(percentage) => Some(percentage / 100.0 * reference?),
+            LengthPercentageAuto::Auto => None,
+        };
+
+        // Out-of-flow children aren't flex items, and start from the
+        // container's content box.
+        let in_flow = |child: &&mut LayoutBox| !child.is_out_of_flow();
+        for child in &mut self.children {
+            if child.is_out_of_flow() {
+                child.set_static_position(content.x, content.y);
+            }
+        }
+        let mut items = vec![];
+        for child in self.children.iter_mut().filter(in_flow) {
+            let style = child.style();
+            (child.dimensions.padding, child.dimensions.border) =
+                padding_and_border(style, containing_width);
+            let Dimensions {
+                padding, border, ..
+            } = child.dimensions;
+            let margin = |value| to_px(value, containing_width);
+            let (edges, size, min, max) = match is_row {
+                true => (
+                    margin(style.margin_left)
+                        + margin(style.margin_right)
+                        + padding.left
+                        + padding.right
+                        + border.left
+                        + border.right,
+                    style.width,
+                    style.min_width,
+                    style.max_width,
+                ),
+                false => (
+                    margin(style.margin_top)
+                        + margin(style.margin_bottom)
+                        + padding.top
+                        + padding.bottom
+                        + border.top
+                        + border.bottom,
+                    style.height,
+                    style.min_height,
+                    style.max_height,
+                ),
+            };
+            let content_size = |child: &LayoutBox, size: f32| match is_row {
+                true => child.content_width(size),
+                false => child.content_height(size),
+            };
+            // An `auto` basis falls back to the main size, then to the size
+            // of the content.
+            let mut laid_out = false;
+            let basis = match specified(style.flex_basis, main_size)
+                .or_else(|| specified(size, main_size))
+            {
+                Some(basis) => content_size(child, basis),
+                None if is_row => match child.measure(container, None, context) {
+                    Some(measured) => measured.width,
+                    None => child.max_content_width(context),
+                },
+                None => {
+                    let width = child.item_width(container, align, context);
+                    child.layout_item(container, width, None, context);
+                    laid_out = true;
+                    child.dimensions.content.height
+                }
+            };
+            let (min, max) = size_bounds(min, max, main_size);
+            let (min, max) = (content_size(child, min), content_size(child, max));
+            items.push(FlexItem {
+                base: basis,
+                size: basis.min(max).max(min),
+                min,
+                max,
+                edges,
+                grow: style.flex_grow,
+                shrink: style.flex_shrink,
+                laid_out,
+            });
+        }
+        let hypothetical: f32 = items.iter().map(|item| item.size + item.edges).sum();
+        // A column without a definite height is as tall as its items.
+        let line_main = main_size.unwrap_or(hypothetical);
+        resolve_flexible_lengths(&mut items, line_main);
+
+        for (child, item) in self.children.iter_mut().filter(in_flow).zip(&items) {
+            match is_row {
+                true => {
+                    let item_height = child
+                        .definite_height(height)
+                        .map(|item_height| child.clamp_height(item_height, height));
+                    child.layout_item(container, item.size, item_height, context);
+                }
+                false => {
+                    let width = child.item_width(container, align, context);
+                    // Items laid out for their basis are already laid out at
+                    // that size, and laying them out again at every level of
+                    // nested columns would take exponential time.
+                    let measured = child.dimensions.content;
+                    if item.laid_out
+                        && (width, item.size) == (measured.width, measured.height)
+                        && !child.depends_on_definite_height(context)
+                    {
+                        continue;
+                    }
+                    child.layout_item(container, width, Some(item.size), context);
+                }
+            }
+        }
+
+        // The cross size of the line is the container's when it's definite,
+        // otherwise that of its largest item.
+        let line_cross = match (is_row, height) {
+            (true, Some(height)) => height,
+            (true, None) => self
+                .children
+                .iter()
+                .filter(|child| !child.is_out_of_flow())
+                .map(|child| child.dimensions.margin_box_height())
+                .fold(0.0, f32::max),
+            (false, _) => content.width,
+        };
+        if is_row && align == AlignItems::Stretch {
+            for child in self.children.iter_mut().filter(in_flow) {
+                if child.style().height != LengthPercentageAuto::Auto {
+                    continue;
+                }
+                let edges = child.dimensions.margin_box_height() - child.dimensions.content.height;
+                let stretched = child.clamp_height((line_cross - edges).max(0.0), height);
+                child.dimensions.content.height = stretched;
+                child.layout_border_radii();
+                child.layout_scrollable_overflow();
+            }
+        }
+
+        let outer_main = |child: &LayoutBox| match is_row {
+            true => child.dimensions.margin_box().width,
+            false => child.dimensions.margin_box().height,
+        };
+        let remaining = line_main
+            - self
+                .children
+                .iter()
+                .filter(|child| !child.is_out_of_flow())
+                .map(outer_main)
+                .sum::<f32>();
+        let count = items.len() as f32;
+        // Space that doesn't fit between the items is given to the ends
+        // instead.
+        let (mut position, gap) = match style.justify_content {
+            JustifyContent::FlexStart => (0.0, 0.0),
+            JustifyContent::FlexEnd => (remaining, 0.0),
+            JustifyContent::Center => (remaining / 2.0, 0.0),
+            JustifyContent::SpaceBetween if remaining > 0.0 && count > 1.0 => {
+                (0.0, remaining / (count - 1.0))
+            }
+            JustifyContent::SpaceAround if remaining > 0.0 => {
+                (remaining / count / 2.0, remaining / count)
+            }
+            JustifyContent::SpaceEvenly if remaining > 0.0 => {
+                (remaining / (count + 1.0), remaining / (count + 1.0))
+            }
+            JustifyContent::SpaceBetween => (0.0, 0.0),
+            JustifyContent::SpaceAround | JustifyContent::SpaceEvenly => (remaining / 2.0, 0.0),
+        };
+        for child in self.children.iter_mut().filter(in_flow) {
+            let margin_box = child.dimensions.margin_box();
+            let (outer_main, outer_cross) = match is_row {
+                true => (margin_box.width, margin_box.height),
+                false => (margin_box.height, margin_box.width),
+            };
+            // Reversed lines start from the other end of the main axis.
+            let main = match direction.is_reverse() {
+                true => line_main - position - outer_main,
+                false => position,
+            };
+            position += outer_main + gap;
+            let cross = match align {
+                AlignItems::Stretch | AlignItems::FlexStart => 0.0,
+                AlignItems::FlexEnd => line_cross - outer_cross,
+                AlignItems::Center => (line_cross - outer_cross) / 2.0,
+            };
+            let (x, y) = match is_row {
+                true => (content.x + main, content.y + cross),
+                false => (content.x + cross, content.y + main),
+            };
+            child.translate(x - margin_box.x, y - margin_box.y);
+        }
+        self.dimensions.content.height = match is_row {
+            true => line_cross,
+            false => line_main,
+        };
+    }
+
+    /// Content width of an item of a column flex container, or of a grid
+    /// item in the cell `container`: its own width, or when it's `auto`, the
+    /// container's width when items are stretched and the width of its
+    /// content otherwise.
+    fn item_width(
+        &self,
+        container: ContainingBlock,
+        align: AlignItems,
+        context: &LayoutContext,
+    ) -> f32 {
+        let style = self.style();
+        let containing_width = container.width;
+        let width = match style.width {
+            LengthPercentageAuto::Auto if align == AlignItems::Stretch => {
+                let Dimensions {
+                    padding, border, ..
+                } = self.dimensions;
+                (containing_width
+                    - to_px(style.margin_left, containing_width)
+                    - to_px(style.margin_right, containing_width)
+                    - padding.left
+                    - padding.right
+                    - border.left
+                    - border.right)
+                    .max(0.0)
+            }
+            LengthPercentageAuto::Auto => self.shrink_to_fit_width(container, context),
+            width => self.content_width(to_px(width, containing_width)),
+        };
+        let (min, max) = size_bounds(style.min_width, style.max_width, Some(containing_width));
+        width
+            .min(self.content_width(max))
+            .max(self.content_width(min))
+    }
+
+    /// Content width of an `auto` width box that's only as wide as its
+    /// content, within the space left by its margins, border and padding
+    /// in `containing_block`.
+    fn shrink_to_fit_width(
+        &self,
+        containing_block: ContainingBlock,
+        context: &LayoutContext,
+    ) -> f32 {
+        let containing_width = containing_block.width;
+        let Dimensions {
+            padding, border, ..
+        } = self.dimensions;
+        let style = self.style();
+        let available = containing_width
+            - to_px(style.margin_left, containing_width)
+            - to_px(style.margin_right, containing_width)
+            - padding.left
+            - padding.right
+            - border.left
+            - border.right;
+        self.max_content_width(context).min(available).max(0.0)
+    }
+
+    /// Specified height of the box in px, unless it's `auto` or a percentage
+    /// of a containing block whose height isn't definite.
+    fn definite_height(&self, containing_height: Option<f32>) -> Option<f32> {
+        let height = match self.style().height {
+            LengthPercentageAuto::Length(height) => height,
+            LengthPercentageAuto::Percent(percentage) => percentage / 100.0 * containing_height?,
+            LengthPercentageAuto::Auto => return None,
+        };
+        Some(self.content_height(height))
+    }
+
+    /// Whether the layout of a flex or grid item could change when its
+    /// content height becomes definite, even at the height it has without
+    /// one: percentage heights of the item and its children resolve against
+    /// it, as do percentage `flex-basis` of a column flex item's children,
+    /// and percentage and `fr` rows of a grid item. Embedders measuring the
+    /// item are given it too.
+    fn depends_on_definite_height(&self, context: &LayoutContext) -> bool {
+        let style = self.style();
+        context.get_measure(self.get_style_node()).is_some()
+            || has_percentage_height(style)
+            || self
+                .children
+                .iter()
+                .any(|child| has_percentage_height(child.style()))
+            || match self.box_type {
+                BoxType::Flex(_) => {
+                    !style.flex_direction.is_row()
+                        && self.children.iter().any(|child| {
+                            matches!(child.style().flex_basis, LengthPercentageAuto::Percent(_))
+                        })
+                }
+                BoxType::Grid(_) => style
+                    .grid_template_rows
+                    .iter()
+                    .any(|row| matches!(row, TrackSize::Percent(_) | TrackSize::Fraction(_))),
+                _ => false,
+            }
+    }
+
+    /// Content width of the box when `width` or a `min-`/`max-width` is
+    /// `width` px, which includes the padding and border under
+    /// `box-sizing: border-box`. Padding and border must be laid out.
+    fn content_width(&self, width: f32) -> f32 {
+        let Dimensions {
+            padding, border, ..
+        } = self.dimensions;
+        match self.style().box_sizing {
+            BoxSizing::ContentBox => width,
+            BoxSizing::BorderBox => {
+                (width - padding.left - padding.right - border.left - border.right).max(0.0)
+            }
+        }
+    }
+
+    /// Like `content_width`, for heights.
+    fn content_height(&self, height: f32) -> f32 {
+        let Dimensions {
+            padding, border, ..
+        } = self.dimensions;
+        match self.style().box_sizing {
+            BoxSizing::ContentBox => height,
+            BoxSizing::BorderBox => {
+                (height - padding.top - padding.bottom - border.top - border.bottom).max(0.0)
+            }
+        }
+    }
+
+    /// Asks the embedder for the size of the box, if it registered a measure
+    /// callback for it.
+    fn measure(
+        &self,
+        containing_block: ContainingBlock,
+        height: Option<f32>,
+        context: &LayoutContext,
+    ) -> Option<Size> {
+        let measure = context.get_measure(self.get_style_node())?;
+        let style = self.style();
+        let containing_width = containing_block.width;
+        let Dimensions {
+            padding, border, ..
+        } = self.dimensions;
+        let edges = to_px(style.margin_left, containing_width)
+            + to_px(style.margin_right, containing_width)
+            + padding.left
+            + padding.right
+            + border.left
+            + border.right;
+        Some(measure(LayoutConstraints {
+            available_width: (containing_width - edges).max(0.0),
+            width: match style.width {
+                LengthPercentageAuto::Auto => None,
+                width => Some(self.content_width(to_px(width, containing_width))),
+            },
+            height,
+        }))
+    }
+
+    /// Content size of an `img` box: its specified width and height, with
+    /// the one left `auto` following the aspect ratio of the image's
+    /// intrinsic size, or the intrinsic size when both are. Images without
+    /// a known intrinsic size are empty. `height` is the content height when
+    /// it's definite.
+    fn replaced_size(
+        &self,
+        containing_block: ContainingBlock,
+        height: Option<f32>,
+        context: &LayoutContext,
+    ) -> Option<Size> {
+        let NodeType::Element(element) = self.get_style_node()?.node.get_node_type() else {
+            return None;
+        };
+        if element.tag_type != TagType::Img {
+            return None;
+        }
+        let intrinsic = element
+            .attribute("src")
+            .and_then(|src| context.intrinsic_sizes.get(src))
+            .copied()
+            .unwrap_or_default();
+        let width = match self.style().width {
+            LengthPercentageAuto::Auto => None,
+            width => Some(self.content_width(to_px(width, containing_block.width))),
+        };
+        // Scales `length` by the ratio of `to` to `from`, keeping it when the
+        // ratio isn't defined.
+        let scale = |length: f32, to: f32, from: f32| match from > 0.0 {
+            true => length * to / from,
+            false => to,
+        };
+        Some(match (width, height) {
+            (Some(width), Some(height)) => Size { width, height },
+            (Some(width), None) => Size {
+                width,
+                height: scale(width, intrinsic.height, intrinsic.width),
+            },
+            (None, Some(height)) => Size {
+                width: scale(height, intrinsic.width, intrinsic.height),
+                height,
+            },
+            (None, None) => intrinsic,
+        })
+    }
+
+    /// Solves `margin-left + border-left + padding-left + width +
+    /// padding-right + border-right + margin-right = containing block width`
+    /// for whichever of the margins and `width` are `auto`, `width` being the
+    /// content width. Percentages refer to the containing block's width.
+    fn layout_block_width(
+        &mut self,
+        containing_block: ContainingBlock,
+        width: LengthPercentageAuto,
+    ) {
+        let auto = LengthPercentageAuto::Auto;
+        let zero = LengthPercentageAuto::Length(0.0);
+        let style = self.style();
+        let mut margin_left = style.margin_left;
+        let mut margin_right = style.margin_right;
+
+        let containing_width = containing_block.width;
+        let to_px = |value| to_px(value, containing_width);
+        let Dimensions {
+            padding, border, ..
+        } = self.dimensions;
+        let edges = padding.left + padding.right + border.left + border.right;
+        let total: f32 = [margin_left, width, margin_right]
+            .into_iter()
+            .map(to_px)
+            .sum::<f32>()
+            + edges;
+        // An over-constrained box treats auto margins as zero.
+        if width != auto && total > containing_block.width {
+            if margin_left == auto {
+                margin_left = zero;
+            }
+            if margin_right == auto {
+                margin_right = zero;
+            }
+        }
+
+        let underflow = containing_width - total;
+        let (mut width_px, mut left_px, mut right_px) =
+            (to_px(width), to_px(margin_left), to_px(margin_right));
+        // Floats and inline-blocks aren't stretched to the containing block,
+        // so their auto margins are zero and the equation doesn't have to
+        // hold.
+        let shrinks_to_fit = self.shrinks_to_fit();
+        // Space that the margin on the end side of the containing block
+        // takes up, or gives up when the box is over-constrained.
+        let mut end_underflow = 0.0;
+        match (width == auto, margin_left == auto, margin_right == auto) {
+            _ if shrinks_to_fit => {}
+            (false, false, false) => end_underflow = underflow,
+            (false, false, true) => right_px = underflow,
+            (false, true, false) => left_px = underflow,
+            (false, true, true) => {
+                left_px = underflow / 2.0;
+                right_px = underflow / 2.0;
+            }
+            (true, _, _) => {
+                if underflow >= 0.0 {
+                    width_px = underflow;
+                } else {
+                    width_px = 0.0;
+                    end_underflow = underflow;
+                }
+            }
+        }
+        match containing_block.direction {
+            Direction::Ltr => right_px += end_underflow,
+            Direction::Rtl => left_px += end_underflow,
+        }
+
+        let dimensions = &mut self.dimensions;
+        dimensions.content.width = width_px;
+        dimensions.margin.left = left_px;
+        dimensions.margin.right = right_px;
+    }
+
+    /// Keeps the used width within `min-width` and `max-width`, solving the
+    /// width equation again with the bound as the width when it's out of
+    /// them. `min-width` wins when the bounds conflict.
+    fn clamp_block_width(&mut self, containing_block: ContainingBlock) {
+        let style = self.style();
+        let (min, max) = size_bounds(
+            style.min_width,
+            style.max_width,
+            Some(containing_block.width),
+        );
+        let (min, max) = (self.content_width(min), self.content_width(max));
+        let width = self.dimensions.content.width;
+        let clamped = width.min(max).max(min);
+        if clamped != width {
+            self.layout_block_width(containing_block, LengthPercentageAuto::Length(clamped));
+        }
+    }
+
+    /// Keeps `height` within `min-height` and `max-height`. Percentages
+    /// refer to the containing block's height, when it's definite.
+    fn clamp_height(&self, height: f32, containing_height: Option<f32>) -> f32 {
+        let style = self.style();
+        let (min, max) = size_bounds(style.min_height, style.max_height, containing_height);
+        height
+            .min(self.content_height(max))
+            .max(self.content_height(min))
+    }
+
+    /// Places the box below the content already laid out in its containing
+    /// block. Percentage margins refer to the containing block's width, on
+    /// every side.
+    fn layout_block_position(&mut self, containing_block: ContainingBlock) {
+        let style = self.style();
+        let containing_width = containing_block.width;
+        let dimensions = &mut self.dimensions;
+        dimensions.margin.top = to_px(style.margin_top, containing_width);
+        dimensions.margin.bottom = to_px(style.margin_bottom, containing_width);
+        dimensions.content.x = containing_block.x
+            + dimensions.margin.left
+            + dimensions.border.left
+            + dimensions.padding.left;
+        dimensions.content.y = containing_block.y
+            + containing_block.filled_height
+            + dimensions.margin.top
+            + dimensions.border.top
+            + dimensions.padding.top;
+    }
+
+    fn layout_block_children(
+        &mut self,
+        height: Option<f32>,
+        direction: Direction,
+        floats: &mut FloatContext,
+        context: &LayoutContext,
+    ) {
+        self.lines.clear();
+        if let Some(threads) = self.parallel_layout_threads(floats, context) {
+            let containing_block =
+                ContainingBlock::content_box(&self.dimensions, height, direction);
+            return self.layout_children_in_parallel(containing_block, threads, context);
+        }
+        // Consecutive inline children share lines, each tall enough for the
+        // boxes on it lined up on its baseline.
+        let mut lines: Option<LineBuilder> = None;
+        let style = self.style();
+        for child in &mut self.children {
+            if child.is_out_of_flow() {
+                let content = self.dimensions.content;
+                let lines_height = lines.as_ref().map_or(0.0, LineBuilder::height);
+                child.set_static_position(content.x, content.y + content.height + lines_height);
+                continue;
+            }
+            if let BoxType::Inline(_) | BoxType::InlineBlock(_) = child.box_type {
+                let lines = lines.get_or_insert_with(|| {
+                    let containing_block =
+                        ContainingBlock::content_box(&self.dimensions, height, direction);
+                    LineBuilder::new(containing_block, floats, style, self.lines.len())
+                });
+                child.layout_inline(lines, context);
+                continue;
+            }
+            if let Some(lines) = lines.take() {
+                self.dimensions.content.height += lines.height();
+                self.lines.extend(lines.finish());
+            }
+            let containing_block =
+                ContainingBlock::content_box(&self.dimensions, height, direction);
+            child.layout_in(containing_block, floats, context);
+            // Boxes clearing floats move down until their border box is
+            // below them.
+            let clearance = floats.clearance(child.style().clear).map_or(0.0, |bottom| {
+                (bottom - child.dimensions.border_box().y).max(0.0)
+            });
+            if child.is_floated() {
+                // Floats are out of the flow, so they don't push the boxes
+                // after them down.
+                let margin_box = child.dimensions.margin_box();
+                let content = self.dimensions.content;
+                let rect = floats.place(
+                    child.style().float,
+                    Size {
+                        width: margin_box.width,
+                        height: margin_box.height,
+                    },
+                    margin_box.y + clearance,
+                    content.x,
+                    content.x + content.width,
+                );
+                child.translate(rect.x - margin_box.x, rect.y - margin_box.y);
+                continue;
+            }
+            // Moving the child walks its whole subtree, so only boxes with
+            // clearance are moved.
+            if clearance > 0.0 {
+                child.translate(0.0, clearance);
+            }
+            self.dimensions.content.height += clearance + child.dimensions.margin_box_height();
+        }
+        if let Some(lines) = lines {
+            self.dimensions.content.height += lines.height();
+            self.lines.extend(lines.finish());
+        }
+        for child in &mut self.children {
+            if let BoxType::Inline(_) | BoxType::InlineBlock(_) = child.box_type {
+                if !child.is_out_of_flow() {
+                    child.align_in_lines(&self.lines);
+                }
+            }
+        }
+    }
+
+    /// Number of threads to lay the children out on, when they don't
+    /// depend on each other and are worth it: they're blocks in the flow
+    /// that don't clear floats, and no float in the block formatting context
+    /// narrows the space next to them. Children of boxes laid out on a
+    /// thread of their own are laid out on that thread.
+    fn parallel_layout_threads(
+        &self,
+        floats: &FloatContext,
+        context: &LayoutContext,
+    ) -> Option<usize> {
+        let threads = context.threads.min(self.children.len());
+        if threads < 2 || IN_LAYOUT_THREAD.get() || floats.bottom().is_some() {
+            return None;
+        }
+        let mut boxes = 0;
+        for child in &self.children {
+            let is_independent_block = matches!(
+                child.box_type,
+                BoxType::Block(_) | BoxType::Flex(_) | BoxType::Grid(_) | BoxType::AnonymousBlock
+            ) && !child.is_out_of_flow()
+                && !child.is_floated()
+                && child.style().clear == Clear::None;
+            if !is_independent_block {
+                return None;
+            }
+            boxes += child.subtree_size_without_floats()?;
+        }
+        (boxes >= MIN_PARALLEL_LAYOUT_BOXES).then_some(threads)
+    }
+
+    /// Number of boxes in the subtree laid out in the flow, or `None` when
+    /// one of them is a float placed in the block formatting context the
+    /// box is in.
+    fn subtree_size_without_floats(&self) -> Option<usize> {
+        let mut size = 0;
+        let mut stack = vec![self];
+        while let Some(layout_box) = stack.pop() {
+            if layout_box.is_floated() {
+                return None;
+            }
+            size += 1;
+            // Floats inside formatting context roots stay inside them.
+            if !layout_box.establishes_formatting_context() {
+                let children = layout_box.children.iter();
+                stack.extend(children.filter(|child| !child.is_out_of_flow()));
+            }
+        }
+        Some(size)
+    }
+
+    /// Lays out block children that don't depend on each other on
+    /// `threads` threads, each child at the top of `containing_block`, then
+    /// moves them down below the ones before.
+    fn layout_children_in_parallel(
+        &mut self,
+        containing_block: ContainingBlock,
+        threads: usize,
+        context: &LayoutContext,
+    ) {
+        let layout_chunk = |chunk: &mut [LayoutBox]| {
+            for child in chunk {
+                let mut floats = FloatContext::default();
+                child.layout_in(containing_block, &mut floats, context);
+            }
+        };
+        let chunk_size = self.children.len().div_ceil(threads);
+        // Chunks whose thread couldn't be started are laid out on this one.
+        let mut unstarted = vec![];
+        thread::scope(|scope| {
+            for (index, chunk) in self.children.chunks_mut(chunk_size).enumerate() {
+                let depth = chunk.iter().map(LayoutBox::depth).max().unwrap_or(0);
+                let started = thread::Builder::new()
+                    .stack_size(layout_stack_size(depth))
+                    .spawn_scoped(scope, move || {
+                        IN_LAYOUT_THREAD.set(true);
+                        layout_chunk(chunk);
+                    });
+                if started.is_err() {
+                    unstarted.push(index);
+                }
+            }
+        });
+        for index in unstarted {
+            layout_chunk(self.children.chunks_mut(chunk_size).nth(index).unwrap());
+        }
+        let top = containing_block.filled_height;
+        for child in &mut self.children {
+            child.translate(0.0, self.dimensions.content.height - top);
+            self.dimensions.content.height += child.dimensions.margin_box_height();
+        }
+    }
+
+    fn layout_block_height(&mut self, height: Option<f32>, measured: Option<Size>) {
+        match (height, measured) {
+            (Some(height), _) => self.dimensions.content.height = height,
+            (None, Some(measured)) => self.dimensions.content.height = measured.height,
+            (None, None) => {}
+        }
+    }
+
+    /// Gathers the overflow of the children, once the box and its children
+    /// have their final sizes. Children that clip their overflow only add
+    /// their border box.
+    fn layout_scrollable_overflow(&mut self) {
+        let mut overflow = self.dimensions.padding_box();
+        for fragment in &self.fragments {
+            overflow = overflow.union(fragment.rect);
+        }
+        for child in &self.children {
+            overflow = overflow.union(child.dimensions.border_box());
+            if !child.clips_overflow() {
+                overflow = overflow.union(child.dimensions.scrollable_overflow);
+            }
+        }
+        self.dimensions.scrollable_overflow = overflow;
+    }
+
+    /// Resolves `border-*-radius` against the final size of the border box.
+    /// Percentages refer to the width horizontally and the height vertically,
+    /// and radii that would overlap are scaled down together, as in CSS.
+    fn layout_border_radii(&mut self) {
+        let style = self.style();
+        let Rect { width, height, .. } = self.dimensions.border_box();
+        let radius = |value: LengthPercentage| match value {
+            LengthPercentage::Length(length) => (length, length),
+            LengthPercentage::Percent(percentage) => {
+                (percentage / 100.0 * width, percentage / 100.0 * height)
+            }
+        };
+        let top_left = radius(style.border_top_left_radius);
+        let top_right = radius(style.border_top_right_radius);
+        let bottom_right = radius(style.border_bottom_right_radius);
+        let bottom_left = radius(style.border_bottom_left_radius);
+
+        let scale = [
+            (width, top_left.0 + top_right.0),
+            (width, bottom_left.0 + bottom_right.0),
+            (height, top_left.1 + bottom_left.1),
+            (height, top_right.1 + bottom_right.1),
+        ]
+        .into_iter()
+        .filter(|(_, radii)| *radii > 0.0)
+        .map(|(side, radii)| side / radii)
+        .fold(1.0f32, f32::min);
+        let resolve = |(x, y): (f32, f32)| CornerRadius {
+            x: x.max(0.0) * scale,
+            y: y.max(0.0) * scale,
+        };
+        self.dimensions.radii = CornerRadii {
+            top_left: resolve(top_left),
+            top_right: resolve(top_right),
+            bottom_right: resolve(bottom_right),
+            bottom_left: resolve(bottom_left),
+        };
+    }
+
+    /// Box that inline-level children should be added to: the box itself when
+    /// it already holds inline content, otherwise a trailing anonymous block.
+    fn get_inline_container(&mut self) -> &mut LayoutBox<'a> {
+        match self.box_type {
+            BoxType::Inline(_) | BoxType::InlineBlock(_) | BoxType::AnonymousBlock => self,
+            BoxType::Block(_) | BoxType::Flex(_) | BoxType::Grid(_) => {
+                match self.children.last() {
+                    Some(LayoutBox {
+                        box_type: BoxType::AnonymousBlock,
+                        ..
+                    }) => {}
+                    _ => self.children.push(LayoutBox::new(BoxType::AnonymousBlock)),
+                }
+                self.children.last_mut().unwrap()
+            }
+        }
+    }
+}
+
+/// Builds the layout tree for `style_node` and lays it out inside the
+/// content box of `viewport`, the initial containing block.
+pub fn layout_tree<'a>(
+    style_node: &'a StyledNode<'a>,
+    viewport: Dimensions,
+    context: &LayoutContext,
+) -> LayoutBox<'a> {
+    let mut root = generate_layout_tree(style_node);
+    let direction = style_node.style.direction;
+    root.layout(
+        ContainingBlock::from_rect(viewport.content, direction),
+        context,
+    );
+    root
+}
+
+fn new_box<'a>(style_node: &'a StyledNode<'a>, display: Display) -> LayoutBox<'a> {
+    LayoutBox::new(match display {
+        Display::Block => BoxType::Block(style_node),
+        // Replaced elements are atomic inline-level boxes, laid out like
+        // inline-blocks.
+        Display::Inline if is_replaced(style_node) => BoxType::InlineBlock(style_node),
+        Display::Inline => BoxType::Inline(style_node),
+        Display::InlineBlock => BoxType::InlineBlock(style_node),
+        Display::Flex => BoxType::Flex(style_node),
+        Display::Grid => BoxType::Grid(style_node),
+        Display::None => panic!("Cannot generate a layout box for a node with display: none"),
+    })
+}
+
+/// Whether the node's content is an external image rather than its
+/// children.
+fn is_replaced(style_node: &StyledNode) -> bool {
+    match style_node.node.get_node_type() {
+        NodeType::Element(element) => element.tag_type == TagType::Img,
+        NodeType::Text(_) => false,
+    }
+}
+
+pub fn generate_layout_tree<'a>(style_node: &'a StyledNode<'a>) -> LayoutBox<'a> {
+    match style_node.style.display {
+        // Nothing is rendered when the root has no box, so the tree is an
+        // empty anonymous block that paints nothing.
+        Display::None => LayoutBox::new(BoxType::AnonymousBlock),
+        display => build_layout_box(style_node, display),
+    }
+}
+
+fn build_layout_box<'a>(style_node: &'a StyledNode<'a>, display: Display) -> LayoutBox<'a> {
+    // Boxes under construction along with the index of the next style child
+    // to visit, so deep trees don't recurse.
+    let mut stack = vec![(new_box(style_node, display), 0)];
+    loop {
+        let (layout_box, next_child) = stack.last_mut().unwrap();
+        let style_node = layout_box.get_style_node().unwrap();
+        if let Some(child) = style_node.children.get(*next_child) {
+            *next_child += 1;
+            let is_container = matches!(layout_box.box_type, BoxType::Flex(_) | BoxType::Grid(_));
+            let is_text = matches!(child.node.get_node_type(), NodeType::Text(_));
+            let is_out_of_flow =
+                matches!(child.style.position, Position::Absolute | Position::Fixed);
+            match child.style.display {
+                Display::None => {}
+                // Flex and grid items, floats and out-of-flow boxes are
+                // blockified, so they never get wrapped in anonymous blocks.
+                // Text in a flex or grid container is wrapped in an anonymous
+                // item instead.
+                Display::Inline | Display::InlineBlock
+                    if (is_container && !is_text)
+                        || child.style.float != Float::None
+                        || is_out_of_flow =>
+                {
+                    stack.push((new_box(child, Display::Block), 0))
+                }
+                display => stack.push((new_box(child, display), 0)),
+            }
+            continue;
+        }
+        let (layout_box, _) = stack.pop().unwrap();
+        let Some((parent, _)) = stack.last_mut() else {
+            return layout_box;
+        };
+        match layout_box.box_type {
+            BoxType::Inline(_) | BoxType::InlineBlock(_) => {
+                parent.get_inline_container().children.push(layout_box)
+            }
+            _ => parent.children.push(layout_box),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        font::{FontMetrics, FontStore},
+        layout::{
+            generate_layout_tree, layout_tree, BoxType, CornerRadii, CornerRadius, Dimensions,
+            LayoutBox, LayoutConstraints, LayoutContext, Rect, Size,
+        },
+        parser::{CSSParser, HTMLParser, IParser},
+        style::{get_styled_node, Overflow},
+    };
+
+    /// Lays out `html` styled by `css` in a viewport `width` px wide. The
+    /// document and its styles are leaked, for the boxes to borrow them
+    /// past the call.
+    fn layout_of(html: &str, css: &str, width: f32) -> LayoutBox<'static> {
+        layout_in(html, css, width, 0.0)
+    }
+
+    /// Like `layout_of`, in a viewport `height` px tall as well.
+    fn layout_in(html: &str, css: &str, width: f32, height: f32) -> LayoutBox<'static> {
+        let stylesheet = Box::leak(Box::new(CSSParser::new(css).parse()));
+        let dom = Box::leak(Box::new(HTMLParser::new(html).parse()));
+        let styled_dom = Box::leak(Box::new(get_styled_node(dom, stylesheet)));
+        let mut viewport = Dimensions::default();
+        viewport.content.width = width;
+        viewport.content.height = height;
+        layout_tree(styled_dom, viewport, &LayoutContext::new())
+    }
+
+    /// Fractional lengths pick up rounding errors as they're summed.
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "{actual} is not close to {expected}"
+        );
+    }
+
+    #[test]
+    fn borrows_styled_nodes() {
+        let html = "<div><p>ab<p class=\"inline\">cd</p></p></div><p></p>";
+        let css = "p.inline { display: inline; }";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let styled_dom = get_styled_node(&dom, &stylesheet);
+        let layout_tree = generate_layout_tree(&styled_dom);
+
+        // Boxes point into the styled tree rather than holding copies of
+        // their nodes, so building the layout tree doesn't copy subtrees.
+        let mut styled_nodes = vec![&styled_dom];
+        let mut stack = vec![&styled_dom];
+        while let Some(node) = stack.pop() {
+            styled_nodes.extend(&node.children);
+            stack.extend(&node.children);
+        }
+        let boxes: Vec<_> = layout_tree
+            .pre_order()
+            .filter_map(LayoutBox::get_style_node)
+            .collect();
+        assert_eq!(boxes.len(), styled_nodes.len());
+        for style_node in boxes {
+            assert!(styled_nodes
+                .iter()
+                .any(|node| std::ptr::eq(*node, style_node)));
+        }
+    }
+
+    #[test]
+    fn generates_box_types_from_display() {
+        let html = "
+            <div class=\"flex\">
+                <p class=\"inline-block\">Item</p>
+                Text
+            </div>
+            <div class=\"grid\"></div>
+            <p class=\"hidden\"></p>
+            <p class=\"inline-block\"></p>
+            <p class=\"inline\"></p>
+        ";
+        let css = "
+            div.flex {
+                display: flex;
+            }
+
+            div.grid {
+                display: grid;
+            }
+
+            p.hidden {
+                display: none;
+            }
+
+            p.inline-block {
+                display: inline-block;
+            }
+
+            p.inline {
+                display: inline;
+            }
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let styled_dom = get_styled_node(&dom, &stylesheet);
+        let layout_tree = generate_layout_tree(&styled_dom);
+
+        assert!(matches!(layout_tree.box_type, BoxType::Block(_)));
+        assert_eq!(layout_tree.children.len(), 3);
+
+        let flex = &layout_tree.children[0];
+        assert!(matches!(flex.box_type, BoxType::Flex(_)));
+        assert_eq!(flex.children.len(), 2);
+        assert!(matches!(flex.children[0].box_type, BoxType::Block(_)));
+        let text_item = &flex.children[1];
+        assert!(matches!(text_item.box_type, BoxType::AnonymousBlock));
+        assert!(matches!(text_item.children[0].box_type, BoxType::Inline(_)));
+
+        assert!(matches!(layout_tree.children[1].box_type, BoxType::Grid(_)));
+
+        let anonymous = &layout_tree.children[2];
+        assert!(matches!(anonymous.box_type, BoxType::AnonymousBlock));
+        assert!(matches!(
+            anonymous.children[0].box_type,
+            BoxType::InlineBlock(_)
+        ));
+        assert!(matches!(anonymous.children[1].box_type, BoxType::Inline(_)));
+    }
+
+    #[test]
+    fn generates_an_empty_tree_for_a_hidden_root() {
+        let layout_root = layout_of("<div><p>Hidden</p></div>", "html { display: none; }", 800.0);
+
+        assert!(matches!(layout_root.box_type, BoxType::AnonymousBlock));
+        assert!(layout_root.children.is_empty());
+        assert_eq!(layout_root.dimensions.content.width, 800.0);
+        assert_eq!(layout_root.dimensions.content.height, 0.0);
+    }
+
+    #[test]
+    fn resolves_percentages_and_font_relative_lengths() {
+        let html = "
+            <div class=\"half\"><div class=\"inner\"></div></div>
+            <div><div class=\"inner\"></div></div>
+        ";
+        let css = "
+            html {
+                height: 100%;
+                font-size: 10px;
+            }
+
+            div.half {
+                width: 50%;
+                height: 50%;
+                margin-left: 10%;
+                font-size: 2em;
+            }
+
+            div.inner {
+                width: 2em;
+                height: 50%;
+                margin-top: 1rem;
+                padding: 5% 0 0 10%;
+            }
+        ";
+        let layout_root = layout_in(html, css, 800.0, 600.0);
+
+        let half = &layout_root.children[0].dimensions;
+        assert_eq!(half.content.width, 400.0);
+        assert_eq!(half.content.height, 300.0);
+        assert_eq!(half.margin.left, 80.0);
+        // The right margin takes up the rest of the containing block.
+        assert_eq!(
+            half.margin_box(),
+            Rect {
+                x: 0.0,
+                y: 0.0,
+                width: 800.0,
+                height: 300.0
+            }
+        );
+        let inner = &layout_root.children[0].children[0].dimensions;
+        assert_eq!(inner.content.width, 40.0);
+        assert_eq!(inner.content.height, 150.0);
+        assert_eq!(inner.margin.top, 10.0);
+        // Percentage paddings refer to the containing block's width, even
+        // vertically.
+        assert_eq!((inner.padding.top, inner.padding.left), (20.0, 40.0));
+        assert_eq!(inner.content.y, 30.0);
+        assert_eq!(inner.margin_box().y, 0.0);
+        assert_eq!(inner.margin_box().height, 180.0);
+        // Percentage heights in a box of auto height behave as `auto`.
+        let inner = &layout_root.children[1].children[0].dimensions;
+        assert_eq!(inner.content.width, 20.0);
+        assert_eq!(inner.content.height, 0.0);
+        assert_eq!((inner.padding.top, inner.padding.left), (40.0, 80.0));
+    }
+
+    #[test]
+    fn nests_content_padding_border_and_margin_boxes() {
+        let html = "<div class=\"boxed\"></div><div class=\"auto\"></div>";
+        let css = "
+            div.boxed {
+                width: 100px;
+                padding: 10px 5px;
+                border: 2px solid;
+                margin: 3px;
+            }
+
+            div.auto {
+                padding-left: 10%;
+                border: 4px none;
+            }
+        ";
+        let layout_root = layout_of(html, css, 800.0);
+
+        let rect = |x, y, width, height| Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+        let boxed = &layout_root.children[0].dimensions;
+        assert_eq!(boxed.content, rect(10.0, 15.0, 100.0, 0.0));
+        assert_eq!(boxed.padding_box(), rect(5.0, 5.0, 110.0, 20.0));
+        assert_eq!(boxed.border_box(), rect(3.0, 3.0, 114.0, 24.0));
+        // The right margin takes up the rest of the line.
+        assert_eq!(boxed.margin_box(), rect(0.0, 0.0, 800.0, 30.0));
+
+        // Borders of style `none` take no room, and `auto` widths leave
+        // room for the padding.
+        let auto = &layout_root.children[1].dimensions;
+        assert_eq!(auto.content, rect(80.0, 30.0, 720.0, 0.0));
+        assert_eq!(auto.border_box(), rect(0.0, 30.0, 800.0, 0.0));
+    }
+
+    #[test]
+    fn solves_block_widths() {
+        let html = "
+            <div class=\"centered\"></div>
+            <div class=\"right\"></div>
+            <div class=\"fill\"></div>
+            <div class=\"padded\"></div>
+            <div class=\"wide\"></div>
+        ";
+        let css = "
+            div.centered {
+                width: 400px;
+                height: 50px;
+                margin: 0 auto;
+            }
+
+            div.right {
+                width: 100px;
+                height: 20px;
+                margin-left: auto;
+            }
+
+            div.fill {
+                height: 10px;
+                margin: 5px 20px;
+            }
+
+            div.padded {
+                width: 300px;
+                padding: 0 40px;
+                border-left: 10px solid;
+                border-right: 10px solid;
+                margin: 0 auto;
+            }
+
+            div.wide {
+                width: 1000px;
+                margin: 0 auto;
+            }
+        ";
+        let layout_root = layout_in(html, css, 800.0, 600.0);
+
+        let content = |i: usize| layout_root.children[i].dimensions.content;
+        assert_eq!(
+            content(0),
+            Rect {
+                x: 200.0,
+                y: 0.0,
+                width: 400.0,
+                height: 50.0
+            }
+        );
+        assert_eq!(
+            content(1),
+            Rect {
+                x: 700.0,
+                y: 50.0,
+                width: 100.0,
+                height: 20.0
+            }
+        );
+        assert_eq!(
+            content(2),
+            Rect {
+                x: 20.0,
+                y: 75.0,
+                width: 760.0,
+                height: 10.0
+            }
+        );
+        // Padding and borders count towards the width being centered.
+        let padded = &layout_root.children[3].dimensions;
+        assert_eq!(padded.content.x, 250.0);
+        assert_eq!((padded.margin.left, padded.margin.right), (200.0, 200.0));
+        // Over-constrained boxes treat auto margins as zero and overflow on
+        // the right.
+        let wide = &layout_root.children[4].dimensions;
+        assert_eq!((wide.content.x, wide.content.width), (0.0, 1000.0));
+        assert_eq!(layout_root.dimensions.content.height, 90.0);
+    }
+
+    #[test]
+    fn clamps_min_and_max_sizes() {
+        let html = "
+            <div class=\"narrow\"></div>
+            <div class=\"conflict\"></div>
+            <div class=\"short\"></div>
+            <div class=\"tall\"></div>
+        ";
+        let css = "
+            html {
+                height: 100%;
+            }
+
+            div.narrow {
+                height: 10px;
+                max-width: 50%;
+                margin: 0 auto;
+            }
+
+            div.conflict {
+                width: 100px;
+                min-width: 300px;
+                max-width: 200px;
+            }
+
+            div.short {
+                height: 100px;
+                max-height: 40px;
+            }
+
+            div.tall {
+                min-height: 10%;
+                max-height: 5px;
+            }
+        ";
+        let layout_root = layout_in(html, css, 800.0, 600.0);
+
+        let dimensions = |i: usize| layout_root.children[i].dimensions;
+        // The auto width fills the containing block, then is clamped, and the
+        // auto margins center the box at its new width.
+        let narrow = dimensions(0);
+        assert_eq!((narrow.content.width, narrow.margin.left), (400.0, 200.0));
+        assert_eq!(dimensions(1).content.width, 300.0);
+        assert_eq!(dimensions(2).content.height, 40.0);
+        // Percentages refer to the root's height, and the minimum wins.
+        assert_eq!(dimensions(3).content.height, 60.0);
+    }
+
+    #[test]
+    fn sizes_content_and_border_boxes() {
+        let html = "
+            <div class=\"border-box\"></div>
+            <div class=\"percent\"></div>
+            <div class=\"thick\"></div>
+            <div class=\"content-box\"></div>
+        ";
+        let css = "
+            div {
+                width: 200px;
+                height: 100px;
+                padding: 10px;
+                border: 5px solid;
+            }
+
+            div.border-box {
+                box-sizing: border-box;
+            }
+
+            div.percent {
+                box-sizing: border-box;
+                width: 50%;
+                max-height: 60px;
+            }
+
+            div.thick {
+                box-sizing: border-box;
+                height: 20px;
+                padding: 20px;
+            }
+        ";
+        let layout_root = layout_of(html, css, 800.0);
+
+        let dimensions = |i: usize| layout_root.children[i].dimensions;
+        let size = |i: usize| {
+            let content = dimensions(i).content;
+            (content.width, content.height)
+        };
+        assert_eq!(size(0), (170.0, 70.0));
+        assert_eq!(dimensions(0).border_box().width, 200.0);
+        // Percentages and bounds size the border box too.
+        assert_eq!(size(1), (370.0, 30.0));
+        // The content can't be smaller than nothing, even when the padding
+        // and border are larger than the specified size.
+        assert_eq!(size(2).1, 0.0);
+        assert_eq!(size(3), (200.0, 100.0));
+    }
+
+    #[test]
+    fn sizes_embedder_measured_content() {
+        let html = "
+            <div class=\"row\">
+                <div id=\"widget\"></div>
+                <div id=\"fixed-widget\"></div>
+            </div>
+        ";
+        let css = "
+            div.row {
+                width: 300px;
+                margin: 0 10px;
+            }
+
+            div#widget {
+                margin-right: 20px;
+            }
+
+            div#fixed-widget {
+                width: 120px;
+                height: 40px;
+            }
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let styled_dom = get_styled_node(&dom, &stylesheet);
+        let mut context = LayoutContext::new();
+        context.register_measure("widget", |constraints: LayoutConstraints| {
+            assert_eq!(constraints.available_width, 280.0);
+            assert_eq!(constraints.width, None);
+            Size {
+                width: constraints.available_width / 2.0,
+                height: 30.0,
+            }
+        });
+        context.register_measure("fixed-widget", |constraints: LayoutConstraints| {
+            assert_eq!(constraints.width, Some(120.0));
+            assert_eq!(constraints.height, Some(40.0));
+            Size {
+                width: 500.0,
+                height: 500.0,
+            }
+        });
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        let layout_root = layout_tree(&styled_dom, viewport, &context);
+
+        let row = &layout_root.children[0];
+        let content = |i: usize| row.children[i].dimensions.content;
+        assert_eq!(
+            content(0),
+            Rect {
+                x: 10.0,
+                y: 0.0,
+                width: 140.0,
+                height: 30.0
+            }
+        );
+        assert_eq!(
+            content(1),
+            Rect {
+                x: 10.0,
+                y: 30.0,
+                width: 120.0,
+                height: 40.0
+            }
+        );
+        assert_eq!(row.dimensions.content.height, 70.0);
+    }
+
+    #[test]
+    fn exposes_overflow() {
+        let html = "
+            <div class=\"hidden\"></div>
+            <div class=\"scroll\"></div>
+            <div class=\"auto\"></div>
+            <div></div>
+        ";
+        let css = "
+            div.hidden {
+                overflow: hidden;
+            }
+
+            div.scroll {
+                overflow: scroll;
+            }
+
+            div.auto {
+                overflow: auto;
+            }
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let styled_dom = get_styled_node(&dom, &stylesheet);
+        let layout_root = generate_layout_tree(&styled_dom);
+        let overflows: Vec<Overflow> = layout_root.children.iter().map(|b| b.overflow()).collect();
+        assert_eq!(
+            overflows,
+            [
+                Overflow::Hidden,
+                Overflow::Scroll,
+                Overflow::Auto,
+                Overflow::Visible
+            ]
+        );
+        let scroll_containers: Vec<bool> = layout_root
+            .children
+            .iter()
+            .map(|b| b.is_scroll_container())
+            .collect();
+        assert_eq!(scroll_containers, [false, true, true, false]);
+        assert!(layout_root.children[0].clips_overflow());
+        assert!(!layout_root.children[3].clips_overflow());
+    }
+
+    #[test]
+    fn records_scrollable_overflow() {
+        let html = "
+            <div class=\"short\">
+                <div class=\"tall\"></div>
+                <div class=\"clip\"><div class=\"tall\"></div></div>
+            </div>
+            <div class=\"fits\"><div></div></div>
+        ";
+        let css = "
+            div.short {
+                height: 20px;
+                padding: 5px;
+            }
+
+            div.tall {
+                width: 900px;
+                height: 50px;
+            }
+
+            div.clip {
+                height: 10px;
+                overflow: hidden;
+            }
+
+            div.fits {
+                height: 20px;
+            }
+        ";
+        let layout_root = layout_of(html, css, 800.0);
+
+        // The content is 60px tall in a 20px box, and the second tall box
+        // only counts up to the bottom of the box clipping it.
+        let short = &layout_root.children[0];
+        assert!(short.dimensions.has_overflow());
+        assert_eq!(
+            short.dimensions.scrollable_overflow,
+            Rect {
+                x: 0.0,
+                y: 0.0,
+                width: 905.0,
+                height: 65.0
+            }
+        );
+        let clip = &short.children[1];
+        assert_eq!(clip.dimensions.scrollable_overflow.height, 50.0);
+        // Overflow propagates up through boxes that don't clip it.
+        assert_eq!(layout_root.dimensions.scrollable_overflow.width, 905.0);
+
+        let fits = &layout_root.children[1];
+        assert!(!fits.dimensions.has_overflow());
+    }
+    #[test]
+    fn resolves_stacking_contexts() {
+        let html = "
+            <div class=\"above\"></div>
+            <div class=\"below\"></div>
+            <div class=\"static\"></div>
+            <div class=\"auto\"></div>
+        ";
+        let css = "
+            div.above {
+                position: relative;
+                z-index: 10;
+            }
+
+            div.below {
+                position: absolute;
+                z-index: -2;
+            }
+
+            div.static {
+                z-index: 5;
+            }
+
+            div.auto {
+                position: fixed;
+                z-index: auto;
+            }
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let styled_dom = get_styled_node(&dom, &stylesheet);
+        let layout_root = generate_layout_tree(&styled_dom);
+        let z_indices: Vec<Option<i32>> =
+            layout_root.children.iter().map(|b| b.z_index()).collect();
+        assert_eq!(z_indices, [Some(10), Some(-2), None, None]);
+        let contexts: Vec<bool> = layout_root
+            .children
+            .iter()
+            .map(|b| b.establishes_stacking_context())
+            .collect();
+        assert_eq!(contexts, [true, true, false, false]);
+    }
+
+    #[test]
+    fn sizes_line_boxes_from_line_height() {
+        let html =
+            "<div class=\"tall\">Text<p class=\"inline\">More</p></div><div>Text</div><p>Text</p>";
+        let css = "
+            div.tall {
+                line-height: 2;
+            }
+
+            p.inline {
+                display: inline;
+                line-height: 40px;
+            }
+
+            p {
+                line-height: 150%;
+            }
+        ";
+        let layout_root = layout_of(html, css, 800.0);
+
+        let line = &layout_root.children[0].children[0];
+        assert!(matches!(line.box_type, BoxType::AnonymousBlock));
+        // The text inherits `line-height: 2` from the div.
+        assert_eq!(line.children[0].dimensions.content.height, 32.0);
+        assert_eq!(line.children[1].dimensions.content.height, 40.0);
+        let height = |i: usize| layout_root.children[i].dimensions.content.height;
+        assert_eq!(height(0), 40.0);
+        // Lines of the paragraph aren't rounded to whole pixels.
+        assert_close(height(1), 19.2);
+        assert_close(height(2), 24.0);
+        assert_close(layout_root.children[2].dimensions.content.y, 59.2);
+    }
+
+    #[test]
+    fn places_and_wraps_inline_boxes() {
+        let html = "<div>aa bb cc<p>dd</p> ee</div>";
+        let css = "
+            div {
+                width: 60px;
+            }
+
+            p {
+                display: inline;
+                margin-left: 6px;
+            }
+        ";
+        let layout_root = layout_of(html, css, 800.0);
+
+        // Characters of the bitmap font are 12px wide at 16px, and lines are
+        // 19.2px tall.
+        let div = &layout_root.children[0];
+        let line = &div.children[0];
+        let rect = |x, y, width, height| Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+        // `cc` doesn't fit after `aa bb`, so the text spans two lines.
+        assert_eq!(
+            line.children[0].dimensions.content,
+            rect(0.0, 0.0, 60.0, 38.4)
+        );
+        assert_eq!(
+            line.children[1].dimensions.content,
+            rect(30.0, 19.2, 24.0, 19.2)
+        );
+        assert_eq!(line.children[1].children[0].dimensions.content.x, 30.0);
+        // The space before `ee` ends the second line, so it wraps too.
+        assert_eq!(
+            line.children[2].dimensions.content,
+            rect(0.0, 38.4, 24.0, 19.2)
+        );
+        assert_close(div.dimensions.content.height, 57.6);
+    }
+
+    #[test]
+    fn splits_inline_boxes_into_fragments() {
+        let html = "<div>ab<p>cd ef gh</p></div>";
+        let css = "
+            div {
+                width: 100px;
+            }
+
+            p {
+                display: inline;
+                padding-left: 4px;
+                padding-right: 4px;
+            }
+        ";
+        let layout_root = layout_of(html, css, 800.0);
+        let line_container = &layout_root.children[0].children[0];
+        let rect = |x, y, width, height| Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+        let lines: Vec<Rect> = line_container.lines.iter().map(|line| line.rect).collect();
+        assert_eq!(
+            lines,
+            [rect(0.0, 0.0, 100.0, 19.2), rect(0.0, 19.2, 100.0, 19.2)]
+        );
+
+        // `gh` wraps, so the inline box has a fragment on each line.
+        let p = &line_container.children[1];
+        let fragments: Vec<(usize, Rect)> = p
+            .fragments
+            .iter()
+            .map(|fragment| (fragment.line, fragment.rect))
+            .collect();
+        assert_eq!(
+            fragments,
+            [
+                (0, rect(28.0, 0.0, 60.0, 19.2)),
+                (1, rect(0.0, 19.2, 24.0, 19.2)),
+            ]
+        );
+        let runs: Vec<String> = p.children[0]
+            .fragments
+            .iter()
+            .map(|fragment| fragment.run.text())
+            .collect();
+        assert_eq!(runs, ["cd ef", "gh"]);
+
+        // Its left padding is on the first fragment and its right padding
+        // on the last, and only the fragments are hit.
+        assert_eq!(
+            p.border_boxes(),
+            [rect(24.0, 0.0, 64.0, 19.2), rect(0.0, 19.2, 28.0, 19.2)]
+        );
+        let hit = layout_root.hit_test(10.0, 25.0).unwrap();
+        assert!(std::ptr::eq(hit.layout_box, &p.children[0]));
+        assert_eq!(hit.node, p.node_id());
+        let hit = layout_root.hit_test(90.0, 5.0).unwrap();
+        assert!(std::ptr::eq(hit.layout_box, line_container));
+    }
+
+    #[test]
+    fn flows_around_floats() {
+        let html = "
+            <div class=\"flow\">
+                <div class=\"left\"></div>
+                <div class=\"right\"></div>
+                <div class=\"wide\"></div>
+                aa bb cc dd
+                <div class=\"clear\"></div>
+            </div>
+            <div class=\"contain\"><p class=\"fit\">abc</p></div>
+        ";
+        let css = "
+            div.flow {
+                width: 200px;
+            }
+
+            div.left {
+                float: left;
+                width: 50px;
+                height: 30px;
+            }
+
+            div.right {
+                float: right;
+                width: 30px;
+                height: 60px;
+            }
+
+            div.wide {
+                float: left;
+                width: 150px;
+                height: 10px;
+            }
+
+            div.clear {
+                clear: both;
+                height: 10px;
+            }
+
+            div.contain {
+                overflow: hidden;
+            }
+
+            p.fit {
+                float: right;
+                padding: 0 4px;
+            }
+        ";
+        let layout_root = layout_of(html, css, 800.0);
+
+        let flow = &layout_root.children[0];
+        let rect = |x, y, width, height| Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+        let content = |i: usize| flow.children[i].dimensions.content;
+        assert_eq!(content(0), rect(0.0, 0.0, 50.0, 30.0));
+        assert_eq!(content(1), rect(170.0, 0.0, 30.0, 60.0));
+        // The wide float doesn't fit next to the others, so it goes below
+        // the first one to end.
+        assert_eq!(content(2), rect(0.0, 30.0, 150.0, 10.0));
+        // The first line is 120px wide between the floats, so `dd` wraps.
+        // It doesn't fit next to the wide float either, so its line moves
+        // below it. The floats themselves don't take up any height.
+        let text = &flow.children[3].children[0];
+        let lines: Vec<(String, f32, f32)> = text
+            .fragments
+            .iter()
+            .map(|fragment| (fragment.run.text(), fragment.rect.x, fragment.rect.y))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                ("aa bb cc".to_string(), 50.0, 0.0),
+                ("dd".to_string(), 0.0, 40.0)
+            ]
+        );
+        // The cleared box goes below all of them.
+        assert_eq!(content(4).y, 60.0);
+        assert_eq!(flow.dimensions.content.height, 70.0);
+
+        // Boxes clipping their overflow contain their floats, which shrink
+        // to fit their content.
+        let contain = &layout_root.children[1];
+        let fit = contain.children[0].dimensions;
+        assert_eq!(fit.content.width, 36.0);
+        assert_eq!(fit.border_box().x, 800.0 - 44.0);
+        assert_close(contain.dimensions.content.height, 19.2);
+    }
+
+    #[test]
+    fn lays_out_flex_items() {
+        let html = "
+            <div class=\"row\">
+                <p class=\"fixed\"></p>
+                <p class=\"grow\"></p>
+                <p class=\"capped\"></p>
+            </div>
+            <div class=\"reverse\">
+                <p class=\"wide\"></p>
+                <p class=\"narrow\"></p>
+            </div>
+            <div class=\"column\">
+                <p class=\"short\"></p>
+                abc
+            </div>
+        ";
+        let css = "
+            div.row {
+                display: flex;
+                width: 300px;
+                height: 40px;
+                align-items: center;
+            }
+
+            p.fixed {
+                width: 50px;
+                height: 10px;
+            }
+
+            p.grow {
+                flex: 1;
+                height: 20px;
+            }
+
+            p.capped {
+                flex: 2;
+                max-width: 100px;
+            }
+
+            div.reverse {
+                display: flex;
+                flex-direction: row-reverse;
+                width: 100px;
+            }
+
+            p.wide {
+                width: 80px;
+            }
+
+            p.narrow {
+                width: 40px;
+                height: 10px;
+                flex-shrink: 3;
+            }
+
+            div.column {
+                display: flex;
+                flex-direction: column;
+                justify-content: space-between;
+                align-items: flex-end;
+                width: 200px;
+                height: 100px;
+            }
+
+            p.short {
+                width: 50px;
+                height: 20px;
+            }
+        ";
+        let layout_root = layout_of(html, css, 800.0);
+        let rect = |x, y, width, height| Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+
+        // The free space goes to the growing items, by their factors, until
+        // one of them reaches its max width. Items are centered across the
+        // line.
+        let row = &layout_root.children[0];
+        let contents: Vec<Rect> = row
+            .children
+            .iter()
+            .map(|item| item.dimensions.content)
+            .collect();
+        assert_eq!(
+            contents,
+            [
+                rect(0.0, 15.0, 50.0, 10.0),
+                rect(50.0, 10.0, 150.0, 20.0),
+                rect(200.0, 20.0, 100.0, 0.0),
+            ]
+        );
+
+        // Overflowing items shrink by their factors times their basis, from
+        // the right in a reversed row. Items without a height are stretched
+        // to the line.
+        let reverse = &layout_root.children[1];
+        let y = reverse.dimensions.content.y;
+        assert_eq!(y, 40.0);
+        assert_eq!(reverse.dimensions.content.height, 10.0);
+        assert_eq!(
+            reverse.children[0].dimensions.content,
+            rect(28.0, y, 72.0, 10.0)
+        );
+        assert_eq!(
+            reverse.children[1].dimensions.content,
+            rect(0.0, y, 28.0, 10.0)
+        );
+
+        // Text is wrapped in an anonymous item, which shrinks to fit it when
+        // items aren't stretched.
+        let column = &layout_root.children[2];
+        let y = column.dimensions.content.y;
+        assert_eq!(
+            column.children[0].dimensions.content,
+            rect(150.0, y, 50.0, 20.0)
+        );
+        let text = column.children[1].dimensions.content;
+        assert_eq!((text.x, text.width), (164.0, 36.0));
+        assert_close(text.y, y + 100.0 - 19.2);
+        assert_close(text.height, 19.2);
+    }
+
+    #[test]
+    fn lays_out_the_content_of_flex_items_once() {
+        let css = "
+            div {
+                display: flex;
+                flex-direction: column;
+                width: 300px;
+            }
+        ";
+        let layout_root = layout_of("<div><p>a</p></div>", css, 800.0);
+
+        // The item is measured before it's laid out, and its descendants
+        // don't keep the height of the first pass.
+        let item = &layout_root.children[0].children[0];
+        assert_close(item.dimensions.content.height, 19.2);
+        let text = &item.children[0];
+        assert!(matches!(text.box_type, BoxType::AnonymousBlock));
+        assert_close(text.dimensions.content.height, 19.2);
+        assert_eq!(text.lines[0].rect.y, 0.0);
+        assert_eq!(text.children[0].fragments[0].rect.y, 0.0);
+    }
+
+    #[test]
+    fn lays_out_nested_flex_columns() {
+        let css = "
+            div {
+                display: flex;
+                flex-direction: column;
+                padding: 1px;
+            }
+        ";
+        let depth = 40;
+        let html = "<div>".repeat(depth) + "a" + &"</div>".repeat(depth);
+        // Items laid out to find their basis aren't laid out again, or each
+        // level would double the time taken.
+        let layout_root = layout_of(&html, css, 800.0);
+
+        let mut item = &layout_root.children[0];
+        for level in 0..depth {
+            let height = 19.2 + 2.0 * (depth - 1 - level) as f32;
+            assert_close(item.dimensions.content.height, height);
+            assert_close(item.dimensions.content.y, 1.0 + level as f32);
+            item = &item.children[0];
+        }
+        assert!(matches!(item.box_type, BoxType::AnonymousBlock));
+        assert_close(item.children[0].fragments[0].rect.y, 1.0 * depth as f32);
+    }
+
+    #[test]
+    fn positions_boxes() {
+        let html = "
+            <div class=\"container\">
+                <p class=\"first\"></p>
+                <p class=\"relative\"></p>
+                <p class=\"absolute\"></p>
+                <p class=\"static\"></p>
+                <p class=\"last\"></p>
+            </div>
+            <p class=\"fixed\">fixed</p>
+        ";
+        let css = "
+            div.container {
+                position: relative;
+                width: 400px;
+                padding: 10px;
+            }
+
+            p.first, p.last {
+                height: 20px;
+            }
+
+            p.relative {
+                position: relative;
+                top: 5px;
+                left: 10%;
+                height: 20px;
+            }
+
+            p.absolute {
+                position: absolute;
+                right: 20px;
+                bottom: 10px;
+                width: 50px;
+                height: 30px;
+            }
+
+            p.static {
+                position: absolute;
+                width: 10px;
+                height: 10px;
+            }
+
+            p.fixed {
+                display: inline;
+                position: fixed;
+                top: 0;
+                left: 0;
+                right: 0;
+            }
+        ";
+        let layout_root = layout_in(html, css, 800.0, 600.0);
+        let rect = |x, y, width, height| Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+
+        // Relative boxes move after the flow is laid out, and absolute ones
+        // take no room in it. Absolute boxes are placed in the padding box of
+        // their positioned ancestor, or at their static position when their
+        // offsets are `auto`.
+        let container = &layout_root.children[0];
+        assert_eq!(container.dimensions.content, rect(10.0, 10.0, 400.0, 60.0));
+        let contents: Vec<Rect> = container
+            .children
+            .iter()
+            .map(|child| child.dimensions.content)
+            .collect();
+        assert_eq!(
+            contents,
+            [
+                rect(10.0, 10.0, 400.0, 20.0),
+                rect(50.0, 35.0, 400.0, 20.0),
+                rect(350.0, 40.0, 50.0, 30.0),
+                rect(10.0, 50.0, 10.0, 10.0),
+                rect(10.0, 50.0, 400.0, 20.0),
+            ]
+        );
+
+        // Fixed boxes are blockified and placed in the viewport.
+        let fixed = &layout_root.children[1];
+        assert!(matches!(fixed.box_type, BoxType::Block(_)));
+        let content = fixed.dimensions.content;
+        assert_eq!((content.x, content.y, content.width), (0.0, 0.0, 800.0));
+    }
+
+    #[test]
+    fn orders_boxes_for_painting() {
+        let html = "
+            <div class=\"positioned\"><div class=\"raised\"></div></div>
+            <div class=\"static\"><div class=\"lowered\"></div></div>
+            <div class=\"faded\"><div class=\"inner\"></div></div>
+            <div class=\"last\"></div>
+        ";
+        // Boxes are told apart by their height.
+        let css = "
+            div.positioned {
+                position: relative;
+                height: 1px;
+            }
+
+            div.raised {
+                position: absolute;
+                z-index: 2;
+                height: 2px;
+            }
+
+            div.static {
+                height: 3px;
+            }
+
+            div.lowered {
+                position: absolute;
+                z-index: -1;
+                height: 4px;
+            }
+
+            div.faded {
+                opacity: 0.5;
+                height: 5px;
+            }
+
+            div.inner {
+                height: 6px;
+            }
+
+            div.last {
+                height: 7px;
+            }
+        ";
+        let layout_root = layout_of(html, css, 800.0);
+        let heights = |boxes: Vec<&LayoutBox>| -> Vec<f32> {
+            boxes
+                .iter()
+                .map(|layout_box| layout_box.dimensions.content.height)
+                .collect()
+        };
+
+        // Descendants of non-positioned and positioned boxes without a
+        // `z-index` are painted in the context of the root, while the
+        // translucent box paints its own.
+        let faded = &layout_root.children[2];
+        assert!(faded.establishes_stacking_context());
+        assert_eq!(faded.z_index(), None);
+        assert_eq!(
+            heights(layout_root.paint_order()),
+            [4.0, 3.0, 7.0, 1.0, 5.0, 2.0]
+        );
+        assert_eq!(heights(faded.paint_order()), [6.0]);
+    }
+
+    #[test]
+    fn computes_clip_rects() {
+        let html = "
+            <div class=\"clip\">
+                <div class=\"inner\"><div></div></div>
+                <div class=\"escape\"></div>
+            </div>
+            <div class=\"anchor\">
+                <div class=\"hidden\">
+                    <div class=\"absolute\"></div>
+                    <div class=\"fixed\"></div>
+                </div>
+            </div>
+        ";
+        let css = "
+            div.clip {
+                width: 100px;
+                height: 50px;
+                padding: 10px;
+                overflow: hidden;
+            }
+
+            div.inner {
+                width: 200px;
+                height: 100px;
+                margin-left: 50px;
+                overflow: hidden;
+            }
+
+            div.escape {
+                position: absolute;
+            }
+
+            div.anchor {
+                position: relative;
+                height: 40px;
+                overflow: hidden;
+            }
+
+            div.hidden {
+                height: 10px;
+                overflow: hidden;
+            }
+
+            div.absolute {
+                position: absolute;
+            }
+
+            div.fixed {
+                position: fixed;
+            }
+        ";
+        let layout_root = layout_of(html, css, 800.0);
+        let rect = |x, y, width, height| {
+            Some(Rect {
+                x,
+                y,
+                width,
+                height,
+            })
+        };
+
+        // Boxes aren't clipped by themselves, and nested clips intersect.
+        let clip = &layout_root.children[0];
+        assert_eq!(layout_root.dimensions.clip, None);
+        assert_eq!(clip.dimensions.clip, None);
+        assert_eq!(
+            clip.children[0].dimensions.clip,
+            rect(0.0, 0.0, 120.0, 70.0)
+        );
+        assert_eq!(
+            clip.children[0].children[0].dimensions.clip,
+            rect(60.0, 10.0, 60.0, 60.0)
+        );
+
+        // Absolutely positioned boxes are only clipped from their containing
+        // block up, and fixed boxes not at all.
+        assert_eq!(clip.children[1].dimensions.clip, None);
+        let hidden = &layout_root.children[1].children[0];
+        assert_eq!(hidden.dimensions.clip, rect(0.0, 70.0, 800.0, 40.0));
+        assert_eq!(
+            hidden.children[0].dimensions.clip,
+            rect(0.0, 70.0, 800.0, 40.0)
+        );
+        assert_eq!(hidden.children[1].dimensions.clip, None);
+    }
+
+    #[test]
+    fn scrolls_overflowing_containers() {
+        let html = "
+            <div class=\"scroll\">
+                <div class=\"wide\"></div>
+                <div class=\"absolute\"></div>
+            </div>
+            <div class=\"auto\"><div></div></div>
+        ";
+        let css = "
+            div.scroll {
+                height: 50px;
+                overflow: scroll;
+            }
+
+            div.wide {
+                width: 1000px;
+                height: 200px;
+            }
+
+            div.absolute {
+                position: absolute;
+            }
+
+            div.auto {
+                height: 10px;
+                overflow: auto;
+            }
+        ";
+        let mut layout_root = layout_of(html, css, 800.0);
+
+        // Only containers whose content overflows them scroll, as far as the
+        // content reaches.
+        let scroll = &mut layout_root.children[0];
+        let state = scroll.scroll.as_mut().unwrap();
+        assert_eq!((state.max_x, state.max_y), (200.0, 150.0));
+        assert_eq!((state.offset_x, state.offset_y), (0.0, 0.0));
+        state.scroll_to(500.0, 20.0);
+        assert_eq!((state.offset_x, state.offset_y), (200.0, 20.0));
+        assert!(layout_root.children[1].scroll.is_none());
+
+        // Content is moved by the offset, and still clipped to the padding
+        // box. The absolutely positioned box is contained by the root, so it
+        // doesn't scroll.
+        layout_root.apply_scroll_offsets();
+        let scroll = &layout_root.children[0];
+        assert_eq!(scroll.dimensions.scroll_translation, (0.0, 0.0));
+        let wide = &scroll.children[0].dimensions;
+        assert_eq!(wide.scroll_translation, (-200.0, -20.0));
+        assert_eq!(wide.clip, Some(scroll.dimensions.padding_box()));
+        let absolute = &scroll.children[1].dimensions;
+        assert_eq!(
+            (absolute.scroll_translation, absolute.clip),
+            ((0.0, 0.0), None)
+        );
+    }
+
+    #[test]
+    fn lays_out_inline_blocks() {
+        let html = "<div>ab<p class=\"shrink\">cd</p><p class=\"wide\"></p></div>";
+        let css = "
+            p.shrink {
+                display: inline-block;
+                padding: 2px;
+            }
+
+            p.wide {
+                display: inline-block;
+                width: 740px;
+                height: 30px;
+                margin-left: 10px;
+            }
+        ";
+        let layout_root = layout_of(html, css, 800.0);
+        let line = &layout_root.children[0].children[0];
+        assert!(matches!(line.box_type, BoxType::AnonymousBlock));
+
+        // The inline-block shrinks to fit its text, after the text before it.
+        let shrink = &line.children[1].dimensions;
+        assert_eq!(
+            shrink.content,
+            Rect {
+                x: 26.0,
+                y: 2.0,
+                width: 24.0,
+                height: 19.2,
+            }
+        );
+
+        // The next one doesn't fit, so it wraps below the tallest box of the
+        // first line.
+        let wide = &line.children[2].dimensions;
+        assert_close(wide.content.x, 10.0);
+        assert_close(wide.content.y, 23.2);
+        assert_close(layout_root.children[0].dimensions.content.height, 53.2);
+    }
+
+    #[test]
+    fn measures_text_with_loaded_faces() {
+        /// Face whose glyphs are all as wide as the font size.
+        struct SquareFont;
+
+        impl FontMetrics for SquareFont {
+            fn ascent(&self, size: f32) -> f32 {
+                size * 0.8
+            }
+
+            fn descent(&self, size: f32) -> f32 {
+                size * 0.2
+            }
+
+            fn advance(&self, _ch: char, size: f32) -> f32 {
+                size
+            }
+        }
+
+        let html = "<div><p class=\"square\">ab</p></div><div><p>ab</p></div>";
+        let css = "
+            p {
+                display: inline-block;
+            }
+
+            p.square {
+                font-family: Missing, Square, serif;
+            }
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let styled_dom = get_styled_node(&dom, &stylesheet);
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        let mut fonts = FontStore::new();
+        fonts.register("square", SquareFont);
+        let mut context = LayoutContext::new();
+        context.set_fonts(fonts);
+        let layout_root = layout_tree(&styled_dom, viewport, &context);
+        let width = |i: usize| {
+            layout_root.children[i].children[0].children[0]
+                .dimensions
+                .content
+                .width
+        };
+        assert_eq!(width(0), 32.0);
+        // Text of other families is measured with the default font.
+        assert_eq!(width(1), 24.0);
+    }
+
+    #[test]
+    fn aligns_inline_boxes_on_baselines() {
+        let html = "<div>ab<p class=\"big\">cd</p><p class=\"box\"></p></div>";
+        let css = "
+            p.big {
+                display: inline;
+                font-size: 32px;
+            }
+
+            p.box {
+                display: inline-block;
+                width: 10px;
+                height: 50px;
+            }
+        ";
+        let layout_root = layout_of(html, css, 800.0);
+        let line_container = &layout_root.children[0].children[0];
+        let baseline = |layout_box: &LayoutBox| {
+            let fragment = &layout_box.fragments[0];
+            fragment.rect.y + fragment.baseline
+        };
+
+        // The inline-block has no lines, so its bottom edge is its baseline,
+        // and the text of both sizes moves down to it. The larger text
+        // reaches lowest below the baseline.
+        let line = line_container.lines[0];
+        assert_eq!(line.baseline, 50.0);
+        assert_close(line.rect.height, 50.0 + (38.4 - 31.2));
+        for child in &line_container.children {
+            assert_close(baseline(child), 50.0);
+        }
+        let big = &line_container.children[1];
+        assert_close(big.dimensions.content.y, 50.0 - 31.2);
+        assert_close(baseline(&big.children[0]), 50.0);
+        assert_close(big.children[0].fragments[0].run.baseline, 31.2);
+        assert_close(layout_root.children[0].dimensions.content.height, 57.2);
+    }
+
+    #[test]
+    fn aligns_inline_boxes_vertically() {
+        let html = "<div>ab<p class=\"tall\"></p><p class=\"top\"></p><p class=\"middle\">cd</p><p class=\"bottom\"></p></div>";
+        let css = "
+            p {
+                display: inline-block;
+                width: 10px;
+            }
+
+            p.tall {
+                height: 40px;
+            }
+
+            p.top {
+                height: 10px;
+                vertical-align: top;
+            }
+
+            p.middle {
+                display: inline;
+                vertical-align: middle;
+            }
+
+            p.bottom {
+                height: 60px;
+                vertical-align: bottom;
+            }
+        ";
+        let layout_root = layout_of(html, css, 800.0);
+        let line_container = &layout_root.children[0].children[0];
+        let top = |i: usize| line_container.children[i].dimensions.content.y;
+
+        // The box aligned with the bottom is taller than the others lined up
+        // on the baseline, so the line grows to fit it, and they stay at the
+        // top.
+        let line = line_container.lines[0];
+        assert_close(line.rect.height, 60.0);
+        assert_eq!(line.baseline, 40.0);
+        assert_eq!((top(1), top(2), top(4)), (0.0, 0.0, 0.0));
+        assert_close(top(0), 40.0 - 15.6);
+        // The middle of the centered box is a quarter of the font size above
+        // the baseline, and its text moves with it.
+        let middle = &line_container.children[3];
+        assert_close(top(3) + 19.2 / 2.0, 40.0 - 16.0 / 4.0);
+        assert_close(middle.children[0].dimensions.content.y, top(3));
+    }
+
+    #[test]
+    fn breaks_long_words() {
+        let html = "
+            <div>ab abcdefghijkl</div>
+            <div class=\"break-word\">ab abcdefghijkl</div>
+            <div class=\"break-all\">ab abcdefghijkl</div>
+        ";
+        let css = "
+            div {
+                width: 60px;
+            }
+
+            div.break-word {
+                overflow-wrap: break-word;
+            }
+
+            div.break-all {
+                word-break: break-all;
+            }
+        ";
+        let layout_root = layout_of(html, css, 800.0);
+        let lines = |i: usize| -> Vec<String> {
+            let text = &layout_root.children[i].children[0].children[0];
+            text.fragments
+                .iter()
+                .map(|fragment| fragment.run.text())
+                .collect()
+        };
+
+        // Lines fit 5 characters of the bitmap font.
+        assert_eq!(lines(0), ["ab", "abcdefghijkl"]);
+        // The long word goes on a line of its own before breaking.
+        assert_eq!(lines(1), ["ab", "abcde", "fghij", "kl"]);
+        // Words break wherever lines are full.
+        assert_eq!(lines(2), ["ab ab", "cdefg", "hijkl"]);
+        let text = &layout_root.children[2].children[0].children[0];
+        assert!(text
+            .fragments
+            .iter()
+            .all(|fragment| fragment.rect.width <= 60.0));
+    }
+
+    #[test]
+    fn honors_white_space() {
+        let html = "<div class=\"nowrap\">aa bb   cc</div><div class=\"pre\">a  b\n\ncd</div>";
+        let css = "
+            div {
+                width: 60px;
+            }
+
+            div.nowrap {
+                white-space: nowrap;
+            }
+
+            div.pre {
+                white-space: pre;
+            }
+        ";
+        let layout_root = layout_of(html, css, 800.0);
+        let lines = |i: usize| -> Vec<String> {
+            let line_container = &layout_root.children[i].children[0];
+            let text = &line_container.children[0];
+            text.fragments
+                .iter()
+                .map(|fragment| fragment.run.text())
+                .collect()
+        };
+
+        // Spaces collapse, but the text overflows its line rather than
+        // wrapping.
+        assert_eq!(lines(0), ["aa bb cc"]);
+        assert_eq!(
+            layout_root.children[0].children[0].children[0]
+                .dimensions
+                .content
+                .width,
+            96.0
+        );
+        // Spaces are kept, and each newline starts a line, empty or not.
+        assert_eq!(lines(1), ["a  b", "", "cd"]);
+        assert_close(
+            layout_root.children[1].dimensions.content.height,
+            19.2 * 3.0,
+        );
+    }
+
+    #[test]
+    fn lays_out_right_to_left_content() {
+        let html = "<div class=\"rtl\">ab cd<p class=\"fixed\"></p><p class=\"ltr\">ef</p></div>";
+        let css = "
+            div.rtl {
+                direction: rtl;
+            }
+
+            p.fixed {
+                width: 100px;
+                height: 10px;
+            }
+
+            p.ltr {
+                direction: ltr;
+            }
+        ";
+        let layout_root = layout_of(html, css, 800.0);
+        let div = &layout_root.children[0];
+
+        // Words are placed from the right edge, the first one rightmost, and
+        // the run reads in visual order.
+        let text = &div.children[0].children[0];
+        let fragment = &text.fragments[0];
+        assert_eq!((fragment.rect.x, fragment.rect.width), (740.0, 60.0));
+        assert_eq!(fragment.run.text(), "cd ab");
+
+        // The over-constrained block gives up the space on its left.
+        let fixed = &div.children[1].dimensions;
+        assert_eq!((fixed.margin.left, fixed.margin.right), (700.0, 0.0));
+        assert_eq!(fixed.content.x, 700.0);
+
+        // Left-to-right content inside goes back to the left edge.
+        let ltr = &div.children[2].children[0].children[0];
+        assert_eq!(ltr.fragments[0].rect.x, 0.0);
+    }
+
+    #[test]
+    fn lays_out_independent_blocks_in_parallel() {
+        let css = "
+            div.section {
+                padding: 4px;
+                margin-bottom: 10px;
+            }
+
+            p.wide {
+                width: 50%;
+                height: 12px;
+            }
+
+            p.float {
+                float: left;
+                width: 40px;
+                height: 30px;
+            }
+        ";
+        let section = |i: usize, float: bool| {
+            let float = if float { "<p class=\"float\"></p>" } else { "" };
+            format!(
+                "<div class=\"section\">{float}<p>ab cd ef gh {i}</p><div><p class=\"wide\"></p>\
+                 <p>ij kl</p></div><div><div><p>mn</p></div></div></div>"
+            )
+        };
+        let stylesheet = CSSParser::new(css).parse();
+        let layout = |html: &str, threads: usize| {
+            let dom = HTMLParser::new(html).parse();
+            let styled_dom = get_styled_node(&dom, &stylesheet);
+            let mut viewport = Dimensions::default();
+            viewport.content.width = 120.0;
+            let mut context = LayoutContext::new();
+            context.set_threads(threads);
+            let layout_root = layout_tree(&styled_dom, viewport, &context);
+            let mut rects = vec![];
+            for layout_box in layout_root.pre_order() {
+                let dimensions = &layout_box.dimensions;
+                rects.extend([dimensions.margin_box(), dimensions.scrollable_overflow]);
+                rects.extend(layout_box.fragments.iter().map(|fragment| fragment.rect));
+            }
+            rects
+        };
+
+        // Sections are laid out on their own threads, then stacked up, as
+        // they would be on a single thread. A float in one of them keeps
+        // them on the calling thread.
+        for float in [None, Some(5)] {
+            let html: String = (0..12).map(|i| section(i, float == Some(i))).collect();
+            let (parallel, sequential) = (layout(&html, 4), layout(&html, 1));
+            assert_eq!(parallel.len(), sequential.len());
+            assert!(parallel.len() > 200);
+            for (a, b) in parallel.iter().zip(&sequential) {
+                for (a, b) in [
+                    (a.x, b.x),
+                    (a.y, b.y),
+                    (a.width, b.width),
+                    (a.height, b.height),
+                ] {
+                    assert_close(a, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sizes_images() {
+        let html = "
+            <div>
+                <img src=\"a.png\">
+                <img class=\"wide\" src=\"a.png\">
+                <img class=\"short\" src=\"a.png\" />
+                <img class=\"both\" src=\"a.png\"/>
+                <img src=\"missing.png\">
+            </div>
+        ";
+        let css = "
+            img.wide {
+                width: 80px;
+            }
+
+            img.short {
+                height: 10px;
+            }
+
+            img.both {
+                width: 5px;
+                height: 50px;
+            }
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let styled_dom = get_styled_node(&dom, &stylesheet);
+        let mut viewport = Dimensions::default();
+        viewport.content.width = 800.0;
+        let mut context = LayoutContext::new();
+        context.set_intrinsic_size(
+            "a.png",
+            Size {
+                width: 40.0,
+                height: 20.0,
+            },
+        );
+        let layout_root = layout_tree(&styled_dom, viewport, &context);
+        let line = &layout_root.children[0].children[0];
+        assert!(matches!(line.children[0].box_type, BoxType::InlineBlock(_)));
+        let sizes: Vec<(f32, f32)> = line
+            .children
+            .iter()
+            .map(|image| {
+                (
+                    image.dimensions.content.width,
+                    image.dimensions.content.height,
+                )
+            })
+            .collect();
+        // A size given on one axis keeps the aspect ratio of the image.
+        assert_eq!(
+            sizes,
+            [
+                (40.0, 20.0),
+                (80.0, 40.0),
+                (20.0, 10.0),
+                (5.0, 50.0),
+                (0.0, 0.0)
+            ]
+        );
+        // Images sit side by side on the line.
+        assert_eq!(line.children[1].dimensions.content.x, 40.0);
+    }
+
+    #[test]
+    fn resolves_percentages_against_containing_blocks() {
+        let html = "
+            <div class=\"anchor\">
+                <div class=\"absolute\"></div>
+                <div class=\"fixed\"></div>
+                <div class=\"flow\"></div>
+            </div>
+        ";
+        let css = "
+            div.anchor {
+                position: relative;
+                width: 200px;
+                height: 100px;
+                padding: 10px;
+            }
+
+            div.absolute {
+                position: absolute;
+                width: 50%;
+                height: 50%;
+            }
+
+            div.fixed {
+                position: fixed;
+                width: 50%;
+                height: 50%;
+            }
+
+            div.flow {
+                width: 50%;
+                height: 50%;
+            }
+        ";
+        let layout_root = layout_in(html, css, 800.0, 600.0);
+        let sizes: Vec<(f32, f32)> = layout_root.children[0]
+            .children
+            .iter()
+            .map(|child| {
+                (
+                    child.dimensions.content.width,
+                    child.dimensions.content.height,
+                )
+            })
+            .collect();
+        // The padding box of the positioned ancestor, the viewport, then the
+        // parent's content box.
+        assert_eq!(sizes, [(110.0, 60.0), (400.0, 300.0), (100.0, 50.0)]);
+    }
+
+    #[test]
+    fn resolves_border_radii() {
+        let html =
+            "<div class=\"round\"></div><div class=\"percent\"></div><div class=\"large\"></div>";
+        let css = "
+            div.round {
+                height: 50px;
+                border-radius: 10px 20px 30px;
+            }
+
+            div.percent {
+                width: 200px;
+                height: 100px;
+                border-radius: 50%;
+                border-top-right-radius: 0;
+            }
+
+            div.large {
+                width: 100px;
+                height: 100px;
+                border-radius: 100px 100px 0 0;
+            }
+        ";
+        let layout_root = layout_of(html, css, 800.0);
+
+        let radii = |i: usize| layout_root.children[i].dimensions.radii;
+        let radius = |x, y| CornerRadius { x, y };
+        assert_eq!(
+            radii(0),
+            CornerRadii {
+                top_left: radius(10.0, 10.0),
+                top_right: radius(20.0, 20.0),
+                bottom_right: radius(30.0, 30.0),
+                bottom_left: radius(20.0, 20.0),
+            }
+        );
+        assert_eq!(radii(1).top_left, radius(100.0, 50.0));
+        assert_eq!(radii(1).top_right, radius(0.0, 0.0));
+        // The top corners would overlap, so every corner is halved.
+        assert_eq!(radii(2).top_left, radius(50.0, 50.0));
+        assert_eq!(radii(2).top_right, radius(50.0, 50.0));
+        assert_eq!(radii(2).bottom_left, radius(0.0, 0.0));
+    }
+}
@@ -0,0 +1,141 @@
+//! Snapping of laid out boxes to device pixels, so they're painted with
+//! crisp edges and without hairline seams between them.
+
+use crate::layout::{EdgeSizes, LayoutBox, Rect};
+
+/// Rounds `value` px to the nearest device pixel, `scale` of them per px.
+/// Values are first rounded to 1/64 px, so edges that differ only by
+/// floating point errors, e.g. 10.499999 and 10.5, snap the same way.
+fn snap(value: f32, scale: f32) -> f32 {
+    let value = (value * 64.0).round() / 64.0;
+    (value * scale).round() / scale
+}
+
+/// Snaps the edges of `rect` where they are, rather than its size, so rects
+/// sharing an edge still share it once snapped.
+fn snap_rect(rect: Rect, scale: f32) -> Rect {
+    let (left, top) = (snap(rect.x, scale), snap(rect.y, scale));
+    Rect {
+        x: left,
+        y: top,
+        width: snap(rect.x + rect.width, scale) - left,
+        height: snap(rect.y + rect.height, scale) - top,
+    }
+}
+
+/// Rounds a border width down to device pixels, keeping at least one so
+/// thin borders don't disappear.
+fn snap_border(width: f32, scale: f32) -> f32 {
+    if width <= 0.0 {
+        return 0.0;
+    }
+    (width * scale).floor().max(1.0) / scale
+}
+
+/// Distances from the edges of `outer` to those of `inner`, never negative.
+fn edges_between(outer: Rect, inner: Rect) -> EdgeSizes {
+    EdgeSizes {
+        left: (inner.x - outer.x).max(0.0),
+        right: (outer.x + outer.width - inner.x - inner.width).max(0.0),
+        top: (inner.y - outer.y).max(0.0),
+        bottom: (outer.y + outer.height - inner.y - inner.height).max(0.0),
+    }
+}
+
+impl LayoutBox<'_> {
+    /// Rounds the edges of the box and its descendants to device pixels,
+    /// `scale` of them per px, just before they're painted. Edges are
+    /// rounded where they are rather than sizes one by one, so rounding
+    /// errors don't add up along a row of boxes and adjacent boxes are
+    /// painted without gaps or overlaps. Border widths are rounded down,
+    /// keeping at least a device pixel, and the padding takes up the rest.
+    pub fn snap_to_pixels(&mut self, scale: f32) {
+        self.for_each_mut(|layout_box| {
+            let dimensions = &mut layout_box.dimensions;
+            let margin_box = snap_rect(dimensions.margin_box(), scale);
+            let border_box = snap_rect(dimensions.border_box(), scale);
+            let content = snap_rect(dimensions.content, scale);
+            let around_content = edges_between(border_box, content);
+            let border = &mut dimensions.border;
+            border.left = snap_border(border.left, scale).min(around_content.left);
+            border.right = snap_border(border.right, scale).min(around_content.right);
+            border.top = snap_border(border.top, scale).min(around_content.top);
+            border.bottom = snap_border(border.bottom, scale).min(around_content.bottom);
+            dimensions.padding = EdgeSizes {
+                left: around_content.left - border.left,
+                right: around_content.right - border.right,
+                top: around_content.top - border.top,
+                bottom: around_content.bottom - border.bottom,
+            };
+            dimensions.margin = edges_between(margin_box, border_box);
+            dimensions.content = content;
+            dimensions.scrollable_overflow = snap_rect(dimensions.scrollable_overflow, scale);
+            dimensions.clip = dimensions.clip.map(|clip| snap_rect(clip, scale));
+            let (dx, dy) = dimensions.scroll_translation;
+            dimensions.scroll_translation = (snap(dx, scale), snap(dy, scale));
+            for fragment in &mut layout_box.fragments {
+                fragment.rect = snap_rect(fragment.rect, scale);
+            }
+            for line in &mut layout_box.lines {
+                line.rect = snap_rect(line.rect, scale);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::layout::{
+        snap::{snap, snap_border},
+        Dimensions, EdgeSizes, LayoutBox, Rect,
+    };
+
+    #[test]
+    fn snaps_edges_and_borders() {
+        assert_eq!(snap(10.499999, 1.0), snap(10.5, 1.0));
+        assert_eq!(snap(10.3, 2.0), 10.5);
+        assert_eq!(snap_border(0.25, 1.0), 1.0);
+        assert_eq!(snap_border(2.7, 1.0), 2.0);
+        assert_eq!(snap_border(0.75, 2.0), 0.5);
+
+        let mut layout_box = LayoutBox::new(crate::layout::BoxType::AnonymousBlock);
+        layout_box.dimensions = Dimensions {
+            content: Rect {
+                x: 10.6,
+                y: 0.2,
+                width: 20.6,
+                height: 10.0,
+            },
+            border: EdgeSizes {
+                left: 0.4,
+                right: 1.5,
+                top: 0.0,
+                bottom: 0.0,
+            },
+            ..Default::default()
+        };
+        layout_box.snap_to_pixels(1.0);
+        // The border box spans 10.2 to 32.7, and its edges are rounded
+        // apart from the content's.
+        let dimensions = &layout_box.dimensions;
+        assert_eq!(
+            dimensions.content,
+            Rect {
+                x: 11.0,
+                y: 0.0,
+                width: 20.0,
+                height: 10.0,
+            }
+        );
+        assert_eq!(
+            (dimensions.border.left, dimensions.border.right),
+            (1.0, 1.0)
+        );
+        assert_eq!(
+            (dimensions.padding.left, dimensions.padding.right),
+            (0.0, 1.0)
+        );
+        assert_eq!(dimensions.border_box().x, 10.0);
+        assert_eq!(dimensions.border_box().width, 23.0);
+    }
+}
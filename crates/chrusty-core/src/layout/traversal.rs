@@ -0,0 +1,187 @@
+//! Walks of the layout tree and lookups in it, with explicit stacks so deep
+//! trees don't overflow the call stack.
+
+use crate::{
+    dom::{NodeId, NodeType},
+    layout::{BoxType, LayoutBox},
+};
+
+/// Kind of a box, without the styled node it's generated by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoxKind {
+    Block,
+    Inline,
+    InlineBlock,
+    Flex,
+    Grid,
+    AnonymousBlock,
+}
+
+impl BoxType<'_> {
+    pub fn kind(&self) -> BoxKind {
+        match self {
+            BoxType::Block(_) => BoxKind::Block,
+            BoxType::Inline(_) => BoxKind::Inline,
+            BoxType::InlineBlock(_) => BoxKind::InlineBlock,
+            BoxType::Flex(_) => BoxKind::Flex,
+            BoxType::Grid(_) => BoxKind::Grid,
+            BoxType::AnonymousBlock => BoxKind::AnonymousBlock,
+        }
+    }
+}
+
+/// A box and its descendants, each parent before its children, which are
+/// in tree order.
+pub struct PreOrder<'b, 'a> {
+    stack: Vec<&'b LayoutBox<'a>>,
+}
+
+impl<'b, 'a> Iterator for PreOrder<'b, 'a> {
+    type Item = &'b LayoutBox<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let layout_box = self.stack.pop()?;
+        self.stack.extend(layout_box.children.iter().rev());
+        Some(layout_box)
+    }
+}
+
+/// A box and its descendants, each parent after its children, which are in
+/// tree order.
+pub struct PostOrder<'b, 'a> {
+    /// Boxes along with whether their children were pushed already.
+    stack: Vec<(&'b LayoutBox<'a>, bool)>,
+}
+
+impl<'b, 'a> Iterator for PostOrder<'b, 'a> {
+    type Item = &'b LayoutBox<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (layout_box, expanded) = self.stack.pop()?;
+            if expanded || layout_box.children.is_empty() {
+                return Some(layout_box);
+            }
+            self.stack.push((layout_box, true));
+            let children = layout_box.children.iter().rev();
+            self.stack.extend(children.map(|child| (child, false)));
+        }
+    }
+}
+
+impl<'a> LayoutBox<'a> {
+    pub fn pre_order(&self) -> PreOrder<'_, 'a> {
+        PreOrder { stack: vec![self] }
+    }
+
+    pub fn post_order(&self) -> PostOrder<'_, 'a> {
+        PostOrder {
+            stack: vec![(self, false)],
+        }
+    }
+
+    /// The box and its descendants of the given kind, in pre-order.
+    pub fn boxes_of_kind(&self, kind: BoxKind) -> impl Iterator<Item = &LayoutBox<'a>> {
+        self.pre_order()
+            .filter(move |layout_box| layout_box.box_type.kind() == kind)
+    }
+
+    /// Id of the element the box is generated by. Anonymous boxes and text
+    /// boxes have none.
+    pub fn node_id(&self) -> Option<NodeId> {
+        match self.get_style_node()?.node.get_node_type() {
+            NodeType::Element(element) => Some(element.node_id()),
+            NodeType::Text(_) => None,
+        }
+    }
+
+    /// The box generated by the element `node`, when it has one among the
+    /// box and its descendants.
+    pub fn find_node(&self, node: NodeId) -> Option<&LayoutBox<'a>> {
+        self.pre_order()
+            .find(|layout_box| layout_box.node_id() == Some(node))
+    }
+
+    /// Calls `visit` on the box and its descendants, in pre-order. Children
+    /// are visited as `visit` leaves them on their parent.
+    pub fn for_each_mut(&mut self, mut visit: impl FnMut(&mut LayoutBox<'a>)) {
+        let mut stack = vec![self];
+        while let Some(layout_box) = stack.pop() {
+            visit(layout_box);
+            stack.extend(layout_box.children.iter_mut().rev());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        dom::NodeType,
+        layout::{layout_tree, BoxKind, Dimensions, LayoutBox, LayoutContext},
+        parser::{CSSParser, HTMLParser, IParser},
+        style::get_styled_node,
+    };
+
+    /// Lays out `html` styled by `css` in a viewport `width` px wide. The
+    /// document and its styles are leaked, for the boxes to borrow them
+    /// past the call.
+    fn layout_of(html: &str, css: &str, width: f32) -> LayoutBox<'static> {
+        let stylesheet = Box::leak(Box::new(CSSParser::new(css).parse()));
+        let dom = Box::leak(Box::new(HTMLParser::new(html).parse()));
+        let styled_dom = Box::leak(Box::new(get_styled_node(dom, stylesheet)));
+        let mut viewport = Dimensions::default();
+        viewport.content.width = width;
+        layout_tree(styled_dom, viewport, &LayoutContext::new())
+    }
+
+    /// Tag or text of the node a box is generated by, `_` for anonymous
+    /// boxes.
+    fn label(layout_box: &LayoutBox) -> String {
+        match layout_box.get_style_node() {
+            Some(style_node) => match style_node.node.get_node_type() {
+                NodeType::Element(element) => element.tag_type.to_string(),
+                NodeType::Text(text) => text.clone(),
+            },
+            None => "_".to_string(),
+        }
+    }
+
+    #[test]
+    fn walks_and_queries_layout_trees() {
+        let html = "<div><p>ab</p><div class=\"flex\"><p>cd</p></div>ef</div>";
+        let css = "div.flex { display: flex; }";
+        let mut layout_root = layout_of(html, css, 800.0);
+
+        let pre_order: Vec<String> = layout_root.pre_order().map(label).collect();
+        assert_eq!(
+            pre_order,
+            ["html", "div", "p", "_", "ab", "div", "p", "_", "cd", "_", "ef"]
+        );
+        let post_order: Vec<String> = layout_root.post_order().map(label).collect();
+        assert_eq!(
+            post_order,
+            ["ab", "_", "p", "cd", "_", "p", "div", "ef", "_", "div", "html"]
+        );
+        assert_eq!(layout_root.boxes_of_kind(BoxKind::Flex).count(), 1);
+        assert_eq!(
+            layout_root.boxes_of_kind(BoxKind::AnonymousBlock).count(),
+            3
+        );
+
+        // Boxes are found by the DOM id of their element.
+        let p = layout_root.pre_order().nth(2).unwrap();
+        let p_id = p.node_id().unwrap();
+        let found = layout_root.find_node(p_id).unwrap();
+        assert!(std::ptr::eq(found, p));
+        let text = layout_root.pre_order().nth(4).unwrap();
+        assert_eq!(text.node_id(), None);
+
+        let mut visited = 0;
+        layout_root.for_each_mut(|layout_box| {
+            layout_box.dimensions.content.x += 1.0;
+            visited += 1;
+        });
+        assert_eq!(visited, 11);
+        assert_eq!(layout_root.dimensions.content.x, 1.0);
+    }
+}
@@ -0,0 +1,14 @@
+pub mod atom;
+pub mod bloom;
+pub mod color;
+pub mod cssom;
+pub mod dom;
+pub mod font;
+pub mod json;
+pub mod layout;
+pub mod parser;
+pub mod resource;
+pub mod style;
+pub mod text;
+pub mod utils;
+pub mod values;
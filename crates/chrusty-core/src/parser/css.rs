@@ -0,0 +1,1579 @@
+use crate::{
+    atom::Atom,
+    color::Color,
+    cssom::{
+        new_css_declaration, new_css_rule, AttributeMatcher, AttributeSelector, CSSDeclaration,
+        CSSImportRule, CSSItem, CSSMediaRule, CSSProperty, CSSRule, CSSSelector, CSSValue,
+        FontFace, Shorthand, SimpleSelector, Stylesheet, Unit,
+    },
+    dom::TagType,
+    parser::{ICharStreamParser, IParser, ParseError},
+    utils::unquote,
+    values::{CSSWideKeyword, LonghandValue},
+};
+
+#[derive(Debug)]
+pub struct CSSParser {
+    pos: usize,
+    input: String,
+}
+impl_CharStream!(for CSSParser);
+
+impl CSSParser {
+    /// Parses the stylesheet, reporting the first syntax error with its
+    /// location instead of panicking.
+    pub fn try_parse(&mut self) -> Result<Stylesheet, ParseError> {
+        let mut rules = vec![];
+        let mut font_faces = vec![];
+        // `@media` blocks being parsed, innermost last. They are kept on a
+        // stack so deeply nested blocks can't overflow the call stack.
+        let mut open_media: Vec<CSSMediaRule> = vec![];
+        self.consume_white_space();
+        loop {
+            if self.eof() {
+                if !open_media.is_empty() {
+                    self.expect_char('}')?;
+                }
+                break;
+            }
+            let item: CSSItem = if self.next_char() == '}' && !open_media.is_empty() {
+                self.consume_char().unwrap();
+                open_media.pop().unwrap().into()
+            } else if self.starts_with("@media") {
+                let media = self.parse_media_rule_start()?;
+                open_media.push(media);
+                self.consume_white_space();
+                continue;
+            } else if self.starts_with("@import") {
+                if !open_media.is_empty() {
+                    return Err(self.error("@import is not allowed inside @media"));
+                }
+                self.parse_import_rule()?.into()
+            } else if self.starts_with("@font-face") {
+                let font_face = self.parse_font_face()?;
+                font_faces.push(font_face);
+                self.consume_white_space();
+                continue;
+            } else {
+                self.parse_rule()?.into()
+            };
+            match open_media.last_mut() {
+                Some(media) => media.rules.push(item),
+                None => rules.push(item),
+            }
+            self.consume_white_space();
+        }
+        let mut stylesheet = Stylesheet::new(rules);
+        stylesheet.font_faces = font_faces;
+        Ok(stylesheet)
+    }
+
+    /// Parses a single style rule, e.g. for `Stylesheet::insert_rule`.
+    pub fn try_parse_rule(&mut self) -> Result<CSSRule, ParseError> {
+        self.consume_white_space();
+        let rule = self.parse_rule()?;
+        self.consume_white_space();
+        match self.eof() {
+            true => Ok(rule),
+            false => Err(self.error("Expected a single rule")),
+        }
+    }
+
+    /// Parses a list of declarations without braces, e.g. `color: red;` for
+    /// `DeclarationBlock::set_property`.
+    pub fn try_parse_declarations(&mut self) -> Result<Vec<CSSDeclaration>, ParseError> {
+        let declarations = self.parse_declarations()?;
+        match self.eof() {
+            true => Ok(declarations),
+            false => Err(self.error(format!("Unexpected '{}'", self.next_char()))),
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError::new(message, &self.input, self.pos)
+    }
+
+    fn expect_char(&mut self, expected: char) -> Result<(), ParseError> {
+        if self.eof() {
+            return Err(self.error(format!(
+                "Expected '{}' but reached the end of input",
+                expected
+            )));
+        }
+        if self.next_char() != expected {
+            return Err(self.error(format!(
+                "Expected '{}' but found '{}'",
+                expected,
+                self.next_char()
+            )));
+        }
+        self.consume_char().unwrap();
+        Ok(())
+    }
+
+    fn parse_identifier(&mut self) -> String {
+        self.consume_while(|chr| {
+            chr != '.'
+                && chr != '#'
+                && chr != '{'
+                && chr != '}'
+                && chr != ':'
+                && chr != ';'
+                && chr != ','
+                && chr != '['
+                && !char::is_whitespace(chr)
+        })
+    }
+
+    fn parse_rule(&mut self) -> Result<CSSRule, ParseError> {
+        let selectors = self.parse_selectors()?;
+        self.expect_char('{')?;
+        let declarations = self.parse_declarations()?;
+        self.consume_white_space();
+        self.expect_char('}')?;
+        Ok(new_css_rule(selectors, declarations))
+    }
+
+    /// Parses `@media <queries> {`, leaving the rules of the block to the
+    /// caller.
+    fn parse_media_rule_start(&mut self) -> Result<CSSMediaRule, ParseError> {
+        self.consume_while(|c| c == '@' || c.is_alphabetic());
+        let media = self.consume_while(|c| c != '{' && c != ';' && c != '}');
+        self.expect_char('{')?;
+        Ok(CSSMediaRule {
+            media: parse_media_list(&media),
+            rules: vec![],
+        })
+    }
+
+    /// Parses `@import url(...) <queries>;` or `@import "..." <queries>;`.
+    fn parse_import_rule(&mut self) -> Result<CSSImportRule, ParseError> {
+        self.consume_while(|c| c == '@' || c.is_alphabetic());
+        self.consume_white_space();
+        let start = self.pos;
+        let prelude = self.consume_while(|c| c != ';' && c != '{' && c != '}');
+        let tokens = split_tokens(&prelude);
+        let url = match tokens.first() {
+            Some(token) if token.starts_with("url(") => match CSSParser::new(token).parse_value() {
+                Ok(CSSValue::Url(url)) => Some(url),
+                _ => None,
+            },
+            Some(token) if token.starts_with(['"', '\'']) => Some(unquote(token).to_string()),
+            _ => None,
+        };
+        let Some(url) = url else {
+            return Err(ParseError::new(
+                "Expected a URL or a string after @import",
+                &self.input,
+                start,
+            ));
+        };
+        self.expect_char(';')?;
+        Ok(CSSImportRule {
+            url,
+            media: parse_media_list(&tokens[1..].join(" ")),
+        })
+    }
+
+    /// Parses an `@font-face` block, keeping `font-family` and the `url()`
+    /// sources of `src`. Other descriptors and `local()` sources are skipped.
+    fn parse_font_face(&mut self) -> Result<FontFace, ParseError> {
+        self.consume_while(|c| c != '{');
+        self.expect_char('{')?;
+        let mut font_face = FontFace {
+            family: String::new(),
+            sources: vec![],
+        };
+        self.consume_white_space();
+        while !self.eof() && self.next_char() != '}' {
+            let descriptor = self.parse_identifier();
+            self.consume_white_space();
+            self.expect_char(':')?;
+            let value = self.consume_while(|c| c != ';' && c != '}');
+            match descriptor.as_str() {
+                "font-family" => font_face.family = unquote(value.trim()).to_string(),
+                "src" => {
+                    font_face.sources = split_top_level(&value, |c| c == ',')
+                        .iter()
+                        .filter_map(|source| match CSSParser::new(source.trim()).parse_value() {
+                            Ok(CSSValue::Url(url)) => Some(url),
+                            _ => None,
+                        })
+                        .collect()
+                }
+                _ => {}
+            }
+            if !self.eof() && self.next_char() == ';' {
+                self.consume_char().unwrap();
+            }
+            self.consume_white_space();
+        }
+        self.expect_char('}')?;
+        Ok(font_face)
+    }
+
+    fn parse_tag(&mut self) -> Result<Option<TagType>, ParseError> {
+        if matches!(self.next_char(), '.' | '#' | '[' | ':') {
+            return Ok(None);
+        }
+
+        let start = self.pos;
+        let tag_name = self.consume_while(|c| {
+            !matches!(c, '.' | '#' | '{' | '[' | ':' | ',') && !char::is_whitespace(c)
+        });
+        Ok(Some(match tag_name.as_ref() {
+            "div" => TagType::Div,
+            "p" => TagType::P,
+            "img" => TagType::Img,
+            "html" => TagType::Html,
+            "style" => TagType::Style,
+            tag => {
+                return Err(ParseError::new(
+                    format!("The following tag type is not supported: '{}'", tag),
+                    &self.input,
+                    start,
+                ))
+            }
+        }))
+    }
+
+    /// Parses `[name]` or `[name op value]`, with the value quoted or bare.
+    fn parse_attribute_selector(&mut self) -> Result<AttributeSelector, ParseError> {
+        self.expect_char('[')?;
+        self.consume_white_space();
+        let name = Atom::from(self.consume_while(|c| {
+            !matches!(c, ']' | '=' | '~' | '|' | '^' | '$' | '*') && !char::is_whitespace(c)
+        }));
+        self.consume_white_space();
+        let operator_start = self.pos;
+        let operator = self.consume_while(|c| c != '=' && c != ']');
+        if !self.eof() && self.next_char() == ']' {
+            let _ = self.consume_char();
+            return Ok(AttributeSelector {
+                name,
+                matcher: AttributeMatcher::Exists,
+            });
+        }
+        self.expect_char('=')?;
+        self.consume_white_space();
+        let value = match self.eof() {
+            false if matches!(self.next_char(), '"' | '\'') => {
+                let quote = self.consume_char().unwrap();
+                let value = self.consume_while(|c| c != quote);
+                self.expect_char(quote)?;
+                value
+            }
+            _ => self.consume_while(|c| c != ']' && !char::is_whitespace(c)),
+        };
+        self.consume_white_space();
+        self.expect_char(']')?;
+        let matcher = match operator.trim() {
+            "" => AttributeMatcher::Equals(value),
+            "~" => AttributeMatcher::Includes(value),
+            "|" => AttributeMatcher::DashMatch(value),
+            "^" => AttributeMatcher::Prefix(value),
+            "$" => AttributeMatcher::Suffix(value),
+            "*" => AttributeMatcher::Substring(value),
+            operator => {
+                return Err(ParseError::new(
+                    format!("Unsupported attribute selector operator: '{}='", operator),
+                    &self.input,
+                    operator_start,
+                ))
+            }
+        };
+        Ok(AttributeSelector { name, matcher })
+    }
+
+    /// Parses a pseudo-class or pseudo-element name, keeping the arguments of
+    /// functional pseudo-classes such as `nth-child(2)`, without the
+    /// whitespace around them.
+    fn parse_pseudo_name(&mut self) -> String {
+        let name = self.consume_while(|c| c.is_alphanumeric() || c == '-' || c == '_');
+        if self.eof() || self.next_char() != '(' {
+            return name;
+        }
+        let _ = self.consume_char();
+        let argument = self.consume_while(|c| c != ')');
+        let _ = self.consume_char();
+        format!("{}({})", name, argument.trim())
+    }
+
+    fn parse_selectors(&mut self) -> Result<Vec<CSSSelector>, ParseError> {
+        let mut selectors: Vec<CSSSelector> = vec![];
+        self.consume_white_space();
+        while !self.eof() && self.next_char() != '{' {
+            // Compound selectors separated by whitespace are descendant
+            // combinators.
+            let mut compounds = vec![self.parse_compound_selector()?];
+            self.consume_white_space();
+            while !self.eof() && !matches!(self.next_char(), ',' | '{') {
+                compounds.push(self.parse_compound_selector()?);
+                self.consume_white_space();
+            }
+            if !self.eof() && self.next_char() == ',' {
+                let _ = self.consume_char();
+                self.consume_white_space();
+            }
+            selectors.push(match compounds.len() {
+                1 => CSSSelector::SimpleSelector(compounds.pop().unwrap()),
+                _ => CSSSelector::Descendant(compounds),
+            });
+        }
+
+        Ok(selectors)
+    }
+
+    /// Parses a tag followed by any ids, classes, attributes and pseudo
+    /// selectors, e.g. `p.note:first-child`.
+    fn parse_compound_selector(&mut self) -> Result<SimpleSelector, ParseError> {
+        let mut selector = SimpleSelector {
+            tag: self.parse_tag()?,
+            id: None,
+            class: vec![],
+            attributes: vec![],
+            pseudo_classes: vec![],
+            pseudo_element: None,
+        };
+        while !self.eof() {
+            match self.next_char() {
+                '#' => {
+                    let _ = self.consume_char();
+                    selector.id = Some(Atom::from(self.parse_identifier()));
+                }
+                '.' => {
+                    let _ = self.consume_char();
+                    selector.class.push(Atom::from(self.parse_identifier()))
+                }
+                '[' => {
+                    let attribute = self.parse_attribute_selector()?;
+                    selector.attributes.push(attribute)
+                }
+                ':' => {
+                    let _ = self.consume_char();
+                    if self.starts_with(":") {
+                        let _ = self.consume_char();
+                        selector.pseudo_element = Some(self.parse_pseudo_name());
+                    } else {
+                        let name = self.parse_pseudo_name();
+                        // Legacy single-colon syntax for CSS2 pseudo-elements.
+                        if matches!(
+                            name.as_str(),
+                            "before" | "after" | "first-line" | "first-letter"
+                        ) {
+                            selector.pseudo_element = Some(name);
+                        } else {
+                            selector.pseudo_classes.push(name);
+                        }
+                    }
+                }
+                _ => break,
+            }
+        }
+        Ok(selector)
+    }
+
+    pub(crate) fn parse_property(prop_name: String) -> CSSProperty {
+        match prop_name.as_ref() {
+            "background-color" => CSSProperty::BackgroundColor,
+            "background-image" => CSSProperty::BackgroundImage,
+            "background-repeat" => CSSProperty::BackgroundRepeat,
+            "background-position" => CSSProperty::BackgroundPosition,
+            "width" => CSSProperty::Width,
+            "height" => CSSProperty::Height,
+            "min-width" => CSSProperty::MinWidth,
+            "min-height" => CSSProperty::MinHeight,
+            "max-width" => CSSProperty::MaxWidth,
+            "max-height" => CSSProperty::MaxHeight,
+            "box-sizing" => CSSProperty::BoxSizing,
+            "float" => CSSProperty::Float,
+            "clear" => CSSProperty::Clear,
+            "flex-direction" => CSSProperty::FlexDirection,
+            "justify-content" => CSSProperty::JustifyContent,
+            "align-items" => CSSProperty::AlignItems,
+            "flex-grow" => CSSProperty::FlexGrow,
+            "flex-shrink" => CSSProperty::FlexShrink,
+            "flex-basis" => CSSProperty::FlexBasis,
+            "grid-template-columns" => CSSProperty::GridTemplateColumns,
+            "grid-template-rows" => CSSProperty::GridTemplateRows,
+            "color" => CSSProperty::Color,
+            "display" => CSSProperty::Display,
+            "position" => CSSProperty::Position,
+            "top" => CSSProperty::Top,
+            "right" => CSSProperty::Right,
+            "bottom" => CSSProperty::Bottom,
+            "left" => CSSProperty::Left,
+            "margin-top" => CSSProperty::MarginTop,
+            "margin-right" => CSSProperty::MarginRight,
+            "margin-bottom" => CSSProperty::MarginBottom,
+            "margin-left" => CSSProperty::MarginLeft,
+            "padding-top" => CSSProperty::PaddingTop,
+            "padding-right" => CSSProperty::PaddingRight,
+            "padding-bottom" => CSSProperty::PaddingBottom,
+            "padding-left" => CSSProperty::PaddingLeft,
+            "overflow" => CSSProperty::Overflow,
+            "visibility" => CSSProperty::Visibility,
+            "direction" => CSSProperty::Direction,
+            "vertical-align" => CSSProperty::VerticalAlign,
+            "white-space" => CSSProperty::WhiteSpace,
+            // `word-wrap` is the legacy name of `overflow-wrap`.
+            "overflow-wrap" | "word-wrap" => CSSProperty::OverflowWrap,
+            "word-break" => CSSProperty::WordBreak,
+            "z-index" => CSSProperty::ZIndex,
+            "line-height" => CSSProperty::LineHeight,
+            "opacity" => CSSProperty::Opacity,
+            "border-top-left-radius" => CSSProperty::BorderTopLeftRadius,
+            "border-top-right-radius" => CSSProperty::BorderTopRightRadius,
+            "border-bottom-right-radius" => CSSProperty::BorderBottomRightRadius,
+            "border-bottom-left-radius" => CSSProperty::BorderBottomLeftRadius,
+            "border-top-width" => CSSProperty::BorderTopWidth,
+            "border-right-width" => CSSProperty::BorderRightWidth,
+            "border-bottom-width" => CSSProperty::BorderBottomWidth,
+            "border-left-width" => CSSProperty::BorderLeftWidth,
+            "border-top-style" => CSSProperty::BorderTopStyle,
+            "border-right-style" => CSSProperty::BorderRightStyle,
+            "border-bottom-style" => CSSProperty::BorderBottomStyle,
+            "border-left-style" => CSSProperty::BorderLeftStyle,
+            "border-top-color" => CSSProperty::BorderTopColor,
+            "border-right-color" => CSSProperty::BorderRightColor,
+            "border-bottom-color" => CSSProperty::BorderBottomColor,
+            "border-left-color" => CSSProperty::BorderLeftColor,
+            "font-family" => CSSProperty::FontFamily,
+            "font-size" => CSSProperty::FontSize,
+            "font-style" => CSSProperty::FontStyle,
+            "font-weight" => CSSProperty::FontWeight,
+            name if name.starts_with("--") => CSSProperty::Custom(Atom::from(name)),
+            _ => CSSProperty::Unknown(prop_name),
+        }
+    }
+
+    fn starts_with_number(&self) -> bool {
+        let mut chars = self.input[self.pos..].chars();
+        match chars.next() {
+            Some('-') | Some('.') => chars.next().is_some_and(char::is_numeric),
+            Some(c) => char::is_numeric(c),
+            None => false,
+        }
+    }
+
+    fn parse_channel(&mut self) -> Result<u8, ParseError> {
+        self.consume_white_space();
+        let start = self.pos;
+        let channel = self
+            .consume_while(char::is_numeric)
+            .parse::<u32>()
+            .map_err(|_| ParseError::new("Expected a color channel", &self.input, start))?;
+        self.consume_white_space();
+        Ok(channel.min(255) as u8)
+    }
+
+    /// Parses the alpha of `rgba()`, as a number from 0 to 1 or a percentage.
+    fn parse_alpha(&mut self) -> Result<u8, ParseError> {
+        self.consume_white_space();
+        let alpha = self.parse_number()?;
+        let alpha = match self.starts_with("%") {
+            true => {
+                self.consume_char().unwrap();
+                alpha / 100.0
+            }
+            false => alpha,
+        };
+        self.consume_white_space();
+        Ok((alpha.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+
+    /// Parses `rgb(r, g, b)` or `rgba(r, g, b, a)`. Either name takes an
+    /// optional alpha.
+    fn parse_rgb(&mut self) -> Result<Color, ParseError> {
+        self.consume_while(|c| c != '(');
+        self.expect_char('(')?;
+        let r = self.parse_channel()?;
+        self.expect_char(',')?;
+        let g = self.parse_channel()?;
+        self.expect_char(',')?;
+        let b = self.parse_channel()?;
+        let a = match self.starts_with(",") {
+            true => {
+                self.consume_char().unwrap();
+                self.parse_alpha()?
+            }
+            false => 255,
+        };
+        self.expect_char(')')?;
+        Ok(Color { r, g, b, a })
+    }
+
+    fn parse_number(&mut self) -> Result<f32, ParseError> {
+        let start = self.pos;
+        self.consume_while(|c| char::is_numeric(c) || c == '.' || c == '-')
+            .parse::<f32>()
+            .map_err(|_| ParseError::new("Invalid number", &self.input, start))
+    }
+
+    pub(crate) fn parse_value(&mut self) -> Result<CSSValue, ParseError> {
+        self.consume_white_space();
+        Ok(if self.starts_with("rgb(") || self.starts_with("rgba(") {
+            CSSValue::Color(self.parse_rgb()?)
+        } else if self.starts_with("url(") {
+            self.parse_url()?
+        } else if self.starts_with_number() {
+            let value = self.parse_number()?;
+            let unit = self.consume_while(|c| char::is_alphabetic(c) || c == '%');
+            CSSValue::Dimension(value, Unit::from_suffix(&unit))
+        } else {
+            let value = self.consume_value_text();
+            match value.trim_end() {
+                "auto" => CSSValue::Auto,
+                value => match Color::from_hex(value) {
+                    Some(color) => CSSValue::Color(color),
+                    None => CSSValue::Keyword(value.to_string()),
+                },
+            }
+        })
+    }
+
+    /// Consumes a declaration value up to the `;`, `!` or `}` that ends it.
+    /// Those characters are part of the value inside quotes or parentheses.
+    fn consume_value_text(&mut self) -> String {
+        let mut value = String::new();
+        let mut depth = 0;
+        let mut quote = None;
+        while !self.eof() {
+            match self.next_char() {
+                c @ ('"' | '\'') if quote.is_none() => quote = Some(c),
+                c if quote == Some(c) => quote = None,
+                '(' if quote.is_none() => depth += 1,
+                ')' if quote.is_none() && depth > 0 => depth -= 1,
+                ';' | '!' | '}' if quote.is_none() && depth == 0 => break,
+                _ => {}
+            }
+            value.push(self.consume_char().unwrap());
+        }
+        value
+    }
+
+    /// Parses the `!important` flag ending a declaration, which may have
+    /// whitespace after the `!`. Anything else before the `;` invalidates it.
+    fn parse_important(&mut self) -> bool {
+        // Skip what the value parser left over, e.g. `10px` in `1px 10px`.
+        self.consume_value_text();
+        if self.eof() || self.next_char() != '!' {
+            return false;
+        }
+        let _ = self.consume_char();
+        self.consume_white_space();
+        let keyword = self.parse_identifier();
+        let rest = self.consume_while(|c| c != ';');
+        keyword.eq_ignore_ascii_case("important") && rest.trim().is_empty()
+    }
+
+    /// Parses `url(...)`, with the URL either quoted or bare.
+    fn parse_url(&mut self) -> Result<CSSValue, ParseError> {
+        self.consume_while(|c| c != '(');
+        self.expect_char('(')?;
+        self.consume_white_space();
+        let url = match self.eof() {
+            false if matches!(self.next_char(), '"' | '\'') => {
+                let quote = self.consume_char().unwrap();
+                let url = self.consume_while(|c| c != quote);
+                self.expect_char(quote)?;
+                url
+            }
+            _ => self.consume_while(|c| c != ')').trim_end().to_string(),
+        };
+        self.consume_white_space();
+        self.expect_char(')')?;
+        Ok(CSSValue::Url(url))
+    }
+
+    /// Parses a value where a number without a unit is a plain number rather
+    /// than a length in px.
+    fn parse_number_value(&mut self) -> Result<CSSValue, ParseError> {
+        self.consume_white_space();
+        if !self.starts_with_number() {
+            return self.parse_value();
+        }
+        let value = self.parse_number()?;
+        let unit = self.consume_while(|c| char::is_alphabetic(c) || c == '%');
+        Ok(match unit.as_str() {
+            "" => CSSValue::Number(value),
+            unit => CSSValue::Dimension(value, Unit::from_suffix(unit)),
+        })
+    }
+
+    fn parse_integer_value(&mut self) -> Result<CSSValue, ParseError> {
+        self.consume_white_space();
+        if !self.starts_with_number() {
+            return self.parse_value();
+        }
+        let value = self.consume_while(|c| char::is_numeric(c) || c == '-');
+        Ok(match value.parse::<i32>() {
+            Ok(value) => CSSValue::Integer(value),
+            Err(_) => CSSValue::Raw(value),
+        })
+    }
+
+    /// Expands `shorthand` into its longhands. A value that doesn't fit the
+    /// shorthand is kept raw under the shorthand's name, like an unknown
+    /// declaration, and a CSS-wide keyword applies to every longhand.
+    fn parse_shorthand(
+        &mut self,
+        name: &str,
+        shorthand: Shorthand,
+    ) -> Vec<(CSSProperty, CSSValue)> {
+        self.consume_white_space();
+        let raw = self.consume_value_text();
+        if CSSWideKeyword::from_keyword(&raw).is_some() {
+            let keyword = CSSValue::Keyword(raw.trim().to_string());
+            return shorthand
+                .longhands()
+                .into_iter()
+                .map(|longhand| (longhand, keyword.clone()))
+                .collect();
+        }
+        let is_font = shorthand == Shorthand::Font;
+        // In `font: bold 12px/1.5 serif`, the `/` is a token of its own, and
+        // unitless numbers are a weight or a line height rather than px, as
+        // are the factors of `flex`.
+        let has_numbers = is_font || shorthand == Shorthand::Flex;
+        let text = match is_font {
+            true => raw.replace('/', " / "),
+            false => raw.clone(),
+        };
+        let values: Option<Vec<CSSValue>> = split_tokens(&text)
+            .iter()
+            .map(|token| match token.parse::<f32>() {
+                Ok(number) if has_numbers => Some(CSSValue::Number(number)),
+                _ => CSSParser::new(token).parse_value().ok(),
+            })
+            .collect();
+        match values.and_then(|values| shorthand.expand(&values)) {
+            Some(entries) => entries,
+            None => vec![(
+                CSSProperty::Unknown(name.to_string()),
+                CSSValue::Raw(raw.trim_end().to_string()),
+            )],
+        }
+    }
+
+    /// Parses a value of several tokens, kept as written, such as a comma
+    /// separated `font-family` list or a `background-position`.
+    fn parse_keyword_list(&mut self) -> CSSValue {
+        self.consume_white_space();
+        let tokens = self.consume_value_text();
+        CSSValue::Keyword(tokens.trim_end().to_string())
+    }
+
+    /// Whether the value ahead references custom properties with `var()`.
+    fn references_variables(&mut self) -> bool {
+        let start = self.pos;
+        let value = self.consume_value_text();
+        self.pos = start;
+        value.to_ascii_lowercase().contains("var(")
+    }
+
+    fn parse_unresolved_value(&mut self, shorthand: Option<Shorthand>) -> CSSValue {
+        self.consume_white_space();
+        let text = self.consume_value_text().trim_end().to_string();
+        CSSValue::Unresolved { text, shorthand }
+    }
+
+    fn parse_raw_value(&mut self) -> CSSValue {
+        self.consume_white_space();
+        let raw = self.consume_value_text();
+        CSSValue::Raw(raw.trim_end().to_string())
+    }
+
+    fn parse_declarations(&mut self) -> Result<Vec<CSSDeclaration>, ParseError> {
+        let mut declarations: Vec<CSSDeclaration> = vec![];
+        self.consume_white_space();
+        while !self.eof() && self.next_char() != '}' {
+            let prop_name = self.parse_identifier();
+            self.consume_white_space();
+            self.expect_char(':')?;
+            let value_start = self.pos;
+            let entries = match Shorthand::from_name(&prop_name) {
+                Some(shorthand) if self.references_variables() => {
+                    let value = self.parse_unresolved_value(Some(shorthand));
+                    shorthand
+                        .longhands()
+                        .into_iter()
+                        .map(|longhand| (longhand, value.clone()))
+                        .collect()
+                }
+                Some(shorthand) => self.parse_shorthand(&prop_name, shorthand),
+                None => {
+                    let property = CSSParser::parse_property(prop_name.clone());
+                    let value = match property {
+                        CSSProperty::Custom(_) | CSSProperty::Unknown(_) => self.parse_raw_value(),
+                        _ if self.references_variables() => self.parse_unresolved_value(None),
+                        CSSProperty::FontFamily
+                        | CSSProperty::BackgroundPosition
+                        | CSSProperty::GridTemplateColumns
+                        | CSSProperty::GridTemplateRows => self.parse_keyword_list(),
+                        CSSProperty::ZIndex => self.parse_integer_value()?,
+                        CSSProperty::LineHeight
+                        | CSSProperty::Opacity
+                        | CSSProperty::FontWeight
+                        | CSSProperty::FlexGrow
+                        | CSSProperty::FlexShrink => self.parse_number_value()?,
+                        _ => self.parse_value()?,
+                    };
+                    vec![(property, value)]
+                }
+            };
+            // Values the engine doesn't support are kept as written under an
+            // unknown property, so they are serialized back but never
+            // applied. Values with `var()` can only be checked once
+            // substituted.
+            let is_valid = entries.iter().all(|(property, value)| {
+                matches!(property, CSSProperty::Custom(_) | CSSProperty::Unknown(_))
+                    || matches!(value, CSSValue::Unresolved { .. })
+                    || property.parse_longhand(value).is_some()
+            });
+            let entries = match is_valid {
+                true => entries,
+                false => {
+                    self.pos = value_start;
+                    vec![(CSSProperty::Unknown(prop_name), self.parse_raw_value())]
+                }
+            };
+            let is_important = self.parse_important();
+            self.consume_white_space();
+            self.expect_char(';')?;
+            for (property, value) in entries {
+                // Color names are only known to be colors from their property.
+                let value = match (property.parse_longhand(&value), value) {
+                    (Some(LonghandValue::Color(color)), CSSValue::Keyword(_)) => {
+                        CSSValue::Color(color)
+                    }
+                    (_, value) => value,
+                };
+                declarations.push(new_css_declaration(property, value, is_important));
+            }
+            self.consume_white_space();
+        }
+        Ok(declarations)
+    }
+}
+
+/// Splits a comma separated list of media queries, normalizing the spaces
+/// within each query.
+fn parse_media_list(raw: &str) -> Vec<String> {
+    split_top_level(raw, |c| c == ',')
+        .iter()
+        .map(|query| query.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .filter(|query| !query.is_empty())
+        .collect()
+}
+
+/// Splits a declaration value on whitespace, keeping function arguments such
+/// as `rgb(1, 2, 3)` and quoted strings together.
+pub(crate) fn split_tokens(raw: &str) -> Vec<String> {
+    split_top_level(raw, char::is_whitespace)
+}
+
+/// Splits `raw` on the characters matching `is_separator` that are neither
+/// inside parentheses nor quotes, dropping empty parts.
+pub(crate) fn split_top_level(raw: &str, is_separator: fn(char) -> bool) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut token = String::new();
+    let mut depth = 0;
+    let mut quote = None;
+    for c in raw.chars() {
+        match c {
+            '"' | '\'' if quote.is_none() => quote = Some(c),
+            c if quote == Some(c) => quote = None,
+            '(' if quote.is_none() => depth += 1,
+            ')' if quote.is_none() => depth -= 1,
+            c if is_separator(c) && depth == 0 && quote.is_none() => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        token.push(c);
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    tokens
+}
+
+impl IParser for CSSParser {
+    type Output = Stylesheet;
+
+    fn new(input: &str) -> CSSParser {
+        CSSParser {
+            pos: 0,
+            input: String::from(input),
+        }
+    }
+    /// Panics with the formatted error on invalid syntax, see `try_parse`.
+    fn parse(&mut self) -> Self::Output {
+        self.try_parse().unwrap_or_else(|error| panic!("{}", error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        atom::Atom,
+        color::Color,
+        cssom::{
+            CSSItem, CSSProperty, CSSRule, CSSValue, FontFace, MediaContext, SerializeMode,
+            Shorthand, Specificity, Stylesheet, Unit,
+        },
+        parser::{CSSParser, IParser, ParseError},
+    };
+
+    /// Checks that the sheet serializes to CSS that parses back to the same
+    /// rules, in both modes.
+    fn assert_round_trips(stylesheet: &Stylesheet) {
+        for mode in [SerializeMode::Minified, SerializeMode::Pretty] {
+            let css = stylesheet.serialize(mode);
+            assert_eq!(CSSParser::new(&css).parse().serialize(mode), css);
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let input = "
+            div#id.hello {
+                height: 100%;
+                background: purple;
+                color: #ffffff !important;
+            }
+
+            div.my-div,
+            div.my-div-2 {
+                width: 100px;
+                height: 100%;
+                background: blue;
+                color: #ffffff;
+            }
+
+            html {
+                background: green;
+            }
+        ";
+        let parsed = CSSParser::new(input).parse();
+        assert_eq!(
+            parsed.serialize(SerializeMode::Minified),
+            concat!(
+                "div#id.hello{height:100%;background-color:rgb(128,0,128);",
+                "background-image:none;background-repeat:repeat;",
+                "background-position:0% 0%;color:rgb(255,255,255)!important;}",
+                "div.my-div,div.my-div-2{width:100px;height:100%;",
+                "background-color:rgb(0,0,255);background-image:none;",
+                "background-repeat:repeat;background-position:0% 0%;",
+                "color:rgb(255,255,255);}",
+                "html{background-color:rgb(0,128,0);background-image:none;",
+                "background-repeat:repeat;background-position:0% 0%;}",
+            )
+        );
+        assert!(parsed.serialize(SerializeMode::Pretty).starts_with(
+            "div#id.hello {\n\theight: 100%;\n\tbackground-color: rgb(128, 0, 128);\n"
+        ));
+        assert_round_trips(&parsed);
+    }
+
+    #[test]
+    fn expands_background_shorthand() {
+        let input = "
+            div {
+                background: #fff url(\"my image.png\") no-repeat center !important;
+            }
+
+            p {
+                background: rgb(10, 20, 30) 10px top;
+            }
+
+            html {
+                background: red blue;
+            }
+        ";
+        let parsed = CSSParser::new(input).parse();
+        let values = |rule: usize| {
+            parsed.style_rules()[rule]
+                .declarations
+                .iter()
+                .map(|declaration| (&declaration.property, &declaration.value))
+                .collect::<Vec<_>>()
+        };
+        let keyword = |value: &str| CSSValue::Keyword(value.to_string());
+        assert_eq!(
+            values(0),
+            [
+                (
+                    &CSSProperty::BackgroundColor,
+                    &CSSValue::Color(Color::from_hex("#fff").unwrap())
+                ),
+                (
+                    &CSSProperty::BackgroundImage,
+                    &CSSValue::Url("my image.png".to_string())
+                ),
+                (&CSSProperty::BackgroundRepeat, &keyword("no-repeat")),
+                (&CSSProperty::BackgroundPosition, &keyword("center")),
+            ]
+        );
+        assert!(parsed.style_rules()[0]
+            .declarations
+            .iter()
+            .all(|declaration| declaration.is_important));
+        assert_eq!(
+            values(1),
+            [
+                (
+                    &CSSProperty::BackgroundColor,
+                    &CSSValue::Color(Color {
+                        r: 10,
+                        g: 20,
+                        b: 30,
+                        a: 255
+                    })
+                ),
+                (&CSSProperty::BackgroundImage, &keyword("none")),
+                (&CSSProperty::BackgroundRepeat, &keyword("repeat")),
+                (&CSSProperty::BackgroundPosition, &keyword("10px top")),
+            ]
+        );
+        assert_eq!(
+            values(2),
+            [(
+                &CSSProperty::Unknown("background".to_string()),
+                &CSSValue::Raw("red blue".to_string())
+            )]
+        );
+    }
+
+    #[test]
+    fn parses_css_wide_keywords() {
+        let input =
+            "div { z-index: inherit; line-height: initial; color: Unset; margin: inherit; }";
+        let parsed = CSSParser::new(input).parse();
+        let declarations: Vec<String> = parsed.style_rules()[0]
+            .declarations
+            .iter()
+            .map(|declaration| declaration.to_string())
+            .collect();
+        assert_eq!(
+            declarations,
+            [
+                "z-index: inherit;",
+                "line-height: initial;",
+                "color: Unset;",
+                "margin-top: inherit;",
+                "margin-right: inherit;",
+                "margin-bottom: inherit;",
+                "margin-left: inherit;",
+            ]
+        );
+    }
+
+    #[test]
+    fn expands_shorthands_into_longhands() {
+        let input = "
+            div {
+                padding: 1px 2px;
+                border: 2px solid red;
+                border-color: blue green;
+                border-left: dashed;
+                font: italic bold 12px/1.5 \"Open Sans\", serif;
+                font: 12px;
+                flex: 2;
+                flex: none;
+                flex: 10px 2 3;
+            }
+        ";
+        let parsed = CSSParser::new(input).parse();
+        let declarations: Vec<String> = parsed.style_rules()[0]
+            .declarations
+            .iter()
+            .map(|declaration| declaration.to_string())
+            .collect();
+        assert_eq!(
+            declarations,
+            [
+                "padding-top: 1px;",
+                "padding-right: 2px;",
+                "padding-bottom: 1px;",
+                "padding-left: 2px;",
+                "border-top-width: 2px;",
+                "border-top-style: solid;",
+                "border-top-color: rgb(255, 0, 0);",
+                "border-right-width: 2px;",
+                "border-right-style: solid;",
+                "border-right-color: rgb(255, 0, 0);",
+                "border-bottom-width: 2px;",
+                "border-bottom-style: solid;",
+                "border-bottom-color: rgb(255, 0, 0);",
+                "border-left-width: 2px;",
+                "border-left-style: solid;",
+                "border-left-color: rgb(255, 0, 0);",
+                "border-top-color: rgb(0, 0, 255);",
+                "border-right-color: rgb(0, 128, 0);",
+                "border-bottom-color: rgb(0, 0, 255);",
+                "border-left-color: rgb(0, 128, 0);",
+                "border-left-width: medium;",
+                "border-left-style: dashed;",
+                "border-left-color: currentcolor;",
+                "font-style: italic;",
+                "font-weight: bold;",
+                "font-size: 12px;",
+                "line-height: 1.5;",
+                "font-family: \"Open Sans\", serif;",
+                "font: 12px;",
+                "flex-grow: 2;",
+                "flex-shrink: 1;",
+                "flex-basis: 0%;",
+                "flex-grow: 0;",
+                "flex-shrink: 0;",
+                "flex-basis: auto;",
+                "flex-grow: 2;",
+                "flex-shrink: 3;",
+                "flex-basis: 10px;",
+            ]
+        );
+        // A font without a family doesn't fit the shorthand.
+        assert_eq!(
+            parsed.style_rules()[0].declarations[28].property,
+            CSSProperty::Unknown("font".to_string())
+        );
+    }
+
+    #[test]
+    fn preserves_unknown_declarations() {
+        let input = "
+            div {
+                text-shadow: 1px 1px red;
+                width: 100px;
+                font-family: serif !important;
+            }
+        ";
+        let parsed = CSSParser::new(input).parse();
+        let declarations = &parsed.style_rules()[0].declarations;
+        assert_eq!(
+            declarations[0].property,
+            CSSProperty::Unknown("text-shadow".to_string())
+        );
+        let CSSValue::Raw(raw) = &declarations[0].value else {
+            panic!("Unknown declaration value was not kept raw")
+        };
+        assert_eq!(raw, "1px 1px red");
+        assert!(declarations[2].is_important);
+        assert_eq!(
+            parsed.serialize(SerializeMode::Minified),
+            "div{text-shadow:1px 1px red;width:100px;font-family:serif!important;}"
+        );
+        assert_round_trips(&parsed);
+    }
+
+    #[test]
+    fn parses_custom_properties_and_var_references() {
+        let input = "
+            div {
+                --Main-Color: rgb(1, 2, 3) ;
+                color: var(--Main-Color);
+                margin: var(--m) 0;
+                width: 10px var(--w);
+            }
+        ";
+        let parsed = CSSParser::new(input).parse();
+        let declarations = &parsed.style_rules()[0].declarations;
+        assert_eq!(
+            declarations[0].property,
+            CSSProperty::Custom(Atom::from("--Main-Color"))
+        );
+        assert_eq!(
+            declarations[0].value,
+            CSSValue::Raw("rgb(1, 2, 3)".to_string())
+        );
+        assert_eq!(
+            declarations[1].value,
+            CSSValue::Unresolved {
+                text: "var(--Main-Color)".to_string(),
+                shorthand: None
+            }
+        );
+        // Every longhand of the shorthand waits for the substitution, and
+        // values with `var()` aren't validated.
+        assert_eq!(declarations.len(), 7);
+        assert!(declarations[2..6]
+            .iter()
+            .all(|declaration| declaration.value
+                == CSSValue::Unresolved {
+                    text: "var(--m) 0".to_string(),
+                    shorthand: Some(Shorthand::Margin)
+                }));
+        assert_eq!(declarations[6].property, CSSProperty::Width);
+    }
+
+    #[test]
+    fn parses_integers() {
+        let input = "
+            div {
+                z-index: -3;
+            }
+
+            p {
+                z-index: auto;
+            }
+        ";
+        let parsed = CSSParser::new(input).parse();
+        assert_eq!(
+            parsed.style_rules()[0].declarations[0].value,
+            CSSValue::Integer(-3)
+        );
+        assert_eq!(
+            parsed.style_rules()[1].declarations[0].value,
+            CSSValue::Auto
+        );
+        assert_eq!(
+            parsed.serialize(SerializeMode::Minified),
+            "div{z-index:-3;}p{z-index:auto;}"
+        );
+        assert_round_trips(&parsed);
+    }
+
+    #[test]
+    fn parses_line_height() {
+        let input = "
+            div {
+                line-height: 1.5;
+                line-height: 20px;
+                line-height: 120%;
+                line-height: normal;
+            }
+        ";
+        let parsed = CSSParser::new(input).parse();
+        let values: Vec<&CSSValue> = parsed.style_rules()[0]
+            .declarations
+            .iter()
+            .map(|declaration| &declaration.value)
+            .collect();
+        assert_eq!(
+            values,
+            [
+                &CSSValue::Number(1.5),
+                &CSSValue::Dimension(20.0, Unit::Px),
+                &CSSValue::Dimension(120.0, Unit::Percent),
+                &CSSValue::Keyword("normal".to_string()),
+            ]
+        );
+        assert_eq!(
+            parsed.serialize(SerializeMode::Minified),
+            "div{line-height:1.5;line-height:20px;line-height:120%;line-height:normal;}"
+        );
+        assert_round_trips(&parsed);
+    }
+
+    #[test]
+    fn parses_font_faces() {
+        let input = "
+            @font-face {
+                font-family: 'Open Sans';
+                font-weight: 400;
+                src: local(\"Open Sans\"), url(\"fonts/open sans.ttf\") format(\"truetype\"),
+                    url(fallback.ttf);
+            }
+
+            p {
+                font-family: \"Open Sans\", sans-serif;
+            }
+        ";
+        let parsed = CSSParser::new(input).parse();
+        assert_eq!(
+            parsed.font_faces,
+            [FontFace {
+                family: "Open Sans".to_string(),
+                sources: vec![
+                    "fonts/open sans.ttf".to_string(),
+                    "fallback.ttf".to_string()
+                ],
+            }]
+        );
+        assert_eq!(parsed.rules.len(), 1);
+        assert_eq!(
+            parsed.style_rules()[0].declarations[0].property,
+            CSSProperty::FontFamily
+        );
+        assert!(parsed
+            .to_string()
+            .starts_with("@font-face {\n\tfont-family: \"Open Sans\";\n\tsrc: url(\"fonts/open sans.ttf\"), url(\"fallback.ttf\");\n}\n"));
+        assert_eq!(
+            parsed.serialize(SerializeMode::Minified),
+            concat!(
+                "@font-face{font-family:\"Open Sans\";",
+                "src:url(\"fonts/open sans.ttf\"),url(\"fallback.ttf\");}",
+                "p{font-family:\"Open Sans\",sans-serif;}",
+            )
+        );
+        assert_round_trips(&parsed);
+    }
+
+    #[test]
+    fn parses_attribute_and_pseudo_selectors() {
+        let input = "div[lang|=en][title=\"a b\"].note:hover::before, [data-x], p:nth-child(2) {}";
+        let parsed = CSSParser::new(input).parse();
+        let selectors = &parsed.style_rules()[0].selectors;
+        assert_eq!(
+            selectors[0].to_string(),
+            "div.note[lang|=\"en\"][title=\"a b\"]:hover::before"
+        );
+        assert_eq!(selectors[0].specificity(), Specificity::new(0, 4, 2));
+        assert_eq!(selectors[1].to_string(), "[data-x]");
+        assert_eq!(selectors[1].specificity(), Specificity::new(0, 1, 0));
+        assert_eq!(selectors[2].to_string(), "p:nth-child(2)");
+        assert_eq!(selectors[2].specificity(), Specificity::new(0, 1, 1));
+
+        // Whitespace may surround the argument, and the sign of `b`.
+        let input = "p:nth-child( 2n + 1 ), p:nth-child(-n+ 3).note {}";
+        let parsed = CSSParser::new(input).parse();
+        let selectors = &parsed.style_rules()[0].selectors;
+        assert_eq!(selectors.len(), 2);
+        assert_eq!(selectors[0].to_string(), "p:nth-child(2n + 1)");
+        assert_eq!(selectors[1].to_string(), "p.note:nth-child(-n+ 3)");
+    }
+
+    #[test]
+    fn parses_descendant_combinators() {
+        let input = "div  .nav\n\tp.item:hover , p, html p{}";
+        let parsed = CSSParser::new(input).parse();
+        let selectors = &parsed.style_rules()[0].selectors;
+        assert_eq!(selectors.len(), 3);
+        assert_eq!(selectors[0].to_string(), "div .nav p.item:hover");
+        assert_eq!(selectors[0].compounds().len(), 3);
+        assert_eq!(selectors[0].specificity(), Specificity::new(0, 3, 2));
+        assert_eq!(selectors[1].to_string(), "p");
+        assert_eq!(selectors[2].to_string(), "html p");
+        assert_round_trips(&parsed);
+    }
+
+    #[test]
+    fn parses_important_flags() {
+        let input = "
+            div {
+                color: red !important;
+                background-color: blue ! important ;
+                width: 10px!IMPORTANT;
+                background-image: url(\"a!b.png\");
+                height: 5px !importantish;
+                top: auto;
+            }
+        ";
+        let parsed = CSSParser::new(input).parse();
+        let declarations = &parsed.style_rules()[0].declarations;
+        let color = |value: &str| CSSValue::Color(Color::from_keyword(value).unwrap());
+        assert_eq!(declarations[0].value, color("red"));
+        assert!(declarations[0].is_important);
+        assert_eq!(declarations[1].value, color("blue"));
+        assert!(declarations[1].is_important);
+        assert_eq!(declarations[2].value, CSSValue::Dimension(10.0, Unit::Px));
+        assert!(declarations[2].is_important);
+        assert_eq!(declarations[3].value, CSSValue::Url("a!b.png".to_string()));
+        assert!(!declarations[3].is_important);
+        assert!(!declarations[4].is_important);
+        assert_eq!(declarations[5].value, CSSValue::Auto);
+    }
+
+    #[test]
+    fn keeps_unsupported_declarations_raw() {
+        let input = "
+            div {
+                width: -10px;
+                display: table;
+                margin: 1px wide;
+                color: #12;
+                z-index: 2;
+                vendor-thing: 10px;
+            }
+        ";
+        let parsed = CSSParser::new(input).parse();
+        let declarations = &parsed.style_rules()[0].declarations;
+        let unknown = |name: &str, value: &str| {
+            (
+                CSSProperty::Unknown(name.to_string()),
+                CSSValue::Raw(value.to_string()),
+            )
+        };
+        let entries: Vec<(&CSSProperty, &CSSValue)> = declarations
+            .iter()
+            .map(|declaration| (&declaration.property, &declaration.value))
+            .collect();
+        let expected = [
+            unknown("width", "-10px"),
+            unknown("display", "table"),
+            unknown("margin", "1px wide"),
+            unknown("color", "#12"),
+            (CSSProperty::ZIndex, CSSValue::Integer(2)),
+            unknown("vendor-thing", "10px"),
+        ];
+        let expected: Vec<(&CSSProperty, &CSSValue)> = expected
+            .iter()
+            .map(|(property, value)| (property, value))
+            .collect();
+        assert_eq!(entries, expected);
+
+        let parsed =
+            CSSParser::new("div { width: calc(1px + 2px) !important; height: 2px; }").parse();
+        assert_eq!(
+            parsed.serialize(SerializeMode::Minified),
+            "div{width:calc(1px + 2px)!important;height:2px;}"
+        );
+        assert_round_trips(&parsed);
+    }
+
+    #[test]
+    fn parses_colors_to_rgba() {
+        let input = "
+            div {
+                color: rgba(0, 0, 255, 0.5);
+                background-color: #0000ff80;
+            }
+
+            p {
+                color: Purple;
+                background: rgb(300, 0, 0);
+            }
+        ";
+        let parsed = CSSParser::new(input).parse();
+        let color = |rule: usize, declaration: usize| {
+            parsed.style_rules()[rule].declarations[declaration]
+                .value
+                .to_string()
+        };
+        assert_eq!(color(0, 0), "rgba(0, 0, 255, 0.502)");
+        assert_eq!(color(0, 1), "rgba(0, 0, 255, 0.502)");
+        assert_eq!(color(1, 0), "rgb(128, 0, 128)");
+        assert_eq!(color(1, 1), "rgb(255, 0, 0)");
+    }
+
+    #[test]
+    fn edits_stylesheet_rules() {
+        let rule = |text: &str| CSSParser::new(text).try_parse_rule().unwrap();
+        let mut stylesheet = CSSParser::new("div { width: 1px; } p { width: 2px; }").parse();
+        let version = stylesheet.version();
+        assert_eq!(stylesheet.insert_rule(rule("html {}"), 1), Ok(1));
+        assert_eq!(stylesheet.insert_rule(rule("div.a, p {}"), 3), Ok(3));
+        assert!(stylesheet.insert_rule(rule("div {}"), 5).is_err());
+        assert_eq!(
+            stylesheet
+                .style_rules()
+                .into_iter()
+                .map(CSSRule::selector_text)
+                .collect::<Vec<_>>(),
+            ["div", "html", "p", "div.a, p"]
+        );
+        assert_eq!(
+            stylesheet
+                .style_rules()
+                .iter()
+                .map(|rule| rule.source_order)
+                .collect::<Vec<_>>(),
+            [0, 1, 2, 3]
+        );
+        assert_eq!(stylesheet.find_rule("div.a, p"), Some(3));
+        let deleted = stylesheet.delete_rule(0).unwrap();
+        assert_eq!(deleted.to_string(), "div {\n\twidth: 1px;\n}\n");
+        assert_eq!(
+            stylesheet
+                .rule(0)
+                .and_then(CSSItem::style_rule)
+                .map(|rule| rule.source_order),
+            Some(0)
+        );
+        assert!(stylesheet.delete_rule(3).is_err());
+        stylesheet.replace_rules(vec![]);
+        assert_eq!(stylesheet.iter().count(), 0);
+        assert_eq!(stylesheet.version(), version + 4);
+        assert!(CSSParser::new("div {} p {}").try_parse_rule().is_err());
+    }
+
+    #[test]
+    fn edits_declaration_blocks() {
+        let mut stylesheet =
+            CSSParser::new("div { color: red !important; width: 1px; color: blue; }").parse();
+        let version = stylesheet.version();
+        let declarations = stylesheet.declarations_mut(0).unwrap();
+        assert_eq!(declarations.get_property_value("color"), "rgb(0, 0, 255)");
+        assert_eq!(declarations.get_property_priority("color"), "");
+        assert_eq!(declarations.get_property_value("height"), "");
+
+        declarations.set_property("Color", "green", true).unwrap();
+        declarations
+            .set_property("margin", "0 auto", false)
+            .unwrap();
+        declarations.set_property("width", "2px", false).unwrap();
+        assert_eq!(declarations.get_property_priority("color"), "important");
+        assert_eq!(
+            declarations
+                .iter()
+                .map(|declaration| declaration.to_string())
+                .collect::<Vec<_>>(),
+            [
+                "color: rgb(0, 128, 0) !important;",
+                "width: 2px;",
+                "margin-top: 0px;",
+                "margin-right: auto;",
+                "margin-bottom: 0px;",
+                "margin-left: auto;",
+            ]
+        );
+
+        let error = declarations.set_property("width", "-1px", false);
+        assert_eq!(error.unwrap_err().message, "Invalid value for 'width'");
+        assert!(declarations
+            .set_property("width", "1px; x: y }", false)
+            .is_err());
+        assert!(declarations
+            .set_property("margin", "1px wide", false)
+            .is_err());
+        assert_eq!(declarations.get_property_value("width"), "2px");
+
+        assert_eq!(declarations.remove_property("width"), "2px");
+        assert_eq!(declarations.remove_property("width"), "");
+        assert_eq!(declarations.remove_property("margin"), "");
+        assert_eq!(declarations.len(), 1);
+        assert!(stylesheet.declarations_mut(1).is_none());
+        assert!(stylesheet.version() > version);
+    }
+
+    #[test]
+    fn parses_media_and_import_rules() {
+        let css = "
+            @import url(\"base.css\") screen;
+            @import 'print.css' print,   (min-width: 600px);
+            div { width: 1px; }
+            @media screen {
+                div { width: 2px; }
+                @media (min-width: 600px) {
+                    p { width: 3px; }
+                }
+            }
+            @media print { div { width: 4px; } }
+            p { width: 5px; }
+        ";
+        let parsed = CSSParser::new(css).parse();
+        assert_eq!(parsed.rules.len(), 6);
+        let Some(CSSItem::ImportRule(import)) = parsed.rule(1) else {
+            panic!("@import was not parsed")
+        };
+        assert_eq!(import.url, "print.css");
+        assert_eq!(import.media, ["print", "(min-width: 600px)"]);
+        let widths = |rules: Vec<&CSSRule>| -> Vec<String> {
+            rules
+                .iter()
+                .map(|rule| rule.declarations[0].value.to_string())
+                .collect()
+        };
+        assert_eq!(
+            widths(parsed.style_rules()),
+            ["1px", "2px", "3px", "4px", "5px"]
+        );
+        assert_eq!(
+            parsed
+                .style_rules()
+                .iter()
+                .map(|rule| rule.source_order)
+                .collect::<Vec<_>>(),
+            [0, 1, 2, 3, 4]
+        );
+        // The default screen is 800px wide.
+        assert_eq!(
+            widths(parsed.active_style_rules()),
+            ["1px", "2px", "3px", "5px"]
+        );
+        let narrow = MediaContext {
+            width: 400.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            widths(parsed.active_style_rules_in(&narrow)),
+            ["1px", "2px", "5px"]
+        );
+        assert_eq!(
+            parsed.to_string(),
+            "@import url(\"base.css\") screen;
+@import url(\"print.css\") print, (min-width: 600px);
+div {
+\twidth: 1px;
+}
+@media screen {
+\tdiv {
+\t\twidth: 2px;
+\t}
+\t@media (min-width: 600px) {
+\t\tp {
+\t\t\twidth: 3px;
+\t\t}
+\t}
+}
+@media print {
+\tdiv {
+\t\twidth: 4px;
+\t}
+}
+p {
+\twidth: 5px;
+}
+"
+        );
+        assert_eq!(
+            parsed.serialize(SerializeMode::Minified),
+            concat!(
+                "@import url(\"base.css\") screen;",
+                "@import url(\"print.css\") print,(min-width: 600px);",
+                "div{width:1px;}",
+                "@media screen{div{width:2px;}@media (min-width: 600px){p{width:3px;}}}",
+                "@media print{div{width:4px;}}",
+                "p{width:5px;}",
+            )
+        );
+        assert_round_trips(&parsed);
+    }
+
+    fn parse_error(input: &str) -> ParseError {
+        match CSSParser::new(input).try_parse() {
+            Err(error) => error,
+            Ok(_) => panic!("Invalid stylesheet was parsed"),
+        }
+    }
+
+    #[test]
+    fn reports_syntax_errors_with_their_location() {
+        let error = parse_error("div {\n\tcolor: red;\n\twidth: 10px\n}");
+        assert_eq!(error.message, "Expected ';' but found '}'");
+        assert_eq!((error.line, error.column), (4, 1));
+
+        let error = parse_error("p {}\ndiv, span {}");
+        assert_eq!(
+            error.message,
+            "The following tag type is not supported: 'span'"
+        );
+        assert_eq!((error.line, error.column), (2, 6));
+        assert_eq!(
+            error.to_string(),
+            "The following tag type is not supported: 'span' at line 2, column 6\n\
+             div, span {}\n     ^"
+        );
+
+        let error = parse_error("div {\n\tcolor: rgb(1, x, 3);\n}");
+        assert_eq!(error.snippet(), "\tcolor: rgb(1, x, 3);\n\t              ^");
+
+        let error = parse_error("div { color: red;");
+        assert_eq!(error.message, "Expected '}' but reached the end of input");
+
+        let error = parse_error("@media screen {\n\tdiv {}\n");
+        assert_eq!(error.message, "Expected '}' but reached the end of input");
+
+        let error = parse_error("@media screen { @import \"a.css\"; }");
+        assert_eq!(error.message, "@import is not allowed inside @media");
+
+        let error = parse_error("@import a.css;");
+        assert_eq!(error.message, "Expected a URL or a string after @import");
+        assert_eq!((error.line, error.column), (1, 9));
+    }
+}
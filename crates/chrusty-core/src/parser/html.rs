@@ -1,8 +1,9 @@
 use crate::{
-    dom::{self, new_element, ElementData, NodeType},
+    atom::Atom,
+    dom::{self, AttrsMap, ElementData, NodeType},
     parser::{ICharStreamParser, IParser},
 };
-use std::collections::HashMap;
+use std::{collections::HashMap, mem};
 
 #[derive(Debug)]
 pub struct HTMLParser {
@@ -23,75 +24,89 @@ impl IParser for HTMLParser {
     fn parse(&mut self) -> dom::Document {
         dom::Document {
             children: self.parse_nodes(),
-            node_type: NodeType::Element(ElementData {
-                tag_type: dom::TagType::Html,
-                attributes: HashMap::new(),
-            }),
+            node_type: NodeType::Element(ElementData::new(dom::TagType::Html, HashMap::new())),
         }
     }
 }
 
 impl HTMLParser {
-    fn parse_node(&mut self) -> dom::Node {
-        match self.next_char() {
-            '<' => self.parse_element(),
-            _ => self.parse_text(),
-        }
-    }
-
     fn parse_text(&mut self) -> dom::Node {
         dom::new_text(&self.consume_while(|c| c != '<'), vec![])
     }
 
-    fn parse_attributes(&mut self) -> HashMap<String, String> {
+    fn parse_attributes(&mut self) -> AttrsMap {
         let mut attributes = HashMap::new();
-        while !self.eof() && self.next_char() != '>' {
+        loop {
             self.consume_white_space();
+            if self.eof() || matches!(self.next_char(), '>' | '/') {
+                break;
+            }
             let atr_name = self.consume_while(|c| char::is_alphabetic(c) || c == '-');
             assert_eq!(self.consume_char(), Ok('='));
             assert_eq!(self.consume_char(), Ok('"'));
             let atr_value = self.consume_while(|c| c != '"');
             assert_eq!(self.consume_char(), Ok('"'));
-            attributes.insert(atr_name, atr_value);
+            attributes.insert(Atom::from(atr_name), atr_value);
         }
-        return attributes;
+        attributes
     }
 
-    fn parse_tag(&mut self) -> (dom::TagType, HashMap<String, String>) {
+    fn parse_tag(&mut self) -> (dom::TagType, AttrsMap) {
         let _ = self.consume_char();
-        let tag = self.consume_while(|c| c != ' ' && c != '>');
+        let tag = self.consume_while(|c| !matches!(c, ' ' | '>' | '/'));
         let attributes = self.parse_attributes();
+        // Void elements may end their tag with `/>`.
+        if !self.eof() && self.next_char() == '/' {
+            let _ = self.consume_char();
+        }
         let _ = self.consume_char();
         let tag_type = match tag.to_lowercase().as_str() {
             "div" => dom::TagType::Div,
             "p" => dom::TagType::P,
+            "img" => dom::TagType::Img,
             "html" => dom::TagType::Html,
             "style" => dom::TagType::Style,
             _ => panic!("The following tag type is not supported: {}", tag),
         };
-        return (tag_type, attributes);
+        (tag_type, attributes)
     }
 
+    /// Parses nodes up to the end tag of their parent, or the end of the
+    /// input. Elements whose end tag is still ahead are kept on a stack with
+    /// the children of their parent parsed so far, so deeply nested
+    /// documents don't recurse.
     fn parse_nodes(&mut self) -> Vec<dom::Node> {
+        let mut open: Vec<(dom::TagType, AttrsMap, Vec<dom::Node>)> = vec![];
         let mut nodes = vec![];
         loop {
             self.consume_white_space();
             if self.eof() || (self.next_char() == '<' && self.next_char_at(1) == '/') {
-                break;
+                let Some((tag_type, attributes, siblings)) = open.pop() else {
+                    break;
+                };
+                self.parse_end_tag();
+                let children = mem::replace(&mut nodes, siblings);
+                nodes.push(dom::new_element(tag_type, attributes, children));
+                continue;
+            }
+            if self.next_char() != '<' {
+                nodes.push(self.parse_text());
+                continue;
+            }
+            let (tag_type, attributes) = self.parse_tag();
+            if tag_type.is_void() {
+                nodes.push(dom::new_element(tag_type, attributes, vec![]));
+            } else {
+                open.push((tag_type, attributes, mem::take(&mut nodes)));
             }
-
-            nodes.push(self.parse_node());
         }
-        return nodes;
+        nodes
     }
 
-    fn parse_element(&mut self) -> dom::Node {
-        let (tag_type, attributes) = self.parse_tag();
-        let children = self.parse_nodes();
+    fn parse_end_tag(&mut self) {
         assert_eq!(self.consume_char().unwrap(), '<');
         assert_eq!(self.consume_char().unwrap(), '/');
         self.consume_while(|c| c != '>');
         assert_eq!(self.consume_char().unwrap(), '>');
-        dom::new_element(tag_type, attributes, children)
     }
 }
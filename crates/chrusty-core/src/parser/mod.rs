@@ -0,0 +1,130 @@
+macro_rules! impl_CharStream {
+    (for $($t:ty),+) => {
+        $(impl ICharStreamParser for $t {
+            fn next_char(&self) -> char {
+                self.input[self.pos..].chars().next().unwrap()
+            }
+
+            fn next_char_at(&self, offset: usize) -> char {
+                self.input[(self.pos + offset)..].chars().next().unwrap()
+            }
+
+            fn eof(&self) -> bool {
+                self.pos >= self.input.len()
+            }
+
+            fn consume_char(&mut self) -> Result<char, &str> {
+                if self.eof() {
+                    return Err("All input characters already consumed");
+                }
+
+                let mut iter = self.input[self.pos..].char_indices();
+                let (_, cur_char) = iter.next().unwrap();
+                let (next_post, _) = iter.next().unwrap_or((1, ' '));
+                self.pos += next_post;
+                Ok(cur_char)
+            }
+
+            fn starts_with(&self, s: &str) -> bool {
+                self.input[self.pos..].starts_with(s)
+            }
+
+            fn consume_while<F>(&mut self, test: F) -> String
+            where
+                F: Fn(char) -> bool,
+            {
+                let mut result = String::new();
+                while !self.eof() && test(self.next_char()) {
+                    result.push(self.consume_char().unwrap())
+                }
+                result
+            }
+
+            fn consume_white_space(&mut self) {
+                self.consume_while(char::is_whitespace);
+            }
+        })*
+    }
+}
+
+mod css;
+mod html;
+
+use std::fmt::{self, Display, Formatter};
+
+pub use css::CSSParser;
+pub(crate) use css::{split_tokens, split_top_level};
+pub use html::HTMLParser;
+
+/// Invalid syntax found while parsing, with where in the source it was.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    /// 1-based line of the offending character.
+    pub line: usize,
+    /// 1-based column of the offending character, counted in characters.
+    pub column: usize,
+    /// The source line containing the offending character.
+    pub source_line: String,
+}
+
+impl ParseError {
+    /// Builds an error for the character at byte offset `pos` of `input`.
+    pub fn new(message: impl Into<String>, input: &str, pos: usize) -> ParseError {
+        let pos = pos.min(input.len());
+        let line_start = input[..pos].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = input[pos..].find('\n').map_or(input.len(), |i| pos + i);
+        ParseError {
+            message: message.into(),
+            line: input[..line_start].matches('\n').count() + 1,
+            column: input[line_start..pos].chars().count() + 1,
+            source_line: input[line_start..line_end].trim_end().to_string(),
+        }
+    }
+
+    /// The source line with a caret under the offending character.
+    pub fn snippet(&self) -> String {
+        // Keeps tabs so the caret lines up however they are displayed.
+        let indent: String = self
+            .source_line
+            .chars()
+            .chain(std::iter::repeat(' '))
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        format!("{}\n{}^", self.source_line, indent)
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}\n{}",
+            self.message,
+            self.line,
+            self.column,
+            self.snippet()
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub trait IParser {
+    type Output;
+    fn new(input: &str) -> Self;
+    fn parse(&mut self) -> Self::Output;
+}
+
+trait ICharStreamParser: IParser {
+    fn next_char(&self) -> char;
+    fn next_char_at(&self, offset: usize) -> char;
+    fn starts_with(&self, s: &str) -> bool;
+    fn eof(&self) -> bool;
+    fn consume_char(&mut self) -> Result<char, &str>;
+    fn consume_while<F>(&mut self, test: F) -> String
+    where
+        F: Fn(char) -> bool;
+    fn consume_white_space(&mut self);
+}
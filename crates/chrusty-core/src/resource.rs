@@ -0,0 +1,207 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::{
+    cssom::{CSSDeclaration, CSSProperty, CSSValue, Stylesheet},
+    dom::{Document, IDomNode, NodeType, TagType},
+    font::{FontStore, TrueTypeFont},
+};
+
+/// Decoded image, 4 bytes (RGBA) per pixel in row-major order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Asked for every image URL referenced by a document, from stylesheets or
+/// `img` elements. Decoding is left to the embedder, which returns `None`
+/// when the image can't be loaded.
+pub type ImageRequest = dyn FnMut(&str) -> Option<Image>;
+
+/// Asked for the data of every `@font-face` source, until one of a face's
+/// sources loads as a TrueType font.
+pub type FontRequest = dyn FnMut(&str) -> Option<Vec<u8>>;
+
+/// Images and fonts referenced by the current document.
+#[derive(Default)]
+pub struct Resources {
+    request: Option<Box<ImageRequest>>,
+    images: HashMap<String, Option<Arc<Image>>>,
+    font_request: Option<Box<FontRequest>>,
+    /// Font sources already requested, whether they loaded or not.
+    font_sources: HashSet<String>,
+    fonts: FontStore,
+}
+
+impl Resources {
+    pub fn new() -> Resources {
+        Resources::default()
+    }
+
+    pub fn set_image_request(&mut self, request: impl FnMut(&str) -> Option<Image> + 'static) {
+        self.request = Some(Box::new(request));
+        self.images.clear();
+    }
+
+    /// Requests every image of `stylesheet` that isn't loaded yet. Failed
+    /// requests are remembered so they aren't retried on every load.
+    pub fn load_images(&mut self, stylesheet: &Stylesheet) {
+        let Some(request) = self.request.as_mut() else {
+            return;
+        };
+        let urls = stylesheet
+            .style_rules()
+            .into_iter()
+            .flat_map(|rule| &rule.declarations)
+            .filter_map(|declaration| match declaration {
+                CSSDeclaration {
+                    property: CSSProperty::BackgroundImage,
+                    value: CSSValue::Url(url),
+                    ..
+                } => Some(url),
+                _ => None,
+            });
+        for url in urls {
+            if !self.images.contains_key(url) {
+                let image = request(url).map(Arc::new);
+                self.images.insert(url.clone(), image);
+            }
+        }
+    }
+
+    /// Requests the sources of the `img` elements of `document` that aren't
+    /// loaded yet. Images are shared with stylesheets referencing the same
+    /// URL.
+    pub fn load_content_images(&mut self, document: &Document) {
+        let Some(request) = self.request.as_mut() else {
+            return;
+        };
+        let mut stack: Vec<&dyn IDomNode> = vec![document];
+        while let Some(node) = stack.pop() {
+            if let NodeType::Element(element) = node.get_node_type() {
+                let src = element.attribute("src");
+                if let (TagType::Img, Some(src)) = (element.tag_type, src) {
+                    if !self.images.contains_key(src) {
+                        let image = request(src).map(Arc::new);
+                        self.images.insert(src.clone(), image);
+                    }
+                }
+            }
+            let children = node.get_children().iter().rev();
+            stack.extend(children.map(|child| child as &dyn IDomNode));
+        }
+    }
+
+    pub fn image(&self, url: &str) -> Option<Arc<Image>> {
+        self.images.get(url).cloned().flatten()
+    }
+
+    /// The images that loaded, by URL.
+    pub fn images(&self) -> impl Iterator<Item = (&str, &Arc<Image>)> {
+        self.images
+            .iter()
+            .filter_map(|(url, image)| Some((url.as_str(), image.as_ref()?)))
+    }
+
+    pub fn set_font_request(&mut self, request: impl FnMut(&str) -> Option<Vec<u8>> + 'static) {
+        self.font_request = Some(Box::new(request));
+        self.font_sources.clear();
+    }
+
+    /// Loads the `@font-face` rules of `stylesheet` into the font store.
+    pub fn load_fonts(&mut self, stylesheet: &Stylesheet) {
+        let Some(request) = self.font_request.as_mut() else {
+            return;
+        };
+        for font_face in &stylesheet.font_faces {
+            for url in &font_face.sources {
+                if !self.font_sources.insert(url.clone()) {
+                    continue;
+                }
+                if let Some(font) = request(url).and_then(TrueTypeFont::parse) {
+                    self.fonts.register(&font_face.family, font);
+                    break;
+                }
+            }
+        }
+    }
+
+    pub fn fonts(&self) -> &FontStore {
+        &self.fonts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{
+        parser::{CSSParser, IParser},
+        resource::{Image, Resources},
+    };
+
+    #[test]
+    fn requests_each_image_once() {
+        let requested = Rc::new(RefCell::new(vec![]));
+        let mut resources = Resources::new();
+        let log = Rc::clone(&requested);
+        resources.set_image_request(move |url| {
+            log.borrow_mut().push(url.to_string());
+            (url == "a.png").then(|| Image {
+                width: 1,
+                height: 1,
+                pixels: vec![255, 0, 0, 255],
+            })
+        });
+        let stylesheet = CSSParser::new(
+            "
+            div {
+                background-image: url(a.png);
+            }
+
+            p {
+                background: red url('b.png');
+            }
+
+            html {
+                background: url(\"a.png\") no-repeat;
+            }
+            ",
+        )
+        .parse();
+        resources.load_images(&stylesheet);
+        resources.load_images(&stylesheet);
+        assert_eq!(*requested.borrow(), ["a.png", "b.png"]);
+        assert_eq!(resources.image("a.png").unwrap().width, 1);
+        assert!(resources.image("b.png").is_none());
+        assert!(resources.image("c.png").is_none());
+    }
+
+    #[test]
+    fn tries_font_sources_in_order() {
+        let requested = Rc::new(RefCell::new(vec![]));
+        let mut resources = Resources::new();
+        let log = Rc::clone(&requested);
+        resources.set_font_request(move |url| {
+            log.borrow_mut().push(url.to_string());
+            (url == "b.ttf").then(|| b"not a font".to_vec())
+        });
+        let stylesheet = CSSParser::new(
+            "
+            @font-face {
+                font-family: Custom;
+                src: url(a.ttf), url(b.ttf);
+            }
+            ",
+        )
+        .parse();
+        resources.load_fonts(&stylesheet);
+        resources.load_fonts(&stylesheet);
+        assert_eq!(*requested.borrow(), ["a.ttf", "b.ttf"]);
+        assert!(resources.fonts().get("Custom").is_none());
+    }
+}
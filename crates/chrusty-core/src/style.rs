@@ -0,0 +1,2782 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    ops::Range,
+    sync::Arc,
+};
+
+use crate::{
+    atom::Atom,
+    bloom::AncestorFilter,
+    color::Color,
+    cssom::{
+        AnPlusB, CSSProperty, CSSRule, CSSSelector, CSSValue, MediaContext, Origin, SimpleSelector,
+        Specificity, Stylesheet,
+    },
+    dom::{
+        self, ElementData, ElementState, ElementStates, IDomNode, Node, NodeId, NodeType, TagType,
+    },
+    parser::{CSSParser, IParser},
+    values::{
+        CSSWideKeyword, ImageValue, LengthContext, LengthPercentage, LengthPercentageAuto,
+        LengthPercentageNone, LineHeight, LonghandValue, TrackSize, ZIndex,
+    },
+};
+
+/// Specified values, borrowed from the stylesheets unless `var()` was
+/// substituted.
+type PropertyMap<'a> = HashMap<&'a CSSProperty, Cow<'a, CSSValue>>;
+
+/// Computed values of an element's custom properties, by name.
+pub type CustomProperties = HashMap<Atom, String>;
+
+pub struct StyledNode<'a> {
+    pub node: &'a dyn IDomNode,
+    pub specified_values: PropertyMap<'a>,
+    /// Rules with a selector matching the node, from the lowest precedence
+    /// sheet to the highest and in source order within a sheet.
+    pub matched_rules: Vec<&'a CSSRule>,
+    /// Shared with the parent when the node declares none of its own.
+    pub custom_properties: Arc<CustomProperties>,
+    /// Typed values for layout and paint, computed from `specified_values`.
+    pub style: ComputedStyle,
+    pub children: Vec<StyledNode<'a>>,
+}
+
+// Dropped iteratively, as the default recursive drop would overflow the
+// stack on deeply nested trees.
+impl Drop for StyledNode<'_> {
+    fn drop(&mut self) {
+        let mut stack = std::mem::take(&mut self.children);
+        while let Some(mut node) = stack.pop() {
+            stack.append(&mut node.children);
+        }
+    }
+}
+
+impl<'a> StyledNode<'a> {
+    pub fn value(&self, property: &CSSProperty) -> Option<&CSSValue> {
+        self.specified_values
+            .get(property)
+            .map(|value| value.as_ref())
+    }
+
+    /// The typed form of the node's value for `property`. `initial` is
+    /// replaced by the property's initial value, while `inherit` gives `None`
+    /// like a missing value, see `inherits`. `em` and `rem` lengths are taken
+    /// against the default font size; `style.font_size` is the node's own.
+    pub fn longhand(&self, property: &CSSProperty) -> Option<LonghandValue> {
+        self.longhand_in(property, &LengthContext::default())
+    }
+
+    /// Like `longhand`, with `em` and `rem` lengths converted to px against
+    /// the font sizes of `context`.
+    pub fn longhand_in(
+        &self,
+        property: &CSSProperty,
+        context: &LengthContext,
+    ) -> Option<LonghandValue> {
+        match property.parse_longhand_in(self.value(property)?, context)? {
+            LonghandValue::CSSWide(keyword) => match keyword.resolve(property.is_inherited()) {
+                CSSWideKeyword::Initial => property.parse_longhand(&property.default_value()?),
+                _ => None,
+            },
+            value => Some(value),
+        }
+    }
+
+    /// Whether the node takes its parent's value for `property`, from
+    /// `inherit`, or `unset` on an inherited property.
+    pub fn inherits(&self, property: &CSSProperty) -> bool {
+        match self
+            .value(property)
+            .and_then(|value| property.parse_longhand(value))
+        {
+            Some(LonghandValue::CSSWide(keyword)) => {
+                keyword.resolve(property.is_inherited()) == CSSWideKeyword::Inherit
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Computed values of the properties used by layout and paint. Lengths are in
+/// px, `em` and `rem` included, except for percentages, which stay relative
+/// until layout knows what they refer to. Inherited properties, see
+/// `CSSProperty::is_inherited`, take their parent's computed value when they
+/// have no value of their own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComputedStyle {
+    pub display: Display,
+    pub position: Position,
+    pub float: Float,
+    pub clear: Clear,
+    pub flex_direction: FlexDirection,
+    pub justify_content: JustifyContent,
+    pub align_items: AlignItems,
+    pub flex_grow: f32,
+    pub flex_shrink: f32,
+    pub flex_basis: LengthPercentageAuto,
+    /// Explicit tracks of a grid container, empty for `none`.
+    pub grid_template_columns: Vec<TrackSize>,
+    pub grid_template_rows: Vec<TrackSize>,
+    pub top: LengthPercentageAuto,
+    pub right: LengthPercentageAuto,
+    pub bottom: LengthPercentageAuto,
+    pub left: LengthPercentageAuto,
+    pub width: LengthPercentageAuto,
+    pub height: LengthPercentageAuto,
+    /// `auto` minimums are 0 for the boxes laid out so far.
+    pub min_width: LengthPercentageAuto,
+    pub min_height: LengthPercentageAuto,
+    pub max_width: LengthPercentageNone,
+    pub max_height: LengthPercentageNone,
+    pub box_sizing: BoxSizing,
+    pub margin_top: LengthPercentageAuto,
+    pub margin_right: LengthPercentageAuto,
+    pub margin_bottom: LengthPercentageAuto,
+    pub margin_left: LengthPercentageAuto,
+    pub padding_top: LengthPercentage,
+    pub padding_right: LengthPercentage,
+    pub padding_bottom: LengthPercentage,
+    pub padding_left: LengthPercentage,
+    /// Border widths in px, 0 when the side's style is `none` or `hidden`.
+    pub border_top_width: f32,
+    pub border_right_width: f32,
+    pub border_bottom_width: f32,
+    pub border_left_width: f32,
+    pub border_top_style: BorderStyle,
+    pub border_right_style: BorderStyle,
+    pub border_bottom_style: BorderStyle,
+    pub border_left_style: BorderStyle,
+    pub border_top_color: Color,
+    pub border_right_color: Color,
+    pub border_bottom_color: Color,
+    pub border_left_color: Color,
+    pub border_top_left_radius: LengthPercentage,
+    pub border_top_right_radius: LengthPercentage,
+    pub border_bottom_right_radius: LengthPercentage,
+    pub border_bottom_left_radius: LengthPercentage,
+    pub overflow: Overflow,
+    pub visibility: Visibility,
+    pub direction: Direction,
+    pub vertical_align: VerticalAlign,
+    pub white_space: WhiteSpace,
+    pub overflow_wrap: OverflowWrap,
+    pub word_break: WordBreak,
+    /// `None` for `z-index: auto`.
+    pub z_index: Option<i32>,
+    pub opacity: f32,
+    /// Height of line boxes, in px.
+    pub line_height: f32,
+    /// Multiplier of the font size for `normal` and unitless line heights,
+    /// which descendants inherit instead of the height in px.
+    pub line_height_factor: Option<f32>,
+    pub color: Color,
+    pub background_color: Color,
+    pub background_image: ImageValue,
+    pub font_families: Vec<String>,
+    /// In px.
+    pub font_size: f32,
+    pub font_style: FontStyle,
+    /// From 1 to 1000.
+    pub font_weight: u16,
+}
+
+/// Initial values, used by anonymous boxes.
+pub static INITIAL_STYLE: ComputedStyle = ComputedStyle {
+    display: Display::Inline,
+    position: Position::Static,
+    float: Float::None,
+    clear: Clear::None,
+    flex_direction: FlexDirection::Row,
+    justify_content: JustifyContent::FlexStart,
+    align_items: AlignItems::Stretch,
+    flex_grow: 0.0,
+    flex_shrink: 1.0,
+    flex_basis: LengthPercentageAuto::Auto,
+    grid_template_columns: vec![],
+    grid_template_rows: vec![],
+    top: LengthPercentageAuto::Auto,
+    right: LengthPercentageAuto::Auto,
+    bottom: LengthPercentageAuto::Auto,
+    left: LengthPercentageAuto::Auto,
+    width: LengthPercentageAuto::Auto,
+    height: LengthPercentageAuto::Auto,
+    min_width: LengthPercentageAuto::Auto,
+    min_height: LengthPercentageAuto::Auto,
+    max_width: LengthPercentageNone::None,
+    max_height: LengthPercentageNone::None,
+    box_sizing: BoxSizing::ContentBox,
+    margin_top: LengthPercentageAuto::Length(0.0),
+    margin_right: LengthPercentageAuto::Length(0.0),
+    margin_bottom: LengthPercentageAuto::Length(0.0),
+    margin_left: LengthPercentageAuto::Length(0.0),
+    padding_top: LengthPercentage::Length(0.0),
+    padding_right: LengthPercentage::Length(0.0),
+    padding_bottom: LengthPercentage::Length(0.0),
+    padding_left: LengthPercentage::Length(0.0),
+    border_top_width: 0.0,
+    border_right_width: 0.0,
+    border_bottom_width: 0.0,
+    border_left_width: 0.0,
+    border_top_style: BorderStyle::None,
+    border_right_style: BorderStyle::None,
+    border_bottom_style: BorderStyle::None,
+    border_left_style: BorderStyle::None,
+    border_top_color: Color::BLACK,
+    border_right_color: Color::BLACK,
+    border_bottom_color: Color::BLACK,
+    border_left_color: Color::BLACK,
+    border_top_left_radius: LengthPercentage::Length(0.0),
+    border_top_right_radius: LengthPercentage::Length(0.0),
+    border_bottom_right_radius: LengthPercentage::Length(0.0),
+    border_bottom_left_radius: LengthPercentage::Length(0.0),
+    overflow: Overflow::Visible,
+    visibility: Visibility::Visible,
+    direction: Direction::Ltr,
+    vertical_align: VerticalAlign::Baseline,
+    white_space: WhiteSpace::Normal,
+    overflow_wrap: OverflowWrap::Normal,
+    word_break: WordBreak::Normal,
+    z_index: None,
+    opacity: 1.0,
+    line_height: 1.2 * DEFAULT_FONT_SIZE,
+    line_height_factor: Some(1.2),
+    color: Color::BLACK,
+    background_color: Color::TRANSPARENT,
+    background_image: ImageValue::None,
+    font_families: vec![],
+    font_size: DEFAULT_FONT_SIZE,
+    font_style: FontStyle::Normal,
+    font_weight: 400,
+};
+
+impl Default for ComputedStyle {
+    fn default() -> Self {
+        INITIAL_STYLE.clone()
+    }
+}
+
+impl ComputedStyle {
+    /// Computes the style of `node`, whose parent has the style `parent`.
+    /// The root's parent style is `INITIAL_STYLE`. `rem` and viewport
+    /// lengths are relative to the root font size and viewport of `lengths`,
+    /// whose own font size is replaced by the node's.
+    pub fn compute(
+        node: &StyledNode,
+        parent: &ComputedStyle,
+        lengths: &LengthContext,
+    ) -> ComputedStyle {
+        let font_size = get_computed_font_size(
+            node,
+            &LengthContext {
+                font_size: parent.font_size,
+                ..*lengths
+            },
+        );
+        let context = LengthContext {
+            font_size,
+            ..*lengths
+        };
+        let length = |property: CSSProperty, initial: LengthPercentageAuto| match node
+            .longhand_in(&property, &context)
+        {
+            Some(LonghandValue::LengthPercentageAuto(value)) => value,
+            _ => initial,
+        };
+        let max_length = |property: CSSProperty| match node.longhand_in(&property, &context) {
+            Some(LonghandValue::LengthPercentageNone(value)) => value,
+            _ => LengthPercentageNone::None,
+        };
+        let length_percentage = |property: CSSProperty| match node.longhand_in(&property, &context)
+        {
+            Some(LonghandValue::LengthPercentage(value)) => value,
+            _ => LengthPercentage::Length(0.0),
+        };
+        let track_list = |property: CSSProperty| match node.longhand_in(&property, &context) {
+            Some(LonghandValue::TrackList(tracks)) => tracks,
+            _ => vec![],
+        };
+        let color = |property: CSSProperty, initial: Color| match node.longhand(&property) {
+            Some(LonghandValue::Color(color)) => color,
+            _ => initial,
+        };
+        // Widths depend on the styles, so `inherit` is resolved here rather
+        // than after the fact like for other properties.
+        let border_style =
+            |property: CSSProperty, parent: BorderStyle| match node.longhand(&property) {
+                Some(LonghandValue::BorderStyle(style)) => style,
+                _ if node.inherits(&property) => parent,
+                _ => BorderStyle::None,
+            };
+        let border_width = |property: CSSProperty, style: BorderStyle| {
+            match (style, node.longhand_in(&property, &context)) {
+                (BorderStyle::None | BorderStyle::Hidden, _) => 0.0,
+                (_, Some(LonghandValue::Length(width))) => width,
+                // `medium`
+                _ => 3.0,
+            }
+        };
+        let initial = &INITIAL_STYLE;
+        let current_color = color(CSSProperty::Color, parent.color);
+        let (line_height, line_height_factor) = get_computed_line_height(node, &context, parent);
+        let border_top_style = border_style(CSSProperty::BorderTopStyle, parent.border_top_style);
+        let border_right_style =
+            border_style(CSSProperty::BorderRightStyle, parent.border_right_style);
+        let border_bottom_style =
+            border_style(CSSProperty::BorderBottomStyle, parent.border_bottom_style);
+        let border_left_style =
+            border_style(CSSProperty::BorderLeftStyle, parent.border_left_style);
+        let mut style = ComputedStyle {
+            display: get_computed_display(node),
+            position: get_computed_position(node),
+            float: match node.longhand(&CSSProperty::Float) {
+                Some(LonghandValue::Float(float)) => float,
+                _ => Float::None,
+            },
+            clear: match node.longhand(&CSSProperty::Clear) {
+                Some(LonghandValue::Clear(clear)) => clear,
+                _ => Clear::None,
+            },
+            flex_direction: match node.longhand(&CSSProperty::FlexDirection) {
+                Some(LonghandValue::FlexDirection(direction)) => direction,
+                _ => FlexDirection::Row,
+            },
+            justify_content: match node.longhand(&CSSProperty::JustifyContent) {
+                Some(LonghandValue::JustifyContent(justify)) => justify,
+                _ => JustifyContent::FlexStart,
+            },
+            align_items: match node.longhand(&CSSProperty::AlignItems) {
+                Some(LonghandValue::AlignItems(align)) => align,
+                _ => AlignItems::Stretch,
+            },
+            flex_grow: match node.longhand(&CSSProperty::FlexGrow) {
+                Some(LonghandValue::Number(grow)) => grow,
+                _ => initial.flex_grow,
+            },
+            flex_shrink: match node.longhand(&CSSProperty::FlexShrink) {
+                Some(LonghandValue::Number(shrink)) => shrink,
+                _ => initial.flex_shrink,
+            },
+            flex_basis: length(CSSProperty::FlexBasis, initial.flex_basis),
+            grid_template_columns: track_list(CSSProperty::GridTemplateColumns),
+            grid_template_rows: track_list(CSSProperty::GridTemplateRows),
+            top: length(CSSProperty::Top, initial.top),
+            right: length(CSSProperty::Right, initial.right),
+            bottom: length(CSSProperty::Bottom, initial.bottom),
+            left: length(CSSProperty::Left, initial.left),
+            width: length(CSSProperty::Width, initial.width),
+            height: length(CSSProperty::Height, initial.height),
+            min_width: length(CSSProperty::MinWidth, initial.min_width),
+            min_height: length(CSSProperty::MinHeight, initial.min_height),
+            max_width: max_length(CSSProperty::MaxWidth),
+            max_height: max_length(CSSProperty::MaxHeight),
+            box_sizing: match node.longhand(&CSSProperty::BoxSizing) {
+                Some(LonghandValue::BoxSizing(box_sizing)) => box_sizing,
+                _ => BoxSizing::ContentBox,
+            },
+            margin_top: length(CSSProperty::MarginTop, initial.margin_top),
+            margin_right: length(CSSProperty::MarginRight, initial.margin_right),
+            margin_bottom: length(CSSProperty::MarginBottom, initial.margin_bottom),
+            margin_left: length(CSSProperty::MarginLeft, initial.margin_left),
+            padding_top: length_percentage(CSSProperty::PaddingTop),
+            padding_right: length_percentage(CSSProperty::PaddingRight),
+            padding_bottom: length_percentage(CSSProperty::PaddingBottom),
+            padding_left: length_percentage(CSSProperty::PaddingLeft),
+            border_top_width: border_width(CSSProperty::BorderTopWidth, border_top_style),
+            border_right_width: border_width(CSSProperty::BorderRightWidth, border_right_style),
+            border_bottom_width: border_width(CSSProperty::BorderBottomWidth, border_bottom_style),
+            border_left_width: border_width(CSSProperty::BorderLeftWidth, border_left_style),
+            border_top_style,
+            border_right_style,
+            border_bottom_style,
+            border_left_style,
+            border_top_color: color(CSSProperty::BorderTopColor, current_color),
+            border_right_color: color(CSSProperty::BorderRightColor, current_color),
+            border_bottom_color: color(CSSProperty::BorderBottomColor, current_color),
+            border_left_color: color(CSSProperty::BorderLeftColor, current_color),
+            border_top_left_radius: length_percentage(CSSProperty::BorderTopLeftRadius),
+            border_top_right_radius: length_percentage(CSSProperty::BorderTopRightRadius),
+            border_bottom_right_radius: length_percentage(CSSProperty::BorderBottomRightRadius),
+            border_bottom_left_radius: length_percentage(CSSProperty::BorderBottomLeftRadius),
+            overflow: get_computed_overflow(node),
+            visibility: match node.longhand(&CSSProperty::Visibility) {
+                Some(LonghandValue::Visibility(visibility)) => visibility,
+                _ => parent.visibility,
+            },
+            direction: match node.longhand(&CSSProperty::Direction) {
+                Some(LonghandValue::Direction(direction)) => direction,
+                _ => parent.direction,
+            },
+            vertical_align: match node.longhand(&CSSProperty::VerticalAlign) {
+                Some(LonghandValue::VerticalAlign(vertical_align)) => vertical_align,
+                _ => VerticalAlign::Baseline,
+            },
+            white_space: match node.longhand(&CSSProperty::WhiteSpace) {
+                Some(LonghandValue::WhiteSpace(white_space)) => white_space,
+                _ => parent.white_space,
+            },
+            overflow_wrap: match node.longhand(&CSSProperty::OverflowWrap) {
+                Some(LonghandValue::OverflowWrap(overflow_wrap)) => overflow_wrap,
+                _ => parent.overflow_wrap,
+            },
+            word_break: match node.longhand(&CSSProperty::WordBreak) {
+                Some(LonghandValue::WordBreak(word_break)) => word_break,
+                _ => parent.word_break,
+            },
+            z_index: get_computed_z_index(node),
+            opacity: get_computed_opacity(node),
+            line_height,
+            line_height_factor,
+            color: current_color,
+            background_color: color(CSSProperty::BackgroundColor, initial.background_color),
+            background_image: match node.longhand(&CSSProperty::BackgroundImage) {
+                Some(LonghandValue::Image(image)) => image,
+                _ => ImageValue::None,
+            },
+            font_families: match node.longhand(&CSSProperty::FontFamily) {
+                Some(LonghandValue::FontFamily(families)) => families,
+                _ => parent.font_families.clone(),
+            },
+            font_size,
+            font_style: match node.longhand(&CSSProperty::FontStyle) {
+                Some(LonghandValue::FontStyle(style)) => style,
+                _ => parent.font_style,
+            },
+            font_weight: match node.longhand(&CSSProperty::FontWeight) {
+                Some(LonghandValue::FontWeight(weight)) => weight.resolve(parent.font_weight),
+                _ => parent.font_weight,
+            },
+        };
+        // Inherited properties already fall back to the parent's values.
+        for property in node.specified_values.keys() {
+            if !property.is_inherited() && node.inherits(property) {
+                style.inherit(parent, property);
+            }
+        }
+        style
+    }
+
+    /// Copies the computed value of `property` from `parent`.
+    fn inherit(&mut self, parent: &ComputedStyle, property: &CSSProperty) {
+        match property {
+            CSSProperty::Display => self.display = parent.display,
+            CSSProperty::Position => self.position = parent.position,
+            CSSProperty::Float => self.float = parent.float,
+            CSSProperty::Clear => self.clear = parent.clear,
+            CSSProperty::FlexDirection => self.flex_direction = parent.flex_direction,
+            CSSProperty::JustifyContent => self.justify_content = parent.justify_content,
+            CSSProperty::AlignItems => self.align_items = parent.align_items,
+            CSSProperty::FlexGrow => self.flex_grow = parent.flex_grow,
+            CSSProperty::FlexShrink => self.flex_shrink = parent.flex_shrink,
+            CSSProperty::FlexBasis => self.flex_basis = parent.flex_basis,
+            CSSProperty::GridTemplateColumns => {
+                self.grid_template_columns = parent.grid_template_columns.clone()
+            }
+            CSSProperty::GridTemplateRows => {
+                self.grid_template_rows = parent.grid_template_rows.clone()
+            }
+            CSSProperty::Top => self.top = parent.top,
+            CSSProperty::Right => self.right = parent.right,
+            CSSProperty::Bottom => self.bottom = parent.bottom,
+            CSSProperty::Left => self.left = parent.left,
+            CSSProperty::Width => self.width = parent.width,
+            CSSProperty::Height => self.height = parent.height,
+            CSSProperty::MinWidth => self.min_width = parent.min_width,
+            CSSProperty::MinHeight => self.min_height = parent.min_height,
+            CSSProperty::MaxWidth => self.max_width = parent.max_width,
+            CSSProperty::MaxHeight => self.max_height = parent.max_height,
+            CSSProperty::BoxSizing => self.box_sizing = parent.box_sizing,
+            CSSProperty::MarginTop => self.margin_top = parent.margin_top,
+            CSSProperty::MarginRight => self.margin_right = parent.margin_right,
+            CSSProperty::MarginBottom => self.margin_bottom = parent.margin_bottom,
+            CSSProperty::MarginLeft => self.margin_left = parent.margin_left,
+            CSSProperty::PaddingTop => self.padding_top = parent.padding_top,
+            CSSProperty::PaddingRight => self.padding_right = parent.padding_right,
+            CSSProperty::PaddingBottom => self.padding_bottom = parent.padding_bottom,
+            CSSProperty::PaddingLeft => self.padding_left = parent.padding_left,
+            CSSProperty::BorderTopWidth => self.border_top_width = parent.border_top_width,
+            CSSProperty::BorderRightWidth => self.border_right_width = parent.border_right_width,
+            CSSProperty::BorderBottomWidth => self.border_bottom_width = parent.border_bottom_width,
+            CSSProperty::BorderLeftWidth => self.border_left_width = parent.border_left_width,
+            CSSProperty::BorderTopStyle => self.border_top_style = parent.border_top_style,
+            CSSProperty::BorderRightStyle => self.border_right_style = parent.border_right_style,
+            CSSProperty::BorderBottomStyle => self.border_bottom_style = parent.border_bottom_style,
+            CSSProperty::BorderLeftStyle => self.border_left_style = parent.border_left_style,
+            CSSProperty::BorderTopColor => self.border_top_color = parent.border_top_color,
+            CSSProperty::BorderRightColor => self.border_right_color = parent.border_right_color,
+            CSSProperty::BorderBottomColor => self.border_bottom_color = parent.border_bottom_color,
+            CSSProperty::BorderLeftColor => self.border_left_color = parent.border_left_color,
+            CSSProperty::BorderTopLeftRadius => {
+                self.border_top_left_radius = parent.border_top_left_radius
+            }
+            CSSProperty::BorderTopRightRadius => {
+                self.border_top_right_radius = parent.border_top_right_radius
+            }
+            CSSProperty::BorderBottomRightRadius => {
+                self.border_bottom_right_radius = parent.border_bottom_right_radius
+            }
+            CSSProperty::BorderBottomLeftRadius => {
+                self.border_bottom_left_radius = parent.border_bottom_left_radius
+            }
+            CSSProperty::Overflow => self.overflow = parent.overflow,
+            CSSProperty::Visibility => self.visibility = parent.visibility,
+            CSSProperty::Direction => self.direction = parent.direction,
+            CSSProperty::VerticalAlign => self.vertical_align = parent.vertical_align,
+            CSSProperty::WhiteSpace => self.white_space = parent.white_space,
+            CSSProperty::OverflowWrap => self.overflow_wrap = parent.overflow_wrap,
+            CSSProperty::WordBreak => self.word_break = parent.word_break,
+            CSSProperty::ZIndex => self.z_index = parent.z_index,
+            CSSProperty::Opacity => self.opacity = parent.opacity,
+            CSSProperty::LineHeight => {
+                self.line_height = parent.line_height;
+                self.line_height_factor = parent.line_height_factor;
+            }
+            CSSProperty::Color => self.color = parent.color,
+            CSSProperty::BackgroundColor => self.background_color = parent.background_color,
+            CSSProperty::BackgroundImage => self.background_image = parent.background_image.clone(),
+            CSSProperty::FontFamily => self.font_families = parent.font_families.clone(),
+            CSSProperty::FontSize => self.font_size = parent.font_size,
+            CSSProperty::FontStyle => self.font_style = parent.font_style,
+            CSSProperty::FontWeight => self.font_weight = parent.font_weight,
+            // Not computed yet.
+            CSSProperty::BackgroundRepeat
+            | CSSProperty::BackgroundPosition
+            | CSSProperty::Custom(_)
+            | CSSProperty::Unknown(_) => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Display {
+    Inline,
+    Block,
+    InlineBlock,
+    Flex,
+    Grid,
+    None,
+}
+
+impl Display {
+    pub fn from_keyword(keyword: &str) -> Option<Display> {
+        match keyword.trim() {
+            "inline" => Some(Display::Inline),
+            "block" => Some(Display::Block),
+            "inline-block" => Some(Display::InlineBlock),
+            "flex" => Some(Display::Flex),
+            "grid" => Some(Display::Grid),
+            "none" => Some(Display::None),
+            _ => None,
+        }
+    }
+
+    /// Display of nodes without a valid `display` declaration.
+    fn default_for(node: &dyn IDomNode) -> Display {
+        match node.get_node_type() {
+            NodeType::Text(_) => Display::Inline,
+            NodeType::Element(element) => match element.tag_type {
+                dom::TagType::Style => Display::None,
+                dom::TagType::Img => Display::Inline,
+                _ => Display::Block,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Position {
+    Static,
+    Relative,
+    Absolute,
+    Fixed,
+}
+
+impl Position {
+    pub fn from_keyword(keyword: &str) -> Option<Position> {
+        match keyword.trim() {
+            "static" => Some(Position::Static),
+            "relative" => Some(Position::Relative),
+            "absolute" => Some(Position::Absolute),
+            "fixed" => Some(Position::Fixed),
+            _ => None,
+        }
+    }
+}
+
+pub fn get_computed_position(node: &StyledNode) -> Position {
+    match node.longhand(&CSSProperty::Position) {
+        Some(LonghandValue::Position(position)) => position,
+        _ => Position::Static,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BorderStyle {
+    None,
+    Hidden,
+    Dotted,
+    Dashed,
+    Solid,
+    Double,
+    Groove,
+    Ridge,
+    Inset,
+    Outset,
+}
+
+impl BorderStyle {
+    pub fn from_keyword(keyword: &str) -> Option<BorderStyle> {
+        match keyword.trim() {
+            "none" => Some(BorderStyle::None),
+            "hidden" => Some(BorderStyle::Hidden),
+            "dotted" => Some(BorderStyle::Dotted),
+            "dashed" => Some(BorderStyle::Dashed),
+            "solid" => Some(BorderStyle::Solid),
+            "double" => Some(BorderStyle::Double),
+            "groove" => Some(BorderStyle::Groove),
+            "ridge" => Some(BorderStyle::Ridge),
+            "inset" => Some(BorderStyle::Inset),
+            "outset" => Some(BorderStyle::Outset),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FontStyle {
+    Normal,
+    Italic,
+    Oblique,
+}
+
+impl FontStyle {
+    pub fn from_keyword(keyword: &str) -> Option<FontStyle> {
+        match keyword.trim() {
+            "normal" => Some(FontStyle::Normal),
+            "italic" => Some(FontStyle::Italic),
+            "oblique" => Some(FontStyle::Oblique),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overflow {
+    Visible,
+    Hidden,
+    Scroll,
+    Auto,
+}
+
+impl Overflow {
+    pub fn from_keyword(keyword: &str) -> Option<Overflow> {
+        match keyword.trim() {
+            "visible" => Some(Overflow::Visible),
+            "hidden" => Some(Overflow::Hidden),
+            "scroll" => Some(Overflow::Scroll),
+            "auto" => Some(Overflow::Auto),
+            _ => None,
+        }
+    }
+}
+
+/// Side a box is floated to, taking it out of the flow.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Float {
+    None,
+    Left,
+    Right,
+}
+
+impl Float {
+    pub fn from_keyword(keyword: &str) -> Option<Float> {
+        match keyword.trim() {
+            "none" => Some(Float::None),
+            "left" => Some(Float::Left),
+            "right" => Some(Float::Right),
+            _ => None,
+        }
+    }
+}
+
+/// Sides of earlier floats that a box is moved below.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Clear {
+    None,
+    Left,
+    Right,
+    Both,
+}
+
+impl Clear {
+    pub fn from_keyword(keyword: &str) -> Option<Clear> {
+        match keyword.trim() {
+            "none" => Some(Clear::None),
+            "left" => Some(Clear::Left),
+            "right" => Some(Clear::Right),
+            "both" => Some(Clear::Both),
+            _ => None,
+        }
+    }
+
+    /// Whether floats on `side` are cleared.
+    pub fn clears(self, side: Float) -> bool {
+        matches!(
+            (self, side),
+            (Clear::Left | Clear::Both, Float::Left) | (Clear::Right | Clear::Both, Float::Right)
+        )
+    }
+}
+
+/// Main axis of a flex container, along which its items are placed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlexDirection {
+    Row,
+    RowReverse,
+    Column,
+    ColumnReverse,
+}
+
+impl FlexDirection {
+    pub fn from_keyword(keyword: &str) -> Option<FlexDirection> {
+        match keyword.trim() {
+            "row" => Some(FlexDirection::Row),
+            "row-reverse" => Some(FlexDirection::RowReverse),
+            "column" => Some(FlexDirection::Column),
+            "column-reverse" => Some(FlexDirection::ColumnReverse),
+            _ => None,
+        }
+    }
+
+    /// Whether the main axis is horizontal.
+    pub fn is_row(self) -> bool {
+        matches!(self, FlexDirection::Row | FlexDirection::RowReverse)
+    }
+
+    /// Whether items are placed from the end of the main axis.
+    pub fn is_reverse(self) -> bool {
+        matches!(
+            self,
+            FlexDirection::RowReverse | FlexDirection::ColumnReverse
+        )
+    }
+}
+
+/// How the free space of a flex container is spread along its main axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JustifyContent {
+    FlexStart,
+    FlexEnd,
+    Center,
+    SpaceBetween,
+    SpaceAround,
+    SpaceEvenly,
+}
+
+impl JustifyContent {
+    pub fn from_keyword(keyword: &str) -> Option<JustifyContent> {
+        match keyword.trim() {
+            // `normal` behaves as `flex-start` in flex containers.
+            "normal" | "flex-start" | "start" => Some(JustifyContent::FlexStart),
+            "flex-end" | "end" => Some(JustifyContent::FlexEnd),
+            "center" => Some(JustifyContent::Center),
+            "space-between" => Some(JustifyContent::SpaceBetween),
+            "space-around" => Some(JustifyContent::SpaceAround),
+            "space-evenly" => Some(JustifyContent::SpaceEvenly),
+            _ => None,
+        }
+    }
+}
+
+/// How flex items are placed along the cross axis of their line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlignItems {
+    /// Items without a cross size fill the line.
+    Stretch,
+    FlexStart,
+    FlexEnd,
+    Center,
+}
+
+impl AlignItems {
+    pub fn from_keyword(keyword: &str) -> Option<AlignItems> {
+        match keyword.trim() {
+            "normal" | "stretch" => Some(AlignItems::Stretch),
+            // Baselines aren't tracked, so items are aligned by their top.
+            "flex-start" | "start" | "baseline" => Some(AlignItems::FlexStart),
+            "flex-end" | "end" => Some(AlignItems::FlexEnd),
+            "center" => Some(AlignItems::Center),
+            _ => None,
+        }
+    }
+}
+
+/// Which box `width` and `height` size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BoxSizing {
+    ContentBox,
+    /// The sizes include the padding and border.
+    BorderBox,
+}
+
+impl BoxSizing {
+    pub fn from_keyword(keyword: &str) -> Option<BoxSizing> {
+        match keyword.trim() {
+            "content-box" => Some(BoxSizing::ContentBox),
+            "border-box" => Some(BoxSizing::BorderBox),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the box is painted. Unlike `display: none`, hidden boxes still
+/// take up space, and their descendants can be made visible again.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Visibility {
+    Visible,
+    Hidden,
+    /// Same as `hidden` outside of tables.
+    Collapse,
+}
+
+impl Visibility {
+    pub fn from_keyword(keyword: &str) -> Option<Visibility> {
+        match keyword.trim() {
+            "visible" => Some(Visibility::Visible),
+            "hidden" => Some(Visibility::Hidden),
+            "collapse" => Some(Visibility::Collapse),
+            _ => None,
+        }
+    }
+}
+
+/// Direction inline content flows in, from the left edge of lines or from
+/// the right one.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Direction {
+    #[default]
+    Ltr,
+    Rtl,
+}
+
+impl Direction {
+    pub fn from_keyword(keyword: &str) -> Option<Direction> {
+        match keyword.trim() {
+            "ltr" => Some(Direction::Ltr),
+            "rtl" => Some(Direction::Rtl),
+            _ => None,
+        }
+    }
+}
+
+/// How an inline-level box is lined up on its line: on the baseline of its
+/// parent, centered on it, or against the top or bottom of the line.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum VerticalAlign {
+    #[default]
+    Baseline,
+    Top,
+    Middle,
+    Bottom,
+}
+
+impl VerticalAlign {
+    pub fn from_keyword(keyword: &str) -> Option<VerticalAlign> {
+        match keyword.trim() {
+            "baseline" => Some(VerticalAlign::Baseline),
+            "top" => Some(VerticalAlign::Top),
+            "middle" => Some(VerticalAlign::Middle),
+            "bottom" => Some(VerticalAlign::Bottom),
+            _ => None,
+        }
+    }
+}
+
+/// Whether white space in text collapses, and where lines wrap.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum WhiteSpace {
+    /// White space collapses, and lines wrap between words.
+    #[default]
+    Normal,
+    /// White space collapses, and lines don't wrap.
+    Nowrap,
+    /// White space is kept as it is, and lines only break at newlines.
+    Pre,
+}
+
+impl WhiteSpace {
+    pub fn from_keyword(keyword: &str) -> Option<WhiteSpace> {
+        match keyword.trim() {
+            "normal" => Some(WhiteSpace::Normal),
+            "nowrap" => Some(WhiteSpace::Nowrap),
+            "pre" => Some(WhiteSpace::Pre),
+            _ => None,
+        }
+    }
+}
+
+/// Whether words too long for their line break rather than overflow it.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum OverflowWrap {
+    #[default]
+    Normal,
+    /// Words break between any two characters when they don't fit on a
+    /// line of their own.
+    BreakWord,
+    /// Same as `break-word`, as the min-content width isn't used in layout.
+    Anywhere,
+}
+
+impl OverflowWrap {
+    pub fn from_keyword(keyword: &str) -> Option<OverflowWrap> {
+        match keyword.trim() {
+            "normal" => Some(OverflowWrap::Normal),
+            "break-word" => Some(OverflowWrap::BreakWord),
+            "anywhere" => Some(OverflowWrap::Anywhere),
+            _ => None,
+        }
+    }
+}
+
+/// Where lines can break inside words.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum WordBreak {
+    #[default]
+    Normal,
+    /// Words break between any two characters to fill lines.
+    BreakAll,
+    /// Legacy keyword, same as `overflow-wrap: anywhere`.
+    BreakWord,
+}
+
+impl WordBreak {
+    pub fn from_keyword(keyword: &str) -> Option<WordBreak> {
+        match keyword.trim() {
+            "normal" => Some(WordBreak::Normal),
+            "break-all" => Some(WordBreak::BreakAll),
+            "break-word" => Some(WordBreak::BreakWord),
+            _ => None,
+        }
+    }
+}
+
+pub fn get_computed_overflow(node: &StyledNode) -> Overflow {
+    match node.longhand(&CSSProperty::Overflow) {
+        Some(LonghandValue::Overflow(overflow)) => overflow,
+        _ => Overflow::Visible,
+    }
+}
+
+/// Stack level of the node, or `None` for `z-index: auto`.
+pub fn get_computed_z_index(node: &StyledNode) -> Option<i32> {
+    match node.longhand(&CSSProperty::ZIndex) {
+        Some(LonghandValue::ZIndex(ZIndex::Integer(z_index))) => Some(z_index),
+        _ => None,
+    }
+}
+
+/// Whether the node's box forms a new stacking context: a positioned box
+/// with an integer `z-index`.
+pub fn establishes_stacking_context(node: &StyledNode) -> bool {
+    get_computed_position(node) != Position::Static && get_computed_z_index(node).is_some()
+}
+
+/// Opacity of the node and its descendants as a group, from 0 to 1.
+pub fn get_computed_opacity(node: &StyledNode) -> f32 {
+    match node.longhand(&CSSProperty::Opacity) {
+        Some(LonghandValue::Opacity(opacity)) => opacity,
+        _ => 1.0,
+    }
+}
+
+/// Font size of `medium`, the initial `font-size`.
+pub const DEFAULT_FONT_SIZE: f32 = 16.0;
+
+/// Font size of the node in px. Percentages and `em` are taken against the
+/// parent's font size, which is the one in `context`.
+pub fn get_computed_font_size(node: &StyledNode, context: &LengthContext) -> f32 {
+    match node.longhand_in(&CSSProperty::FontSize, context) {
+        Some(LonghandValue::LengthPercentage(LengthPercentage::Length(size))) => size,
+        Some(LonghandValue::LengthPercentage(LengthPercentage::Percent(percentage))) => {
+            percentage / 100.0 * context.font_size
+        }
+        _ => context.font_size,
+    }
+}
+
+/// Height of the line boxes of the node's inline content in px, with the
+/// factor of the font size it was computed from, if any. Unitless line
+/// heights are inherited as factors, lengths and percentages as px.
+pub fn get_computed_line_height(
+    node: &StyledNode,
+    context: &LengthContext,
+    parent: &ComputedStyle,
+) -> (f32, Option<f32>) {
+    let font_size = context.font_size;
+    let factor = match node.longhand_in(&CSSProperty::LineHeight, context) {
+        Some(LonghandValue::LineHeight(LineHeight::Number(factor))) => factor,
+        Some(LonghandValue::LineHeight(LineHeight::Length(length))) => return (length, None),
+        Some(LonghandValue::LineHeight(LineHeight::Percent(percentage))) => {
+            return (percentage / 100.0 * font_size, None)
+        }
+        // `normal`, which browsers put at about 1.2 for common fonts.
+        Some(LonghandValue::LineHeight(LineHeight::Normal)) => 1.2,
+        _ => match parent.line_height_factor {
+            Some(factor) => factor,
+            None => return (parent.line_height, None),
+        },
+    };
+    (factor * font_size, Some(factor))
+}
+
+pub fn get_computed_display(node: &StyledNode) -> Display {
+    match node.longhand(&CSSProperty::Display) {
+        Some(LonghandValue::Display(display)) => display,
+        _ => Display::default_for(node.node),
+    }
+}
+
+/// Position of an element among the element children of its parent,
+/// counting from 1.
+#[derive(Debug, Clone, Copy)]
+struct SiblingPosition {
+    index: usize,
+    count: usize,
+}
+
+impl SiblingPosition {
+    /// The root, which has no siblings.
+    const ONLY: SiblingPosition = SiblingPosition { index: 1, count: 1 };
+
+    /// Positions of the element children of `node`, indexed like its
+    /// children.
+    fn of_children(node: &dyn IDomNode) -> Vec<SiblingPosition> {
+        let children = node.get_children();
+        let count = children
+            .iter()
+            .filter(|child| matches!(child.get_node_type(), NodeType::Element(_)))
+            .count();
+        let mut index = 0;
+        children
+            .iter()
+            .map(|child| {
+                if let NodeType::Element(_) = child.get_node_type() {
+                    index += 1;
+                }
+                SiblingPosition { index, count }
+            })
+            .collect()
+    }
+}
+
+/// An element whose descendants are being matched, for descendant
+/// combinators.
+#[derive(Clone, Copy)]
+struct Ancestor<'s> {
+    element: &'s ElementData,
+    position: SiblingPosition,
+}
+
+/// What an element is matched against besides its own tag and attributes.
+#[derive(Clone, Copy)]
+struct MatchingContext<'s> {
+    states: &'s ElementStates,
+    position: SiblingPosition,
+    /// Ancestors of the element, from the root.
+    ancestors: &'s [Ancestor<'s>],
+    /// Filter of `ancestors`, to reject most descendant selectors without
+    /// walking them.
+    filter: &'s AncestorFilter,
+}
+
+fn matches_pseudo_class(name: &str, elem: &ElementData, context: MatchingContext) -> bool {
+    let name = name.to_ascii_lowercase();
+    let position = context.position;
+    match name.as_str() {
+        "first-child" => position.index == 1,
+        "last-child" => position.index == position.count,
+        _ => match name
+            .strip_prefix("nth-child(")
+            .and_then(|argument| argument.strip_suffix(')'))
+        {
+            Some(argument) => {
+                AnPlusB::parse(argument).is_some_and(|nth| nth.matches(position.index))
+            }
+            None => context.states.get(elem.node_id()).matches(&name),
+        },
+    }
+}
+
+fn matches_simple_selector(
+    elem: &ElementData,
+    context: MatchingContext,
+    selector: &SimpleSelector,
+) -> bool {
+    if selector.tag.iter().any(|name| elem.tag_type != *name) {
+        return false;
+    }
+
+    if selector.id.iter().any(|id| elem.id() != Some(id)) {
+        return false;
+    }
+
+    let elem_classes = elem.classes();
+    if selector
+        .class
+        .iter()
+        .any(|class| !elem_classes.contains(class))
+    {
+        return false;
+    }
+
+    if selector
+        .attributes
+        .iter()
+        .any(|attribute| !attribute.matches(elem.attribute(&attribute.name)))
+    {
+        return false;
+    }
+
+    if selector
+        .pseudo_classes
+        .iter()
+        .any(|pseudo_class| !matches_pseudo_class(pseudo_class, elem, context))
+    {
+        return false;
+    }
+
+    // Pseudo-elements never match the element itself.
+    selector.pseudo_element.is_none()
+}
+
+fn matches(node: &ElementData, context: MatchingContext, selector: &CSSSelector) -> bool {
+    let (subject, compounds) = selector.compounds().split_last().unwrap();
+    if !matches_simple_selector(node, context, subject) {
+        return false;
+    }
+    if !compounds
+        .iter()
+        .all(|compound| context.filter.may_match(compound))
+    {
+        return false;
+    }
+    // Each compound selector is matched against the nearest ancestor it
+    // matches, leaving the most ancestors for the ones before it.
+    let mut compounds = compounds.iter().rev().peekable();
+    for ancestor in context.ancestors.iter().rev() {
+        let Some(compound) = compounds.peek() else {
+            break;
+        };
+        let context = MatchingContext {
+            position: ancestor.position,
+            ..context
+        };
+        if matches_simple_selector(ancestor.element, context, compound) {
+            compounds.next();
+        }
+    }
+    compounds.peek().is_none()
+}
+
+type IndexedSelectors<'a> = Vec<(usize, &'a CSSSelector)>;
+
+/// The active style rules of a stylesheet, with each selector filed under
+/// its id, else its first class, else its tag. An element is then only
+/// matched against the selectors filed under its own id, classes and tag,
+/// and those with none of them.
+struct RuleIndex<'a> {
+    rules: Vec<&'a CSSRule>,
+    by_id: HashMap<&'a Atom, IndexedSelectors<'a>>,
+    by_class: HashMap<&'a Atom, IndexedSelectors<'a>>,
+    by_tag: HashMap<TagType, IndexedSelectors<'a>>,
+    universal: IndexedSelectors<'a>,
+}
+
+impl<'a> RuleIndex<'a> {
+    fn new(stylesheet: &'a Stylesheet, media: &MediaContext) -> RuleIndex<'a> {
+        let mut index = RuleIndex {
+            rules: stylesheet.active_style_rules_in(media),
+            by_id: HashMap::new(),
+            by_class: HashMap::new(),
+            by_tag: HashMap::new(),
+            universal: vec![],
+        };
+        for (i, rule) in index.rules.iter().enumerate() {
+            for selector in &rule.selectors {
+                let simple = selector.subject();
+                let bucket = if let Some(id) = &simple.id {
+                    index.by_id.entry(id).or_default()
+                } else if let Some(class) = simple.class.first() {
+                    index.by_class.entry(class).or_default()
+                } else if let Some(tag) = simple.tag {
+                    index.by_tag.entry(tag).or_default()
+                } else {
+                    &mut index.universal
+                };
+                bucket.push((i, selector));
+            }
+        }
+        index
+    }
+
+    /// Rules with a selector matching `element`, along with the specificity
+    /// of the most specific one, in source order.
+    fn matching_rules(
+        &self,
+        element: &ElementData,
+        context: MatchingContext,
+    ) -> Vec<(&'a CSSRule, Specificity)> {
+        let candidates = element
+            .id()
+            .and_then(|id| self.by_id.get(id))
+            .into_iter()
+            .chain(
+                element
+                    .classes()
+                    .iter()
+                    .filter_map(|class| self.by_class.get(class)),
+            )
+            .chain(self.by_tag.get(&element.tag_type))
+            .chain([&self.universal])
+            .flatten();
+        let mut matched: Vec<(usize, Specificity)> = candidates
+            .filter(|(_, selector)| matches(element, context, selector))
+            .map(|(rule, selector)| (*rule, selector.specificity()))
+            .collect();
+        // Most specific first within a rule, so deduplicating keeps it.
+        matched.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+        matched.dedup_by_key(|(rule, _)| *rule);
+        matched
+            .into_iter()
+            .map(|(rule, specificity)| (self.rules[rule], specificity))
+            .collect()
+    }
+}
+
+/// The values declared for `node`, along with the rules they come from.
+fn get_specified_values<'a>(
+    node: &'a dyn IDomNode,
+    indices: &[RuleIndex<'a>],
+    context: MatchingContext,
+) -> (PropertyMap<'a>, Vec<&'a CSSRule>) {
+    if let NodeType::Text(_) = &node.get_node_type() {
+        return (HashMap::new(), vec![]);
+    }
+
+    let NodeType::Element(element) = &node.get_node_type() else {
+        unreachable!();
+    };
+    match element.tag_type {
+        dom::TagType::Style => (HashMap::new(), vec![]),
+        _ => {
+            // Declarations are applied from the lowest precedence to the
+            // highest.
+            let mut declarations = vec![];
+            let mut matched_rules = vec![];
+            for (sheet_order, index) in indices.iter().enumerate() {
+                for (rule, specificity) in index.matching_rules(element, context) {
+                    matched_rules.push(rule);
+                    let specificity = specificity.with_order(sheet_order, rule.source_order);
+                    for declaration in &rule.declarations {
+                        if let CSSProperty::Unknown(_) = declaration.property {
+                            continue;
+                        }
+                        let precedence =
+                            specificity.with_origin(rule.origin, declaration.is_important);
+                        declarations.push((precedence, declaration));
+                    }
+                }
+            }
+            for declaration in element.inline_style() {
+                if let CSSProperty::Unknown(_) = declaration.property {
+                    continue;
+                }
+                let precedence =
+                    Specificity::inline().with_origin(Origin::Author, declaration.is_important);
+                declarations.push((precedence, declaration));
+            }
+
+            // The sort is stable, so later declarations of a rule still win.
+            declarations.sort_by_key(|(precedence, _)| *precedence);
+            let specified_values = declarations
+                .into_iter()
+                .map(|(_, declaration)| (&declaration.property, Cow::Borrowed(&declaration.value)))
+                .collect();
+            (specified_values, matched_rules)
+        }
+    }
+}
+
+/// A `var()` function within a value.
+struct VarFunction<'t> {
+    /// Byte range of the whole function.
+    range: Range<usize>,
+    name: &'t str,
+    fallback: Option<&'t str>,
+}
+
+/// Finds the first `var()` function of `text` starting at byte `from`. An
+/// unclosed one runs to the end of the text.
+fn next_var_function(text: &str, from: usize) -> Option<VarFunction<'_>> {
+    let lowercase = text.to_ascii_lowercase();
+    let mut search = from;
+    let start = loop {
+        let start = search + lowercase[search..].find("var(")?;
+        // Not the end of another function's name, e.g. `somevar(`.
+        if !text[..start].ends_with(|c: char| c.is_alphanumeric() || c == '-' || c == '_') {
+            break start;
+        }
+        search = start + 4;
+    };
+    let arguments = start + 4;
+    let mut depth = 0;
+    let mut comma = None;
+    let mut end = text.len();
+    for (i, c) in text[arguments..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => {
+                end = arguments + i;
+                break;
+            }
+            ')' => depth -= 1,
+            ',' if depth == 0 && comma.is_none() => comma = Some(arguments + i),
+            _ => {}
+        }
+    }
+    Some(VarFunction {
+        range: start..(end + 1).min(text.len()),
+        name: text[arguments..comma.unwrap_or(end)].trim(),
+        fallback: comma.map(|comma| text[comma + 1..end].trim()),
+    })
+}
+
+/// Names of the custom properties `text` references, fallbacks included.
+fn variable_references(text: &str) -> Vec<&str> {
+    let mut names = vec![];
+    let mut from = 0;
+    while let Some(var) = next_var_function(text, from) {
+        names.push(var.name);
+        // Continue inside the function, to find those of its fallback.
+        from = var.range.start + 4;
+    }
+    names
+}
+
+/// Replaces the `var()` functions of `text` with the values of the custom
+/// properties they reference, or else their fallbacks. `None` when one
+/// references an undefined property and has no fallback.
+fn substitute(text: &str, custom_properties: &CustomProperties) -> Option<String> {
+    let mut result = String::new();
+    let mut from = 0;
+    while let Some(var) = next_var_function(text, from) {
+        if !var.name.starts_with("--") {
+            return None;
+        }
+        result.push_str(&text[from..var.range.start]);
+        match (custom_properties.get(var.name), var.fallback) {
+            (Some(value), _) => result.push_str(value),
+            (None, Some(fallback)) => result.push_str(&substitute(fallback, custom_properties)?),
+            (None, None) => return None,
+        }
+        from = var.range.end;
+    }
+    result.push_str(&text[from..]);
+    Some(result)
+}
+
+/// Computes the custom properties of a node from its declarations and
+/// those it inherits. A property that references itself through a chain of
+/// `var()`s is invalid at computed-value time, as is one referencing an
+/// undefined property without a fallback, which leaves it undefined.
+fn compute_custom_properties(
+    specified_values: &PropertyMap,
+    inherited: &CustomProperties,
+) -> CustomProperties {
+    let mut computed = inherited.clone();
+    let mut declared: HashMap<Atom, &str> = HashMap::new();
+    for (property, value) in specified_values {
+        let (CSSProperty::Custom(name), CSSValue::Raw(text)) = (property, value.as_ref()) else {
+            continue;
+        };
+        match CSSWideKeyword::from_keyword(text) {
+            // Custom properties are inherited, so `unset` inherits too.
+            Some(CSSWideKeyword::Inherit | CSSWideKeyword::Unset) => {}
+            Some(CSSWideKeyword::Initial) => {
+                computed.remove(name);
+            }
+            None => {
+                computed.remove(name);
+                declared.insert(name.clone(), text);
+            }
+        }
+    }
+
+    // Depth-first walk of the references between declared properties, with
+    // an explicit stack, computing each after those it references. A
+    // reference to a property on the stack closes a cycle through every
+    // property above it.
+    let mut done: HashSet<Atom> = HashSet::new();
+    let mut cyclic: HashSet<Atom> = HashSet::new();
+    let mut on_stack: HashSet<Atom> = HashSet::new();
+    for root in declared.keys() {
+        if done.contains(root) {
+            continue;
+        }
+        on_stack.insert(root.clone());
+        let mut stack = vec![(root.clone(), variable_references(declared[root]), 0)];
+        while let Some((_, references, next)) = stack.last_mut() {
+            let Some(reference) = references.get(*next).copied() else {
+                let (name, ..) = stack.pop().unwrap();
+                on_stack.remove(&name);
+                if !cyclic.contains(&name) {
+                    if let Some(value) = substitute(declared[&name], &computed) {
+                        computed.insert(name.clone(), value);
+                    }
+                }
+                done.insert(name);
+                continue;
+            };
+            *next += 1;
+            let Some((reference, text)) = declared.get_key_value(reference) else {
+                continue;
+            };
+            if on_stack.contains(reference) {
+                let start = stack
+                    .iter()
+                    .position(|(name, ..)| name == reference)
+                    .unwrap();
+                cyclic.extend(stack[start..].iter().map(|(name, ..)| name.clone()));
+            } else if !done.contains(reference) {
+                on_stack.insert(reference.clone());
+                stack.push((reference.clone(), variable_references(text), 0));
+            }
+        }
+    }
+    computed
+}
+
+/// Substitutes the `var()`s of `specified_values` and parses the results as
+/// declarations of their property, or of the shorthand they were set with.
+/// A value that fails is invalid at computed-value time and acts as `unset`.
+fn substitute_variables(specified_values: &mut PropertyMap, custom_properties: &CustomProperties) {
+    for (property, value) in specified_values.iter_mut() {
+        let CSSValue::Unresolved { text, shorthand } = value.as_ref() else {
+            continue;
+        };
+        let name = match shorthand {
+            Some(shorthand) => shorthand.to_string(),
+            None => property.to_string(),
+        };
+        let resolved = substitute(text, custom_properties).and_then(|text| {
+            CSSParser::new(&format!("{}: {};", name, text))
+                .try_parse_declarations()
+                .ok()?
+                .into_iter()
+                .find(|declaration| declaration.property == **property)
+                .map(|declaration| declaration.value)
+        });
+        *value = Cow::Owned(resolved.unwrap_or_else(|| CSSValue::Keyword("unset".to_string())));
+    }
+}
+
+/// How often the rules of a stylesheet matched the elements of a document.
+/// Rules are indexed as in `Stylesheet::style_rules`, so those nested in
+/// `@media` rules are counted too.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleUsage {
+    /// Number of matched elements per selector, indexed by rule then by
+    /// selector within the rule.
+    pub selector_matches: Vec<Vec<usize>>,
+    /// Number of elements matched by at least one selector, per rule.
+    pub rule_matches: Vec<usize>,
+}
+
+impl RuleUsage {
+    /// Indices of the rules that didn't match any element.
+    pub fn unused_rules(&self) -> Vec<usize> {
+        (0..self.rule_matches.len())
+            .filter(|rule| self.rule_matches[*rule] == 0)
+            .collect()
+    }
+
+    /// `(rule, selector)` indices of the selectors that didn't match any
+    /// element, including those of otherwise used rules.
+    pub fn unused_selectors(&self) -> Vec<(usize, usize)> {
+        self.selector_matches
+            .iter()
+            .enumerate()
+            .flat_map(|(rule, selectors)| {
+                (0..selectors.len())
+                    .filter(move |selector| selectors[*selector] == 0)
+                    .map(move |selector| (rule, selector))
+            })
+            .collect()
+    }
+
+    /// Human readable list of the unused rules and selectors of `stylesheet`.
+    pub fn report(&self, stylesheet: &Stylesheet) -> String {
+        let rules = stylesheet.style_rules();
+        let mut report = String::new();
+        for rule in self.unused_rules() {
+            report += &format!("unused rule: {}", rules[rule]);
+        }
+        for (rule, selector) in self.unused_selectors() {
+            if self.rule_matches[rule] > 0 {
+                report += &format!("unused selector: {}\n", rules[rule].selectors[selector]);
+            }
+        }
+        report
+    }
+}
+
+/// Matches every element of the document against `stylesheet`, counting
+/// which rules and selectors are used. Elements are matched in their default
+/// state, so e.g. `:hover` rules count as unused.
+pub fn get_rule_usage(node: &dyn IDomNode, stylesheet: &Stylesheet) -> RuleUsage {
+    let states = ElementStates::new();
+    let rules = stylesheet.style_rules();
+    let mut usage = RuleUsage {
+        selector_matches: rules
+            .iter()
+            .map(|rule| vec![0; rule.selectors.len()])
+            .collect(),
+        rule_matches: vec![0; rules.len()],
+    };
+    // Nodes to visit, along with the number of their ancestors, which are
+    // kept from the root down to the node being visited.
+    let mut stack = vec![(node, SiblingPosition::ONLY, 0)];
+    let mut ancestors: Vec<Ancestor> = vec![];
+    let mut filter = AncestorFilter::new();
+    while let Some((node, position, depth)) = stack.pop() {
+        let NodeType::Element(element) = node.get_node_type() else {
+            continue;
+        };
+        while ancestors.len() > depth {
+            ancestors.pop();
+            filter.pop();
+        }
+        stack.extend(
+            node.get_children()
+                .iter()
+                .map(|child| child as &dyn IDomNode)
+                .zip(SiblingPosition::of_children(node))
+                .map(|(child, position)| (child, position, depth + 1)),
+        );
+        let context = MatchingContext {
+            states: &states,
+            position,
+            ancestors: &ancestors,
+            filter: &filter,
+        };
+        if element.tag_type != dom::TagType::Style {
+            for (i, rule) in rules.iter().enumerate() {
+                let mut matched = false;
+                for (j, selector) in rule.selectors.iter().enumerate() {
+                    if matches(element, context, selector) {
+                        usage.selector_matches[i][j] += 1;
+                        matched = true;
+                    }
+                }
+                if matched {
+                    usage.rule_matches[i] += 1;
+                }
+            }
+        }
+        ancestors.push(Ancestor { element, position });
+        filter.push(element);
+    }
+    usage
+}
+
+pub fn get_styled_node<'a>(node: &'a dyn IDomNode, stylesheet: &'a Stylesheet) -> StyledNode<'a> {
+    cascade(node, &[stylesheet])
+}
+
+/// Styles the document with several stylesheets at once, e.g. a built-in
+/// user agent sheet along with the page's. Each rule takes part in the
+/// cascade with the origin of its sheet; among declarations of the same
+/// origin, importance and specificity, the sheets listed last win.
+pub fn cascade<'a>(node: &'a dyn IDomNode, stylesheets: &[&'a Stylesheet]) -> StyledNode<'a> {
+    cascade_with_states(
+        node,
+        stylesheets,
+        &ElementStates::new(),
+        &MediaContext::default(),
+    )
+}
+
+/// Like `cascade`, with elements matching `:hover`, `:focus` and `:active`
+/// according to `states`, and `@media` rules and viewport lengths evaluated
+/// against `media`.
+pub fn cascade_with_states<'a>(
+    node: &'a dyn IDomNode,
+    stylesheets: &[&'a Stylesheet],
+    states: &ElementStates,
+    media: &MediaContext,
+) -> StyledNode<'a> {
+    let indices: Vec<RuleIndex> = stylesheets
+        .iter()
+        .map(|stylesheet| RuleIndex::new(stylesheet, media))
+        .collect();
+    let new_node = |node: &'a dyn IDomNode,
+                    parent: Option<&StyledNode<'a>>,
+                    position: SiblingPosition,
+                    ancestors: &[Ancestor],
+                    filter: &AncestorFilter,
+                    root_font_size: f32| {
+        let context = MatchingContext {
+            states,
+            position,
+            ancestors,
+            filter,
+        };
+        let (mut specified_values, matched_rules) = get_specified_values(node, &indices, context);
+        let inherited = parent.map(|parent| &parent.custom_properties);
+        let custom_properties = match specified_values
+            .keys()
+            .any(|property| matches!(property, CSSProperty::Custom(_)))
+        {
+            true => Arc::new(compute_custom_properties(
+                &specified_values,
+                inherited.map_or(&CustomProperties::new(), |inherited| inherited),
+            )),
+            false => inherited.cloned().unwrap_or_default(),
+        };
+        substitute_variables(&mut specified_values, &custom_properties);
+        let mut styled_node = StyledNode {
+            node,
+            specified_values,
+            matched_rules,
+            custom_properties,
+            style: ComputedStyle::default(),
+            children: Vec::with_capacity(node.get_children().len()),
+        };
+        let parent_style = parent.map_or(&INITIAL_STYLE, |parent| &parent.style);
+        let lengths = LengthContext {
+            font_size: parent_style.font_size,
+            root_font_size,
+            viewport_width: media.width,
+            viewport_height: media.height,
+        };
+        styled_node.style = ComputedStyle::compute(&styled_node, parent_style, &lengths);
+        styled_node
+    };
+    // The tree is built with an explicit stack of partially built nodes, so
+    // deeply nested documents can't overflow the call stack. A node's next
+    // child is the one at the index of its number of built children. The
+    // sibling positions of each node's children are kept alongside, as are
+    // the elements of the stack, for descendant combinators.
+    let mut ancestors: Vec<Ancestor> = vec![];
+    let mut filter = AncestorFilter::new();
+    let mut stack = vec![new_node(
+        node,
+        None,
+        SiblingPosition::ONLY,
+        &[],
+        &AncestorFilter::new(),
+        DEFAULT_FONT_SIZE,
+    )];
+    if let NodeType::Element(element) = node.get_node_type() {
+        let position = SiblingPosition::ONLY;
+        ancestors.push(Ancestor { element, position });
+        filter.push(element);
+    }
+    let mut positions = vec![SiblingPosition::of_children(node)];
+    let root_font_size = stack[0].style.font_size;
+    loop {
+        let parent = stack.last().unwrap();
+        let index = parent.children.len();
+        if let Some(child) = parent.node.get_children().get(index) {
+            let position = positions.last().unwrap()[index];
+            stack.push(new_node(
+                child,
+                Some(parent),
+                position,
+                &ancestors,
+                &filter,
+                root_font_size,
+            ));
+            if let NodeType::Element(element) = child.get_node_type() {
+                ancestors.push(Ancestor { element, position });
+                filter.push(element);
+            }
+            positions.push(SiblingPosition::of_children(child));
+            continue;
+        }
+        positions.pop();
+        let node = stack.pop().unwrap();
+        if let NodeType::Element(_) = node.node.get_node_type() {
+            ancestors.pop();
+            filter.pop();
+        }
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => return node,
+        }
+    }
+}
+
+/// A change to the document that may affect which rules match.
+#[derive(Debug, Clone, Copy)]
+pub enum StyleChange<'a> {
+    /// An attribute was set, changed or removed.
+    Attribute {
+        name: &'a str,
+        old: Option<&'a str>,
+        new: Option<&'a str>,
+    },
+    /// The hover, focus or active state changed.
+    State {
+        old: ElementState,
+        new: ElementState,
+    },
+    /// Element children were inserted or removed, leaving `children`.
+    Children(&'a [Node]),
+}
+
+/// Works out which elements need restyling after a change, from the
+/// features the stylesheets' selectors use. Consumers such as DOM mutations
+/// and input events report their changes, and the styling walk restyles the
+/// nodes collected here along with their descendants.
+#[derive(Debug, Default)]
+pub struct StyleInvalidator {
+    ids: HashSet<Atom>,
+    classes: HashSet<Atom>,
+    attributes: HashSet<Atom>,
+    /// Lowercase names, without the arguments of functional ones.
+    pseudo_classes: HashSet<String>,
+    invalid: HashSet<NodeId>,
+}
+
+impl StyleInvalidator {
+    pub fn new(stylesheets: &[&Stylesheet]) -> StyleInvalidator {
+        let mut invalidator = StyleInvalidator::default();
+        let selectors = stylesheets
+            .iter()
+            .flat_map(|stylesheet| stylesheet.style_rules())
+            .flat_map(|rule| &rule.selectors);
+        for selector in selectors.flat_map(CSSSelector::compounds) {
+            invalidator.ids.extend(selector.id.clone());
+            invalidator.classes.extend(selector.class.iter().cloned());
+            invalidator.attributes.extend(
+                selector
+                    .attributes
+                    .iter()
+                    .map(|attribute| attribute.name.clone()),
+            );
+            invalidator
+                .pseudo_classes
+                .extend(selector.pseudo_classes.iter().map(|pseudo_class| {
+                    let name = pseudo_class.split('(').next().unwrap_or_default();
+                    name.to_ascii_lowercase()
+                }));
+        }
+        invalidator
+    }
+
+    fn uses_pseudo_class(&self, name: &str) -> bool {
+        self.pseudo_classes.contains(name)
+    }
+
+    fn uses_structural_pseudo_classes(&self) -> bool {
+        ["first-child", "last-child", "nth-child"]
+            .iter()
+            .any(|name| self.uses_pseudo_class(name))
+    }
+
+    /// Records `change` to `node`, invalidating the elements whose matched
+    /// rules it may have changed.
+    pub fn invalidate(&mut self, node: NodeId, change: StyleChange) {
+        match change {
+            StyleChange::Attribute { name, old, new } => {
+                let affects_selectors = self.attributes.contains(name)
+                    || match name {
+                        // Declarations of the attribute itself changed.
+                        "style" => true,
+                        "id" => [old, new].iter().flatten().any(|id| self.ids.contains(*id)),
+                        "class" => {
+                            let old: HashSet<&str> =
+                                old.unwrap_or_default().split_whitespace().collect();
+                            let new: HashSet<&str> =
+                                new.unwrap_or_default().split_whitespace().collect();
+                            old.symmetric_difference(&new)
+                                .any(|class| self.classes.contains(*class))
+                        }
+                        _ => false,
+                    };
+                if affects_selectors {
+                    self.invalid.insert(node);
+                }
+            }
+            StyleChange::State { old, new } => {
+                let changed = [
+                    ("hover", old.hover != new.hover),
+                    ("focus", old.focus != new.focus),
+                    ("active", old.active != new.active),
+                ];
+                if changed
+                    .iter()
+                    .any(|(name, changed)| *changed && self.uses_pseudo_class(name))
+                {
+                    self.invalid.insert(node);
+                }
+            }
+            StyleChange::Children(children) => {
+                // The new children need styling either way.
+                self.invalid.insert(node);
+                if self.uses_structural_pseudo_classes() {
+                    // Siblings may have moved.
+                    self.invalid.extend(children.iter().filter_map(|child| {
+                        match child.get_node_type() {
+                            NodeType::Element(element) => Some(element.node_id()),
+                            NodeType::Text(_) => None,
+                        }
+                    }));
+                }
+            }
+        }
+    }
+
+    /// Elements that need restyling since the last `clear`.
+    pub fn invalid_nodes(&self) -> &HashSet<NodeId> {
+        &self.invalid
+    }
+
+    pub fn needs_restyle(&self) -> bool {
+        !self.invalid.is_empty()
+    }
+
+    /// Forgets the invalid elements, once they've been restyled.
+    pub fn clear(&mut self) {
+        self.invalid.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{
+        bloom::AncestorFilter,
+        color::Color,
+        cssom::{
+            AnPlusB, CSSProperty, CSSValue, ColorScheme, MediaContext, Origin, Specificity, Unit,
+        },
+        dom::{ElementData, ElementState, ElementStates, IDomNode, NodeType},
+        parser::{CSSParser, HTMLParser, IParser},
+        style::{
+            cascade, cascade_with_states, get_computed_display, get_computed_position,
+            get_rule_usage, get_styled_node, matches, Ancestor, BorderStyle, ComputedStyle,
+            Display, FontStyle, MatchingContext, Position, RuleIndex, SiblingPosition, StyleChange,
+            StyleInvalidator,
+        },
+        values::{LengthPercentage, LengthPercentageAuto},
+    };
+
+    #[test]
+    fn generates_styled_tree() {
+        let html = "
+            <div class=\"my-div\">
+                Hello world!
+            </div>
+        ";
+        let css = "
+            div {
+                color: #fff;
+            }
+            
+            html {
+                color: #000;
+            }
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let styled_dom = get_styled_node(&dom, &stylesheet);
+        assert_eq!(get_computed_display(&styled_dom), Display::Block);
+        assert_eq!(
+            get_computed_display(&styled_dom.children[0].children[0]),
+            Display::Inline
+        );
+        let Some(CSSValue::Color(val)) = styled_dom.value(&CSSProperty::Color) else {
+            panic!("CSS rule was not applied to HTML tag")
+        };
+        assert_eq!(val.to_string(), "rgb(0, 0, 0)");
+        let Some(CSSValue::Color(val)) = styled_dom.children[0].value(&CSSProperty::Color) else {
+            panic!("CSS rule was not applied to DIV tag")
+        };
+        assert_eq!(val.to_string(), "rgb(255, 255, 255)");
+    }
+
+    #[test]
+    fn carries_position_and_offsets() {
+        let html = "<div class=\"positioned\"></div><div></div>";
+        let css = "
+            div.positioned {
+                position: absolute;
+                top: -10px;
+                left: 0;
+                right: 25%;
+            }
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let styled_dom = get_styled_node(&dom, &stylesheet);
+        let positioned = &styled_dom.children[0];
+        assert_eq!(get_computed_position(positioned), Position::Absolute);
+        assert_eq!(
+            get_computed_position(&styled_dom.children[1]),
+            Position::Static
+        );
+        let Some(CSSValue::Dimension(top, Unit::Px)) = positioned.value(&CSSProperty::Top) else {
+            panic!("top offset was not carried onto the styled node")
+        };
+        assert_eq!(*top, -10.0);
+        let Some(CSSValue::Dimension(left, Unit::Px)) = positioned.value(&CSSProperty::Left) else {
+            panic!("left offset was not carried onto the styled node")
+        };
+        assert_eq!(*left, 0.0);
+        assert!(matches!(
+            positioned.value(&CSSProperty::Right),
+            Some(CSSValue::Dimension(_, Unit::Percent))
+        ));
+        assert!(positioned.value(&CSSProperty::Bottom).is_none());
+    }
+
+    #[test]
+    fn reports_unused_rules() {
+        let html = "<div class=\"used\"><p>Text</p></div>";
+        let css = "
+            div.used,
+            div.missing {
+                color: red;
+            }
+
+            p {
+                color: blue;
+            }
+
+            div#nothing {
+                color: green;
+            }
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let usage = get_rule_usage(&dom, &stylesheet);
+        assert_eq!(usage.rule_matches, [1, 1, 0]);
+        assert_eq!(usage.unused_rules(), [2]);
+        assert_eq!(usage.unused_selectors(), [(0, 1), (2, 0)]);
+        let report = usage.report(&stylesheet);
+        assert!(report.contains("unused rule: div#nothing"));
+        assert!(report.contains("unused selector: div.missing"));
+        assert!(!report.contains("div.used"));
+    }
+
+    #[test]
+    fn matches_attributes_and_breaks_ties_by_source_order() {
+        let html = "<div class=\"a\" title=\"main content\"><p>Text</p></div>";
+        let css = "
+            div[title~=content] {
+                color: red;
+            }
+
+            div.a {
+                color: blue;
+            }
+
+            div:hover {
+                width: 10px;
+            }
+
+            div[title^=main] {
+                height: 20px;
+            }
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        assert_eq!(
+            stylesheet
+                .style_rules()
+                .iter()
+                .map(|rule| rule.source_order)
+                .collect::<Vec<_>>(),
+            [0, 1, 2, 3]
+        );
+        let dom = HTMLParser::new(html).parse();
+        let styled_dom = get_styled_node(&dom, &stylesheet);
+        let div = &styled_dom.children[0];
+        assert!(matches!(
+            div.value(&CSSProperty::Color),
+            Some(CSSValue::Color(color)) if color.to_string() == "rgb(0, 0, 255)"
+        ));
+        assert!(div.value(&CSSProperty::Width).is_none());
+        assert!(matches!(
+            div.value(&CSSProperty::Height),
+            Some(CSSValue::Dimension(height, Unit::Px)) if *height == 20.0
+        ));
+    }
+
+    #[test]
+    fn computes_typed_styles() {
+        let html = "<div class=\"a\"><p>Text</p></div>";
+        let css = "
+            div.a {
+                width: 50%;
+                margin: 0 auto;
+                background: rgb(0, 128, 255);
+                color: #fff;
+                line-height: 2;
+                display: none;
+            }
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let styled_dom = get_styled_node(&dom, &stylesheet);
+        let style = &styled_dom.children[0].style;
+        assert_eq!(style.display, Display::None);
+        assert_eq!(style.width, LengthPercentageAuto::Percent(50.0));
+        assert_eq!(style.height, LengthPercentageAuto::Auto);
+        assert_eq!(style.margin_top, LengthPercentageAuto::Length(0.0));
+        assert_eq!(style.margin_left, LengthPercentageAuto::Auto);
+        assert_eq!(
+            style.background_color,
+            Color {
+                r: 0,
+                g: 128,
+                b: 255,
+                a: 255
+            }
+        );
+        assert_eq!(
+            style.color,
+            Color {
+                r: 255,
+                g: 255,
+                b: 255,
+                a: 255
+            }
+        );
+        assert_eq!(style.line_height, 32.0);
+        // Inherited from the div.
+        assert_eq!(styled_dom.children[0].children[0].style.line_height, 32.0);
+        assert_eq!(styled_dom.style.background_color, Color::TRANSPARENT);
+    }
+
+    #[test]
+    fn cascades_by_origin_and_importance() {
+        let html = "<div class=\"a\"></div>";
+        let user_agent = CSSParser::new(
+            "
+            div {
+                display: block !important;
+                width: 10px;
+                height: 10px;
+            }
+        ",
+        )
+        .parse()
+        .with_origin(Origin::UserAgent);
+        let user = CSSParser::new(
+            "
+            div {
+                color: red !important;
+            }
+        ",
+        )
+        .parse()
+        .with_origin(Origin::User);
+        let author = CSSParser::new(
+            "
+            div {
+                display: none;
+                color: blue !important;
+                background-color: green !important;
+            }
+
+            div.a {
+                width: 20px;
+                background-color: blue;
+            }
+        ",
+        )
+        .parse();
+        let dom = HTMLParser::new(html).parse();
+        let styled_dom = cascade(&dom, &[&user_agent, &user, &author]);
+        let style = &styled_dom.children[0].style;
+        // Important user agent and user declarations beat the author's.
+        assert_eq!(style.display, Display::Block);
+        assert_eq!(style.color.to_string(), "rgb(255, 0, 0)");
+        // Normal author declarations beat the user agent's.
+        assert_eq!(style.width, LengthPercentageAuto::Length(20.0));
+        assert_eq!(style.height, LengthPercentageAuto::Length(10.0));
+        // Importance wins over specificity within an origin.
+        assert_eq!(style.background_color.to_string(), "rgb(0, 128, 0)");
+    }
+
+    #[test]
+    fn orders_every_cascade_tier() {
+        // Each property is declared in two adjacent tiers, from user agent
+        // `!important` down to user agent normal.
+        let html = "<div id=\"a\" style=\"font-size: 30px !important; margin-top: 3px\"></div>";
+        let user_agent = CSSParser::new(
+            "
+            div {
+                display: block !important;
+                font-size: 10px !important;
+                height: 10px;
+            }
+        ",
+        )
+        .parse()
+        .with_origin(Origin::UserAgent);
+        let user = CSSParser::new(
+            "
+            div {
+                display: flex !important;
+                color: red !important;
+                width: 10px;
+                height: 5px;
+            }
+        ",
+        )
+        .parse()
+        .with_origin(Origin::User);
+        let author = CSSParser::new(
+            "
+            div {
+                color: blue !important;
+                background-color: green !important;
+                margin-top: 2px !important;
+            }
+
+            #a {
+                background-color: blue;
+                width: 20px;
+            }
+        ",
+        )
+        .parse();
+        let dom = HTMLParser::new(html).parse();
+        let styled_dom = cascade(&dom, &[&user_agent, &user, &author]);
+        let style = &styled_dom.children[0].style;
+        // User agent `!important` beats user `!important`.
+        assert_eq!(style.display, Display::Block);
+        // User agent `!important` beats an `!important` style attribute.
+        assert_eq!(style.font_size, 10.0);
+        // User `!important` beats author `!important`.
+        assert_eq!(style.color.to_string(), "rgb(255, 0, 0)");
+        // Author `!important` beats a more specific normal author rule and
+        // a normal style attribute.
+        assert_eq!(style.background_color.to_string(), "rgb(0, 128, 0)");
+        assert_eq!(style.margin_top, LengthPercentageAuto::Length(2.0));
+        // Author normal beats user normal.
+        assert_eq!(style.width, LengthPercentageAuto::Length(20.0));
+        // User normal beats user agent normal.
+        assert_eq!(style.height, LengthPercentageAuto::Length(5.0));
+    }
+
+    #[test]
+    fn orders_specificity_by_level_selector_then_order() {
+        let id = Specificity::new(1, 0, 0);
+        let classes = Specificity::new(0, 12, 3);
+        assert!(id > classes);
+        assert!(Specificity::new(0, 1, 1) > Specificity::new(0, 1, 0));
+
+        // The cascade level outweighs the selector.
+        let user_agent = id.with_origin(Origin::UserAgent, false);
+        assert!(classes.with_origin(Origin::Author, false) > user_agent);
+        assert!(
+            user_agent.with_origin(Origin::UserAgent, true)
+                > classes.with_origin(Origin::Author, true)
+        );
+
+        // The order of appearance only breaks ties, sheets first.
+        let first = classes.with_order(0, 5);
+        assert!(classes.with_order(1, 0) > first);
+        assert!(classes.with_order(0, 6) > first);
+        assert!(id.with_order(0, 0) > first);
+        assert_eq!(classes.with_order(0, 5), first);
+    }
+
+    #[test]
+    fn computes_box_and_font_longhands() {
+        let html = "<div><p>Text</p></div>";
+        let css = "
+            div {
+                color: blue;
+                padding: 10% 4px;
+                border: 2px solid;
+                border-right-color: red;
+                border-bottom-style: none;
+                font: bold 20px/2 serif;
+            }
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let styled_dom = get_styled_node(&dom, &stylesheet);
+        let style = &styled_dom.children[0].style;
+        assert_eq!(style.padding_top, LengthPercentage::Percent(10.0));
+        assert_eq!(style.padding_left, LengthPercentage::Length(4.0));
+        assert_eq!(style.border_top_style, BorderStyle::Solid);
+        assert_eq!(style.border_top_width, 2.0);
+        // `none` borders have no width.
+        assert_eq!(style.border_bottom_width, 0.0);
+        // Border colors default to the element's color.
+        assert_eq!(style.border_top_color, style.color);
+        assert_eq!(style.border_right_color.to_string(), "rgb(255, 0, 0)");
+        assert_eq!(style.font_size, 20.0);
+        assert_eq!(style.font_weight, 700);
+        assert_eq!(style.font_style, FontStyle::Normal);
+        assert_eq!(style.line_height, 40.0);
+        assert_eq!(style.font_families, ["serif"]);
+        // Font longhands are inherited, paddings and borders aren't.
+        let paragraph = &styled_dom.children[0].children[0].style;
+        assert_eq!(paragraph.font_size, 20.0);
+        assert_eq!(paragraph.padding_top, LengthPercentage::Length(0.0));
+        assert_eq!(paragraph.border_top_width, 0.0);
+    }
+
+    #[test]
+    fn inherits_computed_values() {
+        let html = "<div>Text<p>Nested<div class=\"pct\">Text</div></p></div>";
+        let css = "
+            html {
+                color: red;
+                font-size: 20px;
+            }
+
+            div {
+                font-size: 50%;
+                font-weight: bolder;
+                line-height: 2;
+            }
+
+            div.pct {
+                line-height: 150%;
+            }
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let styled_dom = get_styled_node(&dom, &stylesheet);
+        let div = &styled_dom.children[0];
+        let text = &div.children[0].style;
+        // A rule on `html` reaches the text of its descendants.
+        assert_eq!(text.color.to_string(), "rgb(255, 0, 0)");
+        assert_eq!(text.font_size, 10.0);
+        assert_eq!(text.font_weight, 700);
+        assert_eq!(text.line_height, 20.0);
+        // Relative values are resolved against the parent's computed value.
+        let paragraph = &div.children[1];
+        let nested = &paragraph.children[1].style;
+        assert_eq!(nested.font_size, 5.0);
+        assert_eq!(nested.font_weight, 900);
+        assert_eq!(nested.line_height, 7.5);
+        // Percentages are inherited as px, unitless factors as factors.
+        assert_eq!(nested.line_height_factor, None);
+        assert_eq!(paragraph.children[1].children[0].style.line_height, 7.5);
+        assert_eq!(paragraph.style.line_height, 20.0);
+    }
+
+    #[test]
+    fn applies_style_attributes_above_selectors() {
+        let html = "<div id=\"a\" style=\"width: 10px; height: 10px !important; color: red\"></div><div style=\"width: 10px; {\"></div>";
+        let css = "
+            div#a {
+                width: 20px;
+                height: 20px !important;
+                color: blue !important;
+            }
+
+            div {
+                width: 30px;
+            }
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let styled_dom = get_styled_node(&dom, &stylesheet);
+        let style = &styled_dom.children[0].style;
+        assert_eq!(style.width, LengthPercentageAuto::Length(10.0));
+        // Important declarations of rules beat normal inline ones, but not
+        // important inline ones.
+        assert_eq!(style.height, LengthPercentageAuto::Length(10.0));
+        assert_eq!(style.color.to_string(), "rgb(0, 0, 255)");
+        // An invalid attribute is ignored.
+        assert_eq!(
+            styled_dom.children[1].style.width,
+            LengthPercentageAuto::Length(30.0)
+        );
+    }
+
+    #[test]
+    fn finds_matching_rules_through_the_index() {
+        let html = "<div id=\"a\" class=\"b c\" data-x=\"1\"></div><p class=\"c\"></p>";
+        let css = "
+            #a, div {}
+            .b.c {}
+            p {}
+            [data-x] {}
+            .c, p.c {}
+            #z {}
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let index = RuleIndex::new(&stylesheet, &MediaContext::default());
+        let matching = |i: usize| {
+            let NodeType::Element(element) = dom.children[i].get_node_type() else {
+                unreachable!()
+            };
+            index
+                .matching_rules(
+                    element,
+                    MatchingContext {
+                        states: &ElementStates::new(),
+                        position: SiblingPosition::ONLY,
+                        ancestors: &[],
+                        filter: &AncestorFilter::new(),
+                    },
+                )
+                .into_iter()
+                .map(|(rule, specificity)| (rule.source_order, specificity))
+                .collect::<Vec<_>>()
+        };
+        // A rule matched by several selectors counts once, with the most
+        // specific of them.
+        assert_eq!(
+            matching(0),
+            [
+                (0, Specificity::new(1, 0, 0)),
+                (1, Specificity::new(0, 2, 0)),
+                (3, Specificity::new(0, 1, 0)),
+                (4, Specificity::new(0, 1, 0)),
+            ]
+        );
+        assert_eq!(
+            matching(1),
+            [
+                (2, Specificity::new(0, 0, 1)),
+                (4, Specificity::new(0, 1, 1))
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_descendant_selectors() {
+        let html = "
+            <div class=\"nav\"><div><p class=\"item\">a</p></div></div>
+            <p class=\"item\">b</p>
+        ";
+        let css = "
+            .nav p {
+                margin-top: 1px;
+            }
+
+            .nav div p.item {
+                margin-right: 2px;
+            }
+
+            div .nav p {
+                margin-bottom: 3px;
+            }
+
+            html p:first-child {
+                margin-left: 4px;
+            }
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let styled_dom = get_styled_node(&dom, &stylesheet);
+        let margins = |style: &ComputedStyle| {
+            [
+                style.margin_top,
+                style.margin_right,
+                style.margin_bottom,
+                style.margin_left,
+            ]
+            .map(|margin| match margin {
+                LengthPercentageAuto::Length(length) => length,
+                _ => unreachable!(),
+            })
+        };
+        let nested = &styled_dom.children[0].children[0].children[0];
+        assert_eq!(margins(&nested.style), [1.0, 2.0, 0.0, 4.0]);
+        assert_eq!(margins(&styled_dom.children[1].style), [0.0, 0.0, 0.0, 0.0]);
+
+        let usage = get_rule_usage(&dom, &stylesheet);
+        assert_eq!(usage.rule_matches, [1, 1, 0, 1]);
+    }
+
+    #[test]
+    fn rejects_descendant_selectors_through_the_ancestor_filter() {
+        let html = "<div class=\"nav\"><p></p></div>";
+        let stylesheet = CSSParser::new(".nav p, div p {}").parse();
+        let dom = HTMLParser::new(html).parse();
+        fn element(node: &dyn IDomNode) -> &ElementData {
+            match node.get_node_type() {
+                NodeType::Element(element) => element,
+                NodeType::Text(_) => unreachable!(),
+            }
+        }
+        let div = element(&dom.children[0]);
+        let p = element(&dom.children[0].get_children()[0]);
+        let ancestors = [Ancestor {
+            element: div,
+            position: SiblingPosition::ONLY,
+        }];
+        let states = ElementStates::new();
+        let mut filter = AncestorFilter::new();
+        let selectors = &stylesheet.style_rules()[0].selectors;
+        let matched = |filter: &AncestorFilter| {
+            let context = MatchingContext {
+                states: &states,
+                position: SiblingPosition::ONLY,
+                ancestors: &ancestors,
+                filter,
+            };
+            selectors
+                .iter()
+                .map(|selector| matches(p, context, selector))
+                .collect::<Vec<_>>()
+        };
+        // Ancestors missing from the filter aren't looked for.
+        assert_eq!(matched(&filter), [false, false]);
+        filter.push(div);
+        assert_eq!(matched(&filter), [true, true]);
+    }
+
+    #[test]
+    fn resolves_css_wide_keywords() {
+        let html = "<div><p>Text</p></div>";
+        let css = "
+            div {
+                color: red;
+                display: flex;
+                width: 50px;
+                margin: 4px;
+                border: 2px solid;
+            }
+
+            p {
+                color: initial;
+                display: inherit;
+                width: inherit;
+                margin: unset;
+                border-top-style: inherit;
+                font-size: initial;
+            }
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let styled_dom = get_styled_node(&dom, &stylesheet);
+        let style = &styled_dom.children[0].children[0].style;
+        assert_eq!(style.color, Color::BLACK);
+        assert_eq!(style.display, Display::Flex);
+        assert_eq!(style.width, LengthPercentageAuto::Length(50.0));
+        assert_eq!(style.margin_top, LengthPercentageAuto::Length(0.0));
+        assert_eq!(style.font_size, 16.0);
+        // The inherited style gives the default `medium` width.
+        assert_eq!(style.border_top_style, BorderStyle::Solid);
+        assert_eq!(style.border_top_width, 3.0);
+        // `unset` on an inherited property inherits.
+        let text = &styled_dom.children[0].children[0].children[0].style;
+        assert_eq!(text.color, Color::BLACK);
+        assert_eq!(text.display, Display::Inline);
+    }
+
+    #[test]
+    fn matches_element_states() {
+        let html = "<div><p></p></div>";
+        let css = "
+            div:hover { color: red; }
+            p:focus:active { color: blue; }
+            p:visited { width: 10px; }
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let NodeType::Element(div) = dom.children[0].get_node_type() else {
+            unreachable!()
+        };
+        let NodeType::Element(p) = dom.children[0].get_children()[0].get_node_type() else {
+            unreachable!()
+        };
+
+        let mut states = ElementStates::new();
+        let styled_dom =
+            cascade_with_states(&dom, &[&stylesheet], &states, &MediaContext::default());
+        assert_eq!(styled_dom.children[0].style.color, Color::BLACK);
+
+        let changed = states.set_only(&[div.node_id()], |state| &mut state.hover);
+        assert_eq!(changed, [div.node_id()]);
+        assert!(states
+            .set_only(&[div.node_id()], |state| &mut state.hover)
+            .is_empty());
+        states.set_only(&[p.node_id()], |state| &mut state.focus);
+        let styled_dom =
+            cascade_with_states(&dom, &[&stylesheet], &states, &MediaContext::default());
+        let p_style = &styled_dom.children[0].children[0].style;
+        assert_eq!(
+            styled_dom.children[0].style.color,
+            Color::from_keyword("red").unwrap()
+        );
+        // Inherited from the hovered div, as the `p` isn't active yet.
+        assert_eq!(p_style.color, Color::from_keyword("red").unwrap());
+        assert_eq!(p_style.width, LengthPercentageAuto::Auto);
+
+        states.set(
+            p.node_id(),
+            ElementState {
+                focus: true,
+                active: true,
+                ..Default::default()
+            },
+        );
+        let styled_dom =
+            cascade_with_states(&dom, &[&stylesheet], &states, &MediaContext::default());
+        let p_style = &styled_dom.children[0].children[0].style;
+        assert_eq!(p_style.color, Color::from_keyword("blue").unwrap());
+    }
+
+    #[test]
+    fn matches_structural_pseudo_classes() {
+        assert_eq!(AnPlusB::parse("odd"), Some(AnPlusB { a: 2, b: 1 }));
+        assert_eq!(AnPlusB::parse(" -n + 3 "), Some(AnPlusB { a: -1, b: 3 }));
+        assert_eq!(AnPlusB::parse("-n+ 3"), Some(AnPlusB { a: -1, b: 3 }));
+        assert_eq!(AnPlusB::parse(" 2n + 1 "), Some(AnPlusB { a: 2, b: 1 }));
+        assert_eq!(AnPlusB::parse("2N-1"), Some(AnPlusB { a: 2, b: -1 }));
+        assert_eq!(AnPlusB::parse("+5"), Some(AnPlusB { a: 0, b: 5 }));
+        assert_eq!(AnPlusB::parse("n"), Some(AnPlusB { a: 1, b: 0 }));
+        assert_eq!(AnPlusB::parse("2n+"), None);
+        assert_eq!(AnPlusB::parse("2n 1"), None);
+        assert_eq!(AnPlusB::parse("foo"), None);
+        let nth = AnPlusB { a: -1, b: 3 };
+        assert_eq!(
+            (1..=5).filter(|i| nth.matches(*i)).collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+
+        // Text doesn't count as a sibling.
+        let html = "<div>a<p></p><p></p>b<p></p><p></p><p></p></div>";
+        let css = "
+            p:first-child { width: 1px; }
+            p:last-child { height: 1px; }
+            p:nth-child(even) { margin-top: 1px; }
+            p:nth-child(-n+ 2) { margin-bottom: 1px; }
+            p:nth-child(bogus) { margin-left: 1px; }
+            p:nth-child( 2n + 1 ) { margin-right: 1px; }
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let styled_dom = get_styled_node(&dom, &stylesheet);
+        let set = |length: LengthPercentageAuto| length == LengthPercentageAuto::Length(1.0);
+        let matched = |i: usize| {
+            let style = &styled_dom.children[0].children[i].style;
+            [
+                set(style.width),
+                set(style.height),
+                set(style.margin_top),
+                set(style.margin_bottom),
+                set(style.margin_left),
+                set(style.margin_right),
+            ]
+        };
+        assert_eq!(matched(1), [true, false, false, true, false, true]);
+        assert_eq!(matched(2), [false, false, true, true, false, false]);
+        assert_eq!(matched(4), [false, false, false, false, false, true]);
+        assert_eq!(matched(5), [false, false, true, false, false, false]);
+        assert_eq!(matched(6), [false, true, false, false, false, true]);
+
+        let usage = get_rule_usage(&dom, &stylesheet);
+        assert_eq!(usage.unused_rules(), [4]);
+    }
+
+    #[test]
+    fn invalidates_elements_affected_by_changes() {
+        let html = "<div><p></p>text<p></p></div>";
+        let css = "
+            .used, #used, [data-used] {}
+            p:hover {}
+            p:nth-child(2) {}
+            .menu p {}
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let div = &dom.children[0];
+        let node_id = |node: &dyn IDomNode| match node.get_node_type() {
+            NodeType::Element(element) => element.node_id(),
+            NodeType::Text(_) => unreachable!(),
+        };
+        let (div_id, p_id) = (node_id(div), node_id(&div.get_children()[0]));
+        let mut invalidator = StyleInvalidator::new(&[&stylesheet]);
+        let attribute = |name, old, new| StyleChange::Attribute { name, old, new };
+
+        invalidator.invalidate(p_id, attribute("class", Some("a used"), Some("used a b")));
+        invalidator.invalidate(p_id, attribute("id", None, Some("unused")));
+        invalidator.invalidate(p_id, attribute("data-unused", None, Some("")));
+        let hovered = ElementState {
+            hover: true,
+            ..Default::default()
+        };
+        let focused = ElementState {
+            focus: true,
+            ..Default::default()
+        };
+        invalidator.invalidate(
+            p_id,
+            StyleChange::State {
+                old: hovered,
+                new: ElementState {
+                    focus: true,
+                    ..hovered
+                },
+            },
+        );
+        assert!(!invalidator.needs_restyle());
+
+        invalidator.invalidate(p_id, attribute("class", Some("a"), Some("a used")));
+        assert_eq!(invalidator.invalid_nodes(), &HashSet::from([p_id]));
+        invalidator.clear();
+        invalidator.invalidate(p_id, attribute("id", Some("used"), None));
+        invalidator.invalidate(div_id, attribute("data-used", Some("1"), Some("2")));
+        assert_eq!(invalidator.invalid_nodes(), &HashSet::from([p_id, div_id]));
+        invalidator.clear();
+        invalidator.invalidate(p_id, attribute("style", None, Some("color: red")));
+        invalidator.invalidate(
+            p_id,
+            StyleChange::State {
+                old: focused,
+                new: hovered,
+            },
+        );
+        assert_eq!(invalidator.invalid_nodes(), &HashSet::from([p_id]));
+        invalidator.clear();
+
+        // Structural pseudo-classes invalidate every element sibling.
+        invalidator.invalidate(div_id, StyleChange::Children(div.get_children()));
+        let second_p = node_id(&div.get_children()[2]);
+        assert_eq!(
+            invalidator.invalid_nodes(),
+            &HashSet::from([div_id, p_id, second_p])
+        );
+        invalidator.clear();
+
+        // Classes of ancestors in descendant selectors restyle the element
+        // they're set on, along with its descendants.
+        invalidator.invalidate(div_id, attribute("class", None, Some("menu")));
+        assert_eq!(invalidator.invalid_nodes(), &HashSet::from([div_id]));
+
+        let mut invalidator = StyleInvalidator::new(&[]);
+        invalidator.invalidate(div_id, StyleChange::Children(div.get_children()));
+        assert_eq!(invalidator.invalid_nodes(), &HashSet::from([div_id]));
+    }
+
+    #[test]
+    fn substitutes_custom_properties() {
+        let html = "<div><p></p></div>";
+        let css = "
+            div {
+                --size: 10px;
+                --a: var(--b);
+                --b: var(--size);
+                --cycle-a: var(--cycle-b);
+                --cycle-b: var(--cycle-a, 1px);
+                --uses-cycle: var(--cycle-a, 3px);
+                --color: red;
+                color: blue;
+                width: var(--a);
+                margin: var(--size) 0;
+            }
+
+            p {
+                --size: 5px;
+                color: VAR(--color);
+                height: var(--cycle-b, 7px);
+                width: var(--missing, var(--size));
+                margin-left: var(--color);
+                opacity: 0.5;
+                opacity: var(--missing);
+                padding-top: var(--uses-cycle);
+            }
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let styled_dom = get_styled_node(&dom, &stylesheet);
+        let div = &styled_dom.children[0];
+        // Chains are followed, and properties in a cycle are undefined.
+        assert_eq!(div.custom_properties.get("--a").unwrap(), "10px");
+        assert!(div.custom_properties.get("--cycle-a").is_none());
+        assert!(div.custom_properties.get("--cycle-b").is_none());
+        assert_eq!(div.custom_properties.get("--uses-cycle").unwrap(), "3px");
+        assert_eq!(div.style.width, LengthPercentageAuto::Length(10.0));
+        assert_eq!(div.style.margin_top, LengthPercentageAuto::Length(10.0));
+        assert_eq!(div.style.margin_right, LengthPercentageAuto::Length(0.0));
+
+        // Custom properties are inherited, and references are substituted
+        // with the element's own values.
+        let p = &div.children[0];
+        assert_eq!(p.custom_properties.get("--color").unwrap(), "red");
+        assert_eq!(p.custom_properties.get("--a").unwrap(), "10px");
+        assert_eq!(p.style.color, Color::from_keyword("red").unwrap());
+        assert_eq!(p.style.height, LengthPercentageAuto::Length(7.0));
+        assert_eq!(p.style.width, LengthPercentageAuto::Length(5.0));
+        assert_eq!(p.style.padding_top, LengthPercentage::Length(3.0));
+        // Invalid substitutions act as `unset`, rather than being dropped
+        // like declarations invalid at parse time.
+        assert_eq!(p.style.margin_left, LengthPercentageAuto::Length(0.0));
+        assert_eq!(p.style.opacity, 1.0);
+    }
+
+    #[test]
+    fn evaluates_media_queries_and_viewport_lengths() {
+        let media = MediaContext {
+            width: 1000.0,
+            height: 500.0,
+            device_pixel_ratio: 2.0,
+            color_scheme: ColorScheme::Dark,
+        };
+        for query in [
+            "screen",
+            "only screen and (min-width: 600px)",
+            "(max-height: 500px) and (orientation: landscape)",
+            "(min-resolution: 192dpi)",
+            "(prefers-color-scheme: dark)",
+            "not print",
+            "(min-width: 40em)",
+        ] {
+            assert!(media.matches(query), "{query}");
+        }
+        for query in [
+            "print",
+            "(max-width: 999px)",
+            "(orientation: portrait)",
+            "(resolution: 1dppx)",
+            "(prefers-color-scheme: light)",
+            "not screen and (min-width: 600px)",
+            "(hover: hover)",
+        ] {
+            assert!(!media.matches(query), "{query}");
+        }
+
+        let html = "<div><p></p></div>";
+        let css = "
+            div { width: 50vw; height: 10vh; margin-left: 10vmin; margin-right: 10vmax; }
+            @media (prefers-color-scheme: dark) {
+                p { color: white; }
+            }
+        ";
+        let stylesheet = CSSParser::new(css).parse();
+        let dom = HTMLParser::new(html).parse();
+        let styled_dom = cascade_with_states(&dom, &[&stylesheet], &ElementStates::new(), &media);
+        let div = &styled_dom.children[0];
+        assert_eq!(div.style.width, LengthPercentageAuto::Length(500.0));
+        assert_eq!(div.style.height, LengthPercentageAuto::Length(50.0));
+        assert_eq!(div.style.margin_left, LengthPercentageAuto::Length(50.0));
+        assert_eq!(div.style.margin_right, LengthPercentageAuto::Length(100.0));
+        let p = &div.children[0];
+        assert_eq!(p.style.color, Color::from_keyword("white").unwrap());
+        let light = cascade(&dom, &[&stylesheet]);
+        assert_eq!(
+            light.children[0].children[0].style.color,
+            Color::from_keyword("black").unwrap()
+        );
+    }
+}
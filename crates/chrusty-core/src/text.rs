@@ -0,0 +1,81 @@
+//! Shaping of text into positioned glyphs, for the fragments of text boxes
+//! that inline layout produces for each line they span.
+
+use crate::font::FontMetrics;
+
+/// A glyph placed along a run, `x` px from its start.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionedGlyph {
+    pub ch: char,
+    pub x: f32,
+}
+
+/// Glyphs of a single line of text, ready to be drawn.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct GlyphRun {
+    /// In px.
+    pub font_size: f32,
+    /// Distance from the top of the line to the baseline, in px.
+    pub baseline: f32,
+    pub glyphs: Vec<PositionedGlyph>,
+    /// Advance of the whole run, in px.
+    pub width: f32,
+}
+
+/// Distance from the top of a line `line_height` tall to the baseline of
+/// text set in `font` at `font_size`. The half-leading is split above and
+/// below the glyphs, as in CSS.
+pub fn baseline(font: &dyn FontMetrics, font_size: f32, line_height: f32) -> f32 {
+    let ascent = font.ascent(font_size);
+    let descent = font.descent(font_size);
+    (line_height - ascent - descent) / 2.0 + ascent
+}
+
+impl GlyphRun {
+    /// An empty run for text set in `font` at `font_size` on lines
+    /// `line_height` tall.
+    pub fn new(font: &dyn FontMetrics, font_size: f32, line_height: f32) -> GlyphRun {
+        GlyphRun {
+            font_size,
+            baseline: baseline(font, font_size, line_height),
+            glyphs: vec![],
+            width: 0.0,
+        }
+    }
+
+    /// Appends the glyphs of `text`, placed by their advances. Characters map
+    /// one to one to glyphs, with no kerning or ligatures.
+    pub fn push_str(&mut self, font: &dyn FontMetrics, text: &str) {
+        for ch in text.chars() {
+            self.glyphs.push(PositionedGlyph { ch, x: self.width });
+            self.width += font.advance(ch, self.font_size);
+        }
+    }
+
+    /// The text of the run.
+    pub fn text(&self) -> String {
+        self.glyphs.iter().map(|glyph| glyph.ch).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        font::{BitmapFont, FontMetrics},
+        text::GlyphRun,
+    };
+
+    #[test]
+    fn shapes_runs() {
+        let font = BitmapFont;
+        // 12px wide glyphs, 14px above the baseline and 2px below it.
+        let mut run = GlyphRun::new(&font, 16.0, 20.0);
+        assert_eq!(run.baseline, 16.0);
+        run.push_str(&font, "ab");
+        run.push_str(&font, " c");
+        assert_eq!(run.text(), "ab c");
+        let positions: Vec<f32> = run.glyphs.iter().map(|glyph| glyph.x).collect();
+        assert_eq!(positions, [0.0, 12.0, 24.0, 36.0]);
+        assert_eq!(run.width, font.measure("ab c", 16.0));
+    }
+}
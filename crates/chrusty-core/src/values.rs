@@ -0,0 +1,1017 @@
+//! Typed values of longhand properties. The parser checks each declaration
+//! against what its property accepts, so invalid ones never reach styling.
+
+use std::{
+    borrow::Cow,
+    fmt::{self, Formatter},
+};
+
+use crate::{
+    color::Color,
+    cssom::{CSSProperty, CSSValue, MediaContext, Unit},
+    parser::{split_tokens, split_top_level, CSSParser, IParser},
+    style::{
+        AlignItems, BorderStyle, BoxSizing, Clear, Direction, Display, FlexDirection, Float,
+        FontStyle, JustifyContent, Overflow, OverflowWrap, Position, VerticalAlign, Visibility,
+        WhiteSpace, WordBreak, DEFAULT_FONT_SIZE,
+    },
+    utils::unquote,
+};
+
+/// Font sizes and viewport size in px that `em`, `rem` and viewport
+/// lengths are converted against.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LengthContext {
+    /// The element's font size, or its parent's when computing `font-size`.
+    pub font_size: f32,
+    pub root_font_size: f32,
+    pub viewport_width: f32,
+    pub viewport_height: f32,
+}
+
+impl Default for LengthContext {
+    fn default() -> Self {
+        let media = MediaContext::default();
+        LengthContext {
+            font_size: DEFAULT_FONT_SIZE,
+            root_font_size: DEFAULT_FONT_SIZE,
+            viewport_width: media.width,
+            viewport_height: media.height,
+        }
+    }
+}
+
+impl LengthContext {
+    /// `value` with font-relative and viewport lengths converted to px.
+    fn absolutize<'v>(&self, value: &'v CSSValue) -> Cow<'v, CSSValue> {
+        let CSSValue::Dimension(length, unit) = value else {
+            return Cow::Borrowed(value);
+        };
+        let (width, height) = (self.viewport_width, self.viewport_height);
+        let reference = match unit {
+            Unit::Px | Unit::Percent => return Cow::Borrowed(value),
+            Unit::Em => self.font_size,
+            Unit::Rem => self.root_font_size,
+            Unit::Vw => width / 100.0,
+            Unit::Vh => height / 100.0,
+            Unit::Vmin => width.min(height) / 100.0,
+            Unit::Vmax => width.max(height) / 100.0,
+        };
+        Cow::Owned(CSSValue::Dimension(length * reference, Unit::Px))
+    }
+}
+
+/// `<length-percentage> | auto`, e.g. `width` or `margin-top`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LengthPercentageAuto {
+    Length(f32),
+    Percent(f32),
+    Auto,
+}
+
+impl LengthPercentageAuto {
+    fn from_css_value(value: &CSSValue, allow_negative: bool) -> Option<LengthPercentageAuto> {
+        match value {
+            CSSValue::Auto => Some(LengthPercentageAuto::Auto),
+            _ => Some(
+                match LengthPercentage::from_css_value(value, allow_negative)? {
+                    LengthPercentage::Length(length) => LengthPercentageAuto::Length(length),
+                    LengthPercentage::Percent(percentage) => {
+                        LengthPercentageAuto::Percent(percentage)
+                    }
+                },
+            ),
+        }
+    }
+}
+
+/// `<length-percentage> | none`, e.g. `max-width`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LengthPercentageNone {
+    Length(f32),
+    Percent(f32),
+    None,
+}
+
+impl LengthPercentageNone {
+    fn from_css_value(value: &CSSValue) -> Option<LengthPercentageNone> {
+        match value {
+            CSSValue::Keyword(keyword) if keyword == "none" => Some(LengthPercentageNone::None),
+            _ => Some(match LengthPercentage::from_css_value(value, false)? {
+                LengthPercentage::Length(length) => LengthPercentageNone::Length(length),
+                LengthPercentage::Percent(percentage) => LengthPercentageNone::Percent(percentage),
+            }),
+        }
+    }
+}
+
+/// `<length-percentage>`, e.g. a corner radius.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LengthPercentage {
+    Length(f32),
+    Percent(f32),
+}
+
+impl LengthPercentage {
+    fn from_css_value(value: &CSSValue, allow_negative: bool) -> Option<LengthPercentage> {
+        let (value, typed) = match value {
+            CSSValue::Dimension(length, Unit::Px) => (*length, LengthPercentage::Length(*length)),
+            CSSValue::Dimension(percentage, Unit::Percent) => {
+                (*percentage, LengthPercentage::Percent(*percentage))
+            }
+            _ => return None,
+        };
+        (allow_negative || value >= 0.0).then_some(typed)
+    }
+}
+
+/// Size of a grid track, e.g. `100px`, `1fr` or `auto`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrackSize {
+    Length(f32),
+    Percent(f32),
+    /// A share of the space left by the other tracks.
+    Fraction(f32),
+    /// As large as the items in the track.
+    Auto,
+}
+
+impl TrackSize {
+    /// Parses a `grid-template-*` track list, or `none` for an empty one.
+    /// Lengths in other units than px are converted against `context`.
+    fn parse_list(list: &str, context: &LengthContext) -> Option<Vec<TrackSize>> {
+        if list.trim() == "none" {
+            return Some(vec![]);
+        }
+        let tracks: Option<Vec<TrackSize>> = list
+            .split_whitespace()
+            .map(|token| {
+                if token == "auto" {
+                    return Some(TrackSize::Auto);
+                }
+                let split = token
+                    .find(|c: char| c.is_alphabetic() || c == '%')
+                    .unwrap_or(token.len());
+                let (number, unit) = token.split_at(split);
+                let number: f32 = number.parse().ok()?;
+                match unit {
+                    "fr" if number >= 0.0 => Some(TrackSize::Fraction(number)),
+                    // Only zero lengths may be written without a unit.
+                    "" if number != 0.0 => None,
+                    unit => {
+                        let value = CSSValue::Dimension(number, Unit::from_suffix(unit));
+                        Some(
+                            match LengthPercentage::from_css_value(
+                                &context.absolutize(&value),
+                                false,
+                            )? {
+                                LengthPercentage::Length(length) => TrackSize::Length(length),
+                                LengthPercentage::Percent(percentage) => {
+                                    TrackSize::Percent(percentage)
+                                }
+                            },
+                        )
+                    }
+                }
+            })
+            .collect();
+        tracks.filter(|tracks| !tracks.is_empty())
+    }
+}
+
+/// `thin`, `medium`, `thick` or a length, in px.
+fn border_width(value: &CSSValue) -> Option<f32> {
+    match value {
+        CSSValue::Keyword(keyword) => match keyword.as_str() {
+            "thin" => Some(1.0),
+            "medium" => Some(3.0),
+            "thick" => Some(5.0),
+            _ => None,
+        },
+        _ => match LengthPercentage::from_css_value(value, false)? {
+            LengthPercentage::Length(length) => Some(length),
+            LengthPercentage::Percent(_) => None,
+        },
+    }
+}
+
+/// Absolute size keywords and lengths, with `smaller`, `larger` and
+/// percentages relative to the parent's font size.
+fn font_size(value: &CSSValue) -> Option<LengthPercentage> {
+    let CSSValue::Keyword(keyword) = value else {
+        return LengthPercentage::from_css_value(value, false);
+    };
+    Some(match keyword.as_str() {
+        "xx-small" => LengthPercentage::Length(9.0),
+        "x-small" => LengthPercentage::Length(10.0),
+        "small" => LengthPercentage::Length(13.0),
+        "medium" => LengthPercentage::Length(16.0),
+        "large" => LengthPercentage::Length(18.0),
+        "x-large" => LengthPercentage::Length(24.0),
+        "xx-large" => LengthPercentage::Length(32.0),
+        "xxx-large" => LengthPercentage::Length(48.0),
+        "smaller" => LengthPercentage::Percent(100.0 / 1.2),
+        "larger" => LengthPercentage::Percent(120.0),
+        _ => return None,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FontWeight {
+    /// From 1 to 1000, `normal` being 400 and `bold` 700.
+    Absolute(u16),
+    Bolder,
+    Lighter,
+}
+
+impl FontWeight {
+    /// The weight in numbers, with `bolder` and `lighter` taken relative to
+    /// the parent's weight as in the CSS Fonts table.
+    pub fn resolve(self, parent_weight: u16) -> u16 {
+        match self {
+            FontWeight::Absolute(weight) => weight,
+            FontWeight::Bolder => match parent_weight {
+                0..350 => 400,
+                350..550 => 700,
+                _ => 900,
+            },
+            FontWeight::Lighter => match parent_weight {
+                0..550 => 100,
+                550..750 => 400,
+                _ => 700,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum ImageValue {
+    #[default]
+    None,
+    Url(String),
+    Gradient(Gradient),
+}
+
+impl fmt::Display for ImageValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageValue::None => write!(f, "none"),
+            ImageValue::Url(url) => write!(f, "url({url:?})"),
+            ImageValue::Gradient(gradient) => write!(f, "{gradient}"),
+        }
+    }
+}
+
+/// Color a gradient passes through.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorStop {
+    pub color: Color,
+    /// Where along the gradient the color is, `None` to space it evenly
+    /// between the stops around it.
+    pub position: Option<LengthPercentage>,
+}
+
+/// Direction of a linear gradient.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GradientDirection {
+    /// In degrees, clockwise from pointing up.
+    Angle(f32),
+    /// Towards a corner of the box, e.g. `to top right`, given by the signs
+    /// of its x and y directions. The angle depends on the box's shape.
+    Corner(f32, f32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RadialShape {
+    Circle,
+    Ellipse,
+}
+
+/// `linear-gradient()` or `radial-gradient()`. Radial gradients are centered
+/// in the box and reach its farthest corner.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Gradient {
+    Linear(GradientDirection, Vec<ColorStop>),
+    Radial(RadialShape, Vec<ColorStop>),
+}
+
+impl Gradient {
+    /// Parses a gradient function, with lengths of stops in other units than
+    /// px converted against `context`.
+    fn parse(text: &str, context: &LengthContext) -> Option<Gradient> {
+        let text = text.trim();
+        let (name, arguments) = text.strip_suffix(')')?.split_once('(')?;
+        let arguments = split_top_level(arguments, |c| c == ',');
+        let (first, rest) = arguments.split_first()?;
+        let words: Vec<&str> = first.split_whitespace().collect();
+        let stops = |arguments: &[String]| {
+            let stops: Option<Vec<ColorStop>> = arguments
+                .iter()
+                .map(|argument| ColorStop::parse(argument, context))
+                .collect();
+            stops.filter(|stops| stops.len() >= 2)
+        };
+        match name.trim().to_ascii_lowercase().as_str() {
+            "linear-gradient" => {
+                let direction = match words[..] {
+                    ["to", side] => {
+                        let angle = match side {
+                            "top" => 0.0,
+                            "right" => 90.0,
+                            "bottom" => 180.0,
+                            "left" => 270.0,
+                            _ => return None,
+                        };
+                        Some(GradientDirection::Angle(angle))
+                    }
+                    ["to", first, second] => {
+                        let sign = |side| match side {
+                            "left" | "top" => Some(-1.0),
+                            "right" | "bottom" => Some(1.0),
+                            _ => None,
+                        };
+                        let (x, y) = match (first, second) {
+                            ("left" | "right", "top" | "bottom") => (first, second),
+                            ("top" | "bottom", "left" | "right") => (second, first),
+                            _ => return None,
+                        };
+                        Some(GradientDirection::Corner(sign(x)?, sign(y)?))
+                    }
+                    [angle] => parse_angle(angle).map(GradientDirection::Angle),
+                    _ => None,
+                };
+                Some(match direction {
+                    Some(direction) => Gradient::Linear(direction, stops(rest)?),
+                    None => Gradient::Linear(GradientDirection::Angle(180.0), stops(&arguments)?),
+                })
+            }
+            "radial-gradient" => {
+                let mut shape = None;
+                for word in &words {
+                    match *word {
+                        "circle" => shape = Some(RadialShape::Circle),
+                        "ellipse" => shape = Some(RadialShape::Ellipse),
+                        // Only the default size and position are supported.
+                        "farthest-corner" | "at" | "center" => {}
+                        _ => {
+                            return Some(Gradient::Radial(RadialShape::Ellipse, stops(&arguments)?))
+                        }
+                    }
+                }
+                Some(Gradient::Radial(
+                    shape.unwrap_or(RadialShape::Ellipse),
+                    stops(rest)?,
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl ColorStop {
+    /// Parses a color with an optional position, e.g. `red 50%`.
+    fn parse(text: &str, context: &LengthContext) -> Option<ColorStop> {
+        let tokens = split_tokens(text);
+        let value = |token: &String| CSSParser::new(token).parse_value().ok();
+        let (color, position) = match &tokens[..] {
+            [color] => (value(color)?, None),
+            [color, position] => (value(color)?, Some(value(position)?)),
+            _ => return None,
+        };
+        let position = match position {
+            Some(position) => Some(LengthPercentage::from_css_value(
+                &context.absolutize(&position),
+                true,
+            )?),
+            None => None,
+        };
+        Some(ColorStop {
+            color: Color::from_css_value(&color)?,
+            position,
+        })
+    }
+}
+
+/// An angle in `deg`, `grad`, `rad` or `turn`, in degrees.
+fn parse_angle(angle: &str) -> Option<f32> {
+    let split = angle
+        .find(|c: char| c.is_alphabetic())
+        .unwrap_or(angle.len());
+    let (number, unit) = angle.split_at(split);
+    let number: f32 = number.parse().ok()?;
+    match unit {
+        "deg" => Some(number),
+        "grad" => Some(number * 0.9),
+        "rad" => Some(number.to_degrees()),
+        "turn" => Some(number * 360.0),
+        // Only zero angles may be written without a unit.
+        "" if number == 0.0 => Some(0.0),
+        _ => None,
+    }
+}
+
+impl fmt::Display for Gradient {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let stops = match self {
+            Gradient::Linear(direction, stops) => {
+                match direction {
+                    GradientDirection::Angle(angle) => write!(f, "linear-gradient({angle}deg")?,
+                    GradientDirection::Corner(x, y) => {
+                        let y = if *y < 0.0 { "top" } else { "bottom" };
+                        let x = if *x < 0.0 { "left" } else { "right" };
+                        write!(f, "linear-gradient(to {y} {x}")?
+                    }
+                }
+                stops
+            }
+            Gradient::Radial(shape, stops) => {
+                let shape = match shape {
+                    RadialShape::Circle => "circle",
+                    RadialShape::Ellipse => "ellipse",
+                };
+                write!(f, "radial-gradient({shape}")?;
+                stops
+            }
+        };
+        for stop in stops {
+            write!(f, ", {}", stop.color)?;
+            match stop.position {
+                Some(LengthPercentage::Length(length)) => write!(f, " {length}px")?,
+                Some(LengthPercentage::Percent(percentage)) => write!(f, " {percentage}%")?,
+                None => {}
+            }
+        }
+        write!(f, ")")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ZIndex {
+    Auto,
+    Integer(i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineHeight {
+    Normal,
+    /// Multiplier of the font size.
+    Number(f32),
+    Length(f32),
+    Percent(f32),
+}
+
+/// `inherit`, `initial` and `unset`, which every property accepts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CSSWideKeyword {
+    Inherit,
+    Initial,
+    Unset,
+}
+
+impl CSSWideKeyword {
+    pub fn from_keyword(keyword: &str) -> Option<CSSWideKeyword> {
+        let keyword = keyword.trim();
+        if keyword.eq_ignore_ascii_case("inherit") {
+            Some(CSSWideKeyword::Inherit)
+        } else if keyword.eq_ignore_ascii_case("initial") {
+            Some(CSSWideKeyword::Initial)
+        } else if keyword.eq_ignore_ascii_case("unset") {
+            Some(CSSWideKeyword::Unset)
+        } else {
+            None
+        }
+    }
+
+    /// `unset` acts as `inherit` on inherited properties and as `initial` on
+    /// the others.
+    pub fn resolve(self, is_inherited: bool) -> CSSWideKeyword {
+        match (self, is_inherited) {
+            (CSSWideKeyword::Unset, true) => CSSWideKeyword::Inherit,
+            (CSSWideKeyword::Unset, false) => CSSWideKeyword::Initial,
+            (keyword, _) => keyword,
+        }
+    }
+}
+
+/// The typed value of a declaration, by the kind of value its property takes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LonghandValue {
+    /// A CSS-wide keyword, resolved when values are computed.
+    CSSWide(CSSWideKeyword),
+    LengthPercentageAuto(LengthPercentageAuto),
+    LengthPercentageNone(LengthPercentageNone),
+    LengthPercentage(LengthPercentage),
+    /// A length in px, e.g. a border width.
+    Length(f32),
+    Color(Color),
+    /// `currentcolor`, the element's own `color`.
+    CurrentColor,
+    Image(ImageValue),
+    /// Keywords kept as written, e.g. `background-repeat: no-repeat`.
+    Keyword(String),
+    Display(Display),
+    Position(Position),
+    Float(Float),
+    Clear(Clear),
+    FlexDirection(FlexDirection),
+    JustifyContent(JustifyContent),
+    AlignItems(AlignItems),
+    /// A non-negative number, such as a flex factor.
+    Number(f32),
+    BoxSizing(BoxSizing),
+    Overflow(Overflow),
+    Visibility(Visibility),
+    Direction(Direction),
+    VerticalAlign(VerticalAlign),
+    WhiteSpace(WhiteSpace),
+    OverflowWrap(OverflowWrap),
+    WordBreak(WordBreak),
+    BorderStyle(BorderStyle),
+    FontStyle(FontStyle),
+    FontWeight(FontWeight),
+    ZIndex(ZIndex),
+    LineHeight(LineHeight),
+    /// Opacity from 0 to 1.
+    Opacity(f32),
+    FontFamily(Vec<String>),
+    TrackList(Vec<TrackSize>),
+}
+
+impl CSSProperty {
+    /// Checks `value` against what the property accepts and converts it to
+    /// its typed form. `None` means the declaration is invalid. Unknown
+    /// properties have no typed value. `em` and `rem` lengths are taken
+    /// against the default font size, see `parse_longhand_in`.
+    pub fn parse_longhand(&self, value: &CSSValue) -> Option<LonghandValue> {
+        self.parse_longhand_in(value, &LengthContext::default())
+    }
+
+    /// Like `parse_longhand`, with `em` and `rem` lengths converted to px
+    /// against the font sizes of `context`.
+    pub fn parse_longhand_in(
+        &self,
+        value: &CSSValue,
+        context: &LengthContext,
+    ) -> Option<LonghandValue> {
+        let value = &*context.absolutize(value);
+        if let CSSValue::Keyword(keyword) = value {
+            if let Some(keyword) = CSSWideKeyword::from_keyword(keyword) {
+                return match self {
+                    CSSProperty::Custom(_) | CSSProperty::Unknown(_) => None,
+                    _ => Some(LonghandValue::CSSWide(keyword)),
+                };
+            }
+        }
+        match self {
+            CSSProperty::Width
+            | CSSProperty::Height
+            | CSSProperty::MinWidth
+            | CSSProperty::MinHeight
+            | CSSProperty::FlexBasis => LengthPercentageAuto::from_css_value(value, false)
+                .map(LonghandValue::LengthPercentageAuto),
+            CSSProperty::MaxWidth | CSSProperty::MaxHeight => {
+                LengthPercentageNone::from_css_value(value).map(LonghandValue::LengthPercentageNone)
+            }
+            CSSProperty::Top
+            | CSSProperty::Right
+            | CSSProperty::Bottom
+            | CSSProperty::Left
+            | CSSProperty::MarginTop
+            | CSSProperty::MarginRight
+            | CSSProperty::MarginBottom
+            | CSSProperty::MarginLeft => LengthPercentageAuto::from_css_value(value, true)
+                .map(LonghandValue::LengthPercentageAuto),
+            CSSProperty::BorderTopLeftRadius
+            | CSSProperty::BorderTopRightRadius
+            | CSSProperty::BorderBottomRightRadius
+            | CSSProperty::BorderBottomLeftRadius => {
+                LengthPercentage::from_css_value(value, false).map(LonghandValue::LengthPercentage)
+            }
+            CSSProperty::PaddingTop
+            | CSSProperty::PaddingRight
+            | CSSProperty::PaddingBottom
+            | CSSProperty::PaddingLeft => {
+                LengthPercentage::from_css_value(value, false).map(LonghandValue::LengthPercentage)
+            }
+            CSSProperty::BorderTopWidth
+            | CSSProperty::BorderRightWidth
+            | CSSProperty::BorderBottomWidth
+            | CSSProperty::BorderLeftWidth => border_width(value).map(LonghandValue::Length),
+            CSSProperty::BorderTopStyle
+            | CSSProperty::BorderRightStyle
+            | CSSProperty::BorderBottomStyle
+            | CSSProperty::BorderLeftStyle => match value {
+                CSSValue::Keyword(keyword) => {
+                    BorderStyle::from_keyword(keyword).map(LonghandValue::BorderStyle)
+                }
+                _ => None,
+            },
+            CSSProperty::BorderTopColor
+            | CSSProperty::BorderRightColor
+            | CSSProperty::BorderBottomColor
+            | CSSProperty::BorderLeftColor => match value {
+                CSSValue::Keyword(keyword) if keyword.eq_ignore_ascii_case("currentcolor") => {
+                    Some(LonghandValue::CurrentColor)
+                }
+                _ => Color::from_css_value(value).map(LonghandValue::Color),
+            },
+            CSSProperty::Color | CSSProperty::BackgroundColor => {
+                Color::from_css_value(value).map(LonghandValue::Color)
+            }
+            CSSProperty::BackgroundImage => match value {
+                CSSValue::Url(url) => Some(LonghandValue::Image(ImageValue::Url(url.clone()))),
+                CSSValue::Keyword(keyword) if keyword == "none" => {
+                    Some(LonghandValue::Image(ImageValue::None))
+                }
+                CSSValue::Keyword(keyword) => Gradient::parse(keyword, context)
+                    .map(|gradient| LonghandValue::Image(ImageValue::Gradient(gradient))),
+                _ => None,
+            },
+            CSSProperty::BackgroundRepeat => match value {
+                CSSValue::Keyword(keyword)
+                    if matches!(
+                        keyword.as_str(),
+                        "repeat" | "repeat-x" | "repeat-y" | "no-repeat" | "space" | "round"
+                    ) =>
+                {
+                    Some(LonghandValue::Keyword(keyword.clone()))
+                }
+                _ => None,
+            },
+            CSSProperty::BackgroundPosition => match value {
+                CSSValue::Keyword(_) | CSSValue::Dimension(..) => {
+                    Some(LonghandValue::Keyword(value.to_string()))
+                }
+                _ => None,
+            },
+            CSSProperty::Display => match value {
+                CSSValue::Keyword(keyword) => {
+                    Display::from_keyword(keyword).map(LonghandValue::Display)
+                }
+                _ => None,
+            },
+            CSSProperty::Position => match value {
+                CSSValue::Keyword(keyword) => {
+                    Position::from_keyword(keyword).map(LonghandValue::Position)
+                }
+                _ => None,
+            },
+            CSSProperty::Float => match value {
+                CSSValue::Keyword(keyword) => {
+                    Float::from_keyword(keyword).map(LonghandValue::Float)
+                }
+                _ => None,
+            },
+            CSSProperty::Clear => match value {
+                CSSValue::Keyword(keyword) => {
+                    Clear::from_keyword(keyword).map(LonghandValue::Clear)
+                }
+                _ => None,
+            },
+            CSSProperty::FlexDirection => match value {
+                CSSValue::Keyword(keyword) => {
+                    FlexDirection::from_keyword(keyword).map(LonghandValue::FlexDirection)
+                }
+                _ => None,
+            },
+            CSSProperty::JustifyContent => match value {
+                CSSValue::Keyword(keyword) => {
+                    JustifyContent::from_keyword(keyword).map(LonghandValue::JustifyContent)
+                }
+                _ => None,
+            },
+            CSSProperty::AlignItems => match value {
+                CSSValue::Keyword(keyword) => {
+                    AlignItems::from_keyword(keyword).map(LonghandValue::AlignItems)
+                }
+                _ => None,
+            },
+            CSSProperty::FlexGrow | CSSProperty::FlexShrink => match value {
+                CSSValue::Number(factor) if *factor >= 0.0 => Some(LonghandValue::Number(*factor)),
+                _ => None,
+            },
+            CSSProperty::BoxSizing => match value {
+                CSSValue::Keyword(keyword) => {
+                    BoxSizing::from_keyword(keyword).map(LonghandValue::BoxSizing)
+                }
+                _ => None,
+            },
+            CSSProperty::Overflow => match value {
+                CSSValue::Auto => Some(LonghandValue::Overflow(Overflow::Auto)),
+                CSSValue::Keyword(keyword) => {
+                    Overflow::from_keyword(keyword).map(LonghandValue::Overflow)
+                }
+                _ => None,
+            },
+            CSSProperty::Visibility => match value {
+                CSSValue::Keyword(keyword) => {
+                    Visibility::from_keyword(keyword).map(LonghandValue::Visibility)
+                }
+                _ => None,
+            },
+            CSSProperty::Direction => match value {
+                CSSValue::Keyword(keyword) => {
+                    Direction::from_keyword(keyword).map(LonghandValue::Direction)
+                }
+                _ => None,
+            },
+            CSSProperty::VerticalAlign => match value {
+                CSSValue::Keyword(keyword) => {
+                    VerticalAlign::from_keyword(keyword).map(LonghandValue::VerticalAlign)
+                }
+                _ => None,
+            },
+            CSSProperty::WhiteSpace => match value {
+                CSSValue::Keyword(keyword) => {
+                    WhiteSpace::from_keyword(keyword).map(LonghandValue::WhiteSpace)
+                }
+                _ => None,
+            },
+            CSSProperty::OverflowWrap => match value {
+                CSSValue::Keyword(keyword) => {
+                    OverflowWrap::from_keyword(keyword).map(LonghandValue::OverflowWrap)
+                }
+                _ => None,
+            },
+            CSSProperty::WordBreak => match value {
+                CSSValue::Keyword(keyword) => {
+                    WordBreak::from_keyword(keyword).map(LonghandValue::WordBreak)
+                }
+                _ => None,
+            },
+            CSSProperty::ZIndex => match value {
+                CSSValue::Auto => Some(LonghandValue::ZIndex(ZIndex::Auto)),
+                CSSValue::Integer(z_index) => {
+                    Some(LonghandValue::ZIndex(ZIndex::Integer(*z_index)))
+                }
+                _ => None,
+            },
+            CSSProperty::LineHeight => Some(LonghandValue::LineHeight(match value {
+                CSSValue::Keyword(keyword) if keyword == "normal" => LineHeight::Normal,
+                CSSValue::Number(factor) if *factor >= 0.0 => LineHeight::Number(*factor),
+                _ => match LengthPercentage::from_css_value(value, false)? {
+                    LengthPercentage::Length(length) => LineHeight::Length(length),
+                    LengthPercentage::Percent(percentage) => LineHeight::Percent(percentage),
+                },
+            })),
+            CSSProperty::Opacity => match value {
+                CSSValue::Number(opacity) => Some(LonghandValue::Opacity(opacity.clamp(0.0, 1.0))),
+                CSSValue::Dimension(percentage, Unit::Percent) => {
+                    Some(LonghandValue::Opacity((percentage / 100.0).clamp(0.0, 1.0)))
+                }
+                _ => None,
+            },
+            CSSProperty::FontSize => font_size(value).map(LonghandValue::LengthPercentage),
+            CSSProperty::FontStyle => match value {
+                CSSValue::Keyword(keyword) => {
+                    FontStyle::from_keyword(keyword).map(LonghandValue::FontStyle)
+                }
+                _ => None,
+            },
+            CSSProperty::FontWeight => Some(LonghandValue::FontWeight(match value {
+                CSSValue::Number(weight) if (1.0..=1000.0).contains(weight) => {
+                    FontWeight::Absolute(weight.round() as u16)
+                }
+                CSSValue::Keyword(keyword) => match keyword.as_str() {
+                    "normal" => FontWeight::Absolute(400),
+                    "bold" => FontWeight::Absolute(700),
+                    "bolder" => FontWeight::Bolder,
+                    "lighter" => FontWeight::Lighter,
+                    _ => return None,
+                },
+                _ => return None,
+            })),
+            CSSProperty::GridTemplateColumns | CSSProperty::GridTemplateRows => match value {
+                CSSValue::Keyword(list) => {
+                    TrackSize::parse_list(list, context).map(LonghandValue::TrackList)
+                }
+                _ => None,
+            },
+            CSSProperty::FontFamily => match value {
+                CSSValue::Keyword(families) => {
+                    let families: Vec<String> = families
+                        .split(',')
+                        .map(|family| unquote(family.trim()).to_string())
+                        .collect();
+                    // An empty entry, e.g. from `a,,b`, invalidates the list.
+                    (!families.iter().any(String::is_empty))
+                        .then_some(LonghandValue::FontFamily(families))
+                }
+                _ => None,
+            },
+            CSSProperty::Custom(_) | CSSProperty::Unknown(_) => None,
+        }
+    }
+
+    /// The property's initial value, which `initial` stands for. Unknown
+    /// properties have none.
+    pub fn default_value(&self) -> Option<CSSValue> {
+        let keyword = |keyword: &str| CSSValue::Keyword(keyword.to_string());
+        let zero = CSSValue::Dimension(0.0, Unit::Px);
+        Some(match self {
+            CSSProperty::BackgroundColor => CSSValue::Color(Color::TRANSPARENT),
+            CSSProperty::BackgroundImage
+            | CSSProperty::MaxWidth
+            | CSSProperty::MaxHeight
+            | CSSProperty::Float
+            | CSSProperty::Clear
+            | CSSProperty::GridTemplateColumns
+            | CSSProperty::GridTemplateRows => keyword("none"),
+            CSSProperty::BackgroundRepeat => keyword("repeat"),
+            CSSProperty::BackgroundPosition => keyword("0% 0%"),
+            CSSProperty::Color => CSSValue::Color(Color::BLACK),
+            CSSProperty::Width
+            | CSSProperty::Height
+            | CSSProperty::MinWidth
+            | CSSProperty::MinHeight
+            | CSSProperty::Top
+            | CSSProperty::Right
+            | CSSProperty::Bottom
+            | CSSProperty::Left
+            | CSSProperty::FlexBasis
+            | CSSProperty::ZIndex => CSSValue::Auto,
+            CSSProperty::MarginTop
+            | CSSProperty::MarginRight
+            | CSSProperty::MarginBottom
+            | CSSProperty::MarginLeft
+            | CSSProperty::PaddingTop
+            | CSSProperty::PaddingRight
+            | CSSProperty::PaddingBottom
+            | CSSProperty::PaddingLeft
+            | CSSProperty::BorderTopLeftRadius
+            | CSSProperty::BorderTopRightRadius
+            | CSSProperty::BorderBottomRightRadius
+            | CSSProperty::BorderBottomLeftRadius => zero,
+            CSSProperty::Display => keyword("inline"),
+            CSSProperty::Position => keyword("static"),
+            CSSProperty::BoxSizing => keyword("content-box"),
+            CSSProperty::Overflow | CSSProperty::Visibility => keyword("visible"),
+            CSSProperty::Direction => keyword("ltr"),
+            CSSProperty::VerticalAlign => keyword("baseline"),
+            CSSProperty::WhiteSpace | CSSProperty::OverflowWrap | CSSProperty::WordBreak => {
+                keyword("normal")
+            }
+            CSSProperty::LineHeight | CSSProperty::FontStyle | CSSProperty::FontWeight => {
+                keyword("normal")
+            }
+            CSSProperty::Opacity | CSSProperty::FlexShrink => CSSValue::Number(1.0),
+            CSSProperty::FlexGrow => CSSValue::Number(0.0),
+            CSSProperty::FlexDirection => keyword("row"),
+            CSSProperty::JustifyContent => keyword("normal"),
+            CSSProperty::AlignItems => keyword("normal"),
+            CSSProperty::BorderTopWidth
+            | CSSProperty::BorderRightWidth
+            | CSSProperty::BorderBottomWidth
+            | CSSProperty::BorderLeftWidth
+            | CSSProperty::FontSize => keyword("medium"),
+            CSSProperty::BorderTopStyle
+            | CSSProperty::BorderRightStyle
+            | CSSProperty::BorderBottomStyle
+            | CSSProperty::BorderLeftStyle => keyword("none"),
+            CSSProperty::BorderTopColor
+            | CSSProperty::BorderRightColor
+            | CSSProperty::BorderBottomColor
+            | CSSProperty::BorderLeftColor => keyword("currentcolor"),
+            // Up to the user agent; no font is installed under this name, so
+            // the default font is used.
+            CSSProperty::FontFamily => keyword("serif"),
+            // Custom properties are initially undefined.
+            CSSProperty::Custom(_) | CSSProperty::Unknown(_) => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        color::Color,
+        cssom::{CSSProperty, CSSValue, Unit},
+        style::Display,
+        values::{
+            CSSWideKeyword, ColorStop, Gradient, GradientDirection, ImageValue, LengthPercentage,
+            LengthPercentageAuto, LengthPercentageNone, LineHeight, LonghandValue, RadialShape,
+            TrackSize,
+        },
+    };
+
+    #[test]
+    fn validates_values_per_property() {
+        let px = |length| CSSValue::Dimension(length, Unit::Px);
+        let keyword = |keyword: &str| CSSValue::Keyword(keyword.to_string());
+        assert_eq!(
+            CSSProperty::Width.parse_longhand(&CSSValue::Auto),
+            Some(LonghandValue::LengthPercentageAuto(
+                LengthPercentageAuto::Auto
+            ))
+        );
+        assert_eq!(CSSProperty::Width.parse_longhand(&px(-10.0)), None);
+        assert_eq!(
+            CSSProperty::MarginLeft.parse_longhand(&px(-10.0)),
+            Some(LonghandValue::LengthPercentageAuto(
+                LengthPercentageAuto::Length(-10.0)
+            ))
+        );
+        assert_eq!(CSSProperty::Width.parse_longhand(&keyword("wide")), None);
+        assert_eq!(
+            CSSProperty::MaxWidth.parse_longhand(&keyword("none")),
+            Some(LonghandValue::LengthPercentageNone(
+                LengthPercentageNone::None
+            ))
+        );
+        assert_eq!(CSSProperty::MaxWidth.parse_longhand(&CSSValue::Auto), None);
+        assert_eq!(
+            CSSProperty::Display.parse_longhand(&keyword("inline-block")),
+            Some(LonghandValue::Display(Display::InlineBlock))
+        );
+        assert_eq!(CSSProperty::Display.parse_longhand(&keyword("table")), None);
+        assert_eq!(
+            CSSProperty::GridTemplateColumns.parse_longhand(&keyword("100px 1.5fr auto 2em 0")),
+            Some(LonghandValue::TrackList(vec![
+                TrackSize::Length(100.0),
+                TrackSize::Fraction(1.5),
+                TrackSize::Auto,
+                TrackSize::Length(32.0),
+                TrackSize::Length(0.0),
+            ]))
+        );
+        assert_eq!(
+            CSSProperty::GridTemplateRows.parse_longhand(&keyword("none")),
+            Some(LonghandValue::TrackList(vec![]))
+        );
+        assert_eq!(
+            CSSProperty::GridTemplateRows.parse_longhand(&keyword("1fr -1fr")),
+            None
+        );
+        assert_eq!(
+            CSSProperty::LineHeight.parse_longhand(&CSSValue::Number(1.5)),
+            Some(LonghandValue::LineHeight(LineHeight::Number(1.5)))
+        );
+        assert_eq!(CSSProperty::Color.parse_longhand(&keyword("#12")), None);
+        assert_eq!(CSSProperty::Color.parse_longhand(&px(1.0)), None);
+        assert_eq!(CSSProperty::ZIndex.parse_longhand(&px(1.0)), None);
+        assert_eq!(
+            CSSProperty::Width.parse_longhand(&keyword("INHERIT")),
+            Some(LonghandValue::CSSWide(CSSWideKeyword::Inherit))
+        );
+        assert_eq!(
+            CSSProperty::Unknown("x".to_string()).parse_longhand(&keyword("unset")),
+            None
+        );
+    }
+
+    #[test]
+    fn parses_gradients() {
+        let gradient = |text: &str| {
+            let value = CSSValue::Keyword(text.to_string());
+            match CSSProperty::BackgroundImage.parse_longhand(&value) {
+                Some(LonghandValue::Image(ImageValue::Gradient(gradient))) => Some(gradient),
+                _ => None,
+            }
+        };
+        let (red, blue) = (
+            Color::from_keyword("red").unwrap(),
+            Color::from_keyword("blue").unwrap(),
+        );
+        let stop = |color, position| ColorStop { color, position };
+        assert_eq!(
+            gradient("linear-gradient(red, rgba(0, 0, 255, 1) 2em)"),
+            Some(Gradient::Linear(
+                GradientDirection::Angle(180.0),
+                vec![
+                    stop(red, None),
+                    stop(blue, Some(LengthPercentage::Length(32.0)))
+                ]
+            ))
+        );
+        let Some(Gradient::Linear(direction, _)) = gradient("linear-gradient(0.25turn, red, blue)")
+        else {
+            panic!("turns are angles")
+        };
+        assert_eq!(direction, GradientDirection::Angle(90.0));
+        let Some(Gradient::Linear(direction, _)) =
+            gradient("linear-gradient(to top right, red, blue)")
+        else {
+            panic!("corners are directions")
+        };
+        assert_eq!(direction, GradientDirection::Corner(1.0, -1.0));
+        assert_eq!(
+            gradient("radial-gradient(circle, red, blue 50%)"),
+            Some(Gradient::Radial(
+                RadialShape::Circle,
+                vec![
+                    stop(red, None),
+                    stop(blue, Some(LengthPercentage::Percent(50.0)))
+                ]
+            ))
+        );
+        assert_eq!(
+            gradient("radial-gradient(red, blue)").unwrap().to_string(),
+            "radial-gradient(ellipse, rgb(255, 0, 0), rgb(0, 0, 255))"
+        );
+
+        // A gradient needs two stops, and known directions.
+        assert_eq!(gradient("linear-gradient(red)"), None);
+        assert_eq!(gradient("linear-gradient(to middle, red, blue)"), None);
+        assert_eq!(gradient("conic-gradient(red, blue)"), None);
+    }
+}
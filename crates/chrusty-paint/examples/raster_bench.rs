@@ -0,0 +1,92 @@
+//! Times rasterizing long pages on one thread and on as many as the machine
+//! runs in parallel, or as given:
+//!
+//! ```sh
+//! cargo run --release -p chrusty-paint --example raster_bench [threads]
+//! ```
+
+use std::{
+    env, thread,
+    time::{Duration, Instant},
+};
+
+use chrusty_core::{
+    layout::{layout_tree, Dimensions, LayoutContext},
+    parser::{CSSParser, HTMLParser, IParser},
+    resource::Resources,
+    style::get_styled_node,
+};
+use chrusty_paint::{build_display_list, raster::Canvas, DisplayList};
+
+const CSS: &str = "
+    div.card {
+        padding: 8px;
+        margin-bottom: 8px;
+        border: 2px solid rgb(40, 40, 40);
+        border-radius: 12px;
+        background: linear-gradient(to bottom right, white, rgb(200, 220, 255));
+    }
+
+    p.badge {
+        width: 30%;
+        height: 20px;
+        background: rgba(255, 0, 0, 0.5);
+    }
+";
+
+const RUNS: u32 = 5;
+
+/// Many cards, each with a few lines of text.
+fn long_page(cards: usize) -> String {
+    let card = "<div class=\"card\"><p>lorem ipsum dolor sit amet consectetur</p>\
+                <p class=\"badge\"></p><p>adipiscing elit sed do eiusmod</p></div>";
+    format!("<div>{}</div>", card.repeat(cards))
+}
+
+/// Display list of `html` laid out `width` px wide, and its height.
+fn display_list(html: &str, width: f32) -> (DisplayList, u32) {
+    let stylesheet = CSSParser::new(CSS).parse();
+    let dom = HTMLParser::new(html).parse();
+    let styled_dom = get_styled_node(&dom, &stylesheet);
+    let mut viewport = Dimensions::default();
+    viewport.content.width = width;
+    let mut layout_root = layout_tree(&styled_dom, viewport, &LayoutContext::new());
+    layout_root.snap_to_pixels(1.0);
+    let height = layout_root.dimensions.margin_box().height.ceil() as u32;
+    (build_display_list(&layout_root, &Resources::new()), height)
+}
+
+/// Average time to rasterize `list` on a `width` by `height` canvas on
+/// `threads` threads.
+fn time_raster(list: &DisplayList, width: u32, height: u32, threads: usize) -> Duration {
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        let mut canvas = Canvas::new(width, height);
+        canvas.set_threads(threads);
+        canvas.paint(list);
+    }
+    start.elapsed() / RUNS
+}
+
+fn main() {
+    let threads = match env::args().nth(1) {
+        Some(threads) => threads.parse().expect("threads should be a number"),
+        None => thread::available_parallelism().map_or(1, |threads| threads.get()),
+    };
+    let pages = [
+        ("long (50 cards)", long_page(50)),
+        ("long (200 cards)", long_page(200)),
+    ];
+    println!(
+        "{:<24}{:>14}{:>14}",
+        "page",
+        "1 thread",
+        format!("{threads} threads")
+    );
+    for (name, html) in &pages {
+        let (list, height) = display_list(html, 1024.0);
+        let sequential = time_raster(&list, 1024, height, 1);
+        let parallel = time_raster(&list, 1024, height, threads);
+        println!("{name:<24}{sequential:>14.2?}{parallel:>14.2?}");
+    }
+}
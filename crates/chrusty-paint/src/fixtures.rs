@@ -0,0 +1,345 @@
+use std::fmt::Write;
+
+use chrusty_core::layout::Rect;
+
+/// Small deterministic PRNG (xorshift64*) so fixtures are reproducible from a seed.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn range(&mut self, min: u32, max: u32) -> u32 {
+        min + (self.next() % (max - min + 1) as u64) as u32
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FixtureOptions {
+    pub width: u32,
+    pub height: u32,
+    pub boxes: usize,
+    pub max_depth: usize,
+    /// Emit `z-index` on some boxes, for stacking implementations that support it.
+    pub z_index: bool,
+}
+
+impl Default for FixtureOptions {
+    fn default() -> Self {
+        FixtureOptions {
+            width: 800,
+            height: 600,
+            boxes: 12,
+            max_depth: 3,
+            z_index: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixtureBox {
+    pub id: usize,
+    pub parent: Option<usize>,
+    /// Offset from the parent box (or the viewport for top-level boxes).
+    pub left: u32,
+    pub top: u32,
+    /// Position and size in viewport coordinates.
+    pub rect: Rect,
+    pub color: (u8, u8, u8),
+    pub z_index: Option<i32>,
+}
+
+impl FixtureBox {
+    pub fn class(&self) -> String {
+        format!("b{}", self.id)
+    }
+
+    fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.rect.x
+            && x < self.rect.x + self.rect.width
+            && y >= self.rect.y
+            && y < self.rect.y + self.rect.height
+    }
+}
+
+/// Area of the viewport over which the expected top-most box doesn't change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub rect: Rect,
+    pub topmost: Option<usize>,
+}
+
+/// A synthetic document of absolutely positioned, overlapping boxes along
+/// with an oracle for the expected painting order.
+#[derive(Debug, Clone)]
+pub struct PaintOrderFixture {
+    pub width: u32,
+    pub height: u32,
+    pub boxes: Vec<FixtureBox>,
+}
+
+impl PaintOrderFixture {
+    pub fn generate(seed: u64, options: FixtureOptions) -> PaintOrderFixture {
+        let mut rng = Rng::new(seed);
+        let mut boxes: Vec<FixtureBox> = vec![];
+        let mut depths: Vec<usize> = vec![];
+        for id in 0..options.boxes {
+            let candidates: Vec<usize> = (0..boxes.len())
+                .filter(|i| depths[*i] + 1 < options.max_depth && boxes[*i].rect.width > 4.0)
+                .collect();
+            let parent = match rng.range(0, 2) {
+                0 if !candidates.is_empty() => {
+                    Some(candidates[rng.range(0, candidates.len() as u32 - 1) as usize])
+                }
+                _ => None,
+            };
+            let area = match parent {
+                Some(parent) => boxes[parent].rect,
+                None => Rect {
+                    x: 0.0,
+                    y: 0.0,
+                    width: options.width as f32,
+                    height: options.height as f32,
+                },
+            };
+            // Boxes are placed on whole pixels, so their areas stay integral.
+            let (area_width, area_height) = (area.width as u32, area.height as u32);
+            // Children may overhang their parent to exercise overflow painting.
+            let width = rng.range(1, (area_width * 3 / 4).max(1));
+            let height = rng.range(1, (area_height * 3 / 4).max(1));
+            let left = rng.range(0, area_width.saturating_sub(width / 2));
+            let top = rng.range(0, area_height.saturating_sub(height / 2));
+            let rect = Rect {
+                x: area.x + left as f32,
+                y: area.y + top as f32,
+                width: width as f32,
+                height: height as f32,
+            };
+            let z_index = match options.z_index && rng.range(0, 2) == 0 {
+                true => Some(rng.range(0, 6) as i32 - 3),
+                false => None,
+            };
+            let color = rng.next().to_be_bytes();
+            depths.push(parent.map_or(0, |parent| depths[parent] + 1));
+            boxes.push(FixtureBox {
+                id,
+                parent,
+                left,
+                top,
+                rect,
+                color: (color[0], color[1], color[2]),
+                z_index,
+            });
+        }
+        PaintOrderFixture {
+            width: options.width,
+            height: options.height,
+            boxes,
+        }
+    }
+
+    fn children_of(&self, parent: Option<usize>) -> impl Iterator<Item = &FixtureBox> {
+        self.boxes.iter().filter(move |b| b.parent == parent)
+    }
+
+    fn write_html(&self, parent: Option<usize>, html: &mut String) {
+        for b in self.children_of(parent) {
+            let _ = write!(html, "<div class=\"{}\">", b.class());
+            self.write_html(Some(b.id), html);
+            html.push_str("</div>");
+        }
+    }
+
+    pub fn html(&self) -> String {
+        let mut html = String::new();
+        self.write_html(None, &mut html);
+        html
+    }
+
+    pub fn css(&self) -> String {
+        let mut css = String::new();
+        for b in &self.boxes {
+            let _ = writeln!(css, "div.{} {{", b.class());
+            let _ = writeln!(css, "\tposition: absolute;");
+            let _ = writeln!(css, "\tleft: {}px;", b.left);
+            let _ = writeln!(css, "\ttop: {}px;", b.top);
+            let _ = writeln!(css, "\twidth: {}px;", b.rect.width);
+            let _ = writeln!(css, "\theight: {}px;", b.rect.height);
+            let (r, g, bl) = b.color;
+            let _ = writeln!(css, "\tbackground: #{:02x}{:02x}{:02x};", r, g, bl);
+            if let Some(z_index) = b.z_index {
+                let _ = writeln!(css, "\tz-index: {};", z_index);
+            }
+            let _ = writeln!(css, "}}");
+        }
+        css
+    }
+
+    /// Appends the stacking context rooted at `root` in CSS painting order:
+    /// negative z-index contexts, then z-index auto/0 in tree order, then
+    /// positive z-index contexts.
+    fn collect_paint_order(&self, root: Option<usize>, order: &mut Vec<usize>) {
+        let mut descendants = vec![];
+        self.collect_context_members(root, &mut descendants);
+        // Sorting is stable, so boxes with equal z-index keep tree order.
+        descendants.sort_by_key(|id| self.boxes[*id].z_index.unwrap_or(0));
+        if let Some(root) = root {
+            order.push(root);
+        }
+        for id in descendants {
+            match self.boxes[id].z_index {
+                Some(_) => self.collect_paint_order(Some(id), order),
+                None => order.push(id),
+            }
+        }
+    }
+
+    /// Descendants of `root` in tree order that belong to its stacking
+    /// context, without descending into nested stacking contexts.
+    fn collect_context_members(&self, root: Option<usize>, members: &mut Vec<usize>) {
+        for b in self.children_of(root) {
+            members.push(b.id);
+            if b.z_index.is_none() {
+                self.collect_context_members(Some(b.id), members);
+            }
+        }
+    }
+
+    /// Box ids from bottom-most to top-most.
+    pub fn paint_order(&self) -> Vec<usize> {
+        let mut order = vec![];
+        self.collect_paint_order(None, &mut order);
+        order
+    }
+
+    /// The oracle: id of the box expected to be visible at (x, y).
+    pub fn topmost_at(&self, x: f32, y: f32) -> Option<usize> {
+        self.paint_order()
+            .into_iter()
+            .rev()
+            .find(|id| self.boxes[*id].contains(x, y))
+    }
+
+    /// Splits the viewport along every box edge into regions with a single
+    /// expected top-most box.
+    pub fn regions(&self) -> Vec<Region> {
+        let edges = |start: fn(&Rect) -> f32, size: fn(&Rect) -> f32, limit: f32| {
+            let mut edges: Vec<f32> = vec![0.0, limit];
+            for b in &self.boxes {
+                edges.push(start(&b.rect).min(limit));
+                edges.push((start(&b.rect) + size(&b.rect)).min(limit));
+            }
+            edges.sort_by(f32::total_cmp);
+            edges.dedup();
+            edges
+        };
+        let xs = edges(|r| r.x, |r| r.width, self.width as f32);
+        let ys = edges(|r| r.y, |r| r.height, self.height as f32);
+        let order = self.paint_order();
+        let mut regions = vec![];
+        for y in ys.windows(2) {
+            for x in xs.windows(2) {
+                let topmost = order
+                    .iter()
+                    .rev()
+                    .find(|id| self.boxes[**id].contains(x[0], y[0]))
+                    .copied();
+                regions.push(Region {
+                    rect: Rect {
+                        x: x[0],
+                        y: y[0],
+                        width: x[1] - x[0],
+                        height: y[1] - y[0],
+                    },
+                    topmost,
+                });
+            }
+        }
+        regions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrusty_core::{
+        layout::Rect,
+        parser::{CSSParser, HTMLParser, IParser},
+    };
+
+    use crate::fixtures::{FixtureBox, FixtureOptions, PaintOrderFixture};
+
+    #[test]
+    fn generates_parseable_fixtures() {
+        let options = FixtureOptions {
+            z_index: true,
+            ..Default::default()
+        };
+        let fixture = PaintOrderFixture::generate(42, options);
+        assert_eq!(
+            fixture.html(),
+            PaintOrderFixture::generate(42, options).html()
+        );
+        assert!(fixture.boxes.iter().any(|b| b.parent.is_some()));
+
+        let stylesheet = CSSParser::new(&fixture.css()).parse();
+        assert_eq!(stylesheet.style_rules().len(), options.boxes);
+        let dom = HTMLParser::new(&fixture.html()).parse();
+        assert!(dom.to_string().contains("class='b0'"));
+
+        let mut order = fixture.paint_order();
+        order.sort();
+        assert_eq!(order, (0..options.boxes).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn oracle_follows_stacking_order() {
+        let mut fixture = PaintOrderFixture {
+            width: 800,
+            height: 600,
+            boxes: vec![],
+        };
+        let mut add = |parent, x, y, z_index| {
+            let id = fixture.boxes.len();
+            fixture.boxes.push(FixtureBox {
+                id,
+                parent,
+                left: x,
+                top: y,
+                rect: Rect {
+                    x: x as f32,
+                    y: y as f32,
+                    width: 100.0,
+                    height: 100.0,
+                },
+                color: (0, 0, 0),
+                z_index,
+            });
+        };
+        add(None, 0, 0, Some(1));
+        add(None, 50, 50, None);
+        add(Some(1), 60, 60, Some(-1));
+        add(Some(0), 80, 80, Some(5));
+
+        assert_eq!(fixture.paint_order(), vec![2, 1, 0, 3]);
+        assert_eq!(fixture.topmost_at(10.0, 10.0), Some(0));
+        assert_eq!(fixture.topmost_at(70.0, 70.0), Some(0));
+        assert_eq!(fixture.topmost_at(90.0, 90.0), Some(3));
+        assert_eq!(fixture.topmost_at(155.0, 65.0), Some(2));
+        assert_eq!(fixture.topmost_at(700.0, 500.0), None);
+
+        let regions = fixture.regions();
+        let area: f32 = regions.iter().map(|r| r.rect.width * r.rect.height).sum();
+        assert_eq!(area, 800.0 * 600.0);
+        assert!(regions
+            .iter()
+            .all(|r| fixture.topmost_at(r.rect.x, r.rect.y) == r.topmost));
+    }
+}
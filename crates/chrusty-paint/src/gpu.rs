@@ -0,0 +1,649 @@
+//! GPU backend, built with the `gpu` feature. Display lists are uploaded as
+//! textured quads: every item, or opacity group as a whole, is rasterized on
+//! the CPU into a texture of its own, and the quads are drawn with wgpu into
+//! an offscreen target. Textures are kept across frames for the items that
+//! didn't change, so scrolling only moves quads around.
+
+use std::collections::HashMap;
+
+use chrusty_core::{layout::Rect, resource::Image};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    raster::{painted_extent, Canvas},
+    DisplayCommand, DisplayItem, DisplayList, ItemId, Renderer, RoundedRect,
+};
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// Draws each quad from 4 vertices, as a strip, with its rect in clip space
+/// as the only instance attribute. The target is premultiplied, so blending
+/// quads over each other matches the canvas.
+const SHADER: &str = "
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, @location(0) rect: vec4<f32>) -> VertexOutput {
+    let corner = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(mix(rect.xy, rect.zw, corner), 0.0, 1.0);
+    out.uv = corner;
+    return out;
+}
+
+@group(0) @binding(0) var quad_texture: texture_2d<f32>;
+@group(0) @binding(1) var quad_sampler: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(quad_texture, quad_sampler, in.uv);
+    return vec4<f32>(color.rgb * color.a, color.a);
+}
+";
+
+/// Items painted into one texture: a paint item, or an opacity group, along
+/// with the clips it's in. Their rects are moved so the texture starts at
+/// the origin.
+struct Unit {
+    id: ItemId,
+    items: DisplayList,
+    /// Device pixels the texture covers in the target, as left, top, right
+    /// and bottom edges.
+    area: [u32; 4],
+}
+
+struct Quad {
+    id: ItemId,
+    items: DisplayList,
+    area: [u32; 4],
+    bind_group: wgpu::BindGroup,
+}
+
+/// Renderer drawing frames with wgpu into an offscreen texture, which
+/// embedders can copy to a window or read back with `to_image`.
+pub struct GpuRenderer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    target: wgpu::Texture,
+    scale: f32,
+    /// Quads of the frame shown, in paint order.
+    quads: Vec<Quad>,
+}
+
+impl GpuRenderer {
+    /// Renderer on the default adapter, sized 1 by 1 device pixel until
+    /// it's resized. `None` when no adapter or device is available.
+    pub fn new() -> Option<GpuRenderer> {
+        let instance = wgpu::Instance::default();
+        let adapter =
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))?;
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("chrusty"),
+                required_features: wgpu::Features::empty(),
+                required_limits: adapter.limits(),
+                memory_hints: wgpu::MemoryHints::default(),
+            },
+            None,
+        ))
+        .ok()?;
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("quads"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("quad"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("quads"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("quads"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                compilation_options: Default::default(),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x4],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: FORMAT,
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        // Textures cover whole device pixels, so they're sampled as is.
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+        let target = create_target(&device, 1, 1);
+        Some(GpuRenderer {
+            device,
+            queue,
+            pipeline,
+            bind_group_layout,
+            sampler,
+            target,
+            scale: 1.0,
+            quads: vec![],
+        })
+    }
+
+    /// Texture frames are rendered into, with premultiplied alpha.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.target
+    }
+
+    /// Largest width and height the target can be resized to, in device
+    /// pixels.
+    pub fn max_size(&self) -> u32 {
+        self.device.limits().max_texture_dimension_2d
+    }
+
+    fn create_quad(&self, unit: Unit) -> Quad {
+        let [left, top, right, bottom] = unit.area;
+        let mut canvas = Canvas::new(right - left, bottom - top);
+        canvas.set_scale(self.scale);
+        canvas.paint(&unit.items);
+        let image = canvas.to_image();
+        let texture = self.device.create_texture_with_data(
+            &self.queue,
+            &wgpu::TextureDescriptor {
+                label: Some("quad"),
+                size: wgpu::Extent3d {
+                    width: image.width,
+                    height: image.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: FORMAT,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            &image.pixels,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("quad"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+        Quad {
+            id: unit.id,
+            items: unit.items,
+            area: unit.area,
+            bind_group,
+        }
+    }
+}
+
+fn create_target(device: &wgpu::Device, width: u32, height: u32) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("target"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::COPY_SRC
+            | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
+
+/// Splits `list` into the units painted into textures of their own, for a
+/// target `width` by `height` device pixels with `scale` of them per px.
+/// Units out of the target, or of the clips they're in, are left out.
+fn units(list: &DisplayList, scale: f32, width: u32, height: u32) -> Vec<Unit> {
+    let target = Rect {
+        x: 0.0,
+        y: 0.0,
+        width: width as f32,
+        height: height as f32,
+    };
+    let mut units = vec![];
+    // Clips the items are in, along with the area they leave visible.
+    let mut clips: Vec<(ItemId, RoundedRect, Rect)> = vec![];
+    let mut i = 0;
+    while i < list.len() {
+        let item = &list[i];
+        let group = match &item.command {
+            DisplayCommand::PushClip(clip) => {
+                let visible = match clips.last() {
+                    Some((_, _, visible)) => visible.intersection(clip.rect),
+                    None => clip.rect,
+                };
+                clips.push((item.id, *clip, visible));
+                i += 1;
+                continue;
+            }
+            DisplayCommand::PopClip(_) => {
+                clips.pop();
+                i += 1;
+                continue;
+            }
+            DisplayCommand::PushOpacity(..) => {
+                let mut depth = 0;
+                let end = list[i..]
+                    .iter()
+                    .position(|item| {
+                        match item.command {
+                            DisplayCommand::PushOpacity(..) => depth += 1,
+                            DisplayCommand::PopOpacity(_) => depth -= 1,
+                            _ => {}
+                        }
+                        depth == 0
+                    })
+                    .map_or(list.len(), |end| i + end + 1);
+                &list[i..end]
+            }
+            _ => &list[i..i + 1],
+        };
+        i += group.len();
+
+        let extent = group
+            .iter()
+            .filter_map(|item| painted_extent(&item.command, item.command.rect(), 1.0))
+            .reduce(|extent, rect| extent.union(rect));
+        let Some(mut extent) = extent else {
+            continue;
+        };
+        if let Some((_, _, visible)) = clips.last() {
+            extent = extent.intersection(*visible);
+        }
+        let extent = extent.scaled(scale).intersection(target);
+        if !(extent.width > 0.0 && extent.height > 0.0) {
+            continue;
+        }
+        let area = [
+            extent.x.floor() as u32,
+            extent.y.floor() as u32,
+            (extent.x + extent.width).ceil() as u32,
+            (extent.y + extent.height).ceil() as u32,
+        ];
+        let (dx, dy) = (-(area[0] as f32) / scale, -(area[1] as f32) / scale);
+        let clip_item = |&(id, clip, _): &(ItemId, RoundedRect, Rect), push: bool| DisplayItem {
+            id,
+            command: match push {
+                true => DisplayCommand::PushClip(clip),
+                false => DisplayCommand::PopClip(clip),
+            },
+        };
+        let pushes: DisplayList = clips.iter().map(|clip| clip_item(clip, true)).collect();
+        let pops: DisplayList = clips
+            .iter()
+            .rev()
+            .map(|clip| clip_item(clip, false))
+            .collect();
+        let items = pushes
+            .iter()
+            .chain(group)
+            .chain(&pops)
+            .map(|item| DisplayItem {
+                id: item.id,
+                command: item
+                    .command
+                    .with_rect(item.command.rect().translated(dx, dy)),
+            })
+            .collect();
+        units.push(Unit {
+            id: item.id,
+            items,
+            area,
+        });
+    }
+    units
+}
+
+impl Renderer for GpuRenderer {
+    /// Sizes beyond `max_size` are clamped to it.
+    fn resize(&mut self, width: u32, height: u32, scale: f32) {
+        let max_size = self.max_size();
+        self.target = create_target(
+            &self.device,
+            width.clamp(1, max_size),
+            height.clamp(1, max_size),
+        );
+        self.scale = scale;
+        self.quads.clear();
+    }
+
+    /// Items that are the same as in the frame shown, relative to where
+    /// they're drawn, keep their textures. Everything else is rasterized
+    /// again.
+    fn render(&mut self, previous: Option<&DisplayList>, list: &DisplayList) {
+        let quads = std::mem::take(&mut self.quads);
+        let mut shown: HashMap<ItemId, Quad> = match previous {
+            Some(_) => quads.into_iter().map(|quad| (quad.id, quad)).collect(),
+            None => HashMap::new(),
+        };
+        let (width, height) = (self.target.width(), self.target.height());
+        for unit in units(list, self.scale, width, height) {
+            let size = |[left, top, right, bottom]: [u32; 4]| (right - left, bottom - top);
+            let quad = match shown.remove(&unit.id) {
+                // Moved, e.g. by scrolling, with the same content.
+                Some(quad) if quad.items == unit.items && size(quad.area) == size(unit.area) => {
+                    Quad {
+                        area: unit.area,
+                        ..quad
+                    }
+                }
+                _ => self.create_quad(unit),
+            };
+            self.quads.push(quad);
+        }
+
+        let rects: Vec<f32> = self
+            .quads
+            .iter()
+            .flat_map(|quad| {
+                let [left, top, right, bottom] = quad.area.map(|edge| edge as f32);
+                [
+                    left / width as f32 * 2.0 - 1.0,
+                    1.0 - top / height as f32 * 2.0,
+                    right / width as f32 * 2.0 - 1.0,
+                    1.0 - bottom / height as f32 * 2.0,
+                ]
+            })
+            .collect();
+        let instances = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("quads"),
+                contents: &rects
+                    .iter()
+                    .flat_map(|x| x.to_le_bytes())
+                    .collect::<Vec<_>>(),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+        let view = self
+            .target
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("quads"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            if !self.quads.is_empty() {
+                pass.set_pipeline(&self.pipeline);
+                pass.set_vertex_buffer(0, instances.slice(..));
+                for (i, quad) in self.quads.iter().enumerate() {
+                    pass.set_bind_group(0, &quad.bind_group, &[]);
+                    pass.draw(0..4, i as u32..i as u32 + 1);
+                }
+            }
+        }
+        self.queue.submit([encoder.finish()]);
+    }
+
+    fn to_image(&self) -> Image {
+        let (width, height) = (self.target.width(), self.target.height());
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let row_size = (width * 4).div_ceil(align) * align;
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: row_size as u64 * height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.copy_texture_to_buffer(
+            self.target.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(row_size),
+                    rows_per_image: Some(height),
+                },
+            },
+            self.target.size(),
+        );
+        self.queue.submit([encoder.finish()]);
+        let slice = buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        self.device.poll(wgpu::Maintain::Wait);
+
+        // The target has premultiplied alpha, images have straight alpha.
+        let data = slice.get_mapped_range();
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for row in data.chunks(row_size as usize) {
+            for pixel in row[..width as usize * 4].chunks(4) {
+                let alpha = pixel[3] as u32;
+                let unpremultiply = |channel: u8| match alpha {
+                    0 => 0,
+                    _ => ((channel as u32 * 255 + alpha / 2) / alpha).min(255) as u8,
+                };
+                pixels.extend([pixel[0], pixel[1], pixel[2]].map(unpremultiply));
+                pixels.push(alpha as u8);
+            }
+        }
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrusty_core::{
+        layout::{layout_tree, Dimensions, LayoutBox, LayoutContext, Rect},
+        parser::{CSSParser, HTMLParser, IParser},
+        resource::{Image, Resources},
+        style::get_styled_node,
+    };
+
+    use crate::{
+        build_display_list, gpu::GpuRenderer, raster::Canvas, Color, DisplayCommand, DisplayItem,
+        DisplayList, Renderer,
+    };
+
+    /// Lays out `html` styled by `css` in a viewport `width` px wide. The
+    /// document and its styles are leaked, for the boxes to borrow them
+    /// past the call.
+    fn layout_of(html: &str, css: &str, width: f32) -> LayoutBox<'static> {
+        let stylesheet = Box::leak(Box::new(CSSParser::new(css).parse()));
+        let dom = Box::leak(Box::new(HTMLParser::new(html).parse()));
+        let styled_dom = Box::leak(Box::new(get_styled_node(dom, stylesheet)));
+        let mut viewport = Dimensions::default();
+        viewport.content.width = width;
+        layout_tree(styled_dom, viewport, &LayoutContext::new())
+    }
+
+    /// Renderer sized `width` by `height` device pixels, `scale` per px, or
+    /// `None` on machines without an adapter, where the tests are skipped.
+    fn renderer(width: u32, height: u32, scale: f32) -> Option<GpuRenderer> {
+        let mut renderer = GpuRenderer::new()?;
+        renderer.resize(width, height, scale);
+        Some(renderer)
+    }
+
+    fn canvas_image(list: &DisplayList, width: u32, height: u32, scale: f32) -> Image {
+        let mut canvas = Canvas::new(width, height);
+        canvas.set_scale(scale);
+        canvas.paint(list);
+        canvas.to_image()
+    }
+
+    /// Asserts the images are the same, but for rounding in the blending of
+    /// the GPU. Channels are compared premultiplied, as faint pixels lose
+    /// precision when read back with straight alpha.
+    fn assert_close(image: &Image, expected: &Image) {
+        assert_eq!(
+            (image.width, image.height),
+            (expected.width, expected.height)
+        );
+        let premultiplied = |pixel: &[u8]| -> Vec<u32> {
+            let alpha = pixel[3] as u32;
+            pixel
+                .iter()
+                .map(|&channel| channel as u32 * alpha / 255)
+                .collect()
+        };
+        let pixels = image.pixels.chunks(4).zip(expected.pixels.chunks(4));
+        for (i, (pixel, expected_pixel)) in pixels.enumerate() {
+            let (x, y) = (i as u32 % image.width, i as u32 / image.width);
+            let close = premultiplied(pixel)
+                .iter()
+                .zip(premultiplied(expected_pixel))
+                .all(|(a, b)| a.abs_diff(b) <= 2);
+            assert!(close, "({x}, {y}): {pixel:?} != {expected_pixel:?}");
+        }
+    }
+
+    fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color { r, g, b, a: 255 }
+    }
+
+    fn solid(id: u64, color: Color, x: f32, y: f32) -> DisplayItem {
+        DisplayItem {
+            id,
+            command: DisplayCommand::SolidColor(
+                color,
+                Rect {
+                    x,
+                    y,
+                    width: 10.0,
+                    height: 10.0,
+                },
+            ),
+        }
+    }
+
+    #[test]
+    fn renders_like_the_canvas() {
+        let Some(mut renderer) = renderer(80, 120, 2.0) else {
+            return;
+        };
+        let mut layout_root = layout_of(
+            r#"<div class="a"><div class="b"></div><div class="c">Hi</div></div>"#,
+            ".a { background: #336699; border: 2px solid red; border-radius: 8px;
+                  overflow: hidden; height: 50px; }
+             .b { height: 20px; margin-left: 25px; background: rgba(0, 255, 0, 0.5); }
+             .c { height: 40px; opacity: 0.5; background: yellow; color: black; }",
+            40.0,
+        );
+        layout_root.snap_to_pixels(2.0);
+        let list = build_display_list(&layout_root, &Resources::new());
+
+        renderer.render(None, &list);
+        assert_close(&renderer.to_image(), &canvas_image(&list, 80, 120, 2.0));
+    }
+
+    #[test]
+    fn keeps_the_textures_of_items_that_did_not_change() {
+        let Some(mut renderer) = renderer(30, 30, 1.0) else {
+            return;
+        };
+        let red = rgb(255, 0, 0);
+        let blue = rgb(0, 0, 255);
+        let list = vec![solid(1, red, 0.0, 10.0), solid(2, blue, 15.0, 10.0)];
+        renderer.render(None, &list);
+        let textures = |renderer: &GpuRenderer| {
+            renderer
+                .quads
+                .iter()
+                .map(|quad| quad.bind_group.global_id())
+                .collect::<Vec<_>>()
+        };
+        let before = textures(&renderer);
+
+        // Scrolled by 5px, with the second item turning red.
+        let scrolled = vec![solid(1, red, 0.0, 5.0), solid(2, red, 15.0, 5.0)];
+        renderer.render(Some(&list), &scrolled);
+        let after = textures(&renderer);
+        assert_eq!(after[0], before[0]);
+        assert_ne!(after[1], before[1]);
+        assert_close(&renderer.to_image(), &canvas_image(&scrolled, 30, 30, 1.0));
+    }
+
+    #[test]
+    fn skips_what_is_out_of_the_target() {
+        let Some(mut renderer) = renderer(10, 10, 1.0) else {
+            return;
+        };
+        let list = vec![
+            solid(1, rgb(255, 0, 0), 0.0, 0.0),
+            solid(2, rgb(0, 0, 255), 20.0, 0.0),
+        ];
+        renderer.render(None, &list);
+        assert_eq!(renderer.quads.len(), 1);
+        assert_close(&renderer.to_image(), &canvas_image(&list, 10, 10, 1.0));
+    }
+}
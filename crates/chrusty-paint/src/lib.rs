@@ -0,0 +1,997 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+pub mod fixtures;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod pdf;
+pub mod png;
+pub mod raster;
+pub mod svg;
+
+pub use chrusty_core::color::Color;
+use chrusty_core::{
+    dom::{NodeType, TagType},
+    layout::{CornerRadii, CornerRadius, EdgeSizes, LayoutBox, Rect},
+    resource::{Image, Resources},
+    style::BorderStyle,
+    text::{GlyphRun, PositionedGlyph},
+    values::{Gradient, ImageValue},
+};
+
+/// Rect whose corners are cut along quarter ellipses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoundedRect {
+    pub rect: Rect,
+    pub radii: CornerRadii,
+}
+
+impl RoundedRect {
+    /// Whether `(x, y)` is inside the rect and not cut off by a corner.
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        let Rect {
+            x: left,
+            y: top,
+            width,
+            height,
+        } = self.rect;
+        let (right, bottom) = (left + width, top + height);
+        if x < left || x > right || y < top || y > bottom {
+            return false;
+        }
+        let CornerRadii {
+            top_left,
+            top_right,
+            bottom_right,
+            bottom_left,
+        } = self.radii;
+        // Center of the ellipse of the corner the point is in, if any.
+        let corner = if x < left + top_left.x && y < top + top_left.y {
+            (left + top_left.x, top + top_left.y, top_left)
+        } else if x > right - top_right.x && y < top + top_right.y {
+            (right - top_right.x, top + top_right.y, top_right)
+        } else if x > right - bottom_right.x && y > bottom - bottom_right.y {
+            (
+                right - bottom_right.x,
+                bottom - bottom_right.y,
+                bottom_right,
+            )
+        } else if x < left + bottom_left.x && y > bottom - bottom_left.y {
+            (left + bottom_left.x, bottom - bottom_left.y, bottom_left)
+        } else {
+            return true;
+        };
+        let (center_x, center_y, radius) = corner;
+        let dx = (x - center_x) / radius.x;
+        let dy = (y - center_y) / radius.y;
+        dx * dx + dy * dy <= 1.0
+    }
+
+    /// The rect inside borders `edges` wide, with its corners following the
+    /// inner edges of the borders.
+    pub fn inset_by(&self, edges: EdgeSizes) -> RoundedRect {
+        let Rect {
+            x,
+            y,
+            width,
+            height,
+        } = self.rect;
+        let inset = |radius: CornerRadius, x: f32, y: f32| CornerRadius {
+            x: (radius.x - x).max(0.0),
+            y: (radius.y - y).max(0.0),
+        };
+        let radii = self.radii;
+        RoundedRect {
+            rect: rect(
+                x + edges.left,
+                y + edges.top,
+                (width - edges.left - edges.right).max(0.0),
+                (height - edges.top - edges.bottom).max(0.0),
+            ),
+            radii: CornerRadii {
+                top_left: inset(radii.top_left, edges.left, edges.top),
+                top_right: inset(radii.top_right, edges.right, edges.top),
+                bottom_right: inset(radii.bottom_right, edges.right, edges.bottom),
+                bottom_left: inset(radii.bottom_left, edges.left, edges.bottom),
+            },
+        }
+    }
+
+    fn translated(&self, dx: f32, dy: f32) -> RoundedRect {
+        RoundedRect {
+            rect: self.rect.translated(dx, dy),
+            ..*self
+        }
+    }
+
+    fn scaled(&self, scale: f32) -> RoundedRect {
+        let radius = |radius: CornerRadius| CornerRadius {
+            x: radius.x * scale,
+            y: radius.y * scale,
+        };
+        let radii = self.radii;
+        RoundedRect {
+            rect: self.rect.scaled(scale),
+            radii: CornerRadii {
+                top_left: radius(radii.top_left),
+                top_right: radius(radii.top_right),
+                bottom_right: radius(radii.bottom_right),
+                bottom_left: radius(radii.bottom_left),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DisplayCommand {
+    SolidColor(Color, Rect),
+    /// Rect filled with its corners rounded.
+    RoundedRect(Color, RoundedRect),
+    /// Part of a rounded border: what of the rect is inside the first
+    /// rounded rect, the border box, and outside the second, the padding
+    /// box.
+    RoundedBorder(Color, Rect, RoundedRect, RoundedRect),
+    /// Image drawn at the origin of the rect and clipped to it.
+    Image(Arc<Image>, Rect),
+    /// Image scaled to fill the rect, e.g. the content of an `img`.
+    ScaledImage(Arc<Image>, Rect),
+    /// Gradient stretched over the rect.
+    Gradient(Gradient, Rect),
+    /// Line of text whose glyphs are positioned from the origin of the rect.
+    Text(Color, GlyphRun, Rect),
+    /// Starts a group of items, up to the matching `PopOpacity`, that is
+    /// composited as a whole at the given opacity. The rect is the box that
+    /// created the group.
+    PushOpacity(f32, Rect),
+    PopOpacity(Rect),
+    /// Clips the items up to the matching `PopClip` to the rounded rect,
+    /// within the clips already pushed.
+    PushClip(RoundedRect),
+    PopClip(RoundedRect),
+}
+
+impl DisplayCommand {
+    pub fn rect(&self) -> Rect {
+        match self {
+            DisplayCommand::SolidColor(_, rect)
+            | DisplayCommand::RoundedRect(_, RoundedRect { rect, .. })
+            | DisplayCommand::RoundedBorder(_, rect, ..)
+            | DisplayCommand::Image(_, rect)
+            | DisplayCommand::ScaledImage(_, rect)
+            | DisplayCommand::Gradient(_, rect)
+            | DisplayCommand::Text(_, _, rect)
+            | DisplayCommand::PushOpacity(_, rect)
+            | DisplayCommand::PopOpacity(rect)
+            | DisplayCommand::PushClip(RoundedRect { rect, .. })
+            | DisplayCommand::PopClip(RoundedRect { rect, .. }) => *rect,
+        }
+    }
+
+    fn with_rect(&self, rect: Rect) -> DisplayCommand {
+        match self {
+            DisplayCommand::SolidColor(color, _) => DisplayCommand::SolidColor(*color, rect),
+            DisplayCommand::RoundedRect(color, rounded) => {
+                DisplayCommand::RoundedRect(*color, RoundedRect { rect, ..*rounded })
+            }
+            // The border keeps its shape, moved along with the rect.
+            DisplayCommand::RoundedBorder(color, area, outer, inner) => {
+                let (dx, dy) = (rect.x - area.x, rect.y - area.y);
+                DisplayCommand::RoundedBorder(
+                    *color,
+                    rect,
+                    outer.translated(dx, dy),
+                    inner.translated(dx, dy),
+                )
+            }
+            DisplayCommand::Image(image, _) => DisplayCommand::Image(Arc::clone(image), rect),
+            DisplayCommand::ScaledImage(image, _) => {
+                DisplayCommand::ScaledImage(Arc::clone(image), rect)
+            }
+            DisplayCommand::Gradient(gradient, _) => {
+                DisplayCommand::Gradient(gradient.clone(), rect)
+            }
+            DisplayCommand::Text(color, run, _) => DisplayCommand::Text(*color, run.clone(), rect),
+            DisplayCommand::PushOpacity(opacity, _) => DisplayCommand::PushOpacity(*opacity, rect),
+            DisplayCommand::PopOpacity(_) => DisplayCommand::PopOpacity(rect),
+            DisplayCommand::PushClip(clip) => {
+                DisplayCommand::PushClip(RoundedRect { rect, ..*clip })
+            }
+            DisplayCommand::PopClip(clip) => DisplayCommand::PopClip(RoundedRect { rect, ..*clip }),
+        }
+    }
+
+    /// The command with its geometry scaled by `scale`, e.g. from px to
+    /// device pixels. The pixels of images and the stops of gradients are
+    /// left for the canvas to scale as it draws them.
+    fn scaled(&self, scale: f32) -> DisplayCommand {
+        match self {
+            DisplayCommand::RoundedRect(color, rounded) => {
+                DisplayCommand::RoundedRect(*color, rounded.scaled(scale))
+            }
+            DisplayCommand::RoundedBorder(color, area, outer, inner) => {
+                DisplayCommand::RoundedBorder(
+                    *color,
+                    area.scaled(scale),
+                    outer.scaled(scale),
+                    inner.scaled(scale),
+                )
+            }
+            DisplayCommand::Text(color, run, rect) => {
+                let run = GlyphRun {
+                    font_size: run.font_size * scale,
+                    baseline: run.baseline * scale,
+                    glyphs: run
+                        .glyphs
+                        .iter()
+                        .map(|glyph| PositionedGlyph {
+                            x: glyph.x * scale,
+                            ..*glyph
+                        })
+                        .collect(),
+                    width: run.width * scale,
+                };
+                DisplayCommand::Text(*color, run, rect.scaled(scale))
+            }
+            DisplayCommand::PushClip(clip) => DisplayCommand::PushClip(clip.scaled(scale)),
+            DisplayCommand::PopClip(clip) => DisplayCommand::PopClip(clip.scaled(scale)),
+            _ => self.with_rect(self.rect().scaled(scale)),
+        }
+    }
+}
+
+/// Identifies an item across frames, derived from the box that produced it.
+pub type ItemId = u64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisplayItem {
+    pub id: ItemId,
+    pub command: DisplayCommand,
+}
+
+/// Items in painting order, from bottom-most to top-most.
+pub type DisplayList = Vec<DisplayItem>;
+
+const FNV_OFFSET: ItemId = 0xcbf2_9ce4_8422_2325;
+
+/// FNV-1a, so item ids don't depend on the standard library's hasher.
+/// Hashing is streamed: `hash_key(hash_key(h, a), b)` hashes `a` then `b`.
+fn hash_key(hash: ItemId, key: &str) -> ItemId {
+    key.bytes().fold(hash, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Hashed key for a layout box: its element id when it has one, so the box
+/// keeps its identity when moved around the tree, otherwise its path from the
+/// root, which is hashed incrementally by the caller.
+fn box_key(layout_box: &LayoutBox, path_hash: ItemId) -> ItemId {
+    let element_id =
+        layout_box
+            .get_style_node()
+            .and_then(|style| match style.node.get_node_type() {
+                NodeType::Element(element) => element.id(),
+                NodeType::Text(_) => None,
+            });
+    match element_id {
+        Some(id) => hash_key(hash_key(FNV_OFFSET, "#"), id),
+        None => path_hash,
+    }
+}
+
+fn render_layout_box(
+    list: &mut DisplayList,
+    layout_box: &LayoutBox,
+    key: ItemId,
+    resources: &Resources,
+) {
+    // Hidden boxes keep their place in the layout but aren't drawn.
+    if !layout_box.is_visible() {
+        return;
+    }
+    let start = list.len();
+    render_background(list, layout_box, key);
+    render_background_image(list, layout_box, key, resources);
+    render_borders(list, layout_box, key);
+    render_content_image(list, layout_box, key, resources);
+    render_text(list, layout_box, key);
+    // Boxes inside scroll containers are painted where they're scrolled to.
+    let (dx, dy) = layout_box.dimensions.scroll_translation;
+    if (dx, dy) != (0.0, 0.0) {
+        for item in &mut list[start..] {
+            item.command = item
+                .command
+                .with_rect(item.command.rect().translated(dx, dy));
+        }
+    }
+    // Boxes inside ones clipping their overflow are painted within the clip,
+    // which is already where they're scrolled to. Boxes sharing a clip are
+    // clipped together.
+    let Some(rect) = layout_box.dimensions.clip else {
+        return;
+    };
+    if list.len() == start {
+        return;
+    }
+    let clip = RoundedRect {
+        rect,
+        radii: layout_box.dimensions.clip_radii,
+    };
+    match list[..start].last() {
+        Some(DisplayItem {
+            command: DisplayCommand::PopClip(previous),
+            ..
+        }) if *previous == clip => {
+            list.remove(start - 1);
+        }
+        _ => list.insert(
+            start,
+            DisplayItem {
+                id: hash_key(key, ":clip"),
+                command: DisplayCommand::PushClip(clip),
+            },
+        ),
+    }
+    list.push(DisplayItem {
+        id: hash_key(key, ":clip-end"),
+        command: DisplayCommand::PopClip(clip),
+    });
+}
+
+/// Border box of a box with rounded corners. Inline boxes laid out on lines
+/// keep square corners.
+fn rounded_border_box(layout_box: &LayoutBox) -> Option<RoundedRect> {
+    let dimensions = &layout_box.dimensions;
+    if dimensions.radii == CornerRadii::default() || !layout_box.fragments.is_empty() {
+        return None;
+    }
+    Some(RoundedRect {
+        rect: dimensions.border_box(),
+        radii: dimensions.radii,
+    })
+}
+
+/// Inline boxes split across lines have a background on each fragment.
+fn render_background(list: &mut DisplayList, layout_box: &LayoutBox, key: ItemId) {
+    let color = layout_box.style().background_color;
+    if color.a == 0 {
+        return;
+    }
+    if let Some(rounded) = rounded_border_box(layout_box) {
+        list.push(DisplayItem {
+            id: hash_key(key, ":background"),
+            command: DisplayCommand::RoundedRect(color, rounded),
+        });
+        return;
+    }
+    for (i, border_box) in layout_box.border_boxes().into_iter().enumerate() {
+        let id = match i {
+            0 => hash_key(key, ":background"),
+            i => hash_key(key, &format!(":background/{}", i)),
+        };
+        list.push(DisplayItem {
+            id,
+            command: DisplayCommand::SolidColor(color, border_box),
+        });
+    }
+}
+
+fn render_background_image(
+    list: &mut DisplayList,
+    layout_box: &LayoutBox,
+    key: ItemId,
+    resources: &Resources,
+) {
+    let rect = layout_box.dimensions.padding_box();
+    let command = match &layout_box.style().background_image {
+        ImageValue::Url(url) => match resources.image(url) {
+            Some(image) => DisplayCommand::Image(image, rect),
+            None => return,
+        },
+        ImageValue::Gradient(gradient) => DisplayCommand::Gradient(gradient.clone(), rect),
+        ImageValue::None => return,
+    };
+    // Images follow the rounded corners of the padding box.
+    let clip = rounded_border_box(layout_box)
+        .map(|rounded| rounded.inset_by(layout_box.dimensions.border));
+    if let Some(clip) = clip {
+        list.push(DisplayItem {
+            id: hash_key(key, ":background-image-clip"),
+            command: DisplayCommand::PushClip(clip),
+        });
+    }
+    list.push(DisplayItem {
+        id: hash_key(key, ":background-image"),
+        command,
+    });
+    if let Some(clip) = clip {
+        list.push(DisplayItem {
+            id: hash_key(key, ":background-image-clip-end"),
+            command: DisplayCommand::PopClip(clip),
+        });
+    }
+}
+
+/// Areas of the top, right, bottom and left borders of a box with rounded
+/// corners, to be painted where they're inside its border box and outside
+/// its padding box. The top and bottom borders cover the rows of the corners,
+/// when they're drawn, and the side borders the rows in between, split
+/// halfway across the box.
+fn rounded_border_areas(outer: &RoundedRect, edges: EdgeSizes) -> [Rect; 4] {
+    let Rect {
+        x,
+        y,
+        width,
+        height,
+    } = outer.rect;
+    let radii = outer.radii;
+    let corner_rows = |width: f32, first: CornerRadius, second: CornerRadius| {
+        if width > 0.0 {
+            width.max(first.y).max(second.y).min(height / 2.0)
+        } else {
+            0.0
+        }
+    };
+    let top = corner_rows(edges.top, radii.top_left, radii.top_right);
+    let bottom = corner_rows(edges.bottom, radii.bottom_left, radii.bottom_right);
+    let side_height = height - top - bottom;
+    [
+        rect(x, y, width, top),
+        rect(x + width / 2.0, y + top, width / 2.0, side_height),
+        rect(x, y + height - bottom, width, bottom),
+        rect(x, y + top, width / 2.0, side_height),
+    ]
+}
+
+fn rect(x: f32, y: f32, width: f32, height: f32) -> Rect {
+    Rect {
+        x,
+        y,
+        width,
+        height,
+    }
+}
+
+/// Each border is painted as a rect along its edge, in its color, with the
+/// top and bottom borders covering the corners. Borders of boxes with
+/// rounded corners follow the curve of the corners instead. Styles other
+/// than `none` and `hidden` are all painted as `solid`.
+fn render_borders(list: &mut DisplayList, layout_box: &LayoutBox, key: ItemId) {
+    let style = layout_box.style();
+    let sides = [
+        ("top", style.border_top_style, style.border_top_color),
+        ("right", style.border_right_style, style.border_right_color),
+        (
+            "bottom",
+            style.border_bottom_style,
+            style.border_bottom_color,
+        ),
+        ("left", style.border_left_style, style.border_left_color),
+    ];
+    let rounded = rounded_border_box(layout_box);
+    for (i, (border_box, edges)) in layout_box.border_edges().into_iter().enumerate() {
+        let Rect {
+            x,
+            y,
+            width,
+            height,
+        } = border_box;
+        let side_height = height - edges.top - edges.bottom;
+        let areas = match &rounded {
+            Some(outer) => rounded_border_areas(outer, edges),
+            None => [
+                rect(x, y, width, edges.top),
+                rect(
+                    x + width - edges.right,
+                    y + edges.top,
+                    edges.right,
+                    side_height,
+                ),
+                rect(x, y + height - edges.bottom, width, edges.bottom),
+                rect(x, y + edges.top, edges.left, side_height),
+            ],
+        };
+        let widths = [edges.top, edges.right, edges.bottom, edges.left];
+        for (((side, border_style, color), area), width) in sides.into_iter().zip(areas).zip(widths)
+        {
+            let painted = !matches!(border_style, BorderStyle::None | BorderStyle::Hidden);
+            if !painted || color.a == 0 || width <= 0.0 || area.width <= 0.0 || area.height <= 0.0 {
+                continue;
+            }
+            let id = match i {
+                0 => hash_key(key, &format!(":border-{}", side)),
+                i => hash_key(key, &format!(":border-{}/{}", side, i)),
+            };
+            let command = match rounded {
+                Some(outer) => {
+                    DisplayCommand::RoundedBorder(color, area, outer, outer.inset_by(edges))
+                }
+                None => DisplayCommand::SolidColor(color, area),
+            };
+            list.push(DisplayItem { id, command });
+        }
+    }
+}
+
+/// The image an `img` shows, scaled to its content box.
+fn render_content_image(
+    list: &mut DisplayList,
+    layout_box: &LayoutBox,
+    key: ItemId,
+    resources: &Resources,
+) {
+    let Some(style_node) = layout_box.get_style_node() else {
+        return;
+    };
+    let NodeType::Element(element) = style_node.node.get_node_type() else {
+        return;
+    };
+    if element.tag_type != TagType::Img {
+        return;
+    }
+    if let Some(image) = element
+        .attribute("src")
+        .and_then(|src| resources.image(src))
+    {
+        list.push(DisplayItem {
+            id: hash_key(key, ":image"),
+            command: DisplayCommand::ScaledImage(image, layout_box.dimensions.content),
+        });
+    }
+}
+
+fn render_text(list: &mut DisplayList, layout_box: &LayoutBox, key: ItemId) {
+    let color = layout_box.style().color;
+    if color.a == 0 {
+        return;
+    }
+    let fragments = layout_box.fragments.iter().enumerate();
+    for (i, fragment) in fragments.filter(|(_, fragment)| !fragment.run.glyphs.is_empty()) {
+        list.push(DisplayItem {
+            id: hash_key(key, &format!(":text/{}", i)),
+            command: DisplayCommand::Text(color, fragment.run.clone(), fragment.rect),
+        });
+    }
+}
+
+/// Builds the display list of a layout tree, with images looked up in
+/// `resources`.
+pub fn build_display_list(layout_root: &LayoutBox, resources: &Resources) -> DisplayList {
+    enum Step<'b, 'a> {
+        /// Paints the stacking context established by the box, or the root.
+        Context(&'b LayoutBox<'a>, ItemId),
+        /// Paints the box on its own, its descendants having steps of their
+        /// own.
+        Render(&'b LayoutBox<'a>, ItemId),
+        /// Closes the opacity group of the box with the given key.
+        PopOpacity(Rect, ItemId),
+    }
+
+    let mut list = vec![];
+    // Walk of the stacking contexts with an explicit stack, so deep trees
+    // don't recurse.
+    let mut stack = vec![Step::Context(layout_root, FNV_OFFSET)];
+    while let Some(step) = stack.pop() {
+        let (layout_box, path_hash) = match step {
+            Step::Context(layout_box, path_hash) => (layout_box, path_hash),
+            Step::Render(layout_box, path_hash) => {
+                let key = box_key(layout_box, path_hash);
+                render_layout_box(&mut list, layout_box, key, resources);
+                continue;
+            }
+            Step::PopOpacity(rect, key) => {
+                list.push(DisplayItem {
+                    id: hash_key(key, ":opacity-end"),
+                    command: DisplayCommand::PopOpacity(rect),
+                });
+                continue;
+            }
+        };
+        let key = box_key(layout_box, path_hash);
+        let opacity = layout_box.opacity();
+        if opacity < 1.0 {
+            let (dx, dy) = layout_box.dimensions.scroll_translation;
+            let rect = layout_box.dimensions.border_box().translated(dx, dy);
+            list.push(DisplayItem {
+                id: hash_key(key, ":opacity"),
+                command: DisplayCommand::PushOpacity(opacity, rect),
+            });
+            stack.push(Step::PopOpacity(rect, key));
+        }
+        render_layout_box(&mut list, layout_box, key, resources);
+        let entries =
+            layout_box.paint_order_keyed(path_hash, |hash, i| hash_key(*hash, &format!("/{}", i)));
+        for (layout_box, path_hash) in entries.into_iter().rev() {
+            stack.push(if layout_box.establishes_stacking_context() {
+                Step::Context(layout_box, path_hash)
+            } else {
+                Step::Render(layout_box, path_hash)
+            });
+        }
+    }
+    list
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DisplayListChange {
+    Added {
+        index: usize,
+        item: DisplayItem,
+    },
+    Removed {
+        id: ItemId,
+    },
+    /// Only the geometry of the item changed.
+    Moved {
+        id: ItemId,
+        from: Rect,
+        to: Rect,
+    },
+    /// Anything other than geometry changed, e.g. the item's color.
+    Updated {
+        item: DisplayItem,
+    },
+    /// Items kept from the previous frame changed relative order. Holds the
+    /// new painting order of every item.
+    Reordered {
+        order: Vec<ItemId>,
+    },
+}
+
+/// Changes that turn `old` into `new`, so external compositors can update
+/// retained layers instead of repainting the whole frame.
+pub fn diff_display_lists(old: &DisplayList, new: &DisplayList) -> Vec<DisplayListChange> {
+    let old_items: HashMap<ItemId, &DisplayItem> = old.iter().map(|item| (item.id, item)).collect();
+    let new_ids: HashSet<ItemId> = new.iter().map(|item| item.id).collect();
+
+    let mut changes: Vec<DisplayListChange> = old
+        .iter()
+        .filter(|item| !new_ids.contains(&item.id))
+        .map(|item| DisplayListChange::Removed { id: item.id })
+        .collect();
+
+    for (index, item) in new.iter().enumerate() {
+        let Some(old_item) = old_items.get(&item.id) else {
+            changes.push(DisplayListChange::Added {
+                index,
+                item: item.clone(),
+            });
+            continue;
+        };
+        let (from, to) = (old_item.command.rect(), item.command.rect());
+        if old_item.command.with_rect(to) != item.command {
+            changes.push(DisplayListChange::Updated { item: item.clone() });
+        } else if from != to {
+            changes.push(DisplayListChange::Moved {
+                id: item.id,
+                from,
+                to,
+            });
+        }
+    }
+
+    let kept_old: Vec<ItemId> = old
+        .iter()
+        .map(|item| item.id)
+        .filter(|id| new_ids.contains(id))
+        .collect();
+    let kept_new: Vec<ItemId> = new
+        .iter()
+        .map(|item| item.id)
+        .filter(|id| old_items.contains_key(id))
+        .collect();
+    if kept_old != kept_new {
+        changes.push(DisplayListChange::Reordered {
+            order: new.iter().map(|item| item.id).collect(),
+        });
+    }
+    changes
+}
+
+/// Backend showing the frames of a page, e.g. `raster::Canvas` on the CPU,
+/// or `gpu::GpuRenderer` with the `gpu` feature.
+/// Embedders drive any backend the same way, so they can pick one at
+/// runtime.
+pub trait Renderer {
+    /// Sizes the output in device pixels, `scale` of them per px. What was
+    /// shown is dropped, so the next frame is rendered without a previous
+    /// one.
+    fn resize(&mut self, width: u32, height: u32, scale: f32);
+
+    /// Shows `list`, in place of `previous` if it's what was shown last, so
+    /// backends can keep what didn't change.
+    fn render(&mut self, previous: Option<&DisplayList>, list: &DisplayList);
+
+    /// Pixels of the frame shown, e.g. for screenshots.
+    fn to_image(&self) -> Image;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use chrusty_core::{
+        layout::{layout_tree, Dimensions, LayoutBox, LayoutContext, Rect},
+        parser::{CSSParser, HTMLParser, IParser},
+        resource::{Image, Resources},
+        style::get_styled_node,
+    };
+
+    use crate::{
+        build_display_list, diff_display_lists,
+        fixtures::{FixtureOptions, PaintOrderFixture},
+        Color, DisplayCommand, DisplayList, DisplayListChange,
+    };
+
+    /// Lays out `html` styled by `css` in a viewport `width` px wide. The
+    /// document and its styles are leaked, for the boxes to borrow them
+    /// past the call.
+    fn layout_of(html: &str, css: &str, width: f32) -> LayoutBox<'static> {
+        let stylesheet = Box::leak(Box::new(CSSParser::new(css).parse()));
+        let dom = Box::leak(Box::new(HTMLParser::new(html).parse()));
+        let styled_dom = Box::leak(Box::new(get_styled_node(dom, stylesheet)));
+        let mut viewport = Dimensions::default();
+        viewport.content.width = width;
+        layout_tree(styled_dom, viewport, &LayoutContext::new())
+    }
+
+    fn paint(html: &str, css: &str) -> DisplayList {
+        paint_with(html, css, &Resources::new())
+    }
+
+    fn paint_with(html: &str, css: &str, resources: &Resources) -> DisplayList {
+        let layout_root = layout_of(html, css, 800.0);
+        build_display_list(&layout_root, resources)
+    }
+
+    #[test]
+    fn diffs_display_lists() {
+        let css = "
+            div.a {
+                height: 10px;
+                background: red;
+            }
+
+            div.b {
+                height: 20px;
+                background: blue;
+            }
+
+            div#c {
+                height: 30px;
+                background: green;
+            }
+        ";
+        let old = paint(
+            "<div class=\"a\"></div><div class=\"b\"></div><div id=\"c\"></div>",
+            css,
+        );
+        assert_eq!(old.len(), 3);
+        assert!(diff_display_lists(&old, &old).is_empty());
+
+        let new = paint(
+            "<div id=\"c\"></div><div class=\"a\"></div><div class=\"a\"></div>",
+            css,
+        );
+        let changes = diff_display_lists(&old, &new);
+        let moved_c = DisplayListChange::Moved {
+            id: old[2].id,
+            from: Rect {
+                x: 0.0,
+                y: 30.0,
+                width: 800.0,
+                height: 30.0,
+            },
+            to: Rect {
+                x: 0.0,
+                y: 0.0,
+                width: 800.0,
+                height: 30.0,
+            },
+        };
+        assert!(changes.contains(&moved_c));
+        // The second box keeps its path but now paints red instead of blue.
+        assert!(changes.contains(&DisplayListChange::Updated {
+            item: new[1].clone()
+        }));
+        assert!(changes.contains(&DisplayListChange::Removed { id: old[0].id }));
+        assert!(changes.contains(&DisplayListChange::Added {
+            index: 2,
+            item: new[2].clone()
+        }));
+        assert!(changes.contains(&DisplayListChange::Reordered {
+            order: new.iter().map(|item| item.id).collect()
+        }));
+        assert_eq!(changes.len(), 5);
+    }
+
+    #[test]
+    fn paints_background_images() {
+        let css = "div { height: 10px; background: red url(dot.png); }";
+        let mut resources = Resources::new();
+        resources.set_image_request(|_| {
+            Some(Image {
+                width: 1,
+                height: 1,
+                pixels: vec![0, 0, 0, 255],
+            })
+        });
+        resources.load_images(&CSSParser::new(css).parse());
+        let list = paint_with("<div></div>", css, &resources);
+        assert!(matches!(list[0].command, DisplayCommand::SolidColor(..)));
+        let DisplayCommand::Image(image, rect) = &list[1].command else {
+            panic!("background image was not painted above the color")
+        };
+        assert_eq!(image.width, 1);
+        assert_eq!(rect.height, 10.0);
+
+        assert_eq!(paint("<div></div>", css).len(), 1);
+    }
+
+    #[test]
+    fn paints_borders() {
+        let css = "
+            div {
+                width: 10px;
+                height: 5px;
+                background: white;
+                border: 1px solid black;
+                border-left: 2px dashed red;
+                border-bottom-style: none;
+            }
+        ";
+        let list = paint("<div></div>", css);
+        let rect = |x, y, width, height| Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+        let black = Color::BLACK;
+        let red = Color {
+            r: 255,
+            g: 0,
+            b: 0,
+            a: 255,
+        };
+        let commands: Vec<_> = list.iter().map(|item| item.command.clone()).collect();
+        // Borders are above the background, and no bottom border is drawn.
+        assert_eq!(
+            commands[1..],
+            [
+                DisplayCommand::SolidColor(black, rect(0.0, 0.0, 13.0, 1.0)),
+                DisplayCommand::SolidColor(black, rect(12.0, 1.0, 1.0, 5.0)),
+                DisplayCommand::SolidColor(red, rect(0.0, 1.0, 2.0, 5.0)),
+            ]
+        );
+
+        // An inline box split across lines has its start border on its first
+        // line and its end border on its last one.
+        let css = "
+            div { width: 30px; }
+            p { display: inline; border: 1px solid black; }
+        ";
+        let list = paint("<div><p>ab cd</p></div>", css);
+        let rects: Vec<Rect> = list.iter().map(|item| item.command.rect()).collect();
+        // Top, bottom and left borders on the first line, then top, right
+        // and bottom ones on the second, followed by the text.
+        assert_eq!(rects.len(), 8);
+        assert_eq!(rects[2], rect(0.0, 0.0, 1.0, 19.2));
+        assert_eq!(rects[4], rect(24.0, 19.2, 1.0, 19.2));
+    }
+
+    #[test]
+    fn skips_hidden_boxes() {
+        let css = "
+            div { height: 10px; background: red; }
+            div.hidden { height: auto; visibility: hidden; }
+            div.shown { visibility: visible; }
+        ";
+        let list = paint(
+            "<div class=\"hidden\"><div></div><div class=\"shown\"></div></div><div></div>",
+            css,
+        );
+        // The hidden box and the child inheriting its visibility aren't
+        // painted, but still take up space.
+        let rects: Vec<_> = list.iter().map(|item| item.command.rect().y).collect();
+        assert_eq!(rects, [10.0, 20.0]);
+    }
+
+    #[test]
+    fn paints_in_stacking_order() {
+        let options = FixtureOptions {
+            z_index: true,
+            ..Default::default()
+        };
+        for seed in 1..20 {
+            let fixture = PaintOrderFixture::generate(seed, options);
+            let list = paint(&fixture.html(), &fixture.css());
+            // Boxes are told apart by their color and position.
+            let painted: Vec<usize> = list
+                .iter()
+                .map(|item| {
+                    let DisplayCommand::SolidColor(color, rect) = item.command else {
+                        panic!("only backgrounds are painted")
+                    };
+                    let b = fixture.boxes.iter().find(|b| {
+                        (color.r, color.g, color.b) == b.color
+                            && (rect.x, rect.y) == (b.rect.x, b.rect.y)
+                    });
+                    b.expect("painted box is in the fixture").id
+                })
+                .collect();
+            assert_eq!(painted, fixture.paint_order(), "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn paints_scrolled_content() {
+        let css = "
+            div.scroll { height: 20px; overflow: auto; }
+            div.tall { height: 100px; background: red; }
+        ";
+        let html = "<div class=\"scroll\"><div class=\"tall\"></div></div>";
+        let mut layout_root = layout_of(html, css, 800.0);
+        let scroll = layout_root.children[0].scroll.as_mut().unwrap();
+        scroll.scroll_to(0.0, 30.0);
+        layout_root.apply_scroll_offsets();
+        let list = build_display_list(&layout_root, &Resources::new());
+        // The content is painted scrolled within the clip of its scroller.
+        assert!(matches!(list[0].command, DisplayCommand::PushClip(_)));
+        assert_eq!(list[0].command.rect().height, 20.0);
+        assert_eq!(list[1].command.rect().y, -30.0);
+        assert!(matches!(list[2].command, DisplayCommand::PopClip(_)));
+    }
+    #[test]
+    fn paints_inline_fragments() {
+        let css = "
+            div { width: 100px; }
+            p { display: inline; background: red; }
+        ";
+        let list = paint("<div>ab<p>cd ef gh</p></div>", css);
+        // The inline box wraps, so its background is painted on each line.
+        let backgrounds: Vec<_> = list
+            .iter()
+            .filter_map(|item| match item.command {
+                DisplayCommand::SolidColor(_, rect) => Some((item.id, rect)),
+                _ => None,
+            })
+            .collect();
+        let rects: Vec<_> = backgrounds.iter().map(|(_, rect)| *rect).collect();
+        assert_eq!(
+            rects,
+            [
+                Rect {
+                    x: 24.0,
+                    y: 0.0,
+                    width: 60.0,
+                    height: 19.2,
+                },
+                Rect {
+                    x: 0.0,
+                    y: 19.2,
+                    width: 24.0,
+                    height: 19.2,
+                },
+            ]
+        );
+        assert_ne!(backgrounds[0].0, backgrounds[1].0);
+    }
+
+    #[test]
+    fn builds_trees_for_deeply_nested_documents() {
+        let depth = 50_000;
+        let html = "<div>".repeat(depth) + &"</div>".repeat(depth);
+        let painted = thread::Builder::new()
+            .stack_size(256 * 1024)
+            .spawn(move || {
+                let layout_root = layout_of(&html, "div { background: red; }", 800.0);
+                build_display_list(&layout_root, &Resources::new()).len()
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(painted, depth);
+    }
+}
@@ -0,0 +1,556 @@
+//! Export of display lists to PDF, split into pages of a fixed size. Boxes,
+//! borders and clips are written as vector paths and text as text in the
+//! standard Helvetica font, so it can be selected and searched. Images are
+//! embedded, and gradients are rasterized into images.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Write,
+    sync::Arc,
+};
+
+use chrusty_core::{
+    layout::{CornerRadius, Rect},
+    resource::Image,
+};
+
+use crate::{raster::rasterize, Color, DisplayCommand, DisplayList, RoundedRect};
+
+/// PDF points per px.
+const POINTS_PER_PX: f32 = 0.75;
+
+/// How far along the tangents the control points of a cubic Bézier curve
+/// approximating a quarter ellipse are, as a fraction of its radii.
+const KAPPA: f32 = 0.552_284_8;
+
+/// Formats a number for a content stream, without trailing zeros.
+fn num(value: f32) -> String {
+    let value = format!("{value:.3}");
+    let value = value.trim_end_matches('0').trim_end_matches('.');
+    match value {
+        "-0" | "" => "0".to_string(),
+        value => value.to_string(),
+    }
+}
+
+fn rect_path(out: &mut String, rect: Rect) {
+    let _ = writeln!(
+        out,
+        "{} {} {} {} re",
+        num(rect.x),
+        num(rect.y),
+        num(rect.width),
+        num(rect.height)
+    );
+}
+
+/// Writes the outline of `rounded`, each corner a Bézier curve.
+fn rounded_path(out: &mut String, rounded: &RoundedRect) {
+    let Rect {
+        x: left,
+        y: top,
+        width,
+        height,
+    } = rounded.rect;
+    let (right, bottom) = (left + width, top + height);
+    let radii = rounded.radii;
+    let mut point = |op: &str, points: &[(f32, f32)]| {
+        for (x, y) in points {
+            let _ = write!(out, "{} {} ", num(*x), num(*y));
+        }
+        let _ = writeln!(out, "{op}");
+    };
+    // Control points and end of the curve of a corner from `start` to
+    // `end`, leaving `start` along the x axis if `across_x`, else along y.
+    let corner = |start: (f32, f32), end: (f32, f32), radius: CornerRadius, across_x: bool| {
+        let (dx, dy) = (radius.x * KAPPA, radius.y * KAPPA);
+        let (first, second) = match across_x {
+            true => (
+                (start.0 + (end.0 - start.0).signum() * dx, start.1),
+                (end.0, end.1 - (end.1 - start.1).signum() * dy),
+            ),
+            false => (
+                (start.0, start.1 + (end.1 - start.1).signum() * dy),
+                (end.0 - (end.0 - start.0).signum() * dx, end.1),
+            ),
+        };
+        [first, second, end]
+    };
+    let top_right = radii.top_right;
+    let bottom_right = radii.bottom_right;
+    let bottom_left = radii.bottom_left;
+    let top_left = radii.top_left;
+    point("m", &[(left + top_left.x, top)]);
+    point("l", &[(right - top_right.x, top)]);
+    point(
+        "c",
+        &corner(
+            (right - top_right.x, top),
+            (right, top + top_right.y),
+            top_right,
+            true,
+        ),
+    );
+    point("l", &[(right, bottom - bottom_right.y)]);
+    point(
+        "c",
+        &corner(
+            (right, bottom - bottom_right.y),
+            (right - bottom_right.x, bottom),
+            bottom_right,
+            false,
+        ),
+    );
+    point("l", &[(left + bottom_left.x, bottom)]);
+    point(
+        "c",
+        &corner(
+            (left + bottom_left.x, bottom),
+            (left, bottom - bottom_left.y),
+            bottom_left,
+            true,
+        ),
+    );
+    point("l", &[(left, top + top_left.y)]);
+    point(
+        "c",
+        &corner(
+            (left, top + top_left.y),
+            (left + top_left.x, top),
+            top_left,
+            false,
+        ),
+    );
+    point("h", &[]);
+}
+
+/// Writes `text` as a string literal in the WinAnsi encoding of the
+/// standard fonts, with characters it doesn't have as `?`.
+fn string_literal(out: &mut String, text: &str) {
+    out.push('(');
+    for ch in text.chars() {
+        match ch {
+            '(' | ')' | '\\' => {
+                out.push('\\');
+                out.push(ch);
+            }
+            ' '..='~' => out.push(ch),
+            '\u{a0}'..='\u{ff}' => {
+                let _ = write!(out, "\\{:03o}", ch as u32);
+            }
+            _ => out.push('?'),
+        }
+    }
+    out.push(')');
+}
+
+/// Objects of a PDF file, numbered from 1 in order.
+#[derive(Default)]
+struct Objects {
+    objects: Vec<Vec<u8>>,
+}
+
+impl Objects {
+    fn add(&mut self, object: Vec<u8>) -> usize {
+        self.objects.push(object);
+        self.objects.len()
+    }
+
+    fn set(&mut self, id: usize, object: Vec<u8>) {
+        self.objects[id - 1] = object;
+    }
+
+    fn stream(dictionary: &str, data: &[u8]) -> Vec<u8> {
+        let length = format!("/Length {}", data.len());
+        let entries = match dictionary {
+            "" => length,
+            dictionary => format!("{dictionary} {length}"),
+        };
+        let mut object = format!("<< {entries} >>\nstream\n").into_bytes();
+        object.extend_from_slice(data);
+        object.extend_from_slice(b"\nendstream");
+        object
+    }
+
+    /// Adds the pixels of `image` and its alpha as a soft mask, returning the
+    /// id of the image.
+    fn add_image(&mut self, image: &Image) -> usize {
+        let pixels = || image.pixels.chunks_exact(4);
+        let alpha: Vec<u8> = pixels().map(|pixel| pixel[3]).collect();
+        let color: Vec<u8> = pixels()
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2]])
+            .collect();
+        let size = format!(
+            "/Type /XObject /Subtype /Image /Width {} /Height {} /BitsPerComponent 8",
+            image.width, image.height
+        );
+        let mask = self.add(Objects::stream(
+            &format!("{size} /ColorSpace /DeviceGray"),
+            &alpha,
+        ));
+        self.add(Objects::stream(
+            &format!("{size} /ColorSpace /DeviceRGB /SMask {mask} 0 R"),
+            &color,
+        ))
+    }
+
+    fn write(&self, root: usize) -> Vec<u8> {
+        let mut file = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        let mut offsets = vec![];
+        for (i, object) in self.objects.iter().enumerate() {
+            offsets.push(file.len());
+            file.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+            file.extend_from_slice(object);
+            file.extend_from_slice(b"\nendobj\n");
+        }
+        let xref = file.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(table, "{offset:010} 00000 n ");
+        }
+        let _ = write!(
+            table,
+            "trailer\n<< /Size {} /Root {root} 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+            self.objects.len() + 1
+        );
+        file.extend_from_slice(table.as_bytes());
+        file
+    }
+}
+
+/// Writes the content streams of pages, tracking the state shared by all
+/// of them.
+struct PageWriter<'a> {
+    objects: &'a mut Objects,
+    /// Ids of the embedded images, by the address of their pixels.
+    images: HashMap<*const Image, usize>,
+    /// Ids of the images the parts of gradients on each page are rasterized
+    /// into.
+    gradients: Vec<usize>,
+    /// Width of the pages in px.
+    width: f32,
+    /// Alpha values items are painted with, each needing a graphics state.
+    alphas: BTreeSet<u8>,
+}
+
+impl PageWriter<'_> {
+    /// Sets the fill color, faded by `opacity`.
+    fn fill(&mut self, out: &mut String, color: Color, opacity: f32) {
+        let channel = |channel: u8| num(channel as f32 / 255.0);
+        let _ = writeln!(
+            out,
+            "{} {} {} rg",
+            channel(color.r),
+            channel(color.g),
+            channel(color.b)
+        );
+        self.set_alpha(out, color.a as f32 / 255.0 * opacity);
+    }
+
+    fn set_alpha(&mut self, out: &mut String, alpha: f32) {
+        let alpha = (alpha * 255.0).round() as u8;
+        if alpha < 255 {
+            self.alphas.insert(alpha);
+            let _ = writeln!(out, "/GS{alpha} gs");
+        }
+    }
+
+    /// Draws the image with the given id stretched over `rect`. Images are
+    /// drawn over the unit square, from their top row down.
+    fn draw_image(&mut self, out: &mut String, id: usize, rect: Rect, opacity: f32) {
+        self.set_alpha(out, opacity);
+        let _ = writeln!(
+            out,
+            "{} 0 0 {} {} {} cm /Im{id} Do",
+            num(rect.width),
+            num(-rect.height),
+            num(rect.x),
+            num(rect.y + rect.height)
+        );
+    }
+
+    fn image_id(&mut self, image: &Arc<Image>) -> usize {
+        *self
+            .images
+            .entry(Arc::as_ptr(image))
+            .or_insert_with(|| self.objects.add_image(image))
+    }
+
+    /// Content stream of the page showing what of `list` is between `top`
+    /// and `bottom`, `height` px tall.
+    fn page(&mut self, list: &DisplayList, top: f32, bottom: f32, height: f32) -> String {
+        // px from the top left corner of the page, rather than points from
+        // the bottom left one.
+        let mut out = format!(
+            "{} 0 0 {} 0 {} cm\n1 0 0 1 0 {} cm\n",
+            num(POINTS_PER_PX),
+            num(-POINTS_PER_PX),
+            num(height * POINTS_PER_PX),
+            num(-top)
+        );
+        let mut opacities = vec![1.0];
+        let mut clips = 0;
+        for item in list {
+            let opacity: f32 = opacities.iter().product();
+            let rect = item.command.rect();
+            let on_page = rect.y < bottom && rect.y + rect.height > top;
+            match &item.command {
+                DisplayCommand::PushOpacity(group_opacity, _) => opacities.push(*group_opacity),
+                DisplayCommand::PopOpacity(_) => {
+                    if opacities.len() > 1 {
+                        opacities.pop();
+                    }
+                }
+                DisplayCommand::PushClip(clip) => {
+                    clips += 1;
+                    out.push_str("q\n");
+                    rounded_path(&mut out, clip);
+                    out.push_str("W n\n");
+                }
+                DisplayCommand::PopClip(_) => {
+                    if clips > 0 {
+                        clips -= 1;
+                        out.push_str("Q\n");
+                    }
+                }
+                _ if !on_page => {}
+                DisplayCommand::SolidColor(color, rect) => {
+                    out.push_str("q\n");
+                    self.fill(&mut out, *color, opacity);
+                    rect_path(&mut out, *rect);
+                    out.push_str("f\nQ\n");
+                }
+                DisplayCommand::RoundedRect(color, rounded) => {
+                    out.push_str("q\n");
+                    self.fill(&mut out, *color, opacity);
+                    rounded_path(&mut out, rounded);
+                    out.push_str("f\nQ\n");
+                }
+                DisplayCommand::RoundedBorder(color, area, outer, inner) => {
+                    out.push_str("q\n");
+                    rect_path(&mut out, *area);
+                    out.push_str("W n\n");
+                    self.fill(&mut out, *color, opacity);
+                    rounded_path(&mut out, outer);
+                    rounded_path(&mut out, inner);
+                    out.push_str("f*\nQ\n");
+                }
+                DisplayCommand::Image(image, rect) => {
+                    let id = self.image_id(image);
+                    out.push_str("q\n");
+                    rect_path(&mut out, *rect);
+                    out.push_str("W n\n");
+                    let size = Rect {
+                        width: image.width as f32,
+                        height: image.height as f32,
+                        ..*rect
+                    };
+                    self.draw_image(&mut out, id, size, opacity);
+                    out.push_str("Q\n");
+                }
+                DisplayCommand::ScaledImage(image, rect) => {
+                    let id = self.image_id(image);
+                    out.push_str("q\n");
+                    self.draw_image(&mut out, id, *rect, opacity);
+                    out.push_str("Q\n");
+                }
+                DisplayCommand::Gradient(..) => {
+                    let page = Rect {
+                        x: 0.0,
+                        y: top,
+                        width: self.width,
+                        height: bottom - top,
+                    };
+                    if let Some((image, area)) = rasterize(&item.command, page) {
+                        let id = self.objects.add_image(&image);
+                        self.gradients.push(id);
+                        out.push_str("q\n");
+                        self.draw_image(&mut out, id, area, opacity);
+                        out.push_str("Q\n");
+                    }
+                }
+                DisplayCommand::Text(color, run, rect) => {
+                    out.push_str("q\n");
+                    self.fill(&mut out, *color, opacity);
+                    let _ = writeln!(out, "BT\n/F1 {} Tf", num(run.font_size));
+                    // Glyphs are placed one by one where the layout put
+                    // them, upright again in the flipped page.
+                    for glyph in run.glyphs.iter().filter(|glyph| glyph.ch != ' ') {
+                        let x = rect.x + glyph.x;
+                        let y = rect.y + run.baseline;
+                        let _ = write!(out, "1 0 0 -1 {} {} Tm ", num(x), num(y));
+                        string_literal(&mut out, &glyph.ch.to_string());
+                        out.push_str(" Tj\n");
+                    }
+                    out.push_str("ET\nQ\n");
+                }
+            }
+        }
+        out.push_str(&"Q\n".repeat(clips));
+        out
+    }
+}
+
+/// PDF file showing `list` on pages `width` by `height` px, as many as it
+/// takes to show all of its items from the top, or a single one when
+/// `height` isn't a positive number. Opacity groups fade each of their
+/// items rather than the group as a whole.
+pub fn export_pdf(list: &DisplayList, width: f32, height: f32) -> Vec<u8> {
+    let bottom = list
+        .iter()
+        .map(|item| item.command.rect())
+        .map(|rect| rect.y + rect.height)
+        .fold(0.0, f32::max);
+    let pages = match height > 0.0 && height.is_finite() {
+        true => ((bottom / height).ceil() as usize).max(1),
+        false => 1,
+    };
+
+    let mut objects = Objects::default();
+    let catalog = objects.add(vec![]);
+    let page_tree = objects.add(vec![]);
+    let font = objects.add(
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+            .to_vec(),
+    );
+    let mut writer = PageWriter {
+        objects: &mut objects,
+        images: HashMap::new(),
+        gradients: vec![],
+        width,
+        alphas: BTreeSet::new(),
+    };
+    let contents: Vec<usize> = (0..pages)
+        .map(|page| {
+            let top = page as f32 * height;
+            let content = writer.page(list, top, top + height, height);
+            writer.objects.add(Objects::stream("", content.as_bytes()))
+        })
+        .collect();
+
+    let mut resources = format!("<< /Font << /F1 {font} 0 R >> /ExtGState <<");
+    for alpha in &writer.alphas {
+        let alpha_value = num(*alpha as f32 / 255.0);
+        let _ = write!(
+            resources,
+            " /GS{alpha} << /ca {alpha_value} /CA {alpha_value} >>"
+        );
+    }
+    resources.push_str(" >> /XObject <<");
+    for id in writer.images.values().chain(writer.gradients.iter()) {
+        let _ = write!(resources, " /Im{id} {id} 0 R");
+    }
+    resources.push_str(" >> >>");
+    let media_box = format!(
+        "[0 0 {} {}]",
+        num(width * POINTS_PER_PX),
+        num(height * POINTS_PER_PX)
+    );
+    let kids: Vec<usize> = contents
+        .into_iter()
+        .map(|content| {
+            objects.add(
+                format!(
+                    "<< /Type /Page /Parent {page_tree} 0 R /MediaBox {media_box} \
+                     /Resources {resources} /Contents {content} 0 R >>"
+                )
+                .into_bytes(),
+            )
+        })
+        .collect();
+    let kids: Vec<String> = kids.iter().map(|kid| format!("{kid} 0 R")).collect();
+    objects.set(
+        page_tree,
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            kids.len()
+        )
+        .into_bytes(),
+    );
+    objects.set(
+        catalog,
+        format!("<< /Type /Catalog /Pages {page_tree} 0 R >>").into_bytes(),
+    );
+    objects.write(catalog)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrusty_core::{
+        layout::{layout_tree, Dimensions, LayoutBox, LayoutContext},
+        parser::{CSSParser, HTMLParser, IParser},
+        resource::Resources,
+        style::get_styled_node,
+    };
+
+    use crate::{build_display_list, pdf::export_pdf};
+
+    /// Lays out `html` styled by `css` in a viewport `width` px wide. The
+    /// document and its styles are leaked, for the boxes to borrow them
+    /// past the call.
+    fn layout_of(html: &str, css: &str, width: f32) -> LayoutBox<'static> {
+        let stylesheet = Box::leak(Box::new(CSSParser::new(css).parse()));
+        let dom = Box::leak(Box::new(HTMLParser::new(html).parse()));
+        let styled_dom = Box::leak(Box::new(get_styled_node(dom, stylesheet)));
+        let mut viewport = Dimensions::default();
+        viewport.content.width = width;
+        layout_tree(styled_dom, viewport, &LayoutContext::new())
+    }
+
+    #[test]
+    fn exports_pages_of_vector_content() {
+        let css = "
+            div {
+                height: 80px;
+                background: red;
+            }
+
+            div.round {
+                border-radius: 10px;
+                background: rgba(0, 0, 255, 0.5);
+            }
+
+            p {
+                color: black;
+            }
+        ";
+        let html = "<div></div><div class=\"round\"><p>Hi (there)</p></div>";
+        let layout_root = layout_of(html, css, 200.0);
+        let list = build_display_list(&layout_root, &Resources::new());
+        let pdf = export_pdf(&list, 200.0, 100.0);
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.starts_with("%PDF-1.4"));
+        assert!(text.ends_with("%%EOF\n"));
+
+        // The second box starts on the first page and ends on the second.
+        assert!(text.contains("/Type /Pages /Kids [") && text.contains("/Count 2"));
+        assert!(text.contains("/MediaBox [0 0 150 75]"));
+        assert!(text.contains("0 0 200 80 re\nf"), "{text}");
+        assert!(text.contains("/GS128 gs"));
+        assert!(text.contains("/GS128 << /ca 0.502 /CA 0.502 >>"));
+        assert!(text.contains(" c\n"));
+        assert!(text.contains("/BaseFont /Helvetica"));
+        assert!(text.contains("Tm (H) Tj") && text.contains("Tm (\\() Tj"));
+
+        // The cross-reference table points at each object.
+        let startxref = text.rfind("startxref\n").unwrap() + "startxref\n".len();
+        let xref: usize = text[startxref..].lines().next().unwrap().parse().unwrap();
+        assert!(pdf[xref..].starts_with(b"xref\n0 "));
+        let table = String::from_utf8_lossy(&pdf[xref..]);
+        let offsets = table
+            .lines()
+            .skip(3)
+            .take_while(|line| line.ends_with(" n "));
+        for (i, offset) in offsets.enumerate() {
+            let offset: usize = offset[..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj", i + 1).as_bytes()));
+        }
+
+        // Pages without a height can't split the content.
+        for height in [0.0, -1.0, f32::NAN] {
+            let text = String::from_utf8_lossy(&export_pdf(&list, 200.0, height)).into_owned();
+            assert!(text.contains("/Count 1"));
+        }
+    }
+}
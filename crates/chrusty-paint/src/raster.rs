@@ -0,0 +1,1335 @@
+use std::{borrow::Cow, collections::HashMap, thread};
+
+use chrusty_core::{
+    font::{BitmapFont, FontMetrics, GLYPH_WIDTH},
+    layout::{CornerRadii, EdgeSizes, Rect},
+    resource::Image,
+    text::GlyphRun,
+    values::{ColorStop, Gradient, GradientDirection, LengthPercentage, RadialShape},
+};
+
+use crate::{
+    diff_display_lists, Color, DisplayCommand, DisplayItem, DisplayList, DisplayListChange, ItemId,
+    Renderer, RoundedRect,
+};
+
+/// Side of the square tiles canvases are split into to be painted on several
+/// threads, in canvas pixels.
+const TILE_SIZE: u32 = 256;
+
+/// Samples taken across and down a pixel to find how much of it a rounded
+/// shape covers.
+const SAMPLES: u32 = 4;
+
+/// Most pixels on a side of the images exporters rasterize items into, the
+/// same limit as for screenshots.
+const MAX_RASTER_SIZE: f32 = 8192.0;
+
+/// Blends `source`, faded by `opacity`, over `backdrop` (source-over with
+/// straight alpha).
+fn blend(backdrop: Color, source: Color, opacity: f32) -> Color {
+    let source_alpha = source.a as f32 / 255.0 * opacity;
+    let backdrop_alpha = backdrop.a as f32 / 255.0 * (1.0 - source_alpha);
+    let alpha = source_alpha + backdrop_alpha;
+    if alpha == 0.0 {
+        return Color::default();
+    }
+    let channel = |source: u8, backdrop: u8| {
+        ((source as f32 * source_alpha + backdrop as f32 * backdrop_alpha) / alpha).round() as u8
+    };
+    Color {
+        r: channel(source.r, backdrop.r),
+        g: channel(source.g, backdrop.g),
+        b: channel(source.b, backdrop.b),
+        a: (alpha * 255.0).round() as u8,
+    }
+}
+
+/// Pixels of a canvas or of an offscreen opacity group.
+struct Layer {
+    pixels: Vec<Color>,
+    opacity: f32,
+}
+
+/// How much of the pixel at `(x, y)` is inside a shape, given by whether
+/// points are. Shapes are convex, or the space between two convex ones, so
+/// a pixel whose corners are all in is taken as covered whole, unless
+/// `whole` says otherwise. Other pixels are sampled on a grid.
+fn coverage(x: u32, y: u32, inside: impl Fn(f32, f32) -> bool, whole: bool) -> f32 {
+    let (left, top) = (x as f32, y as f32);
+    let corners = [
+        (left, top),
+        (left + 1.0, top),
+        (left, top + 1.0),
+        (left + 1.0, top + 1.0),
+    ];
+    if whole && corners.iter().all(|&(x, y)| inside(x, y)) {
+        return 1.0;
+    }
+    let samples = (0..SAMPLES * SAMPLES).filter(|i| {
+        let dx = (i % SAMPLES) as f32 + 0.5;
+        let dy = (i / SAMPLES) as f32 + 0.5;
+        inside(left + dx / SAMPLES as f32, top + dy / SAMPLES as f32)
+    });
+    samples.count() as f32 / (SAMPLES * SAMPLES) as f32
+}
+
+/// CPU rasterizer for display lists.
+pub struct Canvas {
+    pub width: u32,
+    pub height: u32,
+    /// Row-major pixels, starting out transparent.
+    pub pixels: Vec<Color>,
+    /// Clips pushed by the items painted so far, each within the ones
+    /// before it.
+    clips: Vec<RoundedRect>,
+    /// Canvas pixels per px.
+    scale: f32,
+    /// Where the canvas is in the frame, when it's a tile of a larger one.
+    origin: (u32, u32),
+    /// Most threads tiles of the canvas are painted on.
+    threads: usize,
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32) -> Canvas {
+        Canvas {
+            width,
+            height,
+            pixels: vec![Color::default(); width as usize * height as usize],
+            clips: vec![],
+            scale: 1.0,
+            origin: (0, 0),
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+        }
+    }
+
+    /// Sets the most threads the tiles of the canvas are painted on, by
+    /// default as many as the machine runs in parallel. With 1, or for
+    /// canvases of a single tile, everything is painted on the calling
+    /// thread.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Sets how many canvas pixels a px of the display lists painted next
+    /// covers, e.g. the device pixel ratio of the screen the canvas is shown
+    /// on. The canvas is then sized in device pixels, and shows pages at
+    /// their size in px with sharper edges, text and images.
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale;
+    }
+
+    /// Resizes the canvas, e.g. along with the window it's shown in. The
+    /// content is cleared, as the layout changes with the viewport and has to
+    /// be painted again.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        self.pixels.clear();
+        self.pixels
+            .resize(width as usize * height as usize, Color::default());
+    }
+
+    fn pixel_count(&self) -> usize {
+        (self.width * self.height) as usize
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    /// The content of the canvas as an image, e.g. to be encoded with
+    /// `png::encode_png`.
+    pub fn to_image(&self) -> Image {
+        Image {
+            width: self.width,
+            height: self.height,
+            pixels: self
+                .pixels
+                .iter()
+                .flat_map(|color| [color.r, color.g, color.b, color.a])
+                .collect(),
+        }
+    }
+
+    /// Pixel coordinates of `rect` that fall inside the canvas and the
+    /// current clip. Edges are rounded to the nearest pixel boundary, so
+    /// adjacent rects tile without gaps or overlaps.
+    fn clip(&self, rect: Rect) -> impl Iterator<Item = (u32, u32)> {
+        let rect = match self.clips.last() {
+            Some(clip) => rect.intersection(clip.rect),
+            None => rect,
+        };
+        let snap = |edge: f32, limit: u32| (edge.round().max(0.0) as u32).min(limit);
+        let xs = snap(rect.x, self.width)..snap(rect.x + rect.width, self.width);
+        let ys = snap(rect.y, self.height)..snap(rect.y + rect.height, self.height);
+        ys.flat_map(move |y| xs.clone().map(move |x| (x, y)))
+    }
+
+    /// Blends `color` into the pixel at `(x, y)`, `coverage` of it, and only
+    /// as much of it as is inside the rounded corners of the current clip.
+    fn plot(&self, pixels: &mut [Color], x: u32, y: u32, color: Color, coverage: f32) {
+        let coverage = match self.clips.last() {
+            Some(clip) if clip.radii != CornerRadii::default() => {
+                coverage * self::coverage(x, y, |x, y| clip.contains(x, y), true)
+            }
+            _ => coverage,
+        };
+        if coverage > 0.0 {
+            let pixel = &mut pixels[(y * self.width + x) as usize];
+            *pixel = blend(*pixel, color, coverage);
+        }
+    }
+
+    /// What of `rect` is inside the current clip, along with the pixels it
+    /// covers even partly.
+    fn covered(&self, rect: Rect) -> (Rect, impl Iterator<Item = (u32, u32)>) {
+        let rect = match self.clips.last() {
+            Some(clip) => rect.intersection(clip.rect),
+            None => rect,
+        };
+        let span = |start: f32, end: f32, limit: u32| {
+            (start.floor().max(0.0) as u32).min(limit)..(end.ceil().max(0.0) as u32).min(limit)
+        };
+        let xs = span(rect.x, rect.x + rect.width, self.width);
+        let ys = span(rect.y, rect.y + rect.height, self.height);
+        (rect, ys.flat_map(move |y| xs.clone().map(move |x| (x, y))))
+    }
+
+    /// Fills `rect`, blending the pixels its edges run through by how much
+    /// of them it covers, so edges between pixels are anti-aliased.
+    fn fill(&self, pixels: &mut [Color], color: Color, rect: Rect) {
+        let (rect, covered) = self.covered(rect);
+        for (x, y) in covered {
+            let overlap = |start: f32, size: f32, pixel: u32| {
+                let pixel = pixel as f32;
+                ((start + size).min(pixel + 1.0) - start.max(pixel)).max(0.0)
+            };
+            let coverage = overlap(rect.x, rect.width, x) * overlap(rect.y, rect.height, y);
+            self.plot(pixels, x, y, color, coverage);
+        }
+    }
+
+    /// Fills what of `area` is inside `outer` and outside `inner`, if any.
+    /// Pixels the curve of a corner runs through are blended by how much of
+    /// them is covered, sampled on a grid, so curves are anti-aliased.
+    fn fill_rounded(
+        &self,
+        pixels: &mut [Color],
+        color: Color,
+        area: Rect,
+        outer: &RoundedRect,
+        inner: Option<&RoundedRect>,
+    ) {
+        // Pixels partly in the area are included, as areas of a border may
+        // meet between pixels.
+        let (_, covered) = self.covered(area);
+        let inside = |x: f32, y: f32| {
+            area.contains(x, y)
+                && outer.contains(x, y)
+                && !inner.is_some_and(|inner| inner.contains(x, y))
+        };
+        for (x, y) in covered {
+            let pixel_rect = Rect {
+                x: x as f32,
+                y: y as f32,
+                width: 1.0,
+                height: 1.0,
+            };
+            // The inner shape may poke into a side of the pixel without
+            // covering any of its corners.
+            let whole = !inner.is_some_and(|inner| {
+                let overlap = inner.rect.intersection(pixel_rect);
+                overlap.width > 0.0 && overlap.height > 0.0
+            });
+            self.plot(pixels, x, y, color, coverage(x, y, inside, whole));
+        }
+    }
+
+    /// Paints `gradient` over `rect`, each pixel taking the color at its
+    /// center.
+    fn draw_gradient(&self, pixels: &mut [Color], gradient: &Gradient, rect: Rect) {
+        let (center_x, center_y) = (rect.x + rect.width / 2.0, rect.y + rect.height / 2.0);
+        let (half_width, half_height) = (rect.width / 2.0, rect.height / 2.0);
+        // How far along the gradient a point is, from 0 at its start to 1
+        // at its end.
+        type Progress = Box<dyn Fn(f32, f32) -> f32>;
+        // Along with the length of the gradient in px.
+        let (stops, length, progress): (_, f32, Progress) = match gradient {
+            Gradient::Linear(direction, stops) => {
+                let (dx, dy) = match *direction {
+                    GradientDirection::Angle(angle) => {
+                        let angle = angle.to_radians();
+                        (angle.sin(), -angle.cos())
+                    }
+                    // Perpendicular to the diagonal between the other two
+                    // corners, so the corner gets the last color.
+                    GradientDirection::Corner(x, y) => {
+                        let (dx, dy) = (x * rect.height, y * rect.width);
+                        let norm = dx.hypot(dy).max(f32::EPSILON);
+                        (dx / norm, dy / norm)
+                    }
+                };
+                let length = (rect.width * dx).abs() + (rect.height * dy).abs();
+                let progress = move |x: f32, y: f32| {
+                    ((x - center_x) * dx + (y - center_y) * dy) / length.max(f32::EPSILON) + 0.5
+                };
+                (stops, length, Box::new(progress))
+            }
+            Gradient::Radial(shape, stops) => {
+                let (radius_x, radius_y) = match shape {
+                    RadialShape::Circle => {
+                        let radius = half_width.hypot(half_height);
+                        (radius, radius)
+                    }
+                    // Of the same proportions as the box.
+                    RadialShape::Ellipse => (
+                        half_width * std::f32::consts::SQRT_2,
+                        half_height * std::f32::consts::SQRT_2,
+                    ),
+                };
+                let progress = move |x: f32, y: f32| {
+                    let dx = (x - center_x) / radius_x.max(f32::EPSILON);
+                    let dy = (y - center_y) / radius_y.max(f32::EPSILON);
+                    dx.hypot(dy)
+                };
+                (stops, radius_x, Box::new(progress))
+            }
+        };
+        let positions = stop_positions(stops, length / self.scale);
+        for (x, y) in self.clip(rect) {
+            let t = progress(x as f32 + 0.5, y as f32 + 0.5);
+            self.plot(pixels, x, y, color_at(stops, &positions, t), 1.0);
+        }
+    }
+
+    /// Draws `image` stretched over `rect`, each pixel taking the color of
+    /// the image pixel nearest to its center.
+    fn draw_scaled_image(&self, pixels: &mut [Color], image: &Image, rect: Rect) {
+        if image.width == 0 || image.height == 0 {
+            return;
+        }
+        for (x, y) in self.clip(rect) {
+            let image_x = (x as f32 + 0.5 - rect.x) / rect.width * image.width as f32;
+            let image_y = (y as f32 + 0.5 - rect.y) / rect.height * image.height as f32;
+            let image_x = (image_x.max(0.0) as u32).min(image.width - 1);
+            let image_y = (image_y.max(0.0) as u32).min(image.height - 1);
+            let offset = ((image_y * image.width + image_x) * 4) as usize;
+            let Some(&[r, g, b, a]) = image.pixels.get(offset..offset + 4) else {
+                continue;
+            };
+            self.plot(pixels, x, y, Color { r, g, b, a }, 1.0);
+        }
+    }
+
+    /// Draws `image` at its size in px, from the origin of `rect` and
+    /// clipped to it.
+    fn draw_image(&self, pixels: &mut [Color], image: &Image, rect: Rect) {
+        let rect = Rect {
+            x: rect.x.round(),
+            y: rect.y.round(),
+            width: rect.width.min(image.width as f32 * self.scale),
+            height: rect.height.min(image.height as f32 * self.scale),
+        };
+        for (x, y) in self.clip(rect) {
+            // The image's origin is snapped like the rect's edges.
+            let image_x = (x as f32 - rect.x) / self.scale;
+            let image_y = (y as f32 - rect.y) / self.scale;
+            let offset = ((image_y as u32 * image.width + image_x as u32) * 4) as usize;
+            let Some([r, g, b, a]) = image.pixels.get(offset..offset + 4) else {
+                continue;
+            };
+            let color = Color {
+                r: *r,
+                g: *g,
+                b: *b,
+                a: *a,
+            };
+            self.plot(pixels, x, y, color, 1.0);
+        }
+    }
+
+    /// Draws the glyphs of `run` with the bundled bitmap font, scaled to the
+    /// run's font size and sitting on its baseline. The dots of glyphs are
+    /// snapped to whole pixels rather than anti-aliased, keeping text crisp.
+    fn draw_text(&self, pixels: &mut [Color], color: Color, run: &GlyphRun, rect: Rect) {
+        let font = BitmapFont;
+        let pixel_size = font.pixel_size(run.font_size);
+        let top = rect.y + run.baseline - font.ascent(run.font_size);
+        for glyph in &run.glyphs {
+            let left = rect.x + glyph.x;
+            for (row, bits) in font.glyph(glyph.ch).iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                        continue;
+                    }
+                    let (x, y) = (
+                        left + column as f32 * pixel_size,
+                        top + row as f32 * pixel_size,
+                    );
+                    let dot = Rect {
+                        x: x.round(),
+                        y: y.round(),
+                        width: (x + pixel_size).round() - x.round(),
+                        height: (y + pixel_size).round() - y.round(),
+                    };
+                    self.fill(pixels, color, dot);
+                }
+            }
+        }
+    }
+
+    /// Paints `list` over the current content. Opacity groups are painted
+    /// into a transparent offscreen layer first, then blended down as a whole,
+    /// so overlapping items in a group don't show through each other. Items
+    /// between a `PushClip` and its `PopClip` are only painted inside the
+    /// clip. Items are scaled by the canvas's scale as they're painted.
+    ///
+    /// Canvases larger than a tile are split into tiles painted on their own
+    /// threads, each painting the items over it.
+    pub fn paint(&mut self, list: &DisplayList) {
+        let tiles = self.width.div_ceil(TILE_SIZE) * self.height.div_ceil(TILE_SIZE);
+        let threads = self.threads.min(tiles as usize);
+        if threads < 2 {
+            self.paint_tile(list);
+        } else {
+            self.paint_in_parallel(list, threads);
+        }
+        self.clips.clear();
+    }
+
+    /// Copy of the part of the canvas `width` by `height` pixels from
+    /// `(x, y)`, to be painted on its own.
+    fn tile(&self, x: u32, y: u32, width: u32, height: u32) -> Canvas {
+        let mut pixels = Vec::with_capacity(width as usize * height as usize);
+        for row in y..y + height {
+            let start = (row * self.width + x) as usize;
+            pixels.extend_from_slice(&self.pixels[start..start + width as usize]);
+        }
+        let (dx, dy) = (-(x as f32), -(y as f32));
+        Canvas {
+            width,
+            height,
+            pixels,
+            clips: self
+                .clips
+                .iter()
+                .map(|clip| clip.translated(dx, dy))
+                .collect(),
+            scale: self.scale,
+            origin: (self.origin.0 + x, self.origin.1 + y),
+            threads: 1,
+        }
+    }
+
+    /// Paints `list` over the tiles of the canvas on `threads` threads, then
+    /// copies the tiles back.
+    fn paint_in_parallel(&mut self, list: &DisplayList, threads: usize) {
+        let mut tiles = vec![];
+        for y in (0..self.height).step_by(TILE_SIZE as usize) {
+            for x in (0..self.width).step_by(TILE_SIZE as usize) {
+                let width = TILE_SIZE.min(self.width - x);
+                let height = TILE_SIZE.min(self.height - y);
+                tiles.push(self.tile(x, y, width, height));
+            }
+        }
+        let chunk_size = tiles.len().div_ceil(threads);
+        thread::scope(|scope| {
+            for chunk in tiles.chunks_mut(chunk_size) {
+                scope.spawn(move || {
+                    for tile in chunk {
+                        tile.paint_tile(list);
+                    }
+                });
+            }
+        });
+        for tile in tiles {
+            let (x, y) = (tile.origin.0 - self.origin.0, tile.origin.1 - self.origin.1);
+            for (row, pixels) in tile.pixels.chunks(tile.width as usize).enumerate() {
+                let start = ((y + row as u32) * self.width + x) as usize;
+                self.pixels[start..start + pixels.len()].copy_from_slice(pixels);
+            }
+        }
+    }
+
+    /// Paints `list` over the canvas on the calling thread.
+    fn paint_tile(&mut self, list: &DisplayList) {
+        let bounds = Rect {
+            x: 0.0,
+            y: 0.0,
+            width: self.width as f32,
+            height: self.height as f32,
+        };
+        let mut layers = vec![Layer {
+            pixels: std::mem::take(&mut self.pixels),
+            opacity: 1.0,
+        }];
+        let (dx, dy) = (-(self.origin.0 as f32), -(self.origin.1 as f32));
+        for item in list {
+            let target = &mut layers.last_mut().unwrap().pixels;
+            let rect = item.command.rect().scaled(self.scale).translated(dx, dy);
+            if let Some(extent) = painted_extent(&item.command, rect, self.scale) {
+                let visible = extent.intersection(bounds);
+                if visible.width <= 0.0 || visible.height <= 0.0 {
+                    continue;
+                }
+            }
+            let command = match (self.scale, self.origin) {
+                (1.0, (0, 0)) => Cow::Borrowed(&item.command),
+                (scale, _) => Cow::Owned(item.command.scaled(scale).with_rect(rect)),
+            };
+            match command.as_ref() {
+                DisplayCommand::SolidColor(color, rect) => self.fill(target, *color, *rect),
+                DisplayCommand::RoundedRect(color, rounded) => {
+                    self.fill_rounded(target, *color, rounded.rect, rounded, None)
+                }
+                DisplayCommand::RoundedBorder(color, area, outer, inner) => {
+                    self.fill_rounded(target, *color, *area, outer, Some(inner))
+                }
+                DisplayCommand::Image(image, rect) => self.draw_image(target, image, *rect),
+                DisplayCommand::Gradient(gradient, rect) => {
+                    self.draw_gradient(target, gradient, *rect)
+                }
+                DisplayCommand::ScaledImage(image, rect) => {
+                    self.draw_scaled_image(target, image, *rect)
+                }
+                DisplayCommand::Text(color, run, rect) => {
+                    self.draw_text(target, *color, run, *rect)
+                }
+                DisplayCommand::PushOpacity(opacity, _) => layers.push(Layer {
+                    pixels: vec![Color::default(); self.pixel_count()],
+                    opacity: *opacity,
+                }),
+                DisplayCommand::PopOpacity(_) if layers.len() > 1 => composite(&mut layers),
+                // Unbalanced pop, nothing to close.
+                DisplayCommand::PopOpacity(_) => {}
+                DisplayCommand::PushClip(clip) => {
+                    let rect = match self.clips.last() {
+                        Some(outer) => clip.rect.intersection(outer.rect),
+                        None => clip.rect,
+                    };
+                    self.clips.push(RoundedRect { rect, ..*clip });
+                }
+                DisplayCommand::PopClip(_) => {
+                    self.clips.pop();
+                }
+            }
+        }
+        while layers.len() > 1 {
+            composite(&mut layers);
+        }
+        self.pixels = layers.pop().unwrap().pixels;
+    }
+
+    /// Turns the canvas from showing `old`, painted over a transparent
+    /// canvas, into showing `new`. Only the area the items that changed
+    /// cover, before or after, is cleared and painted again, so frames
+    /// where little or nothing changed are cheap.
+    pub fn repaint(&mut self, old: &DisplayList, new: &DisplayList) {
+        let changes = diff_display_lists(old, new);
+        let (old_extents, new_extents) = (extents(old), extents(new));
+        let canvas = Rect {
+            x: 0.0,
+            y: 0.0,
+            width: self.width as f32,
+            height: self.height as f32,
+        };
+        let everything = canvas.scaled(1.0 / self.scale);
+        let damage = changes
+            .iter()
+            .flat_map(|change| match change {
+                DisplayListChange::Added { item, .. } => [None, new_extents.get(&item.id)],
+                DisplayListChange::Removed { id } => [old_extents.get(id), None],
+                DisplayListChange::Moved { id, .. } => [old_extents.get(id), new_extents.get(id)],
+                DisplayListChange::Updated { item } => {
+                    [old_extents.get(&item.id), new_extents.get(&item.id)]
+                }
+                // Anything may be painted over something else now.
+                DisplayListChange::Reordered { .. } => [Some(&everything), None],
+            })
+            .flatten()
+            .map(|extent| extent.scaled(self.scale))
+            .reduce(|damage, extent| damage.union(extent));
+        let Some(damage) = damage else {
+            return;
+        };
+        // Pixels the damage only partly covers are painted again whole.
+        let (left, top) = (damage.x.floor(), damage.y.floor());
+        let damage = Rect {
+            x: left,
+            y: top,
+            width: (damage.x + damage.width).ceil() - left,
+            height: (damage.y + damage.height).ceil() - top,
+        }
+        .intersection(canvas);
+        let (_, covered) = self.covered(damage);
+        for (x, y) in covered {
+            self.pixels[(y * self.width + x) as usize] = Color::default();
+        }
+        self.clips.push(RoundedRect {
+            rect: damage,
+            radii: CornerRadii::default(),
+        });
+        self.paint(new);
+    }
+}
+
+/// What each item of `list` may paint over, by id: its rect, along with
+/// the rects of the items inside it for items starting an opacity group or
+/// a clip, as those are painted differently when it changes.
+fn extents(list: &DisplayList) -> HashMap<ItemId, Rect> {
+    let mut extents: HashMap<ItemId, Rect> = HashMap::new();
+    let mut groups: Vec<ItemId> = vec![];
+    for item in list {
+        let rect = item.command.rect();
+        let closes = matches!(
+            item.command,
+            DisplayCommand::PopOpacity(_) | DisplayCommand::PopClip(_)
+        );
+        if closes {
+            groups.pop();
+        }
+        for id in groups.iter().chain([&item.id]) {
+            let extent = extents.entry(*id).or_insert(rect);
+            *extent = extent.union(rect);
+        }
+        if let DisplayCommand::PushOpacity(..) | DisplayCommand::PushClip(_) = item.command {
+            groups.push(item.id);
+        }
+    }
+    extents
+}
+
+/// Positions of `stops` along a gradient `length` px long, from 0 to 1.
+/// The first and last stops default to the ends, others without a position
+/// are spread evenly between the stops around them, and no stop comes
+/// before the one preceding it.
+fn stop_positions(stops: &[ColorStop], length: f32) -> Vec<f32> {
+    let mut positions: Vec<Option<f32>> = stops
+        .iter()
+        .map(|stop| {
+            stop.position.map(|position| match position {
+                LengthPercentage::Length(position) => position / length.max(f32::EPSILON),
+                LengthPercentage::Percent(percentage) => percentage / 100.0,
+            })
+        })
+        .collect();
+    let last = positions.len() - 1;
+    positions[0] = positions[0].or(Some(0.0));
+    positions[last] = positions[last].or(Some(1.0));
+    let mut previous = f32::MIN;
+    for position in positions.iter_mut().flatten() {
+        *position = position.max(previous);
+        previous = *position;
+    }
+    let mut start = 0;
+    for i in 1..=last {
+        let Some(end) = positions[i] else {
+            continue;
+        };
+        let from = positions[start].unwrap();
+        for (j, position) in positions.iter_mut().enumerate().take(i).skip(start + 1) {
+            *position = Some(from + (end - from) * (j - start) as f32 / (i - start) as f32);
+        }
+        start = i;
+    }
+    positions.into_iter().flatten().collect()
+}
+
+/// Color of a gradient `t` of the way along it, interpolated between the
+/// stops around `t` with premultiplied alpha.
+fn color_at(stops: &[ColorStop], positions: &[f32], t: f32) -> Color {
+    let next = positions.iter().position(|&position| position > t);
+    let (from, to) = match next {
+        Some(0) => return stops[0].color,
+        Some(next) => (next - 1, next),
+        None => return stops[stops.len() - 1].color,
+    };
+    let ratio = (t - positions[from]) / (positions[to] - positions[from]);
+    let (from, to) = (stops[from].color, stops[to].color);
+    let alpha = |color: Color| color.a as f32 / 255.0;
+    let a = alpha(from) + (alpha(to) - alpha(from)) * ratio;
+    if a == 0.0 {
+        return Color::default();
+    }
+    let channel = |from_channel: u8, to_channel: u8| {
+        let from_channel = from_channel as f32 * alpha(from);
+        let to_channel = to_channel as f32 * alpha(to);
+        ((from_channel + (to_channel - from_channel) * ratio) / a).round() as u8
+    };
+    Color {
+        r: channel(from.r, to.r),
+        g: channel(from.g, to.g),
+        b: channel(from.b, to.b),
+        a: (a * 255.0).round() as u8,
+    }
+}
+
+impl Renderer for Canvas {
+    fn resize(&mut self, width: u32, height: u32, scale: f32) {
+        Canvas::resize(self, width, height);
+        self.set_scale(scale);
+    }
+
+    fn render(&mut self, previous: Option<&DisplayList>, list: &DisplayList) {
+        match previous {
+            Some(previous) => self.repaint(previous, list),
+            None => {
+                self.pixels.fill(Color::default());
+                self.paint(list);
+            }
+        }
+    }
+
+    fn to_image(&self) -> Image {
+        Canvas::to_image(self)
+    }
+}
+
+/// Image of what `command` paints within `visible`, over a transparent
+/// canvas, along with the rect it covers, for exporters drawing it as an
+/// image. It has a pixel per px, unless that would take more than
+/// `MAX_RASTER_SIZE` pixels on a side. `None` when nothing is visible.
+pub(crate) fn rasterize(command: &DisplayCommand, visible: Rect) -> Option<(Image, Rect)> {
+    let rect = command.rect();
+    let area = rect.intersection(visible);
+    if !(area.width > 0.0 && area.height > 0.0) {
+        return None;
+    }
+    let scale = (MAX_RASTER_SIZE / area.width.max(area.height)).min(1.0);
+    let mut canvas = Canvas::new(
+        (area.width * scale).ceil() as u32,
+        (area.height * scale).ceil() as u32,
+    );
+    canvas.set_scale(scale);
+    canvas.paint(&vec![DisplayItem {
+        id: 0,
+        command: command.with_rect(rect.translated(-area.x, -area.y)),
+    }]);
+    Some((canvas.to_image(), area))
+}
+
+/// Area `command` may paint over once its rect is `rect`, to skip it when
+/// that's out of the canvas. `None` for items opening or closing groups,
+/// which are never skipped.
+pub(crate) fn painted_extent(command: &DisplayCommand, rect: Rect, scale: f32) -> Option<Rect> {
+    match command {
+        // Glyphs may reach a little out of the line of text.
+        DisplayCommand::Text(_, run, _) => {
+            let margin = run.font_size * scale;
+            Some(rect.expanded_by(EdgeSizes {
+                left: margin,
+                right: margin,
+                top: margin,
+                bottom: margin,
+            }))
+        }
+        DisplayCommand::PushOpacity(..)
+        | DisplayCommand::PopOpacity(_)
+        | DisplayCommand::PushClip(_)
+        | DisplayCommand::PopClip(_) => None,
+        _ => Some(rect),
+    }
+}
+
+/// Blends the top-most layer into the one below it.
+fn composite(layers: &mut Vec<Layer>) {
+    let layer = layers.pop().unwrap();
+    let target = &mut layers.last_mut().unwrap().pixels;
+    for (backdrop, source) in target.iter_mut().zip(layer.pixels) {
+        *backdrop = blend(*backdrop, source, layer.opacity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrusty_core::{
+        layout::{layout_tree, Dimensions, LayoutBox, LayoutContext, Rect},
+        parser::{CSSParser, HTMLParser, IParser},
+        resource::{Image, Resources},
+        style::get_styled_node,
+        values::{ColorStop, Gradient, GradientDirection},
+    };
+
+    use crate::{
+        build_display_list,
+        raster::{rasterize, Canvas},
+        Color, DisplayCommand, DisplayItem, Renderer,
+    };
+
+    /// Lays out `html` styled by `css` in a viewport `width` px wide. The
+    /// document and its styles are leaked, for the boxes to borrow them
+    /// past the call.
+    fn layout_of(html: &str, css: &str, width: f32) -> LayoutBox<'static> {
+        let stylesheet = Box::leak(Box::new(CSSParser::new(css).parse()));
+        let dom = Box::leak(Box::new(HTMLParser::new(html).parse()));
+        let styled_dom = Box::leak(Box::new(get_styled_node(dom, stylesheet)));
+        let mut viewport = Dimensions::default();
+        viewport.content.width = width;
+        layout_tree(styled_dom, viewport, &LayoutContext::new())
+    }
+
+    fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color { r, g, b, a: 255 }
+    }
+
+    /// Lays out and paints `html` on a canvas of the given size, snapped to
+    /// whole pixels as the engine does before painting.
+    fn render(html: &str, css: &str, width: u32, height: u32) -> Canvas {
+        let mut layout_root = layout_of(html, css, width as f32);
+        layout_root.snap_to_pixels(1.0);
+        let mut canvas = Canvas::new(width, height);
+        canvas.paint(&build_display_list(&layout_root, &Resources::new()));
+        canvas
+    }
+
+    #[test]
+    fn paints_fractional_boxes_without_seams() {
+        let html = "
+            <div class=\"row\"><p class=\"a\"></p><p class=\"b\"></p><p class=\"c\"></p></div>
+            <div class=\"a\"></div><div class=\"b\"></div><div class=\"c\"></div>
+            <div class=\"a\"></div><div class=\"b\"></div>
+        ";
+        let colors = "
+            html { background: white; }
+            .a { background: red; }
+            .b { background: green; }
+            .c { background: blue; }
+            div.row { display: flex; }
+            p { height: 10px; }
+        ";
+        // Thirds of a 100px row, with boxes 10.3px tall stacked below it.
+        let test = format!(
+            "{colors}
+            p {{ flex-grow: 1; }}
+            div.a, div.b, div.c {{ height: 10.3px; }}"
+        );
+        // The same boxes, sized to where their edges round to.
+        let reference = format!(
+            "{colors}
+            p.a, p.c {{ width: 33px; }}
+            p.b {{ width: 34px; }}
+            div.a, div.c {{ height: 10px; }}
+            div.b {{ height: 11px; }}"
+        );
+        let canvas = render(html, &test, 100, 64);
+        assert_eq!(canvas.pixels, render(html, &reference, 100, 64).pixels);
+        // Adjacent boxes leave no white between them.
+        let white = rgb(255, 255, 255);
+        assert!((0..62).all(|y| canvas.pixel(50, y) != white));
+        assert!((0..100).all(|x| canvas.pixel(x, 5) != white));
+    }
+
+    #[test]
+    fn paints_rounded_corners() {
+        let css = "
+            html {
+                background: white;
+            }
+
+            div {
+                width: 20px;
+                height: 20px;
+                border-radius: 10px;
+            }
+
+            div.filled {
+                background: red;
+            }
+
+            div.outlined {
+                width: 16px;
+                height: 16px;
+                border: 2px solid blue;
+            }
+        ";
+        let html = "<div class=\"filled\"></div><div class=\"outlined\"></div>";
+        let canvas = render(html, css, 30, 40);
+        let (white, red, blue) = (rgb(255, 255, 255), rgb(255, 0, 0), rgb(0, 0, 255));
+        assert_eq!(canvas.pixel(0, 0), white);
+        assert_eq!(canvas.pixel(10, 0), red);
+        assert_eq!(canvas.pixel(0, 10), red);
+        assert_eq!(canvas.pixel(19, 19), white);
+        // The curve runs through the pixel, which is partly red.
+        let edge = canvas.pixel(2, 3);
+        assert!(edge != white && edge != red && edge.r == 255, "{edge:?}");
+
+        // The border follows the corners, and the inside is left unpainted.
+        assert_eq!(canvas.pixel(10, 20), blue);
+        assert_eq!(canvas.pixel(0, 30), blue);
+        assert_eq!(canvas.pixel(19, 30), blue);
+        assert_eq!(canvas.pixel(10, 30), white);
+        assert_eq!(canvas.pixel(0, 20), white);
+        let edge = canvas.pixel(2, 23);
+        assert!(edge != white && edge != blue && edge.b == 255, "{edge:?}");
+    }
+
+    #[test]
+    fn paints_gradients() {
+        let css = "
+            div {
+                width: 10px;
+                height: 10px;
+            }
+
+            div.linear {
+                background: linear-gradient(to right, red, blue);
+            }
+
+            div.stops {
+                background-image: linear-gradient(to right, red 40%, blue 40%);
+            }
+
+            div.radial {
+                background-image: radial-gradient(circle, white, black);
+            }
+        ";
+        let html =
+            "<div class=\"linear\"></div><div class=\"stops\"></div><div class=\"radial\"></div>";
+        let canvas = render(html, css, 10, 30);
+        let linear: Vec<Color> = (0..10).map(|x| canvas.pixel(x, 5)).collect();
+        assert_eq!(linear[0], rgb(242, 0, 13));
+        assert_eq!(linear[9], rgb(13, 0, 242));
+        assert!(linear.windows(2).all(|pair| pair[0].r > pair[1].r));
+
+        // Stops at the same position make a hard edge.
+        assert_eq!(canvas.pixel(3, 15), rgb(255, 0, 0));
+        assert_eq!(canvas.pixel(4, 15), rgb(0, 0, 255));
+
+        let center = canvas.pixel(5, 25);
+        assert!(center.r > 200, "{center:?}");
+        assert!(canvas.pixel(0, 20).r < 40);
+    }
+
+    #[test]
+    fn rasterizes_the_visible_part_of_items() {
+        let stop = |color| ColorStop {
+            color,
+            position: None,
+        };
+        let stops = vec![stop(rgb(255, 0, 0)), stop(rgb(0, 0, 255))];
+        let gradient = Gradient::Linear(GradientDirection::Angle(180.0), stops);
+        let rect = |x, y, width, height| Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+        let command = DisplayCommand::Gradient(gradient, rect(0.0, 0.0, 100.0, 1e8));
+        let (image, area) = rasterize(&command, rect(-10.0, 50.0, 200.0, 20.0)).unwrap();
+        assert_eq!((image.width, image.height), (100, 20));
+        assert_eq!(area, rect(0.0, 50.0, 100.0, 20.0));
+
+        // Too large to show a pixel per px.
+        let (image, area) = rasterize(&command, rect(0.0, 0.0, 100.0, 1e9)).unwrap();
+        assert_eq!((image.width, image.height), (1, 8192));
+        assert_eq!(area, rect(0.0, 0.0, 100.0, 1e8));
+
+        assert!(rasterize(&command, rect(0.0, -20.0, 100.0, 20.0)).is_none());
+    }
+
+    #[test]
+    fn clips_background_images_to_rounded_corners() {
+        let css = "
+            div {
+                width: 20px;
+                height: 20px;
+                border: 2px solid blue;
+                border-radius: 10px;
+                background-image: linear-gradient(red, red);
+            }
+        ";
+        let canvas = render("<div></div>", css, 24, 24);
+        assert_eq!(canvas.pixel(12, 12), rgb(255, 0, 0));
+        assert_eq!(canvas.pixel(12, 0), rgb(0, 0, 255));
+        // Outside the curve of the border as well as the padding box.
+        assert_eq!(canvas.pixel(0, 0), Color::default());
+        assert_eq!(canvas.pixel(2, 2), Color::default());
+        assert_eq!(canvas.pixel(21, 21), Color::default());
+    }
+
+    #[test]
+    fn paints_opacity_groups() {
+        let html = "<div class=\"group\"><div class=\"a\"></div><div class=\"b\"></div></div>";
+        let css = "
+            html {
+                background: white;
+            }
+
+            div.group {
+                opacity: 0.5;
+            }
+
+            div.a {
+                height: 10px;
+                background: red;
+            }
+
+            div.b {
+                height: 10px;
+                background: blue;
+            }
+        ";
+        let layout_root = layout_of(html, css, 20.0);
+        let list = build_display_list(&layout_root, &Resources::new());
+        assert!(matches!(
+            list[1].command,
+            DisplayCommand::PushOpacity(opacity, _) if opacity == 0.5
+        ));
+        assert!(matches!(list[4].command, DisplayCommand::PopOpacity(_)));
+
+        let mut canvas = Canvas::new(20, 30);
+        canvas.paint(&list);
+        assert_eq!(canvas.pixel(0, 0), rgb(255, 128, 128));
+        assert_eq!(canvas.pixel(0, 15), rgb(128, 128, 255));
+        assert_eq!(canvas.pixel(0, 29), Color::default());
+    }
+
+    #[test]
+    fn repaints_resized_canvases() {
+        let css = "div { height: 10px; background: red; }";
+        let paint = |canvas: &mut Canvas| {
+            let layout_root = layout_of("<div></div>", css, canvas.width as f32);
+            canvas.paint(&build_display_list(&layout_root, &Resources::new()));
+        };
+        let mut canvas = Canvas::new(20, 10);
+        paint(&mut canvas);
+        canvas.resize(40, 20);
+        assert_eq!(canvas.pixels.len(), 800);
+        assert_eq!(canvas.pixel(0, 0), Color::default());
+
+        // The layout follows the new viewport width.
+        paint(&mut canvas);
+        assert_eq!(canvas.pixel(39, 9), rgb(255, 0, 0));
+        assert_eq!(canvas.pixel(39, 10), Color::default());
+    }
+
+    #[test]
+    fn scales_images_to_their_rect() {
+        let image = Arc::new(Image {
+            width: 2,
+            height: 1,
+            pixels: vec![255, 0, 0, 255, 0, 0, 255, 255],
+        });
+        let rect = Rect {
+            x: 0.0,
+            y: 0.0,
+            width: 4.0,
+            height: 2.0,
+        };
+        let list = vec![DisplayItem {
+            id: 0,
+            command: DisplayCommand::ScaledImage(image, rect),
+        }];
+        let mut canvas = Canvas::new(4, 2);
+        canvas.paint(&list);
+        let row = |y| (0..4).map(|x| canvas.pixel(x, y)).collect::<Vec<_>>();
+        let (red, blue) = (rgb(255, 0, 0), rgb(0, 0, 255));
+        assert_eq!(row(0), [red, red, blue, blue]);
+        assert_eq!(row(1), row(0));
+    }
+
+    #[test]
+    fn anti_aliases_fractional_edges() {
+        let rect = Rect {
+            x: 0.5,
+            y: 0.0,
+            width: 2.25,
+            height: 1.5,
+        };
+        let list = vec![DisplayItem {
+            id: 0,
+            command: DisplayCommand::SolidColor(rgb(255, 0, 0), rect),
+        }];
+        let mut canvas = Canvas::new(4, 2);
+        canvas.paint(&list);
+        // Pixels the edges run through get as much alpha as they're covered.
+        let alphas = |y| (0..4).map(|x| canvas.pixel(x, y).a).collect::<Vec<_>>();
+        assert_eq!(alphas(0), [128, 255, 191, 0]);
+        assert_eq!(alphas(1), [64, 128, 96, 0]);
+        assert_eq!(
+            canvas.pixel(1, 1),
+            Color {
+                a: 128,
+                ..rgb(255, 0, 0)
+            }
+        );
+    }
+
+    #[test]
+    fn paints_scaled_to_device_pixels() {
+        let css = "
+            div {
+                width: 3px;
+                height: 2px;
+                border-left: 1px solid blue;
+                background: red;
+            }
+        ";
+        let mut layout_root = layout_of("<div></div>", css, 5.0);
+        layout_root.snap_to_pixels(2.0);
+        let list = build_display_list(&layout_root, &Resources::new());
+        // The canvas is sized in device pixels, two per px each way.
+        let mut canvas = Canvas::new(10, 6);
+        canvas.set_scale(2.0);
+        canvas.paint(&list);
+        let (red, blue) = (rgb(255, 0, 0), rgb(0, 0, 255));
+        let row = |y| (0..10).map(|x| canvas.pixel(x, y)).collect::<Vec<_>>();
+        let none = Color::default();
+        assert_eq!(
+            row(0),
+            [blue, blue, red, red, red, red, red, red, none, none]
+        );
+        assert_eq!(row(3), row(0));
+        assert_eq!(row(4), [none; 10]);
+
+        let image = Arc::new(Image {
+            width: 2,
+            height: 1,
+            pixels: vec![255, 0, 0, 255, 0, 0, 255, 255],
+        });
+        let rect = Rect {
+            x: 0.0,
+            y: 0.0,
+            width: 5.0,
+            height: 3.0,
+        };
+        let list = vec![DisplayItem {
+            id: 0,
+            command: DisplayCommand::Image(image, rect),
+        }];
+        let mut canvas = Canvas::new(10, 6);
+        canvas.set_scale(2.0);
+        canvas.paint(&list);
+        // Images are drawn at their size in px, each pixel over 2x2.
+        let row = |y| (0..6).map(|x| canvas.pixel(x, y)).collect::<Vec<_>>();
+        assert_eq!(row(0), [red, red, blue, blue, none, none]);
+        assert_eq!(row(1), row(0));
+        assert_eq!(row(2), [none; 6]);
+    }
+
+    #[test]
+    fn repaints_only_what_changed() {
+        let html = "<div class=\"a\"></div><div class=\"b\"></div>";
+        let paint = |css: &str| {
+            let layout_root = layout_of(html, css, 10.0);
+            build_display_list(&layout_root, &Resources::new())
+        };
+        let old =
+            paint("div { height: 5px; } div.a { background: red; } div.b { background: blue; }");
+        let new = paint(
+            "div { height: 5px; } div.a { background: red; } div.b { background: green; opacity: 0.5; }",
+        );
+        let mut canvas = Canvas::new(10, 10);
+        canvas.paint(&old);
+        // Marks a pixel of the box that doesn't change, to tell whether it's
+        // painted again.
+        let marked = rgb(1, 2, 3);
+        canvas.pixels[0] = marked;
+        canvas.repaint(&old, &old);
+        assert_eq!(canvas.pixel(0, 0), marked);
+
+        canvas.repaint(&old, &new);
+        assert_eq!(canvas.pixel(0, 0), marked);
+        let mut fresh = Canvas::new(10, 10);
+        fresh.paint(&new);
+        assert_eq!(canvas.pixels[1..], fresh.pixels[1..]);
+        assert_eq!(canvas.pixel(0, 5).a, 128);
+    }
+
+    #[test]
+    fn renders_frames_through_the_renderer_trait() {
+        let rect = Rect {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+        };
+        let item = |color| DisplayItem {
+            id: 0,
+            command: DisplayCommand::SolidColor(color, rect),
+        };
+        let (red, blue) = (rgb(255, 0, 0), rgb(0, 0, 255));
+        let mut canvas = Canvas::new(1, 1);
+        let renderer: &mut dyn Renderer = &mut canvas;
+        renderer.resize(4, 4, 2.0);
+        let first = vec![item(red)];
+        renderer.render(None, &first);
+        let second = vec![item(Color { a: 128, ..blue })];
+        renderer.render(Some(&first), &second);
+        // The new frame replaces the old one rather than blending over it.
+        assert_eq!(canvas.pixel(1, 1), Color { a: 128, ..blue });
+        assert_eq!(canvas.pixel(2, 2), Color::default());
+    }
+
+    #[test]
+    fn paints_tiles_on_several_threads() {
+        let css = "
+            div.box {
+                width: 500px;
+                height: 300px;
+                padding: 10px;
+                border: 3px solid blue;
+                border-radius: 40px;
+                overflow: hidden;
+                background: linear-gradient(to right, red, green);
+                opacity: 0.8;
+            }
+
+            p {
+                width: 700px;
+                color: white;
+            }
+        ";
+        let html = "<div class=\"box\"><p>tiles tiles tiles tiles tiles tiles</p></div>";
+        let mut layout_root = layout_of(html, css, 600.0);
+        layout_root.snap_to_pixels(1.5);
+        let list = build_display_list(&layout_root, &Resources::new());
+        let paint = |threads| {
+            let mut canvas = Canvas::new(900, 500);
+            canvas.set_scale(1.5);
+            canvas.set_threads(threads);
+            canvas.paint(&list);
+            canvas
+        };
+        // Tiles meet without seams, and items spanning them paint the same
+        // as on a single thread.
+        let (single, tiled) = (paint(1), paint(4));
+        assert!(single.pixels == tiled.pixels);
+        assert_ne!(single.pixel(300, 260), Color::default());
+    }
+
+    #[test]
+    fn blends_translucent_colors() {
+        let rect = Rect {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+        };
+        let item = |command| DisplayItem { id: 0, command };
+        let translucent_blue = Color {
+            a: 64,
+            ..rgb(0, 0, 255)
+        };
+        let list = vec![
+            item(DisplayCommand::SolidColor(rgb(255, 255, 255), rect)),
+            item(DisplayCommand::SolidColor(translucent_blue, rect)),
+        ];
+        let mut canvas = Canvas::new(2, 1);
+        canvas.paint(&list);
+        assert_eq!(canvas.pixel(0, 0), rgb(191, 191, 255));
+
+        // Over a transparent backdrop, the color keeps its own alpha.
+        canvas.resize(1, 1);
+        canvas.paint(&list[1..].to_vec());
+        assert_eq!(canvas.pixel(0, 0), translucent_blue);
+    }
+
+    #[test]
+    fn composites_groups_as_a_whole() {
+        let rect = |x, width| Rect {
+            x,
+            y: 0.0,
+            width,
+            height: 1.0,
+        };
+        let item = |command| DisplayItem { id: 0, command };
+        let list = vec![
+            item(DisplayCommand::SolidColor(
+                rgb(255, 255, 255),
+                rect(0.0, 3.0),
+            )),
+            item(DisplayCommand::PushOpacity(0.5, rect(0.0, 3.0))),
+            item(DisplayCommand::SolidColor(rgb(255, 0, 0), rect(0.0, 2.0))),
+            item(DisplayCommand::SolidColor(rgb(0, 0, 255), rect(1.0, 2.0))),
+            item(DisplayCommand::PopOpacity(rect(0.0, 3.0))),
+        ];
+        let mut canvas = Canvas::new(3, 1);
+        canvas.paint(&list);
+        assert_eq!(canvas.pixel(0, 0), rgb(255, 128, 128));
+        // Blue covers red inside the group before the group is faded, so no
+        // red shows through where they overlap.
+        assert_eq!(canvas.pixel(1, 0), rgb(128, 128, 255));
+        assert_eq!(canvas.pixel(2, 0), rgb(128, 128, 255));
+    }
+
+    #[test]
+    fn clips_to_overflow_hidden_ancestors() {
+        let css = "
+            html {
+                background: white;
+            }
+
+            div.clip {
+                width: 20px;
+                height: 20px;
+                overflow: hidden;
+                border-radius: 10px;
+            }
+
+            div.wide {
+                width: 40px;
+                height: 40px;
+                background: red;
+            }
+        ";
+        let html = "<div class=\"clip\"><div class=\"wide\"></div></div>";
+        let canvas = render(html, css, 50, 50);
+        let (white, red) = (rgb(255, 255, 255), rgb(255, 0, 0));
+        assert_eq!(canvas.pixel(10, 10), red);
+        assert_eq!(canvas.pixel(10, 0), red);
+        // The child overflows its parent, which cuts it off at its edges
+        // and rounded corners.
+        assert_eq!(canvas.pixel(25, 10), white);
+        assert_eq!(canvas.pixel(10, 25), Color::default());
+        assert_eq!(canvas.pixel(0, 0), white);
+        assert_eq!(canvas.pixel(19, 19), white);
+        let edge = canvas.pixel(2, 3);
+        assert!(edge != white && edge != red && edge.r == 255, "{edge:?}");
+    }
+
+    #[test]
+    fn draws_text_fragments() {
+        let html = "<p>HH H</p>";
+        let css = "
+            p {
+                width: 30px;
+                color: red;
+            }
+        ";
+        let layout_root = layout_of(html, css, 100.0);
+        let list = build_display_list(&layout_root, &Resources::new());
+        // The last `H` wraps, so the text box draws one run per line.
+        let runs: Vec<(String, Rect)> = list
+            .iter()
+            .filter_map(|item| match &item.command {
+                DisplayCommand::Text(_, run, rect) => Some((run.text(), *rect)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].0, "HH");
+        assert_eq!((runs[1].0.as_str(), runs[1].1.y), ("H", 19.2));
+
+        // Glyph pixels are 2px at 16px, and the first row of `H` sits 2px
+        // below the top of the 19.2px line.
+        let mut canvas = Canvas::new(100, 40);
+        canvas.paint(&list);
+        let red = rgb(255, 0, 0);
+        assert_eq!(canvas.pixel(0, 2), red);
+        assert_eq!(canvas.pixel(1, 3), red);
+        assert_eq!(canvas.pixel(2, 2), Color::default());
+        assert_eq!(canvas.pixel(8, 2), red);
+        assert_eq!(canvas.pixel(0, 1), Color::default());
+        assert_eq!(canvas.pixel(12, 2), red);
+        assert_eq!(canvas.pixel(0, 21), red);
+    }
+}
@@ -0,0 +1,274 @@
+//! Export of display lists to SVG, to look at layouts without a window or
+//! to illustrate documentation. Boxes, borders and clips are written as
+//! shapes, text as text in a monospace font, and images as PNG data URIs.
+//! Gradients are rasterized into images.
+
+use std::fmt::Write;
+
+use chrusty_core::{layout::Rect, resource::Image};
+
+use crate::{png::encode_png, raster::rasterize, Color, DisplayCommand, DisplayList, RoundedRect};
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (bits >> (18 - 6 * i)) & 0x3f;
+                encoded.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Escapes `text` for attribute values and character data.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// `fill` attributes painting with `color`.
+fn fill(color: Color) -> String {
+    let mut fill = format!("fill=\"rgb({},{},{})\"", color.r, color.g, color.b);
+    if color.a < 255 {
+        let _ = write!(fill, " fill-opacity=\"{}\"", color.a as f32 / 255.0);
+    }
+    fill
+}
+
+fn rect_attributes(rect: Rect) -> String {
+    format!(
+        "x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"",
+        rect.x, rect.y, rect.width, rect.height
+    )
+}
+
+/// Path data of the outline of `rounded`, each corner an elliptical arc.
+fn rounded_path(rounded: &RoundedRect) -> String {
+    let Rect {
+        x: left,
+        y: top,
+        width,
+        height,
+    } = rounded.rect;
+    let (right, bottom) = (left + width, top + height);
+    let radii = rounded.radii;
+    let (top_left, top_right) = (radii.top_left, radii.top_right);
+    let (bottom_right, bottom_left) = (radii.bottom_right, radii.bottom_left);
+    let arc = |radius: chrusty_core::layout::CornerRadius, x: f32, y: f32| {
+        format!("A{} {} 0 0 1 {x} {y}", radius.x, radius.y)
+    };
+    format!(
+        "M{} {top}H{}{}V{}{}H{}{}V{}{}Z",
+        left + top_left.x,
+        right - top_right.x,
+        arc(top_right, right, top + top_right.y),
+        bottom - bottom_right.y,
+        arc(bottom_right, right - bottom_right.x, bottom),
+        left + bottom_left.x,
+        arc(bottom_left, left, bottom - bottom_left.y),
+        top + top_left.y,
+        arc(top_left, left + top_left.x, top),
+    )
+}
+
+fn image_element(image: &Image, rect: Rect) -> String {
+    format!(
+        "<image {} preserveAspectRatio=\"none\" href=\"data:image/png;base64,{}\"/>\n",
+        rect_attributes(rect),
+        base64(&encode_png(image))
+    )
+}
+
+/// SVG document showing the area of `list` `width` by `height` px from its
+/// origin.
+pub fn export_svg(list: &DisplayList, width: f32, height: f32) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+         viewBox=\"0 0 {width} {height}\">\n"
+    );
+    // Groups opened by opacity groups and clips, closed as they end.
+    let mut groups = 0;
+    let mut clips = 0;
+    let mut clip_group = |svg: &mut String, shape: String| {
+        clips += 1;
+        let _ = writeln!(svg, "<clipPath id=\"clip{clips}\">{shape}</clipPath>");
+        let _ = writeln!(svg, "<g clip-path=\"url(#clip{clips})\">");
+    };
+    let visible = Rect {
+        x: 0.0,
+        y: 0.0,
+        width,
+        height,
+    };
+    for item in list {
+        match &item.command {
+            DisplayCommand::SolidColor(color, rect) => {
+                let _ = writeln!(svg, "<rect {} {}/>", rect_attributes(*rect), fill(*color));
+            }
+            DisplayCommand::RoundedRect(color, rounded) => {
+                let _ = writeln!(
+                    svg,
+                    "<path d=\"{}\" {}/>",
+                    rounded_path(rounded),
+                    fill(*color)
+                );
+            }
+            DisplayCommand::RoundedBorder(color, area, outer, inner) => {
+                clip_group(&mut svg, format!("<rect {}/>", rect_attributes(*area)));
+                let _ = writeln!(
+                    svg,
+                    "<path d=\"{}{}\" fill-rule=\"evenodd\" {}/>\n</g>",
+                    rounded_path(outer),
+                    rounded_path(inner),
+                    fill(*color)
+                );
+            }
+            DisplayCommand::Image(image, rect) => {
+                clip_group(&mut svg, format!("<rect {}/>", rect_attributes(*rect)));
+                let size = Rect {
+                    width: image.width as f32,
+                    height: image.height as f32,
+                    ..*rect
+                };
+                svg.push_str(&image_element(image, size));
+                svg.push_str("</g>\n");
+            }
+            DisplayCommand::ScaledImage(image, rect) => {
+                svg.push_str(&image_element(image, *rect));
+            }
+            DisplayCommand::Gradient(..) => {
+                if let Some((image, area)) = rasterize(&item.command, visible) {
+                    svg.push_str(&image_element(&image, area));
+                }
+            }
+            DisplayCommand::Text(color, run, rect) => {
+                let xs: Vec<String> = run
+                    .glyphs
+                    .iter()
+                    .map(|glyph| (rect.x + glyph.x).to_string())
+                    .collect();
+                let _ = writeln!(
+                    svg,
+                    "<text x=\"{}\" y=\"{}\" font-family=\"monospace\" font-size=\"{}\" \
+                     xml:space=\"preserve\" {}>{}</text>",
+                    xs.join(" "),
+                    rect.y + run.baseline,
+                    run.font_size,
+                    fill(*color),
+                    escape(&run.text())
+                );
+            }
+            DisplayCommand::PushOpacity(opacity, _) => {
+                groups += 1;
+                let _ = writeln!(svg, "<g opacity=\"{opacity}\">");
+            }
+            DisplayCommand::PushClip(clip) => {
+                groups += 1;
+                clip_group(&mut svg, format!("<path d=\"{}\"/>", rounded_path(clip)));
+            }
+            DisplayCommand::PopOpacity(_) | DisplayCommand::PopClip(_) => {
+                if groups > 0 {
+                    groups -= 1;
+                    svg.push_str("</g>\n");
+                }
+            }
+        }
+    }
+    svg.push_str(&"</g>\n".repeat(groups));
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use chrusty_core::{
+        layout::{layout_tree, Dimensions, LayoutBox, LayoutContext},
+        parser::{CSSParser, HTMLParser, IParser},
+        resource::Resources,
+        style::get_styled_node,
+    };
+
+    use crate::{
+        build_display_list,
+        svg::{base64, export_svg},
+    };
+
+    /// Lays out `html` styled by `css` in a viewport `width` px wide. The
+    /// document and its styles are leaked, for the boxes to borrow them
+    /// past the call.
+    fn layout_of(html: &str, css: &str, width: f32) -> LayoutBox<'static> {
+        let stylesheet = Box::leak(Box::new(CSSParser::new(css).parse()));
+        let dom = Box::leak(Box::new(HTMLParser::new(html).parse()));
+        let styled_dom = Box::leak(Box::new(get_styled_node(dom, stylesheet)));
+        let mut viewport = Dimensions::default();
+        viewport.content.width = width;
+        layout_tree(styled_dom, viewport, &LayoutContext::new())
+    }
+
+    #[test]
+    fn exports_svg_documents() {
+        assert_eq!(base64(b"Man"), "TWFu");
+        assert_eq!(base64(b"Ma"), "TWE=");
+        assert_eq!(base64(b"M"), "TQ==");
+
+        let css = "
+            div {
+                height: 40px;
+                background: red;
+            }
+
+            div.round {
+                border-radius: 10px;
+                overflow: hidden;
+                opacity: 0.5;
+                background: linear-gradient(red, blue);
+            }
+
+            p {
+                color: black;
+            }
+        ";
+        let html = "<div></div><div class=\"round\"><p>a&lt;b</p></div>";
+        let layout_root = layout_of(html, css, 100.0);
+        let list = build_display_list(&layout_root, &Resources::new());
+        let svg = export_svg(&list, 100.0, 80.0);
+        assert!(svg.starts_with(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"100\" height=\"80\" \
+             viewBox=\"0 0 100 80\">\n<rect x=\"0\" y=\"0\" width=\"100\" height=\"40\" \
+             fill=\"rgb(255,0,0)\"/>\n"
+        ));
+        assert!(svg.ends_with("</svg>\n"));
+        assert!(svg.contains("<g opacity=\"0.5\">"));
+        assert!(
+            svg.contains("<path d=\"M10 40H90A10 10 0 0 1 100 50V70"),
+            "{svg}"
+        );
+        assert!(svg.contains("href=\"data:image/png;base64,iVBORw0KGgo"));
+        // The parser keeps entities as they are, so the ampersand is escaped.
+        assert!(svg.contains(">a&amp;lt;b</text>"));
+        // Each group opened is closed.
+        assert_eq!(svg.matches("<g ").count(), svg.matches("</g>").count());
+    }
+}